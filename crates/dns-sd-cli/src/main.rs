@@ -0,0 +1,675 @@
+//! `dns-sd` command line tool
+//!
+//! Exercises the same backends as the Node addon, so discovery can be
+//! checked on a target machine without writing a script.
+
+use dns_sd::health::Check;
+use dns_sd::query::{self, rrtype_name};
+use dns_sd::rpc::{self, Transport};
+use dns_sd::websocket::{self, BridgeConfig};
+use dns_sd::{
+    AdvertiseOptions, Backend, BrowseCallback, BrowseOptions, FindOptions, InstanceTable, InterfaceMode, NamePattern,
+    ResultFilter, ServiceInfo, Stoppable, TYPE_ENUMERATION, TxtRecord,
+};
+use serde_json::{Value, json};
+use std::collections::BTreeSet;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "\
+Usage: dns-sd <command> [options]
+
+Commands:
+  browse <type>                           Print services of a type as they come and go
+  register <name> <type> <port> [k=v...]  Advertise a service until interrupted
+  alias <hostname>                        Publish another .local name for this host until interrupted
+  host <hostname> <address...>            Publish address records for a .local name until interrupted
+  resolve <name> <type>                   Resolve one service instance
+  query <name> [rrtype]                   Query DNS records (default: A); --watch follows changes
+  types                                   List service types on the network
+  health                                  Check the daemon and multicast on each interface
+  selftest                                Register, browse and resolve a LocalOnly service
+  interfaces                              List network interfaces and their indices
+  bridge [--port <port>] [--origin <url>] Serve JSON-RPC over a local WebSocket
+  serve [--socket <path>]                 Serve JSON-RPC lines on stdio or a Unix socket
+
+Options:
+  --json              Print one JSON object per line
+  --timeout <secs>    Stop after this long
+  --domain <domain>   Browse/register domain (default: local)
+  --interface <index> Interface index, local-only, or a name such as eth0 (names repeatable)
+  --port <port>       Bridge listen port on 127.0.0.1 (default: 8765)
+  --origin <url>      Browser Origin the bridge accepts; others are refused (repeatable)
+  --allow-any-origin  Let pages from any Origin use the bridge
+  --socket <path>     Unix socket for serve (default: stdio)
+  --record <file>     Capture backend events to a JSONL file
+  --replay <file>     Replay a capture through the mock backend instead of the network
+  --pcap <file>       Write the mdns-sd fallback's mDNS packets to a pcap file
+  --loopback-only     Stay on this machine: no multicast on the network (for tests)
+  --force-multicast   Use mDNS even for names outside .local (browse, query)
+  --all-domains       Also browse every domain the daemon enumerates (browse)
+  --validate          Check DNSSEC signatures of answers (resolve, query)
+  --name <glob>       Only report instances whose name matches (browse, resolve)
+  --txt <key[=value]> Only report services with this TXT entry (repeatable)
+  --ignore <glob>     Skip instances with this name, without resolving them (repeatable)
+  --no-resolve        Report browse answers only, without resolving (browse)
+  --watch             Keep querying and print records as they come and go (query)";
+
+/// Options shared by every command
+struct Args {
+    positional: Vec<String>,
+    json: bool,
+    timeout: Option<Duration>,
+    domain: Option<String>,
+    interface_index: u32,
+    interfaces: Vec<String>,
+    port: Option<u16>,
+    origins: Vec<String>,
+    allow_any_origin: bool,
+    socket: Option<String>,
+    record: Option<String>,
+    replay: Option<String>,
+    pcap: Option<String>,
+    loopback_only: bool,
+    force_multicast: bool,
+    all_domains: bool,
+    validate: bool,
+    result_filter: ResultFilter,
+    resolve: bool,
+    watch: bool,
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args {
+        positional: Vec::new(),
+        json: false,
+        timeout: None,
+        domain: None,
+        interface_index: 0,
+        interfaces: Vec::new(),
+        port: None,
+        origins: Vec::new(),
+        allow_any_origin: false,
+        socket: None,
+        record: None,
+        replay: None,
+        pcap: None,
+        loopback_only: false,
+        force_multicast: false,
+        all_domains: false,
+        validate: false,
+        result_filter: ResultFilter::default(),
+        resolve: true,
+        watch: false,
+    };
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or_else(|| format!("{} needs a value", flag));
+        match arg.as_str() {
+            "--json" => parsed.json = true,
+            "--loopback-only" => parsed.loopback_only = true,
+            "--force-multicast" => parsed.force_multicast = true,
+            "--all-domains" => parsed.all_domains = true,
+            "--validate" => parsed.validate = true,
+            "--no-resolve" => parsed.resolve = false,
+            "--watch" => parsed.watch = true,
+            "--allow-any-origin" => parsed.allow_any_origin = true,
+            "--timeout" => {
+                let secs: f64 = value(&arg)?.parse().map_err(|_| "--timeout must be a number")?;
+                parsed.timeout = Some(Duration::from_secs_f64(secs.max(0.0)));
+            }
+            "--domain" => parsed.domain = Some(value(&arg)?),
+            "--interface" => {
+                let value = value(&arg)?;
+                match dns_sd::parse_interface(&value) {
+                    Ok(index) => parsed.interface_index = index,
+                    // Anything else names an interface, as in `--interface eth0`
+                    Err(_) => parsed.interfaces.push(value),
+                }
+            }
+            "--port" => parsed.port = Some(value(&arg)?.parse().map_err(|_| "--port must be a port number")?),
+            "--origin" => parsed.origins.push(value(&arg)?),
+            "--socket" => parsed.socket = Some(value(&arg)?),
+            "--record" => parsed.record = Some(value(&arg)?),
+            "--replay" => parsed.replay = Some(value(&arg)?),
+            "--pcap" => parsed.pcap = Some(value(&arg)?),
+            "--name" => parsed.result_filter.name = Some(NamePattern::Glob(value(&arg)?)),
+            "--ignore" => parsed.result_filter.names.ignore.push(value(&arg)?),
+            "--txt" => {
+                let entry = value(&arg)?;
+                let (key, value) = entry.split_once('=').unwrap_or((&entry, ""));
+                parsed.result_filter.txt.insert(key.to_string(), value.to_string());
+            }
+            flag if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => parsed.positional.push(arg),
+        }
+    }
+    Ok(parsed)
+}
+
+impl Args {
+    fn browse_options(&self, service_type: &str) -> BrowseOptions {
+        let mut options = BrowseOptions {
+            service_type: service_type.to_string(),
+            domain: self.domain.clone(),
+            all_domains: self.all_domains,
+            interface_index: self.interface_index,
+            interfaces: self.interfaces.clone(),
+            result_filter: self.result_filter.clone(),
+            resolve_services: self.resolve,
+            ..Default::default()
+        };
+        if self.force_multicast {
+            options.force_multicast();
+        }
+        options.resolve.validate = self.validate;
+        options
+    }
+}
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+fn handle_interrupt() {
+    extern "C" fn on_signal(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+    unsafe {
+        libc::signal(libc::SIGINT, on_signal as *const () as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, on_signal as *const () as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+fn handle_interrupt() {}
+
+/// Block until interrupted or `timeout` passes
+fn wait(timeout: Option<Duration>) {
+    let start = Instant::now();
+    while !INTERRUPTED.load(Ordering::SeqCst) && timeout.is_none_or(|t| start.elapsed() < t) {
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// One-line human readable service
+fn describe(info: &ServiceInfo) -> String {
+    let mut line = format!("{} ({}.{})", info.name, info.service_type, info.domain);
+    if !info.host_name.is_empty() {
+        line.push_str(&format!(" at {}:{}", info.host_name, info.port));
+    }
+    if info.priority != 0 || info.weight != 0 {
+        line.push_str(&format!(" (priority {}, weight {})", info.priority, info.weight));
+    }
+    if !info.addresses.is_empty() {
+        line.push_str(&format!(" [{}]", info.addresses.join(", ")));
+    }
+    if let Some(dnssec) = info.dnssec {
+        line.push_str(&format!(" (dnssec {})", dnssec.as_str()));
+    }
+    let mut txt: Vec<String> = info.txt.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+    txt.sort();
+    if !txt.is_empty() {
+        line.push_str(&format!(" {}", txt.join(" ")));
+    }
+    line
+}
+
+fn browse(backend: Backend, args: &Args) -> Result<(), String> {
+    let [service_type] = &args.positional[..] else {
+        return Err("Usage: dns-sd browse <type>".into());
+    };
+
+    let json = args.json;
+    let table = Mutex::new(InstanceTable::new(InterfaceMode::Merged));
+    let result_filter = args.result_filter.clone();
+    let on_event: BrowseCallback = Arc::new(move |event: &str, info: ServiceInfo| {
+        let mut table = table.lock().unwrap();
+        let Some(event) = result_filter.screen(event, &info, table.contains(&info)) else {
+            return;
+        };
+        let Some((event, info)) = table.apply(event, info) else {
+            return;
+        };
+        drop(table);
+        if json {
+            println!("{}", json!({ "event": event, "service": Value::from(&info) }));
+            return;
+        }
+        let sign = match event.as_str() {
+            "serviceFound" => "+",
+            "serviceUpdated" => "~",
+            "serviceLost" => "-",
+            "resolveTimeout" => "?",
+            "resolveFailed" => "!",
+            _ => return,
+        };
+        match &info.reason {
+            Some(reason) => println!("{} {}: {}", sign, describe(&info), reason),
+            None => println!("{} {}", sign, describe(&info)),
+        }
+    });
+
+    let mut browser = dns_sd::open_browser(backend, &args.browse_options(service_type), on_event)?;
+    wait(args.timeout);
+    browser.stop();
+    Ok(())
+}
+
+fn register(backend: Backend, args: &Args) -> Result<(), String> {
+    let [name, service_type, port, txt @ ..] = &args.positional[..] else {
+        return Err("Usage: dns-sd register <name> <type> <port> [key=value...]".into());
+    };
+    let port = port.parse().map_err(|_| format!("Invalid port: {}", port))?;
+    let txt: TxtRecord = txt
+        .iter()
+        .map(|entry| {
+            let (k, v) = entry.split_once('=').unwrap_or((entry, ""));
+            (k.to_string(), v.to_string())
+        })
+        .collect();
+
+    let options = AdvertiseOptions {
+        name: name.clone(),
+        service_type: service_type.clone(),
+        port,
+        txt: (!txt.is_empty()).then_some(txt),
+        domain: args.domain.clone(),
+        interface_index: args.interface_index,
+        interfaces: args.interfaces.clone(),
+        ..Default::default()
+    };
+
+    let json = args.json;
+    let mut ad = dns_sd::open_advertisement(backend, &options, move |event, data| {
+        if json {
+            println!("{}", json!({ "event": event, "data": Value::from(data) }));
+        } else {
+            println!("{}: {}", event, data);
+        }
+    })?;
+    wait(args.timeout);
+    ad.stop();
+    Ok(())
+}
+
+fn alias(backend: Backend, args: &Args) -> Result<(), String> {
+    let [alias] = &args.positional[..] else {
+        return Err("Usage: dns-sd alias <hostname>".into());
+    };
+
+    let json = args.json;
+    let mut ad = dns_sd::open_alias(backend, alias, args.interface_index, move |event, data| {
+        if json {
+            println!("{}", json!({ "event": event, "data": Value::from(data) }));
+        } else {
+            println!("{}: {}", event, data);
+        }
+    })?;
+    wait(args.timeout);
+    ad.stop();
+    Ok(())
+}
+
+fn host(backend: Backend, args: &Args) -> Result<(), String> {
+    let [hostname, addresses @ ..] = &args.positional[..] else {
+        return Err("Usage: dns-sd host <hostname> <address...>".into());
+    };
+    let addresses = addresses
+        .iter()
+        .map(|a| a.parse().map_err(|_| format!("Invalid address: {}", a)))
+        .collect::<Result<Vec<std::net::IpAddr>, String>>()?;
+
+    let json = args.json;
+    let mut ad = dns_sd::open_host_records(backend, hostname, &addresses, args.interface_index, move |event, data| {
+        if json {
+            println!("{}", json!({ "event": event, "data": Value::from(data) }));
+        } else {
+            println!("{}: {}", event, data);
+        }
+    })?;
+    wait(args.timeout);
+    ad.stop();
+    Ok(())
+}
+
+fn resolve(backend: Backend, args: &Args) -> Result<(), String> {
+    let [name, service_type] = &args.positional[..] else {
+        return Err("Usage: dns-sd resolve <name> <type>".into());
+    };
+    let find = FindOptions {
+        browse: args.browse_options(service_type),
+        name: Some(name.clone()),
+        timeout: args.timeout.unwrap_or(Duration::from_secs(5)),
+    };
+
+    let info = dns_sd::find_blocking(backend, &find)?;
+    if args.json {
+        println!("{}", Value::from(&info));
+    } else {
+        println!("{}", describe(&info));
+    }
+    Ok(())
+}
+
+fn interfaces(args: &Args) -> Result<(), String> {
+    for iface in dns_sd::list_interfaces()? {
+        if args.json {
+            let mut value = json!({ "index": iface.index, "name": iface.name });
+            if let Some(mac) = &iface.mac {
+                value["mac"] = mac.as_str().into();
+            }
+            value["addresses"] = iface.addresses.iter().map(|a| a.to_string()).collect();
+            value["up"] = iface.up.into();
+            value["multicast"] = iface.multicast.into();
+            value["loopback"] = iface.loopback.into();
+            println!("{}", value);
+            continue;
+        }
+        let flags: Vec<&str> = [("up", iface.up), ("multicast", iface.multicast), ("loopback", iface.loopback)]
+            .into_iter()
+            .filter_map(|(name, set)| set.then_some(name))
+            .collect();
+        let addresses: Vec<String> = iface.addresses.iter().map(|a| a.to_string()).collect();
+        println!(
+            "{:>3} {:<12} {:<17} {:<20} {}",
+            iface.index,
+            iface.name,
+            iface.mac.as_deref().unwrap_or("-"),
+            flags.join(","),
+            addresses.join(" ")
+        );
+    }
+    Ok(())
+}
+
+fn health(args: &Args) -> Result<(), String> {
+    let report = dns_sd::health::check(args.timeout.unwrap_or(Duration::from_secs(2)));
+    if args.json {
+        let interfaces: Vec<Value> = report
+            .interfaces
+            .iter()
+            .map(|iface| {
+                let mut value = json!({
+                    "name": iface.name,
+                    "index": iface.index,
+                    "address": iface.address.to_string(),
+                    "sent": iface.sent,
+                    "looped": iface.looped,
+                    "peers": iface.peers,
+                });
+                if let Some(error) = &iface.error {
+                    value["error"] = error.as_str().into();
+                }
+                value
+            })
+            .collect();
+        println!(
+            "{}",
+            json!({
+                "healthy": report.healthy(),
+                "backend": report.backend.name(),
+                "nativeLibrary": check_json(&report.native_library),
+                "daemon": check_json(&report.daemon),
+                "roundTrip": check_json(&report.round_trip),
+                "interfaces": interfaces,
+            })
+        );
+    } else {
+        for (label, check) in [("native library", &report.native_library), ("daemon", &report.daemon), ("round-trip", &report.round_trip)] {
+            println!("{:<15} {:<8} {}", label, check.status.name(), check.detail);
+        }
+        for iface in &report.interfaces {
+            let status = match (&iface.error, iface.looped) {
+                (Some(e), _) => e.clone(),
+                (None, true) => format!("ok, {} peer(s)", iface.peers),
+                (None, false) => "query not looped back".to_string(),
+            };
+            println!("{:<15} {:<8} {}", iface.name, iface.address, status);
+        }
+    }
+    if report.healthy() { Ok(()) } else { Err("Discovery is not healthy on this machine".into()) }
+}
+
+fn selftest(args: &Args) -> Result<(), String> {
+    let report = dns_sd::selftest::run(args.timeout.unwrap_or(Duration::from_secs(3)));
+    if args.json {
+        let stages: Vec<Value> = report
+            .stages
+            .iter()
+            .map(|stage| {
+                let mut value = json!({
+                    "name": stage.name,
+                    "status": stage.check.status.name(),
+                    "detail": stage.check.detail,
+                    "elapsedMs": stage.check.elapsed.as_secs_f64() * 1000.0,
+                });
+                if let Some(hint) = &stage.hint {
+                    value["hint"] = hint.as_str().into();
+                }
+                value
+            })
+            .collect();
+        println!("{}", json!({ "passed": report.passed(), "backend": report.backend.name(), "stages": stages }));
+    } else {
+        for stage in &report.stages {
+            println!("{:<11} {:<8} {}", stage.name, stage.check.status.name(), stage.check.detail);
+            if let Some(hint) = &stage.hint {
+                println!("{:<20} {}", "", hint);
+            }
+        }
+    }
+    if report.passed() { Ok(()) } else { Err("Self-test failed".into()) }
+}
+
+/// A health check, as printed with `--json`
+fn check_json(check: &Check) -> Value {
+    json!({
+        "status": check.status.name(),
+        "detail": check.detail,
+        "elapsedMs": check.elapsed.as_secs_f64() * 1000.0,
+    })
+}
+
+/// Query answer, as printed with `--json`
+fn record_json(name: &str, record: &query::Record) -> Value {
+    let mut value = json!({
+        "name": name,
+        "type": rrtype_name(record.rrtype),
+        "ttl": record.ttl,
+        "data": record.data,
+    });
+    if let Some(flags) = record.flags {
+        value["flags"] = flags.into();
+    }
+    if let Some(dnssec) = record.dnssec {
+        value["dnssec"] = dnssec.as_str().into();
+    }
+    value
+}
+
+fn query(backend: Backend, args: &Args) -> Result<(), String> {
+    let (name, rrtype) = match &args.positional[..] {
+        [name] => (name, "A"),
+        [name, rrtype] => (name, rrtype.as_str()),
+        _ => return Err("Usage: dns-sd query <name> [rrtype]".into()),
+    };
+    let rrtype = query::parse_rrtype(rrtype).ok_or_else(|| format!("Unknown record type: {}", rrtype))?;
+
+    if args.watch {
+        let watched = name.clone();
+        let json = args.json;
+        let mut watch = query::watch(backend, name, rrtype, args.force_multicast, args.validate, move |event, record| {
+            if json {
+                println!("{}", json!({ "event": event, "record": record_json(&watched, &record) }));
+                return;
+            }
+            let sign = if event == "recordAdded" { "+" } else { "-" };
+            println!("{} {} {} {} {}", sign, watched, rrtype_name(record.rrtype), record.ttl, record.data);
+        })?;
+        wait(args.timeout);
+        watch.stop();
+        return Ok(());
+    }
+
+    let timeout = args.timeout.unwrap_or(Duration::from_secs(3));
+    let records = query::query(backend, name, rrtype, args.force_multicast, args.validate, timeout)?;
+    if records.is_empty() {
+        return Err(format!("No {} records for {}", rrtype_name(rrtype), name));
+    }
+    for record in &records {
+        if args.json {
+            println!("{}", record_json(name, record));
+        } else {
+            match record.dnssec {
+                Some(dnssec) => println!("{} {} {} {} ; {}", name, rrtype_name(record.rrtype), record.ttl, record.data, dnssec.as_str()),
+                None => println!("{} {} {} {}", name, rrtype_name(record.rrtype), record.ttl, record.data),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn types(backend: Backend, args: &Args) -> Result<(), String> {
+    if !args.positional.is_empty() {
+        return Err("Usage: dns-sd types".into());
+    }
+
+    let found = Arc::new(Mutex::new(BTreeSet::new()));
+    let collected = found.clone();
+    let on_event: BrowseCallback = Arc::new(move |event: &str, info: ServiceInfo| {
+        if event == "serviceFound" {
+            // Enumeration results are "_http" of type "_tcp"
+            collected.lock().unwrap().insert(format!("{}.{}", info.name, info.service_type));
+        }
+    });
+
+    let mut browser = dns_sd::open_browser(backend, &args.browse_options(TYPE_ENUMERATION), on_event)?;
+    wait(Some(args.timeout.unwrap_or(Duration::from_secs(3))));
+    browser.stop();
+
+    for service_type in found.lock().unwrap().iter() {
+        if args.json {
+            println!("{}", json!({ "type": service_type }));
+        } else {
+            println!("{}", service_type);
+        }
+    }
+    Ok(())
+}
+
+fn bridge(backend: Backend, args: &Args) -> Result<(), String> {
+    if !args.positional.is_empty() {
+        return Err("Usage: dns-sd bridge [--port <port>] [--origin <url>...]".into());
+    }
+    let mut config = BridgeConfig {
+        allowed_origins: args.origins.clone(),
+        allow_any_origin: args.allow_any_origin,
+        ..Default::default()
+    };
+    if let Some(port) = args.port {
+        config.addr.set_port(port);
+    }
+
+    eprintln!("Listening on ws://{}", config.addr);
+    // The listener blocks forever; a timeout or interrupt ends the process
+    thread::spawn(move || {
+        if let Err(e) = websocket::serve(backend, config) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    });
+    wait(args.timeout);
+    Ok(())
+}
+
+fn serve(backend: Backend, args: &Args) -> Result<(), String> {
+    if !args.positional.is_empty() {
+        return Err("Usage: dns-sd serve [--socket <path>]".into());
+    }
+    let transport = match &args.socket {
+        None => Transport::Stdio,
+        #[cfg(unix)]
+        Some(path) => Transport::Unix(path.into()),
+        #[cfg(not(unix))]
+        Some(_) => return Err("--socket requires a Unix platform".into()),
+    };
+    rpc::serve(backend, transport)
+}
+
+/// Apply --record, --replay, --pcap and --loopback-only before any handle
+/// starts
+fn start_session(args: &Args) -> Result<(), String> {
+    if args.loopback_only {
+        dns_sd::set_loopback_only(true)?;
+    }
+    if let Some(path) = &args.replay {
+        let count = dns_sd::load_recording(path)?;
+        dns_sd::set_backend(Some(Backend::Mock))?;
+        eprintln!("Replaying {} events from {}", count, path);
+    }
+    if let Some(path) = &args.record {
+        dns_sd::start_recording(path)?;
+    }
+    if let Some(path) = &args.pcap {
+        dns_sd::start_capture(path)?;
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let mut argv = std::env::args().skip(1);
+    let Some(command) = argv.next() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    };
+    if command == "-h" || command == "--help" {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    let args = match parse_args(argv) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Err(e) = start_session(&args) {
+        eprintln!("{}", e);
+        return ExitCode::FAILURE;
+    }
+
+    let backend = dns_sd::get_backend();
+    eprintln!("Using backend: {}", backend.name());
+    handle_interrupt();
+
+    let result = match command.as_str() {
+        "browse" => browse(backend, &args),
+        "register" => register(backend, &args),
+        "alias" => alias(backend, &args),
+        "host" => host(backend, &args),
+        "resolve" => resolve(backend, &args),
+        "query" => query(backend, &args),
+        "types" => types(backend, &args),
+        "health" => health(&args),
+        "selftest" => selftest(&args),
+        "interfaces" => interfaces(&args),
+        "bridge" => bridge(backend, &args),
+        "serve" => serve(backend, &args),
+        other => Err(format!("Unknown command: {}\n\n{}", other, USAGE)),
+    };
+
+    dns_sd::stop_recording();
+    dns_sd::stop_capture();
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
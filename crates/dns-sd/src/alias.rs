@@ -0,0 +1,148 @@
+//! Extra `.local` host names
+//!
+//! An alias makes the device reachable as `myproduct.local` besides its
+//! own host name; host records publish given addresses under any `.local`
+//! name, such as a hub answering for `hub.local`. The native backend
+//! registers the records themselves (a CNAME pointing at this host, or
+//! A/AAAA records). mdns-sd has no way to publish a bare record, so the
+//! fallback registers a `_device-info._tcp` placeholder whose target is
+//! the name, which makes it answer A/AAAA queries for it.
+
+use crate::ffi::{K_DNS_SERVICE_TYPE_A, K_DNS_SERVICE_TYPE_AAAA};
+use crate::handles::AdvertisementHandle;
+use crate::options::AdvertiseOptions;
+use crate::{AdvertiseData, Backend, fallback, manifest, may_fall_back, metrics, mock, native, native_interface, text_events};
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Service type of the fallback's placeholder registration
+pub const PLACEHOLDER_TYPE: &str = "_device-info._tcp";
+
+/// Longest alias label, in bytes
+const MAX_LABEL_BYTES: usize = 63;
+
+/// Check a host name and return its full name: `myproduct` and
+/// `myproduct.local` both become `myproduct.local.`
+pub fn normalize(alias: &str) -> Result<String, String> {
+    let label = alias.trim_end_matches('.');
+    let label = label.strip_suffix(".local").unwrap_or(label);
+    if label.is_empty() {
+        return Err("Host name must not be empty".to_string());
+    }
+    if label.len() > MAX_LABEL_BYTES {
+        return Err(format!("Host name {:?} is longer than {} bytes", alias, MAX_LABEL_BYTES));
+    }
+    if !label.chars().all(|c| c.is_alphanumeric() || c == '-') {
+        return Err(format!("Host name {:?} must be a single label of letters, digits and hyphens under .local", alias));
+    }
+    Ok(format!("{}.local.", label))
+}
+
+/// Publish `alias` for this host, reporting `(event, data)` to `callback`
+///
+/// `registered` carries the alias's full name. As with advertisements, a
+/// detected native backend that can't publish it (Avahi has no
+/// `DNSServiceRegisterRecord`) hands over to mdns-sd after reporting
+/// `backendFallback`.
+pub fn open_alias<F>(backend: Backend, alias: &str, interface_index: u32, callback: F) -> Result<AdvertisementHandle, String>
+where
+    F: Fn(&str, &AdvertiseData) + Send + Sync + 'static,
+{
+    let fullname = normalize(alias)?;
+    open(backend, &fullname, &[], interface_index, callback, |callback| {
+        let target = format!("{}.local.", manifest::local_hostname());
+        native::NativeAdvertisement::alias(&fullname, &target, native_interface(interface_index), callback)
+    })
+}
+
+/// Publish A/AAAA records for `hostname` with `addresses`, which needn't
+/// be this host's
+///
+/// Events and fallback as for [`open_alias`].
+pub fn open_host_records<F>(
+    backend: Backend,
+    hostname: &str,
+    addresses: &[IpAddr],
+    interface_index: u32,
+    callback: F,
+) -> Result<AdvertisementHandle, String>
+where
+    F: Fn(&str, &AdvertiseData) + Send + Sync + 'static,
+{
+    let fullname = normalize(hostname)?;
+    if addresses.is_empty() {
+        return Err(format!("No addresses to publish for {}", fullname));
+    }
+    let records: Vec<(u16, Vec<u8>)> = addresses
+        .iter()
+        .map(|addr| match addr {
+            IpAddr::V4(v4) => (K_DNS_SERVICE_TYPE_A, v4.octets().to_vec()),
+            IpAddr::V6(v6) => (K_DNS_SERVICE_TYPE_AAAA, v6.octets().to_vec()),
+        })
+        .collect();
+    open(backend, &fullname, addresses, interface_index, callback, |callback| {
+        native::NativeAdvertisement::records(&fullname, &records, native_interface(interface_index), callback)
+    })
+}
+
+/// Register with `native` or a placeholder service, falling back like
+/// `open_advertisement`
+///
+/// The placeholder publishes `addresses`, or this host's when empty.
+fn open<F, N>(
+    backend: Backend,
+    fullname: &str,
+    addresses: &[IpAddr],
+    interface_index: u32,
+    callback: F,
+    native: N,
+) -> Result<AdvertisementHandle, String>
+where
+    F: Fn(&str, &AdvertiseData) + Send + Sync + 'static,
+    N: Fn(Box<dyn Fn(&str, &str) + Send>) -> Result<native::NativeAdvertisement, String>,
+{
+    let label = fullname.strip_suffix(".local.").unwrap_or(fullname).to_string();
+    let callback = Arc::new(callback);
+    let attempt = |backend: Backend| {
+        let callback = callback.clone();
+        let result = match backend {
+            Backend::Native => {
+                native(Box::new(text_events(move |event: &str, data: &AdvertiseData| callback(event, data))))
+                    .map(AdvertisementHandle::Native)
+            }
+            Backend::Fallback | Backend::Mock => {
+                let options = AdvertiseOptions {
+                    name: label.clone(),
+                    service_type: PLACEHOLDER_TYPE.to_string(),
+                    port: 0,
+                    host: Some(label.clone()),
+                    interface_index,
+                    addresses: addresses.iter().map(IpAddr::to_string).collect(),
+                    ..Default::default()
+                };
+                // Report the host name rather than the placeholder's instance name
+                let fullname = fullname.to_string();
+                let callback = move |event: &str, data: &AdvertiseData| match event {
+                    "registered" => callback(event, &AdvertiseData::Text(fullname.clone())),
+                    _ => callback(event, data),
+                };
+                match backend {
+                    Backend::Mock => mock::MockAdvertisement::new(&options, text_events(callback)).map(AdvertisementHandle::Mock),
+                    _ => fallback::FallbackAdvertisement::new(&options, callback).map(AdvertisementHandle::Fallback),
+                }
+            }
+        };
+        if let Err(e) = &result {
+            metrics::backend_error(&format!("Host records of {} on {}: {}", label, backend.name(), e));
+        }
+        result
+    };
+    match attempt(backend) {
+        Err(e) if may_fall_back(backend, None, interface_index) => {
+            tracing::warn!("Native registration of {} failed ({}); publishing address records on mdns-sd", fullname, e);
+            callback("backendFallback", &AdvertiseData::Text(e));
+            attempt(Backend::Fallback)
+        }
+        result => result,
+    }
+}
@@ -0,0 +1,325 @@
+//! Process-wide cache of resolved services
+//!
+//! Every backend browser reports through [`record`], so resolved services
+//! are kept by full name and interface, whichever browser saw them. A native
+//! browser that finds an instance with a fresh entry reports the entry
+//! instead of resolving it again. Entries expire a TTL after their contents
+//! last changed (identical re-reports don't extend them); a sweeper thread
+//! evicts them and reports each eviction to the `cacheEvicted` callback.
+//! Services reported lost are dropped silently.
+//!
+//! With a cache file (`set_path` or `DNS_SD_CACHE_FILE`), services found and
+//! not lost are also written to disk as JSON lines, regardless of TTL. Those
+//! loaded from the file at startup are reported by browsers as `stale` until
+//! the network confirms them.
+
+use crate::{ServiceInfo, Sink};
+use serde_json::Value;
+use neon::event::Channel;
+use neon::handle::Root;
+use neon::prelude::*;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Lifetime of an entry whose records carried no TTL (the mDNS host record TTL)
+const DEFAULT_TTL: Duration = Duration::from_secs(120);
+
+/// How often the sweeper looks for expired entries and writes the file
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+struct Entry {
+    info: ServiceInfo,
+    expires: Instant,
+}
+
+/// Last-known service for the cache file
+struct Known {
+    info: ServiceInfo,
+    /// Reported by a backend since the process started (not just loaded)
+    confirmed: bool,
+}
+
+/// Lower-cased full name and interface index
+type Key = (String, u32);
+
+struct Cache {
+    enabled: bool,
+    entries: HashMap<Key, Entry>,
+    /// Whether the sweeper thread is running
+    sweeping: bool,
+    /// Cache file, if persisting
+    path: Option<PathBuf>,
+    /// Services found and not lost, by lower-cased full name
+    known: HashMap<String, Known>,
+    /// `known` changed since the file was last written
+    dirty: bool,
+}
+
+static CACHE: Lazy<Mutex<Cache>> = Lazy::new(|| {
+    let mut cache = Cache {
+        enabled: true,
+        entries: HashMap::new(),
+        sweeping: false,
+        path: None,
+        known: HashMap::new(),
+        dirty: false,
+    };
+    if let Ok(path) = std::env::var("DNS_SD_CACHE_FILE")
+        && let Err(e) = cache.open(PathBuf::from(path))
+    {
+        tracing::warn!("{}", e);
+    }
+    Mutex::new(cache)
+});
+
+/// JS function receiving `(service)`
+static SINK: Sink = Sink::new();
+
+fn key(full_name: &str, interface_index: u32) -> Key {
+    (full_name.to_ascii_lowercase(), interface_index)
+}
+
+/// Resolved far enough to stand in for a resolve
+fn is_resolved(info: &ServiceInfo) -> bool {
+    !info.full_name.is_empty() && !info.host_name.is_empty() && !info.addresses.is_empty()
+}
+
+/// Same records, ignoring flags and TTL
+fn same_records(a: &ServiceInfo, b: &ServiceInfo) -> bool {
+    a.host_name == b.host_name
+        && a.port == b.port
+        && a.priority == b.priority
+        && a.weight == b.weight
+        && a.txt == b.txt
+        && a.addresses.len() == b.addresses.len()
+        && a.addresses.iter().all(|addr| b.addresses.contains(addr))
+}
+
+impl Cache {
+    /// Persist to `path`, loading what it holds
+    fn open(&mut self, path: PathBuf) -> Result<usize, String> {
+        self.known = load(&path)?;
+        self.path = Some(path);
+        self.dirty = false;
+        Ok(self.known.len())
+    }
+
+    fn ensure_sweeper(&mut self) {
+        if !self.sweeping {
+            self.sweeping = true;
+            thread::spawn(sweep);
+        }
+    }
+
+    /// Write the cache file if it changed
+    fn flush(&mut self) {
+        let Some(path) = self.path.as_ref().filter(|_| self.dirty) else { return };
+        self.dirty = false;
+        let mut known: Vec<&ServiceInfo> = self.known.values().map(|k| &k.info).collect();
+        known.sort_by(|a, b| a.full_name.cmp(&b.full_name));
+        if let Err(e) = save(path, &known) {
+            tracing::warn!("Failed to write cache file {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Read a cache file; a missing file is empty
+fn load(path: &Path) -> Result<HashMap<String, Known>, String> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(format!("Failed to read cache file {}: {}", path.display(), e)),
+    };
+    let mut known = HashMap::new();
+    for (n, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let value: Value = serde_json::from_str(line).map_err(|e| format!("{}:{}: {}", path.display(), n + 1, e))?;
+        let info = ServiceInfo { flags: None, stale: true, ..ServiceInfo::from(&value) }.normalize();
+        if is_resolved(&info) {
+            known.insert(info.full_name.to_ascii_lowercase(), Known { info, confirmed: false });
+        }
+    }
+    Ok(known)
+}
+
+/// Replace the cache file, through a temporary file so a crash can't truncate it
+fn save(path: &Path, services: &[&ServiceInfo]) -> std::io::Result<()> {
+    let mut text = String::new();
+    for info in services {
+        let info = ServiceInfo { flags: None, stale: false, timings: None, ..(*info).clone() };
+        text.push_str(&Value::from(&info).to_string());
+        text.push('\n');
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, text)?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Update the cache from a (normalized) backend event
+pub fn record(event: &str, info: &ServiceInfo) {
+    let mut cache = CACHE.lock().unwrap();
+    if !cache.enabled {
+        return;
+    }
+    match event {
+        "serviceFound" if is_resolved(info) => {
+            let now = Instant::now();
+            let ttl = if info.ttl > 0 { Duration::from_secs(info.ttl as u64) } else { DEFAULT_TTL };
+            let entry = cache.entries.entry(key(&info.full_name, info.interface_index));
+            let entry = entry.or_insert_with(|| Entry { info: info.clone(), expires: now + ttl });
+            if !same_records(&entry.info, info) {
+                entry.expires = now + ttl;
+            }
+            entry.info = info.clone();
+
+            if cache.path.is_some() {
+                let known = Known { info: info.clone(), confirmed: true };
+                let previous = cache.known.insert(info.full_name.to_ascii_lowercase(), known);
+                if !previous.is_some_and(|p| p.confirmed && same_records(&p.info, info)) {
+                    cache.dirty = true;
+                }
+            }
+            cache.ensure_sweeper();
+        }
+        "serviceLost" if !info.full_name.is_empty() => {
+            cache.entries.remove(&key(&info.full_name, info.interface_index));
+            if cache.known.remove(&info.full_name.to_ascii_lowercase()).is_some() {
+                cache.dirty = true;
+                cache.ensure_sweeper();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// A fresh entry for `full_name` seen on `interface_index`
+pub fn lookup(full_name: &str, interface_index: u32) -> Option<ServiceInfo> {
+    let cache = CACHE.lock().unwrap();
+    cache
+        .entries
+        .get(&key(full_name, interface_index))
+        .filter(|entry| entry.expires > Instant::now())
+        .map(|entry| entry.info.clone())
+}
+
+/// Services loaded from the cache file and not yet confirmed, for a browse
+/// of `service_type` in `domain` (`interface_index` 0 = any)
+pub fn stale(service_type: &str, domain: &str, interface_index: u32) -> Vec<ServiceInfo> {
+    let cache = CACHE.lock().unwrap();
+    if !cache.enabled {
+        return Vec::new();
+    }
+    let domain = domain.trim_end_matches('.');
+    let mut services: Vec<ServiceInfo> = cache
+        .known
+        .values()
+        .filter(|k| !k.confirmed)
+        .map(|k| &k.info)
+        .filter(|info| info.service_type.eq_ignore_ascii_case(service_type) && info.domain.eq_ignore_ascii_case(domain))
+        .filter(|info| interface_index == 0 || info.interface_index == interface_index)
+        .cloned()
+        .collect();
+    services.sort_by(|a, b| a.full_name.cmp(&b.full_name));
+    services
+}
+
+/// Every entry still fresh, sorted by full name
+pub fn entries() -> Vec<ServiceInfo> {
+    let now = Instant::now();
+    let cache = CACHE.lock().unwrap();
+    let mut services: Vec<ServiceInfo> =
+        cache.entries.values().filter(|e| e.expires > now).map(|e| e.info.clone()).collect();
+    services.sort_by(|a, b| (&a.full_name, a.interface_index).cmp(&(&b.full_name, b.interface_index)));
+    services
+}
+
+/// Drop every entry without reporting evictions, emptying the cache file too
+pub fn clear() {
+    let mut cache = CACHE.lock().unwrap();
+    cache.entries.clear();
+    if !cache.known.is_empty() {
+        cache.known.clear();
+        cache.dirty = true;
+        cache.flush();
+    }
+}
+
+/// Expire every entry now, e.g. after a sleep during which TTLs didn't
+/// count down; the sweeper reports them evicted
+pub fn expire() {
+    let mut cache = CACHE.lock().unwrap();
+    let now = Instant::now();
+    for entry in cache.entries.values_mut() {
+        entry.expires = now;
+    }
+    if !cache.entries.is_empty() {
+        cache.ensure_sweeper();
+    }
+}
+
+/// Turn the cache on or off; turning it off also clears it
+pub fn set_enabled(enabled: bool) {
+    let mut cache = CACHE.lock().unwrap();
+    cache.enabled = enabled;
+    if !enabled {
+        cache.entries.clear();
+    }
+}
+
+/// Persist last-known services to `path`, loading what it holds; `None`
+/// stops writing (the file is kept). Returns the number of services loaded.
+pub fn set_path(path: Option<PathBuf>) -> Result<usize, String> {
+    let mut cache = CACHE.lock().unwrap();
+    cache.flush();
+    match path {
+        Some(path) => cache.open(path),
+        None => {
+            cache.path = None;
+            cache.known.clear();
+            Ok(0)
+        }
+    }
+}
+
+/// Deliver evicted services to `callback`, or stop with `None`
+pub fn set_callback(sink: Option<(Channel, Root<JsFunction>)>) {
+    SINK.set(sink);
+}
+
+/// Evict expired entries and write the cache file until there's nothing left to do
+fn sweep() {
+    loop {
+        thread::sleep(SWEEP_INTERVAL);
+        let evicted: Vec<ServiceInfo> = {
+            let mut cache = CACHE.lock().unwrap();
+            let now = Instant::now();
+            let expired: Vec<Key> = cache.entries.iter().filter(|(_, e)| e.expires <= now).map(|(k, _)| k.clone()).collect();
+            let evicted = expired.iter().filter_map(|k| cache.entries.remove(k)).map(|e| e.info).collect();
+            cache.flush();
+            if cache.entries.is_empty() {
+                cache.sweeping = false;
+                drop(cache);
+                notify(evicted);
+                return;
+            }
+            evicted
+        };
+        notify(evicted);
+    }
+}
+
+fn notify(evicted: Vec<ServiceInfo>) {
+    if evicted.is_empty() {
+        return;
+    }
+    for info in &evicted {
+        tracing::debug!("Cache entry for {} expired", info.full_name);
+    }
+    for info in evicted {
+        SINK.call(move |cx| Ok(vec![crate::service_info_to_js(cx, &info)?.upcast()]));
+    }
+}
@@ -0,0 +1,142 @@
+//! Pcap capture of the fallback's mDNS traffic
+//!
+//! Packets come from the [`tap`](crate::tap), which hears only the UDP
+//! payload and its source. Each is written as a raw IP packet (link type
+//! 101) with IP and UDP headers rebuilt around it, addressed to the mDNS
+//! group, so Wireshark and tcpdump decode the DNS inside.
+
+use crate::multicast::{GROUP_V4, GROUP_V6, MDNS_PORT};
+use crate::tap::{self, Packet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+/// LINKTYPE_RAW: each record starts with an IPv4 or IPv6 header
+const LINKTYPE_RAW: u32 = 101;
+
+const SNAPLEN: u32 = 65535;
+
+struct Capture {
+    out: BufWriter<File>,
+    tap: u32,
+}
+
+static CAPTURE: Mutex<Option<Capture>> = Mutex::new(None);
+
+/// Start writing the fallback's mDNS packets to `path`, replacing any
+/// capture in progress
+pub fn start_capture(path: &str) -> Result<(), String> {
+    let mut out = BufWriter::new(File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?);
+    // Magic, version 2.4, no timezone offset or accuracy, snaplen, link type
+    let mut header = Vec::with_capacity(24);
+    header.extend_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+    header.extend_from_slice(&2u16.to_le_bytes());
+    header.extend_from_slice(&4u16.to_le_bytes());
+    header.extend_from_slice(&[0; 8]);
+    header.extend_from_slice(&SNAPLEN.to_le_bytes());
+    header.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+    out.write_all(&header).and_then(|()| out.flush()).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+
+    let mut capture = CAPTURE.lock().unwrap();
+    if let Some(previous) = capture.take() {
+        tap::remove(previous.tap);
+    }
+    let id = tap::add(Arc::new(write));
+    *capture = Some(Capture { out, tap: id });
+    Ok(())
+}
+
+/// Stop the capture in progress, returning false if there was none
+pub fn stop_capture() -> bool {
+    let Some(capture) = CAPTURE.lock().unwrap().take() else {
+        return false;
+    };
+    tap::remove(capture.tap);
+    true
+}
+
+fn write(packet: &Packet) {
+    let mut slot = CAPTURE.lock().unwrap();
+    let Some(capture) = slot.as_mut() else {
+        return;
+    };
+    let frame = ip_frame(packet);
+    let since = packet.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut record = Vec::with_capacity(16 + frame.len());
+    record.extend_from_slice(&(since.as_secs() as u32).to_le_bytes());
+    record.extend_from_slice(&since.subsec_micros().to_le_bytes());
+    record.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+    record.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+    record.extend_from_slice(&frame);
+    // Flushed per packet, so a crash leaves a readable capture
+    if let Err(e) = capture.out.write_all(&record).and_then(|()| capture.out.flush()) {
+        tracing::warn!("Capture stopped: {}", e);
+        if let Some(capture) = slot.take() {
+            tap::remove(capture.tap);
+        }
+    }
+}
+
+/// `packet`'s payload in UDP and IP headers, sent from its source to the
+/// mDNS group
+fn ip_frame(packet: &Packet) -> Vec<u8> {
+    let payload = &packet.data;
+    let udp_len = (8 + payload.len()) as u16;
+    let mut udp = Vec::with_capacity(udp_len as usize);
+    udp.extend_from_slice(&packet.source.port().to_be_bytes());
+    udp.extend_from_slice(&MDNS_PORT.to_be_bytes());
+    udp.extend_from_slice(&udp_len.to_be_bytes());
+    udp.extend_from_slice(&[0, 0]);
+    udp.extend_from_slice(payload);
+
+    match packet.source.ip() {
+        IpAddr::V4(source) => {
+            let mut frame = Vec::with_capacity(20 + udp.len());
+            frame.extend_from_slice(&[0x45, 0]);
+            frame.extend_from_slice(&(20 + udp_len).to_be_bytes());
+            // Id, no fragmentation, TTL 255 as RFC 6762 requires, UDP
+            frame.extend_from_slice(&[0, 0, 0x40, 0, 255, 17, 0, 0]);
+            frame.extend_from_slice(&source.octets());
+            frame.extend_from_slice(&GROUP_V4.octets());
+            let sum = checksum(0, &frame);
+            frame[10..12].copy_from_slice(&sum.to_be_bytes());
+            // The UDP checksum is optional over IPv4 and left 0
+            frame.extend_from_slice(&udp);
+            frame
+        }
+        IpAddr::V6(source) => {
+            let sum = checksum(pseudo_header_v6(&source, &GROUP_V6, udp_len), &udp);
+            // 0 means no checksum, which IPv6 doesn't allow
+            let sum = if sum == 0 { 0xffff } else { sum };
+            udp[6..8].copy_from_slice(&sum.to_be_bytes());
+            let mut frame = Vec::with_capacity(40 + udp.len());
+            frame.extend_from_slice(&[0x60, 0, 0, 0]);
+            frame.extend_from_slice(&udp_len.to_be_bytes());
+            // Next header UDP, hop limit 255
+            frame.extend_from_slice(&[17, 255]);
+            frame.extend_from_slice(&source.octets());
+            frame.extend_from_slice(&GROUP_V6.octets());
+            frame.extend_from_slice(&udp);
+            frame
+        }
+    }
+}
+
+/// Sum of the IPv6 pseudo-header's 16-bit words, to start the UDP checksum
+fn pseudo_header_v6(source: &Ipv6Addr, destination: &Ipv6Addr, udp_len: u16) -> u32 {
+    let words = |ip: &Ipv6Addr| ip.segments().iter().map(|w| *w as u32).sum::<u32>();
+    words(source) + words(destination) + udp_len as u32 + 17
+}
+
+/// Internet checksum of `data`, starting from the partial sum `sum`
+fn checksum(mut sum: u32, data: &[u8]) -> u16 {
+    for pair in data.chunks(2) {
+        sum += u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]) as u32;
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
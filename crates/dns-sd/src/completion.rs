@@ -0,0 +1,162 @@
+//! Withholding services until they are resolved far enough, for browses
+//! with `waitForComplete`, `minimumAddresses` or `emitPartial: false`
+//!
+//! A resolve reports its service several times as answers arrive: first
+//! with the host and port, then again with each address. A sighting is held
+//! back until it has a host name, [`Completion::min_addresses`] addresses
+//! and, with [`Completion::each_family`], an address of every requested
+//! family. If no more answers come for [`QUIET_PERIOD`], the family
+//! requirement is dropped and it is released if it has enough addresses;
+//! one that never does is never reported (the native backend reports
+//! `resolveFailed` instead).
+
+use crate::BrowseCallback;
+use crate::ServiceInfo;
+use crate::ffi::{K_DNS_SERVICE_PROTOCOL_IPV4, K_DNS_SERVICE_PROTOCOL_IPV6};
+use crate::instances::instance_key;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a held sighting waits for another answer before it is released
+/// with the addresses it has
+pub const QUIET_PERIOD: Duration = Duration::from_millis(500);
+
+/// What a sighting needs before `serviceFound` reports it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Completion {
+    /// An address of each family the resolve asks for (`waitForComplete`)
+    pub each_family: bool,
+    /// Addresses needed, whatever their family (`minimumAddresses`)
+    pub min_addresses: usize,
+}
+
+/// Instance and interface of a sighting
+type Key = (String, u32);
+
+struct Held {
+    info: ServiceInfo,
+    due: Instant,
+}
+
+struct State {
+    held: HashMap<Key, Held>,
+    /// Sightings passed on, whose later events go through untouched
+    released: HashSet<Key>,
+    timer_armed: bool,
+}
+
+struct Gate {
+    min_addresses: usize,
+    want_ipv4: bool,
+    want_ipv6: bool,
+    state: Mutex<State>,
+    on_event: BrowseCallback,
+}
+
+/// Wrap `on_event` so `serviceFound` waits for `completion`
+///
+/// `protocol` is the resolve's address family (`kDNSServiceProtocol_*`, 0
+/// for both), which decides the families [`Completion::each_family`] asks for.
+pub fn complete_only(on_event: BrowseCallback, completion: Completion, protocol: u32) -> BrowseCallback {
+    let gate = Arc::new(Gate {
+        min_addresses: completion.min_addresses,
+        want_ipv4: completion.each_family && protocol != K_DNS_SERVICE_PROTOCOL_IPV6,
+        want_ipv6: completion.each_family && protocol != K_DNS_SERVICE_PROTOCOL_IPV4,
+        state: Mutex::new(State {
+            held: HashMap::new(),
+            released: HashSet::new(),
+            timer_armed: false,
+        }),
+        on_event,
+    });
+    Arc::new(move |event: &str, info: ServiceInfo| gate.push(event, info))
+}
+
+impl Gate {
+    fn push(self: &Arc<Self>, event: &str, info: ServiceInfo) {
+        let key = (instance_key(&info), info.interface_index);
+        let mut state = self.state.lock().unwrap();
+        match event {
+            "serviceFound" if !state.released.contains(&key) => {
+                if self.is_complete(&info) {
+                    state.held.remove(&key);
+                    state.released.insert(key);
+                    drop(state);
+                    (self.on_event)(event, info);
+                    return;
+                }
+                state.held.insert(key, Held { info, due: Instant::now() + QUIET_PERIOD });
+                if !std::mem::replace(&mut state.timer_armed, true) {
+                    drop(state);
+                    self.start_timer();
+                }
+            }
+            "serviceLost" => {
+                let held = state.held.remove(&key).is_some();
+                let released = state.released.remove(&key);
+                drop(state);
+                // A sighting that was only ever held was never reported
+                if released || !held {
+                    (self.on_event)(event, info);
+                }
+            }
+            _ => {
+                drop(state);
+                (self.on_event)(event, info);
+            }
+        }
+    }
+
+    /// Enough addresses to release once the sighting has gone quiet
+    fn has_min_addresses(&self, info: &ServiceInfo) -> bool {
+        info.addresses.len() >= self.min_addresses.max(1)
+    }
+
+    /// Host name known, enough addresses and one of each wanted family
+    fn is_complete(&self, info: &ServiceInfo) -> bool {
+        let families: Vec<bool> = info
+            .addresses
+            .iter()
+            .filter_map(|addr| addr.split('%').next()?.parse::<IpAddr>().ok())
+            .map(|ip| ip.is_ipv4())
+            .collect();
+        !info.host_name.is_empty()
+            && self.has_min_addresses(info)
+            && (!self.want_ipv4 || families.contains(&true))
+            && (!self.want_ipv6 || families.contains(&false))
+    }
+
+    /// Release held sightings as they go quiet, until none are left
+    fn start_timer(self: &Arc<Self>) {
+        let gate = self.clone();
+        thread::spawn(move || loop {
+            let now = Instant::now();
+            let mut ready = Vec::new();
+            let next = {
+                let mut state = gate.state.lock().unwrap();
+                let due: Vec<Key> = state.held.iter().filter(|(_, held)| held.due <= now).map(|(key, _)| key.clone()).collect();
+                for key in due {
+                    let held = state.held.remove(&key).unwrap();
+                    // Short of addresses it stays unreported until more arrive
+                    if gate.has_min_addresses(&held.info) {
+                        state.released.insert(key);
+                        ready.push(held.info);
+                    }
+                }
+                let next = state.held.values().map(|held| held.due).min();
+                state.timer_armed = next.is_some();
+                next
+            };
+            for info in ready {
+                (gate.on_event)("serviceFound", info);
+            }
+            match next {
+                Some(due) => thread::sleep(due.saturating_duration_since(Instant::now())),
+                None => break,
+            }
+        });
+    }
+}
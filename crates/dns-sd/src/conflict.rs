@@ -0,0 +1,155 @@
+//! Host name conflicts seen by the fallback
+//!
+//! mdns-sd probes for service names but not for the host name it answers
+//! for, and goes on answering when another device claims the same name.
+//! Peers then cache whichever answer came last and both devices become
+//! unreachable. Each fallback advertisement [`watch`]es its host name, and
+//! an A/AAAA answer for it from another device, with an address the
+//! advertisement doesn't publish, is reported as `hostnameConflict`. The
+//! packets come from the [`tap`](crate::tap).
+
+use crate::HostnameConflict;
+use crate::flush::host_key;
+use crate::multicast;
+use crate::tap::{self, Packet};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A conflict already reported isn't reported again for this long
+const REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Receiver of the conflicts found for one advertisement
+pub type ConflictCallback = Arc<Mutex<Box<dyn Fn(HostnameConflict) + Send>>>;
+
+struct Watch {
+    host: String,
+    /// Addresses the advertisement publishes; empty for this host's own
+    addresses: Vec<IpAddr>,
+    callback: ConflictCallback,
+    /// When each (responder, address) conflict was last reported
+    reported: HashMap<(IpAddr, IpAddr), Instant>,
+}
+
+static WATCHES: Lazy<Mutex<HashMap<u32, Watch>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+static NEXT_WATCH: AtomicU32 = AtomicU32::new(1);
+
+/// Id of the tap, added while anything is watched
+static TAP: Mutex<Option<u32>> = Mutex::new(None);
+
+/// Report answers that conflict with `host` to `callback`, returning an id
+/// for [`unwatch`]
+///
+/// `addresses` are those the advertisement publishes; empty means this
+/// host's own, whichever they currently are.
+pub fn watch(host: &str, addresses: &[String], callback: ConflictCallback) -> u32 {
+    let id = NEXT_WATCH.fetch_add(1, Ordering::SeqCst);
+    let mut watches = WATCHES.lock().unwrap();
+    watches.insert(
+        id,
+        Watch {
+            host: host_key(host),
+            addresses: parse_addresses(addresses),
+            callback,
+            reported: HashMap::new(),
+        },
+    );
+    let mut tap = TAP.lock().unwrap();
+    if tap.is_none() {
+        *tap = Some(tap::add(Arc::new(record)));
+    }
+    id
+}
+
+/// Follow a changed host name or address list of the watch `id`
+pub fn update(id: u32, host: &str, addresses: &[String]) {
+    if let Some(watch) = WATCHES.lock().unwrap().get_mut(&id) {
+        watch.host = host_key(host);
+        watch.addresses = parse_addresses(addresses);
+        watch.reported.clear();
+    }
+}
+
+/// Stop watching, removing the tap after the last watch
+pub fn unwatch(id: u32) {
+    let mut watches = WATCHES.lock().unwrap();
+    watches.remove(&id);
+    if watches.is_empty()
+        && let Some(tap) = TAP.lock().unwrap().take()
+    {
+        tap::remove(tap);
+    }
+}
+
+/// Addresses in `addresses`, without the `%interface` scopes
+fn parse_addresses(addresses: &[String]) -> Vec<IpAddr> {
+    addresses
+        .iter()
+        .filter_map(|addr| addr.split('%').next().unwrap_or(addr).parse().ok())
+        .collect()
+}
+
+fn record(packet: &Packet) {
+    // The daemon's own answers are heard too while multicast loop is on
+    if packet.outgoing {
+        return;
+    }
+    let Some(records) = multicast::response_records(&packet.data) else { return };
+    let answers: Vec<(String, IpAddr)> = records
+        .iter()
+        .filter(|record| record.ttl > 0)
+        .filter_map(|record| Some((host_key(&record.name), record.address()?)))
+        .collect();
+    if answers.is_empty() {
+        return;
+    }
+
+    let now = Instant::now();
+    let mut own: Option<Vec<IpAddr>> = None;
+    let mut reports = Vec::new();
+    let mut watches = WATCHES.lock().unwrap();
+    for watch in watches.values_mut() {
+        for (name, address) in &answers {
+            if *name != watch.host {
+                continue;
+            }
+            let published = if watch.addresses.is_empty() {
+                own.get_or_insert_with(|| {
+                    if_addrs::get_if_addrs().unwrap_or_default().iter().map(|a| a.ip()).collect()
+                })
+            } else {
+                &watch.addresses
+            };
+            // The same answer from another responder, such as a sleep proxy, is no conflict
+            if published.contains(address) {
+                continue;
+            }
+            let key = (packet.source.ip(), *address);
+            if watch.reported.get(&key).is_some_and(|at| now.duration_since(*at) < REPORT_INTERVAL) {
+                continue;
+            }
+            watch.reported.insert(key, now);
+            tracing::warn!("{}. is also answered by {} with {}", watch.host, packet.source.ip(), address);
+            reports.push((watch.callback.clone(), details(&watch.host, *address, packet)));
+        }
+    }
+    drop(watches);
+
+    for (callback, conflict) in reports {
+        (callback.lock().unwrap())(conflict);
+    }
+}
+
+fn details(host: &str, address: IpAddr, packet: &Packet) -> HostnameConflict {
+    HostnameConflict {
+        host_name: format!("{}.", host),
+        address,
+        responder: packet.source.ip(),
+        interface: packet.interface.clone(),
+        interface_index: packet.interface_index,
+    }
+}
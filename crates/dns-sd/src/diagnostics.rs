@@ -0,0 +1,89 @@
+//! One structured report of the addon's internal state, for bug reports
+//!
+//! `dump` gathers what a maintainer asks for first: the backend and the
+//! library behind it, every live handle with its threads and queued events,
+//! the shared browsers and their subscribers, the discovery cache and the
+//! most recent backend errors. It reads state only and returns at once;
+//! `health::check` is the one that probes the network.
+
+use crate::{cache, fallback, ffi, get_backend, handles, loopback_only, metrics, native, shared};
+use serde_json::{Value, json};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn millis(time: SystemTime) -> Value {
+    time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64).into()
+}
+
+fn handle(environment: usize, kind: &str, info: &handles::HandleInfo) -> Value {
+    let mut value = json!({
+        "environment": environment,
+        "kind": kind,
+        "id": info.id,
+        "type": info.service_type,
+        "backend": info.backend.name(),
+        "state": info.state,
+        "createdAt": millis(info.created_at),
+        "threads": info.threads,
+    });
+    if let Some(depth) = info.queue_depth {
+        value["queueDepth"] = depth.into();
+    }
+    value
+}
+
+/// Snapshot of the addon's state across every JS environment
+pub fn dump() -> Value {
+    let library = match native::DnsSdLibrary::get() {
+        Ok(_) => json!({ "path": ffi::get_library_path(), "loaded": true }),
+        Err(e) => json!({ "path": ffi::get_library_path(), "loaded": false, "error": e }),
+    };
+
+    let mut live = Vec::new();
+    for (environment, env) in handles::environments().iter().enumerate() {
+        live.extend(env.browsers.list().iter().map(|info| handle(environment, "browser", info)));
+        live.extend(env.advertisements.list().iter().map(|info| handle(environment, "advertisement", info)));
+    }
+
+    let shared: Vec<Value> = shared::list()
+        .iter()
+        .map(|browse| {
+            let mut value = json!({
+                "backend": browse.backend.name(),
+                "type": browse.service_type,
+                "interfaceIndex": browse.interface_index,
+                "subscribers": browse.subscribers,
+                "services": browse.known,
+            });
+            if let Some(domain) = &browse.domain {
+                value["domain"] = domain.as_str().into();
+            }
+            if let Some(refresh) = browse.refresh {
+                value["refreshIntervalMs"] = (refresh.as_millis() as u64).into();
+            }
+            value
+        })
+        .collect();
+
+    let errors: Vec<Value> = metrics::recent_errors()
+        .into_iter()
+        .map(|(time, message)| json!({ "time": millis(time), "message": message }))
+        .collect();
+
+    let snapshot = metrics::snapshot();
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "platform": format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH),
+        "time": millis(SystemTime::now()),
+        "backend": get_backend().name(),
+        "nativeLibrary": library,
+        "loopbackOnly": loopback_only(),
+        "fallbackDaemonRefs": fallback::daemon_refs(),
+        "handles": live,
+        "sharedBrowsers": shared,
+        "pendingStops": handles::pending_stops(),
+        "cache": cache::entries().iter().map(Value::from).collect::<Vec<_>>(),
+        "droppedEvents": snapshot.events_dropped,
+        "backendErrors": snapshot.backend_errors,
+        "recentErrors": errors,
+    })
+}
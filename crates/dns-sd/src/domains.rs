@@ -0,0 +1,149 @@
+//! Browsing one service type in every browse domain (`browseAllDomains`)
+//!
+//! The requested domain (`.local` unless given) is browsed right away. On the
+//! native backend the daemon's browse domains are then enumerated, and each
+//! one that appears gets a browse of its own; results keep the domain they
+//! were found in. A domain the daemon withdraws has its browse stopped and
+//! its services reported lost. mdns-sd and the mock backend know no domains
+//! beyond `.local`, so there the requested domain is all that is browsed.
+
+use crate::handles::{BrowserHandle, Stoppable};
+use crate::instances::instance_key;
+use crate::{Backend, BrowseCallback, ServiceInfo, native};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Opens the browse of one domain (None = the requested domain)
+pub type OpenDomain = dyn Fn(Option<&str>, BrowseCallback) -> Result<BrowserHandle, String> + Send + Sync;
+
+/// Last result of each instance reported by one domain's browse
+type Reported = Arc<Mutex<HashMap<(String, u32), ServiceInfo>>>;
+
+/// One domain's browse and what it has reported
+struct DomainBrowse {
+    browser: BrowserHandle,
+    reported: Reported,
+}
+
+/// Browses of every known domain, grown and shrunk as the daemon
+/// enumerates domains
+pub struct DomainsBrowser {
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+    browses: Arc<Mutex<HashMap<String, DomainBrowse>>>,
+    backend: Option<Backend>,
+}
+
+impl DomainsBrowser {
+    /// Backend serving the requested domain's browse
+    pub fn backend(&self) -> Option<Backend> {
+        self.backend
+    }
+}
+
+/// Key of a domain, so `Example.com.` and `example.com` are browsed once
+fn domain_key(domain: &str) -> String {
+    domain.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Open `domain`'s browse, recording what it reports
+fn open_domain(open: &OpenDomain, domain: Option<&str>, on_event: &BrowseCallback) -> Result<DomainBrowse, String> {
+    let reported: Reported = Arc::default();
+    let tracked = reported.clone();
+    let on_event = on_event.clone();
+    let browser = open(domain, Arc::new(move |event: &str, info: ServiceInfo| {
+        let key = (instance_key(&info), info.interface_index);
+        match event {
+            "serviceFound" | "serviceUpdated" if !info.name.is_empty() => {
+                tracked.lock().unwrap().insert(key, info.clone());
+            }
+            "serviceLost" => {
+                tracked.lock().unwrap().remove(&key);
+            }
+            _ => {}
+        }
+        on_event(event, info);
+    }))?;
+    Ok(DomainBrowse { browser, reported })
+}
+
+/// Browse `domain` (the requested one, or `.local`) at once, and on the
+/// native backend every browse domain the daemon enumerates as well
+pub fn browse_all(
+    backend: Backend,
+    domain: Option<&str>,
+    on_event: BrowseCallback,
+    open: Box<OpenDomain>,
+) -> Result<DomainsBrowser, String> {
+    let requested = domain_key(domain.unwrap_or("local"));
+    let first = open_domain(&*open, domain, &on_event)?;
+    let browser_backend = first.browser.backend();
+    let browses = Arc::new(Mutex::new(HashMap::from([(requested.clone(), first)])));
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut browser = DomainsBrowser { stop: stop.clone(), thread: None, browses: browses.clone(), backend: browser_backend };
+    if backend != Backend::Native {
+        return Ok(browser);
+    }
+
+    let watched = native::watch_domains(stop.clone(), move |added, domain| {
+        let key = domain_key(&domain);
+        if key == requested || key == "local" {
+            return;
+        }
+        if added {
+            if browses.lock().unwrap().contains_key(&key) || stop.load(Ordering::SeqCst) {
+                return;
+            }
+            tracing::debug!("Browse domain {} appeared", domain);
+            match open_domain(&*open, Some(&domain), &on_event) {
+                Ok(browse) => {
+                    browses.lock().unwrap().insert(key, browse);
+                }
+                Err(e) => tracing::warn!("Failed to browse domain {}: {}", domain, e),
+            }
+        } else {
+            let Some(mut browse) = browses.lock().unwrap().remove(&key) else {
+                return;
+            };
+            tracing::debug!("Browse domain {} went away", domain);
+            browse.browser.stop();
+            let lost: Vec<ServiceInfo> = browse.reported.lock().unwrap().drain().map(|(_, info)| info).collect();
+            for info in lost {
+                on_event("serviceLost", info);
+            }
+        }
+    });
+    match watched {
+        Ok(thread) => browser.thread = Some(thread),
+        // Avahi's compat layer has no domain enumeration; the requested
+        // domain is still browsed
+        Err(e) => tracing::info!("Browsing {} only: {}", domain.unwrap_or("local"), e),
+    }
+    Ok(browser)
+}
+
+impl Stoppable for DomainsBrowser {
+    fn threads(&self) -> usize {
+        let browses: usize = self.browses.lock().unwrap().values().map(|browse| browse.browser.threads()).sum();
+        browses + crate::handles::running(&self.thread)
+    }
+
+    fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        let browses: Vec<DomainBrowse> = self.browses.lock().unwrap().drain().map(|(_, browse)| browse).collect();
+        for mut browse in browses {
+            browse.browser.stop();
+        }
+    }
+}
+
+impl Drop for DomainsBrowser {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
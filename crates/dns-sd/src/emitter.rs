@@ -0,0 +1,171 @@
+//! EventEmitter-style `Browser` and `Advertisement` objects
+//!
+//! The JS classes are bare `EventEmitter` subclasses; everything else
+//! happens here. Addon events are renamed to the vocabulary of the
+//! bonjour/mdns packages and emitted on the object itself: `up`, `update`
+//! and `down` for services, `error` with an `Error`, and a final `end` once
+//! the handle is closed. Nothing is emitted after `end`, even events that
+//! were already queued for the JS thread.
+
+use crate::handles::{HandleBox, Stoppable};
+use neon::handle::Root;
+use neon::prelude::*;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// JS object the events are emitted on
+pub struct Emitter {
+    target: Root<JsObject>,
+    closed: AtomicBool,
+}
+
+impl Emitter {
+    pub fn new<'cx>(cx: &mut impl Context<'cx>, target: Handle<'cx, JsObject>) -> Arc<Self> {
+        Arc::new(Emitter {
+            target: target.root(cx),
+            closed: AtomicBool::new(false),
+        })
+    }
+
+    /// Call `target.emit(event, ...args)`
+    fn emit<'cx>(&self, cx: &mut impl Context<'cx>, event: &str, args: &[Handle<'cx, JsValue>]) -> NeonResult<()> {
+        let target = self.target.to_inner(cx);
+        let emit: Handle<JsFunction> = target.get(cx, "emit")?;
+        let mut argv = vec![cx.string(event).upcast()];
+        argv.extend_from_slice(args);
+        emit.call(cx, target, argv)?;
+        Ok(())
+    }
+
+    fn emit_error<'cx>(&self, cx: &mut impl Context<'cx>, message: &str) -> NeonResult<Handle<'cx, JsError>> {
+        let error = cx.error(message)?;
+        self.emit(cx, "error", &[error.upcast()])?;
+        Ok(error)
+    }
+
+    /// Addon callback for a browser: `(event, data)` in, named events out
+    pub fn browse_callback<'cx>(self: &Arc<Self>, cx: &mut impl Context<'cx>) -> JsResult<'cx, JsFunction> {
+        let emitter = self.clone();
+        JsFunction::new(cx, move |mut cx| {
+            let event = cx.argument::<JsString>(0)?.value(&mut cx);
+            let data = cx.argument::<JsValue>(1)?;
+            emitter.dispatch_browse(&mut cx, &event, data)?;
+            Ok(cx.undefined())
+        })
+    }
+
+    fn dispatch_browse<'cx>(&self, cx: &mut FunctionContext<'cx>, event: &str, data: Handle<'cx, JsValue>) -> NeonResult<()> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        match event {
+            "serviceFound" | "serviceUpdated" | "serviceLost" => {
+                let service = data.downcast_or_throw::<JsObject, _>(cx)?;
+                strip_scopes(cx, service)?;
+                let name = match event {
+                    "serviceFound" => "up",
+                    "serviceUpdated" => "update",
+                    _ => "down",
+                };
+                self.emit(cx, name, &[service.upcast()])
+            }
+            "batch" => {
+                let entries = data.downcast_or_throw::<JsArray, _>(cx)?.to_vec(cx)?;
+                for entry in entries {
+                    let entry = entry.downcast_or_throw::<JsObject, _>(cx)?;
+                    let event = entry.get::<JsString, _, _>(cx, "event")?.value(cx);
+                    let service = entry.get(cx, "service")?;
+                    self.dispatch_browse(cx, &event, service)?;
+                }
+                Ok(())
+            }
+            "txtError" => {
+                let service = data.downcast_or_throw::<JsObject, _>(cx)?;
+                let name = service.get::<JsString, _, _>(cx, "name")?.value(cx);
+                let mut errors = Vec::new();
+                if let Some(list) = service.get_opt::<JsArray, _, _>(cx, "txtErrors")? {
+                    for error in list.to_vec(cx)? {
+                        errors.push(error.to_string(cx)?.value(cx));
+                    }
+                }
+                let message = format!("Malformed TXT record for {}: {}", name, errors.join("; "));
+                let error = cx.error(message)?;
+                error.set(cx, "service", service)?;
+                self.emit(cx, "error", &[error.upcast()])
+            }
+            "backendFallback" => self.emit(cx, "backendFallback", &[]),
+            "queueOverflow" => self.emit_error(cx, "Event queue overflowed; events were dropped").map(drop),
+            "error" => {
+                let message = data.to_string(cx)?.value(cx);
+                self.emit_error(cx, &message).map(drop)
+            }
+            // `batchComplete` has no counterpart in the bonjour vocabulary
+            _ => Ok(()),
+        }
+    }
+
+    /// Addon callback for an advertisement: `registered` becomes `up`
+    pub fn advertise_callback<'cx>(self: &Arc<Self>, cx: &mut impl Context<'cx>) -> JsResult<'cx, JsFunction> {
+        let emitter = self.clone();
+        JsFunction::new(cx, move |mut cx| {
+            if emitter.closed.load(Ordering::SeqCst) {
+                return Ok(cx.undefined());
+            }
+            let event = cx.argument::<JsString>(0)?.value(&mut cx);
+            let data = cx.argument::<JsValue>(1)?;
+            match event.as_str() {
+                "registered" => emitter.emit(&mut cx, "up", &[data])?,
+                "backendFallback" => emitter.emit(&mut cx, "backendFallback", &[data])?,
+                "hostnameConflict" => emitter.emit(&mut cx, "hostnameConflict", &[data])?,
+                "error" => {
+                    let message = data.downcast_or_throw::<JsString, _>(&mut cx)?.value(&mut cx);
+                    emitter.emit_error(&mut cx, &message)?;
+                }
+                _ => {}
+            }
+            Ok(cx.undefined())
+        })
+    }
+}
+
+/// Remove the `%iface` scope suffix from a service's addresses, in place
+fn strip_scopes<'cx>(cx: &mut FunctionContext<'cx>, service: Handle<'cx, JsObject>) -> NeonResult<()> {
+    let Some(addresses) = service.get_opt::<JsArray, _, _>(cx, "addresses")? else {
+        return Ok(());
+    };
+    for (i, address) in addresses.to_vec(cx)?.into_iter().enumerate() {
+        let Ok(address) = address.downcast::<JsString, _>(cx) else {
+            continue;
+        };
+        let address = address.value(cx);
+        if let Some((bare, _)) = address.rsplit_once('%') {
+            let bare = cx.string(bare);
+            addresses.set(cx, i as u32, bare)?;
+        }
+    }
+    Ok(())
+}
+
+/// Handle of a `Browser` or `Advertisement` object
+pub struct EmitterBox<H> {
+    pub handle: HandleBox<H>,
+    pub emitter: Arc<Emitter>,
+}
+
+impl<H: Stoppable + Send + 'static> EmitterBox<H> {
+    /// Stop the handle and emit `end`, returning false if it was already closed
+    pub fn close<'cx>(&self, cx: &mut impl Context<'cx>) -> NeonResult<bool> {
+        if self.emitter.closed.swap(true, Ordering::SeqCst) {
+            return Ok(false);
+        }
+        self.handle.0.stop_in_background();
+        self.emitter.emit(cx, "end", &[])?;
+        Ok(true)
+    }
+}
+
+impl<H: Stoppable + Send + 'static> Finalize for EmitterBox<H> {
+    fn finalize<'a, C: Context<'a>>(self, cx: &mut C) {
+        self.handle.finalize(cx);
+    }
+}
@@ -1,27 +1,336 @@
 //! Fallback DNS-SD backend using mdns-sd (pure Rust)
 
-use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo as MdnsServiceInfo};
+use crate::{AdvertiseData, ServiceInfo};
+use crate::handles::running;
+use crate::model::Timings;
+use crate::multicast::Listener;
+use crate::{conflict, flush, metrics, names, tap, ttls};
+use crate::options::AdvertiseOptions;
+use mdns_sd::{HostnameResolutionEvent, IfKind, ServiceDaemon, ServiceEvent, ServiceInfo as MdnsServiceInfo, UnregisterStatus};
+use once_cell::sync::{Lazy, OnceCell};
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-/// Service info (matching native backend)
+/// How long a resolved result waits for the listener to hear the packet
+/// behind it, see [`flush`]
+const FLUSH_HOLD: Duration = Duration::from_millis(50);
+
+/// mdns-sd repeats a goodbye this long after confirming the first one
+const GOODBYE_REPEAT: Duration = Duration::from_millis(150);
+
+/// mdns-sd daemon shared by every fallback handle, shut down with the last
+/// one, and the listener that hears the same packets
+///
+/// The listener starts with the first thing that needs it: a browser, for
+/// cache flushes, SRV TTLs and cadence queries, or a [`tap`], for packet
+/// taps, captures and host name conflicts. It can't start on Windows, and
+/// those go without.
+pub struct SharedDaemon(ServiceDaemon, OnceCell<Option<Listener>>);
+
+impl Deref for SharedDaemon {
+    type Target = ServiceDaemon;
+
+    fn deref(&self) -> &ServiceDaemon {
+        &self.0
+    }
+}
+
+impl SharedDaemon {
+    /// The listener, started on first use; None if it can't start
+    fn listen(&self) -> Option<&Listener> {
+        self.1
+            .get_or_init(|| {
+                Listener::start(|packet, source| {
+                    flush::record(packet);
+                    ttls::record(packet);
+                    tap::deliver(packet, source);
+                })
+                .inspect_err(|e| {
+                    tracing::warn!(
+                        "Not listening to the mDNS group ({}): cache flushes, SRV TTLs, query cadence, \
+                         packet taps, captures and host name conflicts are unavailable",
+                        e
+                    )
+                })
+                .ok()
+            })
+            .as_ref()
+    }
+
+    /// Send `packet` to the mDNS group through the listener
+    fn send(&self, packet: &[u8]) -> Result<usize, String> {
+        let listener = self.listen().ok_or("No mDNS listener to send from")?;
+        listener.send(packet, &config())
+    }
+}
+
+impl Drop for SharedDaemon {
+    fn drop(&mut self) {
+        let _ = self.0.shutdown();
+        flush::clear();
+        ttls::clear();
+    }
+}
+
+static DAEMON: Lazy<Mutex<Weak<SharedDaemon>>> = Lazy::new(|| Mutex::new(Weak::new()));
+
+/// Shut down the shared daemon now, even if stray references keep it alive
+pub fn shutdown_daemon() {
+    if let Some(daemon) = DAEMON.lock().unwrap().upgrade() {
+        let _ = daemon.0.shutdown();
+        if let Some(Some(listener)) = daemon.1.get() {
+            listener.stop();
+        }
+    }
+}
+
+/// References keeping the shared daemon up, one per fallback handle
+/// (0 while it is down)
+pub fn daemon_refs() -> usize {
+    DAEMON.lock().unwrap().strong_count()
+}
+
+/// Settings applied to the shared daemon
 #[derive(Debug, Clone)]
-pub struct ServiceInfo {
-    pub name: String,
-    pub service_type: String,
-    pub domain: String,
-    pub host_name: String,
-    pub addresses: Vec<String>,
-    pub port: u16,
-    pub txt: HashMap<String, String>,
-    pub ttl: u32,
+pub struct FallbackConfig {
+    /// Interface names to use (empty = all)
+    pub interfaces: Vec<String>,
+    pub ipv4: bool,
+    pub ipv6: bool,
+    /// Receive our own multicast packets (needed to see local services)
+    pub multicast_loop_v4: bool,
+    pub multicast_loop_v6: bool,
+    /// How often mdns-sd rescans network interfaces (None = mdns-sd default)
+    pub ip_check_interval: Option<u32>,
+    /// When browsers send their queries (None = mdns-sd's own schedule)
+    pub cadence: Option<QueryCadence>,
+}
+
+/// When a browser queries: `burst` queries `burst_interval` apart, then a
+/// retry after `retry_interval`, each later retry waiting twice as long as
+/// the one before, up to `max_interval`
+///
+/// The default is mdns-sd's own schedule: one query, then retries after
+/// 1s, 2s, 4s and so on up to an hour.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QueryCadence {
+    pub burst: u32,
+    pub burst_interval: Duration,
+    pub retry_interval: Duration,
+    pub max_interval: Duration,
+}
+
+impl Default for QueryCadence {
+    fn default() -> Self {
+        QueryCadence {
+            burst: 1,
+            burst_interval: Duration::from_secs(1),
+            retry_interval: Duration::from_secs(1),
+            max_interval: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+impl QueryCadence {
+    /// Wait before query number `sent + 1`, counting from the first
+    fn next_delay(&self, sent: u32) -> Duration {
+        if sent < self.burst.max(1) {
+            return self.burst_interval;
+        }
+        let doublings = (sent - self.burst.max(1)).min(31);
+        self.retry_interval.saturating_mul(1 << doublings).min(self.max_interval)
+    }
+}
+
+impl Default for FallbackConfig {
+    fn default() -> Self {
+        FallbackConfig {
+            interfaces: Vec::new(),
+            ipv4: true,
+            ipv6: true,
+            multicast_loop_v4: true,
+            multicast_loop_v6: true,
+            ip_check_interval: None,
+            cadence: None,
+        }
+    }
+}
+
+impl FallbackConfig {
+    /// Apply to a daemon, undoing any interface selection made earlier
+    fn apply(&self, daemon: &ServiceDaemon) -> Result<(), String> {
+        let err = |e: mdns_sd::Error| format!("Failed to configure daemon: {}", e);
+
+        daemon.enable_interface(IfKind::All).map_err(err)?;
+        let loopback_only = crate::loopback_only();
+        if loopback_only {
+            daemon.disable_interface(IfKind::All).map_err(err)?;
+            daemon.enable_interface(vec![IfKind::LoopbackV4, IfKind::LoopbackV6]).map_err(err)?;
+        } else if !self.interfaces.is_empty() {
+            daemon.disable_interface(IfKind::All).map_err(err)?;
+            let names: Vec<IfKind> = self.interfaces.iter().cloned().map(IfKind::Name).collect();
+            daemon.enable_interface(names).map_err(err)?;
+        }
+        if !self.ipv4 {
+            daemon.disable_interface(IfKind::IPv4).map_err(err)?;
+        }
+        if !self.ipv6 {
+            daemon.disable_interface(IfKind::IPv6).map_err(err)?;
+        }
+        // On loopback the only peers are our own handles, so we must hear ourselves
+        daemon.set_multicast_loop_v4(self.multicast_loop_v4 || loopback_only).map_err(err)?;
+        daemon.set_multicast_loop_v6(self.multicast_loop_v6 || loopback_only).map_err(err)?;
+        if let Some(secs) = self.ip_check_interval {
+            daemon.set_ip_check_interval(secs).map_err(err)?;
+        }
+        Ok(())
+    }
+}
+
+static CONFIG: Lazy<Mutex<FallbackConfig>> = Lazy::new(|| Mutex::new(FallbackConfig::default()));
+
+/// Current fallback configuration
+pub fn config() -> FallbackConfig {
+    CONFIG.lock().unwrap().clone()
+}
+
+/// Replace the fallback configuration, applying it to the running daemon if any
+pub fn configure(config: FallbackConfig) -> Result<(), String> {
+    let slot = DAEMON.lock().unwrap();
+    if let Some(daemon) = slot.upgrade() {
+        config.apply(&daemon)?;
+    }
+    *CONFIG.lock().unwrap() = config;
+    Ok(())
+}
+
+/// Ask the running daemon to re-query an instance (`name._type._tcp.local.`)
+///
+/// If nothing answers within `timeout`, its records are flushed and browsers
+/// get `serviceLost`. Returns false when no fallback handle is running, as
+/// there is then no cache to verify.
+pub fn verify(fullname: &str, timeout: Duration) -> Result<bool, String> {
+    let Some(daemon) = DAEMON.lock().unwrap().upgrade() else {
+        return Ok(false);
+    };
+    // mdns-sd keys its cache by the unescaped name
+    let fullname = match names::split_fullname(fullname) {
+        Some((name, service_type, domain)) => format!("{}.{}.{}.", name, service_type, domain),
+        None if fullname.ends_with('.') => fullname.to_string(),
+        None => format!("{}.", fullname),
+    };
+    daemon
+        .verify(fullname, timeout)
+        .map_err(|e| format!("Failed to verify: {}", e))?;
+    Ok(true)
+}
+
+/// Look up the addresses of a `.local` host name
+pub fn resolve_hostname(hostname: &str, timeout: Duration) -> Result<Vec<std::net::IpAddr>, String> {
+    let hostname = format!("{}.local.", hostname.trim_end_matches('.').trim_end_matches(".local"));
+    let daemon = shared_daemon()?;
+    let receiver = daemon
+        .resolve_hostname(&hostname, Some(timeout.as_millis() as u64))
+        .map_err(|e| format!("Failed to resolve: {}", e))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut addresses = Vec::new();
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        match receiver.recv_timeout(remaining) {
+            Ok(HostnameResolutionEvent::AddressesFound(_, found)) => {
+                for addr in found {
+                    let addr = addr.to_ip_addr();
+                    if !addresses.contains(&addr) {
+                        addresses.push(addr);
+                    }
+                }
+            }
+            Ok(HostnameResolutionEvent::SearchTimeout(_) | HostnameResolutionEvent::SearchStopped(_)) | Err(_) => break,
+            Ok(_) => {}
+        }
+    }
+    let _ = daemon.stop_resolve_hostname(&hostname);
+    Ok(addresses)
+}
+
+/// Follow the addresses of a `.local` host name, calling `on_change` with
+/// `true` for each address that appears and `false` for each one that goes
+/// away, until `stop` is set
+pub fn watch_hostname<F>(hostname: &str, stop: Arc<AtomicBool>, mut on_change: F) -> Result<thread::JoinHandle<()>, String>
+where
+    F: FnMut(bool, std::net::IpAddr) + Send + 'static,
+{
+    let hostname = format!("{}.local.", hostname.trim_end_matches('.').trim_end_matches(".local"));
+    let daemon = shared_daemon()?;
+    let receiver = daemon
+        .resolve_hostname(&hostname, None)
+        .map_err(|e| format!("Failed to resolve: {}", e))?;
+
+    Ok(thread::spawn(move || {
+        while !stop.load(Ordering::SeqCst) {
+            match receiver.recv_timeout(Duration::from_millis(100)) {
+                Ok(HostnameResolutionEvent::AddressesFound(_, found)) => {
+                    found.iter().for_each(|addr| on_change(true, addr.to_ip_addr()));
+                }
+                Ok(HostnameResolutionEvent::AddressesRemoved(_, removed)) => {
+                    removed.iter().for_each(|addr| on_change(false, addr.to_ip_addr()));
+                }
+                Ok(HostnameResolutionEvent::SearchStopped(_)) => break,
+                Err(_) if receiver.is_disconnected() => break,
+                Ok(_) | Err(_) => {}
+            }
+        }
+        let _ = daemon.stop_resolve_hostname(&hostname);
+    }))
+}
+
+/// Start the running daemon's listener, for a tap added while it runs
+pub fn listen() {
+    let daemon = DAEMON.lock().unwrap().upgrade();
+    if let Some(daemon) = daemon {
+        daemon.listen();
+    }
+}
+
+/// Get the process-wide daemon, starting it if no handle holds it
+fn shared_daemon() -> Result<Arc<SharedDaemon>, String> {
+    let mut slot = DAEMON.lock().unwrap();
+    if let Some(daemon) = slot.upgrade() {
+        return Ok(daemon);
+    }
+    let daemon = ServiceDaemon::new().map_err(|e| format!("Failed to create daemon: {}", e))?;
+    tracing::debug!("Started mdns-sd daemon");
+    let daemon = Arc::new(SharedDaemon(daemon, OnceCell::new()));
+    CONFIG.lock().unwrap().apply(&daemon)?;
+    // Taps added before the daemon started hear its traffic from now on
+    if tap::active() {
+        daemon.listen();
+    }
+    *slot = Arc::downgrade(&daemon);
+    Ok(daemon)
+}
+
+/// Type enumeration result, shaped like the native one
+///
+/// `_http._tcp.local.` is reported as instance `_http` of type `_tcp`.
+fn enumerated_type(fullname: &str) -> ServiceInfo {
+    let (name, stype) = fullname.split_once('.').unwrap_or((fullname, ""));
+    ServiceInfo {
+        name: name.to_string(),
+        service_type: stype.to_string(),
+        domain: "local".to_string(),
+        ..Default::default()
+    }
 }
 
 /// Browser handle for fallback backend
 pub struct FallbackBrowser {
-    daemon: Arc<ServiceDaemon>,
+    daemon: Arc<SharedDaemon>,
+    service_type: String,
     stop_flag: Arc<Mutex<bool>>,
     thread: Option<thread::JoinHandle<()>>,
 }
@@ -32,8 +341,10 @@ impl FallbackBrowser {
     where
         F: Fn(&str, ServiceInfo) + Send + Sync + 'static,
     {
-        let daemon = ServiceDaemon::new().map_err(|e| format!("Failed to create daemon: {}", e))?;
-        let daemon = Arc::new(daemon);
+        let daemon = shared_daemon()?;
+        // Without the listener, addresses replaced by a cache flush linger
+        // until the instance is resolved again
+        daemon.listen();
 
         // Normalize service type to include .local. if needed
         let service_type = if service_type.ends_with(".local.") {
@@ -44,61 +355,134 @@ impl FallbackBrowser {
             format!("{}.local.", service_type)
         };
 
-        let receiver = daemon
-            .browse(&service_type)
-            .map_err(|e| format!("Failed to browse: {}", e))?;
+        // With a cadence of our own, mdns-sd only reports what its cache
+        // hears; the queries come from the browse loop
+        let cadence = config().cadence;
+        let receiver = match cadence {
+            Some(_) => daemon.browse_cache(&service_type),
+            None => daemon.browse(&service_type),
+        }
+        .map_err(|e| format!("Failed to browse: {}", e))?;
 
         let stop_flag = Arc::new(Mutex::new(false));
         let stop_flag_clone = stop_flag.clone();
         let callback = Arc::new(callback);
+        let browsed_type = service_type.clone();
+        let enumerating = crate::model::is_type_enumeration(&service_type);
 
+        // Weak, so the browse thread doesn't count as a handle in `daemon_refs`
+        let sender = Arc::downgrade(&daemon);
         let thread = thread::spawn(move || {
+            // mdns-sd has no MoreComing flag; a quiet poll interval ends a batch
+            let mut batch_pending = true;
+            // mdns-sd resolves on its own; time instances from PTR to resolution
+            let mut resolving: HashMap<String, Instant> = HashMap::new();
+            // Resolved results wait FLUSH_HOLD, so the listener has heard the
+            // packet behind them before cache-flushed addresses are dropped
+            let mut held: HashMap<String, (Instant, ServiceInfo)> = HashMap::new();
+            // Each instance's latest result and the addresses last reported
+            let mut reported: HashMap<String, (ServiceInfo, Vec<String>)> = HashMap::new();
+            let mut flushes = flush::generation();
+            let query = crate::multicast::ptr_query(&service_type);
+            let mut next_query = Instant::now();
+            let mut queries_sent = 0;
+
             loop {
                 if *stop_flag_clone.lock().unwrap() {
                     break;
                 }
-                
-                match receiver.recv_timeout(Duration::from_millis(100)) {
+
+                let now = Instant::now();
+                if let Some(cadence) = cadence.filter(|_| now >= next_query) {
+                    let sent = sender
+                        .upgrade()
+                        .ok_or_else(|| "The daemon shut down".to_string())
+                        .and_then(|daemon| daemon.send(&query));
+                    if let Err(e) = sent {
+                        tracing::debug!("Failed to query for {}: {}", service_type, e);
+                    }
+                    next_query = now + cadence.next_delay(queries_sent);
+                    queries_sent += 1;
+                }
+                let ready: Vec<String> = held
+                    .iter()
+                    .filter(|(_, (at, _))| now.duration_since(*at) >= FLUSH_HOLD)
+                    .map(|(fullname, _)| fullname.clone())
+                    .collect();
+                for fullname in ready {
+                    let Some((_, info)) = held.remove(&fullname) else { continue };
+                    let addresses = flush::current(&info);
+                    // Left at 0 when the listener didn't hear the SRV record, as for unicast replies
+                    let info = match ttls::srv(&fullname) {
+                        Some(srv) => ServiceInfo { ttl: srv.ttl, priority: srv.priority, weight: srv.weight, ..info },
+                        None => info,
+                    };
+                    callback("serviceFound", ServiceInfo { addresses: addresses.clone(), ..info.clone() });
+                    reported.insert(fullname, (ServiceInfo { timings: None, ..info }, addresses));
+                }
+                // A cache flush replaced addresses: report what is left as one update
+                if flush::generation() != flushes {
+                    flushes = flush::generation();
+                    for (info, delivered) in reported.values_mut() {
+                        let addresses = flush::current(info);
+                        if addresses != *delivered {
+                            *delivered = addresses.clone();
+                            callback("serviceFound", ServiceInfo { addresses, ..info.clone() });
+                        }
+                    }
+                }
+
+                let wait = if held.is_empty() { Duration::from_millis(100) } else { FLUSH_HOLD / 2 };
+                match receiver.recv_timeout(wait) {
                     Ok(event) => {
+                        batch_pending = true;
                         match event {
+                            ServiceEvent::ServiceFound(_, fullname) if enumerating => {
+                                callback("serviceFound", enumerated_type(&fullname));
+                            }
+                            ServiceEvent::ServiceRemoved(_, fullname) if enumerating => {
+                                callback("serviceLost", enumerated_type(&fullname));
+                            }
+                            ServiceEvent::ServiceFound(_, fullname)
+                                if !reported.contains_key(&fullname)
+                                    && !held.contains_key(&fullname)
+                                    && !resolving.contains_key(&fullname) =>
+                            {
+                                metrics::resolve_started();
+                                resolving.insert(fullname, Instant::now());
+                            }
                             ServiceEvent::ServiceResolved(resolved) => {
-                                // Extract service name from fullname
                                 let fullname = resolved.get_fullname();
-                                let name = fullname.split('.').next().unwrap_or("").to_string();
-                                
-                                // Get service type from the fullname (e.g., "_http._tcp.local.")
-                                let parts: Vec<&str> = fullname.split('.').collect();
-                                let stype = if parts.len() >= 3 {
-                                    format!("{}.{}", parts[1], parts[2])
-                                } else {
-                                    String::new()
-                                };
-
-                                let info = ServiceInfo {
-                                    name,
-                                    service_type: stype,
-                                    domain: "local".to_string(),
-                                    host_name: resolved.get_hostname().to_string(),
-                                    addresses: resolved.get_addresses().iter().map(|a| a.to_string()).collect(),
-                                    port: resolved.get_port(),
-                                    txt: resolved.get_properties().iter()
-                                        .map(|p| (p.key().to_string(), p.val_str().to_string()))
-                                        .collect(),
-                                    ttl: 0,
-                                };
-                                callback("serviceFound", info);
+                                // Only profiling browsers pass these on to JS
+                                let timings = resolving.remove(fullname).map(|started| {
+                                    metrics::resolve_succeeded(started.elapsed());
+                                    Timings { resolve: Some(started.elapsed()), ..Default::default() }
+                                });
+                                let info = ServiceInfo { timings, ..ServiceInfo::from(&*resolved) };
+                                match held.get_mut(fullname) {
+                                    Some((_, pending)) => *pending = info,
+                                    None => {
+                                        held.insert(fullname.to_string(), (Instant::now(), info));
+                                    }
+                                }
                             }
+                            // Goodbyes, and instances whose SRV records expired in
+                            // mdns-sd's cache without being refreshed
                             ServiceEvent::ServiceRemoved(stype, fullname) => {
-                                let name = fullname.split('.').next().unwrap_or("").to_string();
+                                held.remove(&fullname);
+                                reported.remove(&fullname);
+                                if resolving.remove(&fullname).is_some() {
+                                    metrics::resolve_timed_out();
+                                }
+                                let (name, domain) = match names::split_fullname(&fullname) {
+                                    Some((name, _, domain)) => (name, domain),
+                                    None => (fullname.clone(), "local".to_string()),
+                                };
                                 let info = ServiceInfo {
                                     name,
-                                    service_type: stype.to_string(),
-                                    domain: "local".to_string(),
-                                    host_name: String::new(),
-                                    addresses: vec![],
-                                    port: 0,
-                                    txt: HashMap::new(),
-                                    ttl: 0,
+                                    service_type: stype,
+                                    domain,
+                                    ..Default::default()
                                 };
                                 callback("serviceLost", info);
                             }
@@ -106,29 +490,44 @@ impl FallbackBrowser {
                         }
                     }
                     Err(_) => {
+                        if batch_pending && held.is_empty() {
+                            batch_pending = false;
+                            callback("batchComplete", ServiceInfo {
+                                service_type: service_type.clone(),
+                                domain: "local".to_string(),
+                                ..Default::default()
+                            });
+                        }
                         // Timeout or disconnected - continue or break based on stop flag
                         continue;
                     }
                 }
             }
+            tracing::debug!("Browse loop for {} exited", service_type);
         });
 
         Ok(FallbackBrowser {
             daemon,
+            service_type: browsed_type,
             stop_flag,
             thread: Some(thread),
         })
     }
 
+    /// Threads still running, for diagnostics
+    pub fn threads(&self) -> usize {
+        running(&self.thread)
+    }
+
     /// Stop browsing
     pub fn stop(&mut self) {
         *self.stop_flag.lock().unwrap() = true;
         
         if let Some(thread) = self.thread.take() {
             let _ = thread.join();
+            // The daemon itself stays up for other handles
+            let _ = self.daemon.stop_browse(&self.service_type);
         }
-        
-        let _ = self.daemon.shutdown();
     }
 }
 
@@ -140,78 +539,185 @@ impl Drop for FallbackBrowser {
 
 /// Advertisement handle for fallback backend
 pub struct FallbackAdvertisement {
-    daemon: Arc<ServiceDaemon>,
+    daemon: Arc<SharedDaemon>,
     stop_flag: Arc<Mutex<bool>>,
     fullname: String,
+    /// Registered again to re-announce
+    info: Box<MdnsServiceInfo>,
+    /// Watch on the host name, for answers from another device
+    conflicts: Option<u32>,
 }
 
-impl FallbackAdvertisement {
-    /// Advertise a service
-    pub fn new<F>(
-        name: &str,
-        service_type: &str,
-        port: u16,
-        txt: Option<&HashMap<String, String>>,
-        callback: F,
-    ) -> Result<Self, String>
-    where
-        F: Fn(&str, &str) + Send + 'static,
-    {
-        let daemon = ServiceDaemon::new().map_err(|e| format!("Failed to create daemon: {}", e))?;
-        let daemon = Arc::new(daemon);
+/// mdns-sd's description of the service `options` advertise
+fn service_info(options: &AdvertiseOptions) -> Result<MdnsServiceInfo, String> {
+    let name = options.name.as_str();
+    let service_type = options.service_type.as_str();
 
-        // Normalize service type
-        let service_type = if service_type.ends_with(".local.") {
-            service_type.to_string()
-        } else if service_type.ends_with('.') {
-            format!("{}local.", service_type)
-        } else {
-            format!("{}.local.", service_type)
-        };
+    // mdns-sd carries a single subtype in the type: "_printer._sub._http._tcp"
+    let service_type = match options.subtypes.as_slice() {
+        [] => service_type.to_string(),
+        [subtype] => format!("{}._sub.{}", subtype, service_type),
+        _ => return Err("Fallback backend supports at most one subtype".to_string()),
+    };
 
-        // Get hostname
-        let sys_hostname = hostname::get()
+    // Normalize service type
+    let service_type = if service_type.ends_with(".local.") {
+        service_type.to_string()
+    } else if service_type.ends_with('.') {
+        format!("{}local.", service_type)
+    } else {
+        format!("{}.local.", service_type)
+    };
+
+    // Caller's host name, else the system one
+    let host = match options.host.as_deref() {
+        Some(host) => host.trim_end_matches('.').trim_end_matches(".local").to_string(),
+        None => hostname::get()
             .map(|h| h.to_string_lossy().into_owned())
-            .unwrap_or_else(|_| "localhost".to_string());
-        let host = format!("{}.local.", sys_hostname);
-
-        // Build properties
-        let properties: Vec<(&str, &str)> = txt
-            .map(|t| t.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect())
-            .unwrap_or_default();
-
-        // Create service info
-        let service_info = MdnsServiceInfo::new(
-            &service_type,
-            name,
-            &host,
-            "",  // Use default addresses
-            port,
-            &properties[..],
-        ).map_err(|e| format!("Failed to create service info: {}", e))?;
+            .unwrap_or_else(|_| "localhost".to_string()),
+    };
+    let host = format!("{}.local.", host);
+
+    // Build properties
+    let properties: Vec<(&str, &str)> = options.txt.as_ref()
+        .map(|t| t.ordered(options.txtvers_first))
+        .unwrap_or_default();
+
+    let addresses = options.addresses.join(",");
+
+    // Create service info
+    let mut service_info = MdnsServiceInfo::new(
+        &service_type,
+        name,
+        &host,
+        addresses.as_str(),
+        options.port,
+        &properties[..],
+    ).map_err(|e| format!("Failed to create service info: {}", e))?;
+
+    // Without explicit addresses, publish the host's and track changes
+    if options.addresses.is_empty() {
+        service_info = service_info.enable_addr_auto();
+    }
 
+    if options.interface_index == crate::interfaces::LOCAL_ONLY {
+        // Only answer on loopback, so nothing reaches the LAN
+        service_info.set_interfaces(vec![IfKind::LoopbackV4, IfKind::LoopbackV6]);
+    } else if options.interface_index != 0 {
+        let ifname = crate::interfaces::index_to_name(options.interface_index)
+            .ok_or_else(|| format!("Unknown interface index: {}", options.interface_index))?;
+        service_info.set_interfaces(vec![IfKind::Name(ifname)]);
+    } else if !options.interfaces.is_empty() {
+        crate::interfaces::select(&options.interfaces)?;
+        service_info.set_interfaces(options.interfaces.iter().cloned().map(IfKind::Name).collect());
+    }
+    Ok(service_info)
+}
+
+impl FallbackAdvertisement {
+    /// Advertise a service
+    pub fn new<F>(options: &AdvertiseOptions, callback: F) -> Result<Self, String>
+    where
+        F: Fn(&str, &AdvertiseData) + Send + 'static,
+    {
+        let service_info = service_info(options)?;
+        let daemon = shared_daemon()?;
         let fullname = service_info.get_fullname().to_string();
 
+        // mdns-sd keeps its record TTLs (120 s host, 75 min other) private
+        if options.ttl.is_some() || options.host_ttl.is_some() {
+            tracing::warn!("mdns-sd can't change record TTLs; {} uses the defaults", fullname);
+        }
+        // ...and only publishes the records a service info describes
+        if !options.records.is_empty() {
+            tracing::warn!("mdns-sd can't publish extra records; {} is registered without them", fullname);
+        }
+
         // Register service
         daemon
-            .register(service_info)
+            .register(service_info.clone())
             .map_err(|e| format!("Failed to register: {}", e))?;
 
-        callback("registered", name);
+        callback("registered", &AdvertiseData::Text(options.name.clone()));
+
+        // Loopback answers can't clash with another device's
+        let conflicts = (options.interface_index != crate::interfaces::LOCAL_ONLY).then(|| {
+            let callback: conflict::ConflictCallback = Arc::new(Mutex::new(Box::new(move |conflict| {
+                callback("hostnameConflict", &AdvertiseData::HostnameConflict(conflict))
+            })));
+            conflict::watch(service_info.get_hostname(), &options.addresses, callback)
+        });
 
         Ok(FallbackAdvertisement {
             daemon,
             stop_flag: Arc::new(Mutex::new(false)),
             fullname,
+            info: Box::new(service_info),
+            conflicts,
         })
     }
 
+    /// Announce again, picking up the host's current addresses
+    pub fn refresh(&self) {
+        if !*self.stop_flag.lock().unwrap()
+            && let Err(e) = self.daemon.register((*self.info).clone())
+        {
+            tracing::warn!("Failed to re-announce {}: {}", self.fullname, e);
+        }
+    }
+
+    /// Register changed options under the same name, such as a new port
+    ///
+    /// mdns-sd replaces the records and announces them with the cache-flush
+    /// bit, so peers update the service instead of losing it.
+    pub fn update(&mut self, options: &AdvertiseOptions) -> Result<(), String> {
+        let info = service_info(options)?;
+        if info.get_fullname() != self.fullname {
+            return Err("The instance name can't change in place".to_string());
+        }
+        self.daemon.register(info.clone()).map_err(|e| format!("Failed to register: {}", e))?;
+        if let Some(id) = self.conflicts {
+            conflict::update(id, info.get_hostname(), &options.addresses);
+        }
+        *self.info = info;
+        Ok(())
+    }
+
     /// Stop advertising
     pub fn stop(&mut self) {
         if !*self.stop_flag.lock().unwrap() {
             *self.stop_flag.lock().unwrap() = true;
+            if let Some(id) = self.conflicts.take() {
+                conflict::unwatch(id);
+            }
             let _ = self.daemon.unregister(&self.fullname);
-            let _ = self.daemon.shutdown();
+        }
+    }
+
+    /// Stop advertising, returning once the goodbyes were sent or at
+    /// `deadline`; false if they weren't confirmed
+    pub fn stop_confirmed(&mut self, deadline: Instant) -> bool {
+        if std::mem::replace(&mut *self.stop_flag.lock().unwrap(), true) {
+            return true;
+        }
+        if let Some(id) = self.conflicts.take() {
+            conflict::unwatch(id);
+        }
+        let status = self
+            .daemon
+            .unregister(&self.fullname)
+            .map_err(|e| e.to_string())
+            .and_then(|rx| rx.recv_timeout(deadline.saturating_duration_since(Instant::now())).map_err(|e| e.to_string()));
+        match status {
+            Ok(UnregisterStatus::OK) => {
+                thread::sleep(GOODBYE_REPEAT.min(deadline.saturating_duration_since(Instant::now())));
+                true
+            }
+            Ok(UnregisterStatus::NotFound) => true,
+            Err(e) => {
+                tracing::warn!("Goodbye for {} not confirmed: {}", self.fullname, e);
+                false
+            }
         }
     }
 }
@@ -4,12 +4,15 @@
 #![allow(non_camel_case_types)]
 #![allow(dead_code)]
 
-use libc::{c_char, c_void, c_int};
+use libc::{c_char, c_void};
 use std::os::raw::c_ushort;
 
 /// Opaque reference to a DNS service
 pub type DNSServiceRef = *mut c_void;
 
+/// Opaque reference to a record added to a registration
+pub type DNSRecordRef = *mut c_void;
+
 /// Flags for DNS-SD operations
 pub type DNSServiceFlags = u32;
 
@@ -46,10 +49,73 @@ pub const K_DNS_SERVICE_FLAGS_UNIQUE: DNSServiceFlags = 0x20;
 pub const K_DNS_SERVICE_FLAGS_BROWSE_DOMAINS: DNSServiceFlags = 0x40;
 pub const K_DNS_SERVICE_FLAGS_REGISTRATION_DOMAINS: DNSServiceFlags = 0x80;
 pub const K_DNS_SERVICE_FLAGS_MORE_COMING: DNSServiceFlags = 0x1;
+pub const K_DNS_SERVICE_FLAGS_ALLOW_REMOTE_QUERY: DNSServiceFlags = 0x200;
+pub const K_DNS_SERVICE_FLAGS_FORCE_MULTICAST: DNSServiceFlags = 0x400;
+pub const K_DNS_SERVICE_FLAGS_TIMEOUT: DNSServiceFlags = 0x10000;
+pub const K_DNS_SERVICE_FLAGS_INCLUDE_P2P: DNSServiceFlags = 0x20000;
+pub const K_DNS_SERVICE_FLAGS_VALIDATE: DNSServiceFlags = 0x200000;
+
+// DNSSEC results: the Validate bit plus one status bit, on validated answers
+pub const K_DNS_SERVICE_FLAGS_SECURE: DNSServiceFlags = 0x200010;
+pub const K_DNS_SERVICE_FLAGS_INSECURE: DNSServiceFlags = 0x200020;
+pub const K_DNS_SERVICE_FLAGS_BOGUS: DNSServiceFlags = 0x200040;
+pub const K_DNS_SERVICE_FLAGS_INDETERMINATE: DNSServiceFlags = 0x200080;
+
+/// Flag bits by their dns_sd.h name without the `kDNSServiceFlags` prefix,
+/// as exported to JS
+///
+/// `ExpiredAnswer` shares its bit with `PathEvaluationDone`; which one is
+/// meant depends on the call, and only query/resolve answers use it. The
+/// DNSSEC results reuse `Shared` through `RegistrationDomains` alongside
+/// `Validate`, so test them with all of their bits.
+pub const DNS_SERVICE_FLAG_NAMES: &[(&str, DNSServiceFlags)] = &[
+    ("MoreComing", K_DNS_SERVICE_FLAGS_MORE_COMING),
+    ("Add", K_DNS_SERVICE_FLAGS_ADD),
+    ("Default", K_DNS_SERVICE_FLAGS_DEFAULT),
+    ("NoAutoRename", K_DNS_SERVICE_FLAGS_NO_AUTO_RENAME),
+    ("Shared", K_DNS_SERVICE_FLAGS_SHARED),
+    ("Unique", K_DNS_SERVICE_FLAGS_UNIQUE),
+    ("BrowseDomains", K_DNS_SERVICE_FLAGS_BROWSE_DOMAINS),
+    ("RegistrationDomains", K_DNS_SERVICE_FLAGS_REGISTRATION_DOMAINS),
+    ("LongLivedQuery", 0x100),
+    ("AllowRemoteQuery", K_DNS_SERVICE_FLAGS_ALLOW_REMOTE_QUERY),
+    ("ForceMulticast", K_DNS_SERVICE_FLAGS_FORCE_MULTICAST),
+    ("ReturnIntermediates", 0x1000),
+    ("NonBrowsable", 0x2000),
+    ("ShareConnection", 0x4000),
+    ("SuppressUnusable", 0x8000),
+    ("Timeout", K_DNS_SERVICE_FLAGS_TIMEOUT),
+    ("IncludeP2P", K_DNS_SERVICE_FLAGS_INCLUDE_P2P),
+    ("WakeOnResolve", 0x40000),
+    ("BackgroundTrafficClass", 0x80000),
+    ("IncludeAWDL", 0x100000),
+    ("Validate", K_DNS_SERVICE_FLAGS_VALIDATE),
+    ("Secure", K_DNS_SERVICE_FLAGS_SECURE),
+    ("Insecure", K_DNS_SERVICE_FLAGS_INSECURE),
+    ("Bogus", K_DNS_SERVICE_FLAGS_BOGUS),
+    ("Indeterminate", K_DNS_SERVICE_FLAGS_INDETERMINATE),
+    ("UnicastResponse", 0x400000),
+    ("ExpiredAnswer", 0x40000000),
+    ("AllowExpiredAnswers", 0x80000000),
+];
+
+// Special interface indexes
+pub const K_DNS_SERVICE_INTERFACE_INDEX_LOCAL_ONLY: u32 = u32::MAX;
+pub const K_DNS_SERVICE_INTERFACE_INDEX_P2P: u32 = u32::MAX - 2;
+
+// Address protocols for DNSServiceGetAddrInfo
+pub const K_DNS_SERVICE_PROTOCOL_IPV4: u32 = 0x01;
+pub const K_DNS_SERVICE_PROTOCOL_IPV6: u32 = 0x02;
 
 // Service Types
 pub const K_DNS_SERVICE_TYPE_A: u16 = 1;
+pub const K_DNS_SERVICE_TYPE_CNAME: u16 = 5;
+pub const K_DNS_SERVICE_TYPE_NULL: u16 = 10;
+pub const K_DNS_SERVICE_TYPE_PTR: u16 = 12;
+pub const K_DNS_SERVICE_TYPE_TXT: u16 = 16;
 pub const K_DNS_SERVICE_TYPE_AAAA: u16 = 28;
+pub const K_DNS_SERVICE_TYPE_SRV: u16 = 33;
+pub const K_DNS_SERVICE_TYPE_ANY: u16 = 255;
 
 /// TXT record reference
 pub type TXTRecordRef = [u8; 16]; // Opaque, 16 bytes should be enough
@@ -97,6 +163,17 @@ pub type DNSServiceRegisterReply = Option<
     ),
 >;
 
+/// RegisterRecord callback type
+pub type DNSServiceRegisterRecordReply = Option<
+    unsafe extern "C" fn(
+        sd_ref: DNSServiceRef,
+        record_ref: DNSRecordRef,
+        flags: DNSServiceFlags,
+        error_code: DNSServiceErrorType,
+        context: *mut c_void,
+    ),
+>;
+
 /// GetAddrInfo callback type
 pub type DNSServiceGetAddrInfoReply = Option<
     unsafe extern "C" fn(
@@ -111,6 +188,18 @@ pub type DNSServiceGetAddrInfoReply = Option<
     ),
 >;
 
+/// EnumerateDomains callback type
+pub type DNSServiceDomainEnumReply = Option<
+    unsafe extern "C" fn(
+        sd_ref: DNSServiceRef,
+        flags: DNSServiceFlags,
+        interface_index: u32_t,
+        error_code: DNSServiceErrorType,
+        reply_domain: *const c_char,
+        context: *mut c_void,
+    ),
+>;
+
 /// QueryRecord callback type
 pub type DNSServiceQueryRecordReply = Option<
     unsafe extern "C" fn(
@@ -186,7 +275,64 @@ pub type FnDNSServiceQueryRecord = unsafe extern "C" fn(
     context: *mut c_void,
 ) -> DNSServiceErrorType;
 
-pub type FnDNSServiceRefSockFD = unsafe extern "C" fn(sd_ref: DNSServiceRef) -> c_int;
+pub type FnDNSServiceEnumerateDomains = unsafe extern "C" fn(
+    sd_ref: *mut DNSServiceRef,
+    flags: DNSServiceFlags,
+    interface_index: u32_t,
+    callback: DNSServiceDomainEnumReply,
+    context: *mut c_void,
+) -> DNSServiceErrorType;
+
+pub type FnDNSServiceAddRecord = unsafe extern "C" fn(
+    sd_ref: DNSServiceRef,
+    record_ref: *mut DNSRecordRef,
+    flags: DNSServiceFlags,
+    rrtype: u16,
+    rdlen: c_ushort,
+    rdata: *const c_void,
+    ttl: u32_t,
+) -> DNSServiceErrorType;
+
+pub type FnDNSServiceCreateConnection = unsafe extern "C" fn(sd_ref: *mut DNSServiceRef) -> DNSServiceErrorType;
+
+pub type FnDNSServiceRegisterRecord = unsafe extern "C" fn(
+    sd_ref: DNSServiceRef,
+    record_ref: *mut DNSRecordRef,
+    flags: DNSServiceFlags,
+    interface_index: u32_t,
+    fullname: *const c_char,
+    rrtype: u16,
+    rrclass: u16,
+    rdlen: c_ushort,
+    rdata: *const c_void,
+    ttl: u32_t,
+    callback: DNSServiceRegisterRecordReply,
+    context: *mut c_void,
+) -> DNSServiceErrorType;
+
+pub type FnDNSServiceUpdateRecord = unsafe extern "C" fn(
+    sd_ref: DNSServiceRef,
+    record_ref: DNSRecordRef, // null = the registration's TXT record
+    flags: DNSServiceFlags,
+    rdlen: c_ushort,
+    rdata: *const c_void,
+    ttl: u32_t,
+) -> DNSServiceErrorType;
+
+/// Socket behind a service ref (`dnssd_sock_t`): a file descriptor on
+/// Unix, a winsock `SOCKET` handle on Windows
+#[cfg(not(windows))]
+pub type dnssd_sock_t = libc::c_int;
+#[cfg(windows)]
+pub type dnssd_sock_t = usize;
+
+/// `dnssd_sock_t` returned for a ref without a socket (`INVALID_SOCKET` on Windows)
+#[cfg(not(windows))]
+pub const DNSSD_INVALID_SOCK: dnssd_sock_t = -1;
+#[cfg(windows)]
+pub const DNSSD_INVALID_SOCK: dnssd_sock_t = !0;
+
+pub type FnDNSServiceRefSockFD = unsafe extern "C" fn(sd_ref: DNSServiceRef) -> dnssd_sock_t;
 
 pub type FnDNSServiceProcessResult = unsafe extern "C" fn(sd_ref: DNSServiceRef) -> DNSServiceErrorType;
 
@@ -237,6 +383,7 @@ pub fn check_error(err: DNSServiceErrorType) -> Result<(), String> {
     if err == K_DNS_SERVICE_ERR_NO_ERROR {
         Ok(())
     } else {
+        tracing::warn!("DNS-SD call failed with error {}", err);
         Err(format!("DNS-SD error: {}", err))
     }
 }
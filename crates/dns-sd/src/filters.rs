@@ -0,0 +1,201 @@
+//! Filters applied to browse results before they reach JS
+
+use crate::ServiceInfo;
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+/// Which address classes to drop from `ServiceInfo.addresses`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AddressFilter {
+    pub exclude_loopback: bool,
+    pub exclude_link_local: bool,
+    pub exclude_site_local: bool,
+    pub exclude_ipv4: bool,
+    pub exclude_ipv6: bool,
+}
+
+impl AddressFilter {
+    /// Whether any exclusion is enabled
+    pub fn is_active(&self) -> bool {
+        self.exclude_loopback
+            || self.exclude_link_local
+            || self.exclude_site_local
+            || self.exclude_ipv4
+            || self.exclude_ipv6
+    }
+
+    /// Remove excluded addresses in place
+    pub fn apply(&self, addresses: &mut Vec<String>) {
+        if self.is_active() {
+            addresses.retain(|addr| self.allows(addr));
+        }
+    }
+
+    /// Check a single address (may carry a `%scope` suffix)
+    pub fn allows(&self, addr: &str) -> bool {
+        let ip = match addr.split('%').next().unwrap_or(addr).parse::<IpAddr>() {
+            Ok(ip) => ip,
+            Err(_) => return true,
+        };
+
+        if (self.exclude_ipv4 && ip.is_ipv4()) || (self.exclude_ipv6 && ip.is_ipv6()) {
+            return false;
+        }
+        if self.exclude_loopback && ip.is_loopback() {
+            return false;
+        }
+        if self.exclude_link_local && is_link_local(&ip) {
+            return false;
+        }
+        if self.exclude_site_local && is_site_local(&ip) {
+            return false;
+        }
+        true
+    }
+}
+
+/// 169.254.0.0/16 and fe80::/10
+fn is_link_local(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.is_link_local(),
+        IpAddr::V6(v6) => (v6.segments()[0] & 0xffc0) == 0xfe80,
+    }
+}
+
+/// RFC 1918 private ranges, fec0::/10 (deprecated site-local) and fc00::/7 (ULA)
+fn is_site_local(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.is_private(),
+        IpAddr::V6(v6) => {
+            let first = v6.segments()[0];
+            (first & 0xffc0) == 0xfec0 || (first & 0xfe00) == 0xfc00
+        }
+    }
+}
+
+/// Pattern a service's instance name must match
+#[derive(Debug, Clone)]
+pub enum NamePattern {
+    /// `*` matches any run of characters and `?` any one, ignoring case
+    Glob(String),
+    Regex(regex::Regex),
+}
+
+impl NamePattern {
+    /// Compile a JS RegExp from its `source` and `flags`
+    pub fn regex(source: &str, flags: &str) -> Result<Self, String> {
+        regex::RegexBuilder::new(source)
+            .case_insensitive(flags.contains('i'))
+            .multi_line(flags.contains('m'))
+            .dot_matches_new_line(flags.contains('s'))
+            .build()
+            .map(NamePattern::Regex)
+            .map_err(|e| format!("Unsupported nameFilter pattern /{}/: {}", source, e))
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            NamePattern::Glob(pattern) => glob_match(pattern, name),
+            NamePattern::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+/// Instance names a browser leaves alone (`ignoreNames`, `onlyNames`)
+///
+/// Checked on browse answers, before any resolve starts, so the native
+/// backend spends no queries on them. Entries are exact names or globs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct NameList {
+    pub ignore: Vec<String>,
+    /// When non-empty, the only names reported
+    pub only: Vec<String>,
+}
+
+impl NameList {
+    pub fn is_active(&self) -> bool {
+        !self.ignore.is_empty() || !self.only.is_empty()
+    }
+
+    pub fn allows(&self, name: &str) -> bool {
+        !self.ignore.iter().any(|pattern| glob_match(pattern, name))
+            && (self.only.is_empty() || self.only.iter().any(|pattern| glob_match(pattern, name)))
+    }
+}
+
+/// Which services a browser reports, checked before events reach JS
+#[derive(Debug, Clone, Default)]
+pub struct ResultFilter {
+    pub name: Option<NamePattern>,
+    /// Also applied by the native browse itself, so these never resolve
+    pub names: NameList,
+    /// TXT entries the service must carry; an empty value only requires the key
+    pub txt: HashMap<String, String>,
+}
+
+impl ResultFilter {
+    pub fn is_active(&self) -> bool {
+        self.name.is_some() || self.names.is_active() || !self.txt.is_empty()
+    }
+
+    /// Whether a resolved service satisfies the name and TXT filters
+    pub fn matches(&self, info: &ServiceInfo) -> bool {
+        self.matches_name(info)
+            && self.txt.iter().all(|(key, value)| {
+                info.txt
+                    .get(key)
+                    .is_some_and(|actual| value.is_empty() || actual == value)
+            })
+    }
+
+    fn matches_name(&self, info: &ServiceInfo) -> bool {
+        self.name.as_ref().is_none_or(|pattern| pattern.matches(&info.name)) && self.names.allows(&info.name)
+    }
+
+    /// Event to pass on for a backend event, if any
+    ///
+    /// `reported` says whether the sighting was delivered before. Lost
+    /// services carry no TXT record, so only their name is checked; a
+    /// reported service whose TXT record stops matching is reported lost.
+    pub fn screen<'a>(&self, event: &'a str, info: &ServiceInfo, reported: bool) -> Option<&'a str> {
+        match event {
+            _ if !self.is_active() => Some(event),
+            "serviceLost" => self.matches_name(info).then_some(event),
+            "serviceFound" | "serviceUpdated" | "txtError" | "resolveTimeout" | "resolveFailed" if !self.matches(info) => {
+                (reported && event == "serviceFound").then_some("serviceLost")
+            }
+            _ => Some(event),
+        }
+    }
+}
+
+/// Match `name` against a glob of `*` and `?`, ignoring case
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().flat_map(char::to_lowercase).collect();
+    let name: Vec<char> = name.chars().flat_map(char::to_lowercase).collect();
+    let (mut p, mut n) = (0, 0);
+    // Position after the last `*` and the name position it is retried from
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // Let the last `*` absorb one more character
+                Some((after, from)) => {
+                    star = Some((after, from + 1));
+                    p = after;
+                    n = from + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,121 @@
+//! Cache-flush view of the address records the fallback hears
+//!
+//! A responder whose address changes announces the new A/AAAA set with the
+//! cache-flush bit, meaning every other record of that name and type is
+//! gone (RFC 6762 section 10.2). mdns-sd expires the old records a second
+//! later but only tells hostname lookups, so a browser would go on
+//! reporting them. The fallback feeds every response heard to [`record`],
+//! and its browsers pass resolved addresses through [`current`] and watch
+//! [`generation`] to report a replaced set as one update.
+
+use crate::ServiceInfo;
+use crate::ffi::{K_DNS_SERVICE_TYPE_A, K_DNS_SERVICE_TYPE_AAAA};
+use crate::multicast;
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Records of one set sent within this long of each other belong together,
+/// e.g. the same announcement repeated on each link
+const FLUSH_WINDOW: Duration = Duration::from_secs(1);
+
+/// What is known of one host's A or AAAA records
+#[derive(Default)]
+struct Rrset {
+    /// Addresses of the latest cache-flush announcements, with when each
+    /// was last announced
+    live: HashMap<IpAddr, Instant>,
+    /// Addresses a later announcement left out, or a goodbye withdrew
+    replaced: HashSet<IpAddr>,
+}
+
+static RRSETS: Lazy<Mutex<HashMap<(String, u16), Rrset>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Bumped whenever an address stops being current
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Key of a host name, so `Printer.local.` and `printer.local` match
+pub fn host_key(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Take in the address records of a packet heard on the mDNS port
+pub fn record(packet: &[u8]) {
+    let Some(records) = multicast::response_records(packet) else { return };
+    let now = Instant::now();
+    let mut announced: HashMap<(String, u16), Vec<IpAddr>> = HashMap::new();
+    let mut withdrawn: Vec<((String, u16), IpAddr)> = Vec::new();
+    for record in &records {
+        let Some(addr) = record.address() else { continue };
+        let key = (host_key(&record.name), record.rrtype);
+        if record.ttl == 0 {
+            withdrawn.push((key, addr));
+        } else if record.cache_flush {
+            announced.entry(key).or_default().push(addr);
+        }
+    }
+    if announced.is_empty() && withdrawn.is_empty() {
+        return;
+    }
+
+    let mut changed = false;
+    let mut rrsets = RRSETS.lock().unwrap();
+    for (key, addrs) in announced {
+        let rrset = rrsets.entry(key).or_default();
+        let flushed: Vec<IpAddr> = rrset
+            .live
+            .iter()
+            .filter(|(addr, seen)| !addrs.contains(addr) && now.duration_since(**seen) >= FLUSH_WINDOW)
+            .map(|(addr, _)| *addr)
+            .collect();
+        for addr in flushed {
+            rrset.live.remove(&addr);
+            changed |= rrset.replaced.insert(addr);
+        }
+        for addr in addrs {
+            rrset.replaced.remove(&addr);
+            rrset.live.insert(addr, now);
+        }
+    }
+    for (key, addr) in withdrawn {
+        let rrset = rrsets.entry(key).or_default();
+        rrset.live.remove(&addr);
+        changed |= rrset.replaced.insert(addr);
+    }
+    if changed {
+        GENERATION.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Changes whenever an address heard before stops being current
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// `info`'s addresses without those a later announcement replaced or a
+/// goodbye withdrew
+pub fn current(info: &ServiceInfo) -> Vec<String> {
+    let host = host_key(&info.host_name);
+    let rrsets = RRSETS.lock().unwrap();
+    info.addresses
+        .iter()
+        .filter(|addr| {
+            // mdns-sd scopes link-local IPv6 addresses with `%interface`
+            let Ok(ip) = addr.split('%').next().unwrap_or(addr).parse::<IpAddr>() else {
+                return true;
+            };
+            let rrtype = if ip.is_ipv4() { K_DNS_SERVICE_TYPE_A } else { K_DNS_SERVICE_TYPE_AAAA };
+            // Addresses not heard yet may be newer than this view, so they stay
+            !rrsets.get(&(host.clone(), rrtype)).is_some_and(|rrset| rrset.replaced.contains(&ip))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Forget everything heard, when the fallback daemon shuts down
+pub fn clear() {
+    RRSETS.lock().unwrap().clear();
+}
@@ -0,0 +1,708 @@
+//! Live browse/advertise handles and the registries used for introspection
+//!
+//! Each JS environment (the main thread and every worker thread) gets its
+//! own registries, kept in Neon instance data. Listing only sees the
+//! caller's handles, and when an environment is torn down its remaining
+//! handles are stopped with it.
+//!
+//! An `atexit` hook does the same for every environment before Node's own
+//! teardown, so no poll thread is still running (or calling into a freed
+//! environment) while the process exits.
+
+use crate::instances::InstanceTable;
+use crate::manager::{DiscoveryManager, ManagerBox};
+use crate::options::BrowseOptions;
+use crate::queue::EventQueue;
+use crate::{AdvertiseOptions, Backend, ServiceInfo, domains, fallback, manifest, mock, native, shared};
+#[cfg(feature = "dns-update")]
+use crate::update;
+use neon::prelude::*;
+use neon::thread::LocalKey;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, Once, Weak};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+// Global handle counter
+static NEXT_HANDLE: AtomicU32 = AtomicU32::new(1);
+
+fn next_handle() -> u32 {
+    NEXT_HANDLE.fetch_add(1, Ordering::SeqCst)
+}
+
+/// Longest a confirmed stop waits for goodbyes to go out
+pub const GOODBYE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Backend object that can be torn down
+pub trait Stoppable {
+    fn stop(&mut self);
+
+    /// Backend threads still running, for diagnostics
+    fn threads(&self) -> usize {
+        0
+    }
+}
+
+/// 1 while `thread` runs, else 0
+pub fn running(thread: &Option<thread::JoinHandle<()>>) -> usize {
+    thread.as_ref().is_some_and(|t| !t.is_finished()) as usize
+}
+
+// Browser handles storage
+pub enum BrowserHandle {
+    Native(native::NativeBrowser),
+    Fallback(fallback::FallbackBrowser),
+    Mock(mock::MockBrowser),
+    /// Share of a browser used by several handles
+    Shared(shared::Subscription),
+    /// Browses of the same type in every browse domain
+    Domains(domains::DomainsBrowser),
+}
+
+impl BrowserHandle {
+    /// Backend actually serving the browse, which differs from the requested
+    /// one after a fallback
+    pub fn backend(&self) -> Option<Backend> {
+        match self {
+            BrowserHandle::Native(_) => Some(Backend::Native),
+            BrowserHandle::Fallback(_) => Some(Backend::Fallback),
+            BrowserHandle::Mock(_) => Some(Backend::Mock),
+            BrowserHandle::Shared(s) => Some(s.backend()),
+            BrowserHandle::Domains(d) => d.backend(),
+        }
+    }
+}
+
+impl Stoppable for BrowserHandle {
+    fn stop(&mut self) {
+        match self {
+            BrowserHandle::Native(b) => b.stop(),
+            BrowserHandle::Fallback(b) => b.stop(),
+            BrowserHandle::Mock(b) => b.stop(),
+            BrowserHandle::Shared(s) => s.stop(),
+            BrowserHandle::Domains(d) => d.stop(),
+        }
+    }
+
+    fn threads(&self) -> usize {
+        match self {
+            BrowserHandle::Native(b) => b.threads(),
+            BrowserHandle::Fallback(b) => b.threads(),
+            BrowserHandle::Mock(b) => b.threads(),
+            BrowserHandle::Shared(s) => s.threads(),
+            BrowserHandle::Domains(d) => d.threads(),
+        }
+    }
+}
+
+// Advertisement handles storage
+pub enum AdvertisementHandle {
+    Native(native::NativeAdvertisement),
+    Fallback(fallback::FallbackAdvertisement),
+    Mock(mock::MockAdvertisement),
+    /// Several services registered and withdrawn together
+    Bundle(Vec<AdvertisementHandle>),
+    /// Services kept in sync with a manifest file
+    Manifest(manifest::ManifestAdvertisement),
+    /// Service registered in a unicast zone by DNS Update
+    #[cfg(feature = "dns-update")]
+    WideArea(update::WideAreaAdvertisement),
+}
+
+impl AdvertisementHandle {
+    /// Backend actually holding the registration (None for DNS Update and manifests)
+    pub fn backend(&self) -> Option<Backend> {
+        match self {
+            AdvertisementHandle::Native(_) => Some(Backend::Native),
+            AdvertisementHandle::Fallback(_) => Some(Backend::Fallback),
+            AdvertisementHandle::Mock(_) => Some(Backend::Mock),
+            AdvertisementHandle::Bundle(ads) => ads.first().and_then(AdvertisementHandle::backend),
+            AdvertisementHandle::Manifest(_) => None,
+            #[cfg(feature = "dns-update")]
+            AdvertisementHandle::WideArea(_) => None,
+        }
+    }
+
+    /// Stop, returning once goodbyes went out or at `deadline`
+    pub fn stop_confirmed(&mut self, deadline: Instant) {
+        if self.withdraw(deadline) {
+            thread::sleep(native::GOODBYE_GRACE.min(deadline.saturating_duration_since(Instant::now())));
+        }
+    }
+
+    /// Stop, waiting for the confirmations backends give; true if a native
+    /// registration was left to send its goodbyes unconfirmed
+    fn withdraw(&mut self, deadline: Instant) -> bool {
+        match self {
+            AdvertisementHandle::Native(a) => {
+                a.stop();
+                true
+            }
+            AdvertisementHandle::Fallback(a) => {
+                a.stop_confirmed(deadline);
+                false
+            }
+            AdvertisementHandle::Bundle(ads) => {
+                // Every service is withdrawn, not just up to the first native one
+                let mut native = false;
+                for ad in ads {
+                    native |= ad.withdraw(deadline);
+                }
+                native
+            }
+            // Withdrawn from the watcher thread, which confirms them itself
+            AdvertisementHandle::Manifest(m) => {
+                m.stop();
+                false
+            }
+            // Returns once the server acknowledged the removal
+            #[cfg(feature = "dns-update")]
+            AdvertisementHandle::WideArea(a) => {
+                a.stop();
+                false
+            }
+            AdvertisementHandle::Mock(a) => {
+                a.stop();
+                false
+            }
+        }
+    }
+
+    /// Announce the service again after a network change
+    ///
+    /// The native daemon follows network changes itself, so only the
+    /// backends that don't are told.
+    pub fn refresh(&mut self) {
+        match self {
+            AdvertisementHandle::Native(_) | AdvertisementHandle::Mock(_) => {}
+            AdvertisementHandle::Fallback(a) => a.refresh(),
+            AdvertisementHandle::Bundle(ads) => ads.iter_mut().for_each(AdvertisementHandle::refresh),
+            AdvertisementHandle::Manifest(m) => m.refresh(),
+            #[cfg(feature = "dns-update")]
+            AdvertisementHandle::WideArea(a) => a.refresh(),
+        }
+    }
+}
+
+impl Stoppable for AdvertisementHandle {
+    fn stop(&mut self) {
+        match self {
+            AdvertisementHandle::Native(a) => a.stop(),
+            AdvertisementHandle::Fallback(a) => a.stop(),
+            AdvertisementHandle::Mock(a) => a.stop(),
+            AdvertisementHandle::Bundle(ads) => ads.iter_mut().for_each(Stoppable::stop),
+            AdvertisementHandle::Manifest(m) => m.stop(),
+            #[cfg(feature = "dns-update")]
+            AdvertisementHandle::WideArea(a) => a.stop(),
+        }
+    }
+
+    fn threads(&self) -> usize {
+        match self {
+            AdvertisementHandle::Native(a) => a.threads(),
+            // The daemon's threads serve every fallback handle
+            AdvertisementHandle::Fallback(_) => 0,
+            AdvertisementHandle::Mock(a) => a.threads(),
+            AdvertisementHandle::Bundle(ads) => ads.iter().map(Stoppable::threads).sum(),
+            AdvertisementHandle::Manifest(m) => m.threads(),
+            #[cfg(feature = "dns-update")]
+            AdvertisementHandle::WideArea(a) => a.threads(),
+        }
+    }
+}
+
+/// Lifecycle of a handle's backend object
+pub enum Slot<H> {
+    Active(H),
+    Paused,
+    /// Withdrawn to be registered again with changed options
+    Changing,
+    Stopped,
+}
+
+/// Reopens the backend object of a paused handle
+pub type Restart<H> = Box<dyn Fn() -> Result<H, String> + Send + Sync>;
+
+/// State shared by a handle's JS box and the registry
+pub struct HandleEntry<H> {
+    pub id: u32,
+    pub service_type: String,
+    pub backend: Backend,
+    pub created_at: SystemTime,
+    pub slot: Mutex<Slot<H>>,
+    /// Dropped on stop: it holds the handle's channel, which keeps Node's event loop alive
+    restart: Mutex<Option<Arc<Restart<H>>>>,
+    /// Browsers only
+    browse: Option<BrowseState>,
+    /// Single advertisements only: what `restart` registers, renamed in place
+    options: Option<Arc<Mutex<AdvertiseOptions>>>,
+}
+
+/// What a browser handle keeps besides its backend object
+pub struct BrowseState {
+    /// Services reported and not yet lost
+    pub table: Arc<Mutex<InstanceTable>>,
+    /// Delivers events past the table; weak, as it holds the handle's channel
+    pub deliver: Weak<Deliver>,
+    /// Events waiting for the JS thread, for diagnostics
+    pub queue: Weak<EventQueue>,
+    /// What the browser was opened with, for resolving its results on demand
+    pub options: BrowseOptions,
+}
+
+/// Event sink of a browser handle
+pub type Deliver = dyn Fn(&str, ServiceInfo) + Send + Sync;
+
+impl<H: Stoppable> HandleEntry<H> {
+    /// Stop the backend object, returning false if it was already stopped
+    pub fn stop(&self) -> bool {
+        self.restart.lock().unwrap().take();
+        let slot = std::mem::replace(&mut *self.slot.lock().unwrap(), Slot::Stopped);
+        match slot {
+            Slot::Active(mut inner) => {
+                inner.stop();
+                true
+            }
+            // A change in progress withdraws what it registered
+            Slot::Paused | Slot::Changing => true,
+            Slot::Stopped => false,
+        }
+    }
+
+    /// Stop without blocking the caller
+    ///
+    /// The handle reads as stopped right away; joining the backend thread
+    /// (up to one poll interval) happens on a background thread.
+    pub fn stop_in_background(&self) -> bool
+    where
+        H: Send + 'static,
+    {
+        self.restart.lock().unwrap().take();
+        match std::mem::replace(&mut *self.slot.lock().unwrap(), Slot::Stopped) {
+            Slot::Active(mut inner) => {
+                stop_in_background(move || inner.stop());
+                true
+            }
+            // A change in progress withdraws what it registered
+            Slot::Paused | Slot::Changing => true,
+            Slot::Stopped => false,
+        }
+    }
+
+    /// Tear down the backend object but keep the handle resumable
+    pub fn pause(&self) -> bool {
+        if self.restart.lock().unwrap().is_none() {
+            return false;
+        }
+        let mut slot = self.slot.lock().unwrap();
+        match std::mem::replace(&mut *slot, Slot::Paused) {
+            Slot::Active(mut inner) => {
+                inner.stop();
+                true
+            }
+            other => {
+                *slot = other;
+                false
+            }
+        }
+    }
+
+    /// Reopen the backend object of a paused handle
+    pub fn resume(&self) -> Result<bool, String> {
+        let mut slot = self.slot.lock().unwrap();
+        match (&*slot, &*self.restart.lock().unwrap()) {
+            (Slot::Paused, Some(restart)) => {
+                *slot = Slot::Active(restart()?);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Services a browser currently reports; empty once stopped
+    pub fn services(&self) -> Vec<ServiceInfo> {
+        match (&*self.slot.lock().unwrap(), &self.browse) {
+            (Slot::Stopped, _) | (_, None) => Vec::new(),
+            (_, Some(browse)) => browse.table.lock().unwrap().services(),
+        }
+    }
+
+    /// A running browser's sighting of the instance `name`, with the
+    /// options it browses with
+    pub fn sighting(&self, name: &str) -> Option<(ServiceInfo, BrowseOptions)> {
+        match (&*self.slot.lock().unwrap(), &self.browse) {
+            (Slot::Stopped, _) | (_, None) => None,
+            (_, Some(browse)) => Some((browse.table.lock().unwrap().sighting(name)?, browse.options.clone())),
+        }
+    }
+
+    /// Snapshot for introspection
+    pub fn info(&self) -> HandleInfo {
+        let (state, threads) = match &*self.slot.lock().unwrap() {
+            Slot::Active(inner) => ("active", inner.threads()),
+            Slot::Paused => ("paused", 0),
+            Slot::Changing => ("active", 0),
+            Slot::Stopped => ("stopped", 0),
+        };
+        HandleInfo {
+            id: self.id,
+            service_type: self.service_type.clone(),
+            backend: self.backend,
+            created_at: self.created_at,
+            state,
+            queue_depth: self.browse.as_ref().and_then(|browse| browse.queue.upgrade()).map(|queue| queue.depth()),
+            threads,
+        }
+    }
+}
+
+impl HandleEntry<BrowserHandle> {
+    /// Start tracking which services the browser reports again
+    fn begin_reconfirm(&self) {
+        if let Some(browse) = &self.browse {
+            browse.table.lock().unwrap().begin_reconfirm();
+        }
+    }
+
+    /// Report the services not seen again since `begin_reconfirm` as lost
+    fn expire_unconfirmed(&self) {
+        let Some(browse) = &self.browse else { return };
+        let lost = browse.table.lock().unwrap().expire_unconfirmed();
+        if let Some(deliver) = browse.deliver.upgrade() {
+            for info in lost {
+                deliver("serviceLost", info);
+            }
+        }
+    }
+}
+
+impl HandleEntry<AdvertisementHandle> {
+    /// Stop, returning once goodbyes went out (or after `timeout`); false if
+    /// it was already stopped
+    pub fn stop_confirmed(&self, timeout: Duration) -> bool {
+        self.restart.lock().unwrap().take();
+        // Released before waiting, so listing and stopping don't block on the goodbyes
+        let slot = std::mem::replace(&mut *self.slot.lock().unwrap(), Slot::Stopped);
+        match slot {
+            Slot::Active(mut ad) => {
+                ad.stop_confirmed(Instant::now() + timeout);
+                true
+            }
+            // A change in progress withdraws what it registered
+            Slot::Paused | Slot::Changing => true,
+            Slot::Stopped => false,
+        }
+    }
+
+    /// Re-announce a running advertisement
+    pub fn refresh(&self) {
+        if let Slot::Active(ad) = &mut *self.slot.lock().unwrap() {
+            ad.refresh();
+        }
+    }
+
+    /// Register under a new instance name, sending goodbyes for the old one
+    ///
+    /// Keeps the handle and its callback, which reports `registered` again.
+    /// If the new name can't be registered, the old one is put back and the
+    /// error returned. False if the advertisement was already stopped.
+    pub fn rename(&self, name: &str) -> Result<bool, String> {
+        self.reregister(|options| options.name = name.to_string())
+    }
+
+    /// Move the service to another port, keeping the handle
+    ///
+    /// mdns-sd announces the new SRV record under the same name, so peers
+    /// see an update. A DNS-SD daemon can't change the SRV record of a
+    /// registration, so elsewhere the service is registered again, which
+    /// peers see as lost and found. Fails like [`Self::rename`].
+    pub fn update_port(&self, port: u16) -> Result<bool, String> {
+        if let Some(options) = &self.options
+            && let Slot::Active(AdvertisementHandle::Fallback(ad)) = &mut *self.slot.lock().unwrap()
+        {
+            let mut options = options.lock().unwrap();
+            let updated = AdvertiseOptions { port, ..options.clone() };
+            ad.update(&updated)?;
+            *options = updated;
+            return Ok(true);
+        }
+        self.reregister(|options| options.port = port)
+    }
+
+    /// Withdraw the registration and register it again with changed options,
+    /// going back to the old ones if that fails
+    fn reregister(&self, change: impl FnOnce(&mut AdvertiseOptions)) -> Result<bool, String> {
+        let Some(options) = &self.options else {
+            return Err("Only single advertisements can be changed".to_string());
+        };
+        // Neither lock is held while the goodbyes and the new registration
+        // take their time, so listing or stopping the handle doesn't block
+        let Some(restart) = self.restart.lock().unwrap().clone() else {
+            return Ok(false);
+        };
+        let mut ad = {
+            let mut slot = self.slot.lock().unwrap();
+            match std::mem::replace(&mut *slot, Slot::Changing) {
+                Slot::Active(ad) => ad,
+                Slot::Changing => return Err("The advertisement is already being changed".to_string()),
+                other => {
+                    *slot = other;
+                    return Ok(false);
+                }
+            }
+        };
+        ad.stop_confirmed(Instant::now() + GOODBYE_TIMEOUT);
+        let old = options.lock().unwrap().clone();
+        change(&mut options.lock().unwrap());
+        let (result, reopened) = match restart() {
+            Ok(ad) => (Ok(true), Some(ad)),
+            Err(e) => {
+                *options.lock().unwrap() = old;
+                let restored = restart()
+                    .inspect_err(|e| tracing::warn!("Couldn't restore advertisement after a failed change: {}", e))
+                    .ok();
+                (Err(e), restored)
+            }
+        };
+
+        let mut slot = self.slot.lock().unwrap();
+        if !matches!(*slot, Slot::Changing) {
+            // Stopped meanwhile: withdraw what was just registered
+            drop(slot);
+            if let Some(mut ad) = reopened {
+                stop_in_background(move || ad.stop());
+            }
+            return Ok(false);
+        }
+        *slot = reopened.map_or(Slot::Stopped, Slot::Active);
+        result
+    }
+}
+
+/// Handle owned by a JS object; stopped explicitly or when garbage collected
+///
+/// Neither blocks the JS thread: the backend thread is joined in the background.
+pub struct HandleBox<H>(pub Arc<HandleEntry<H>>);
+
+impl<H: Stoppable + Send + 'static> Finalize for HandleBox<H> {
+    fn finalize<'a, C: Context<'a>>(self, _cx: &mut C) {
+        self.0.stop_in_background();
+    }
+}
+
+pub type BrowserBox = HandleBox<BrowserHandle>;
+pub type AdvertisementBox = HandleBox<AdvertisementHandle>;
+
+/// Weak references to every handle not yet garbage collected
+pub struct Registry<H>(Mutex<HashMap<u32, Weak<HandleEntry<H>>>>);
+
+impl<H> Default for Registry<H> {
+    fn default() -> Self {
+        Registry(Mutex::new(HashMap::new()))
+    }
+}
+
+impl<H: Stoppable> Registry<H> {
+    /// Create and register an entry for a freshly started backend object
+    pub fn insert(
+        &self,
+        service_type: &str,
+        backend: Backend,
+        inner: H,
+        restart: Option<Restart<H>>,
+        browse: Option<BrowseState>,
+        options: Option<Arc<Mutex<AdvertiseOptions>>>,
+    ) -> HandleBox<H> {
+        let entry = Arc::new(HandleEntry {
+            id: next_handle(),
+            service_type: service_type.to_string(),
+            backend,
+            created_at: SystemTime::now(),
+            slot: Mutex::new(Slot::Active(inner)),
+            restart: Mutex::new(restart.map(Arc::new)),
+            browse,
+            options,
+        });
+        self.0.lock().unwrap().insert(entry.id, Arc::downgrade(&entry));
+        HandleBox(entry)
+    }
+
+    /// Snapshots of all live entries, pruning collected ones
+    pub fn list(&self) -> Vec<HandleInfo> {
+        let mut map = self.0.lock().unwrap();
+        map.retain(|_, weak| weak.strong_count() > 0);
+        let mut infos: Vec<HandleInfo> = map.values().filter_map(|w| w.upgrade()).map(|e| e.info()).collect();
+        infos.sort_by_key(|info| info.id);
+        infos
+    }
+
+    /// Every live entry
+    fn entries(&self) -> Vec<Arc<HandleEntry<H>>> {
+        self.0.lock().unwrap().values().filter_map(Weak::upgrade).collect()
+    }
+
+    /// Stop every live entry
+    fn stop_all(&self) {
+        let entries: Vec<_> = self.0.lock().unwrap().drain().filter_map(|(_, w)| w.upgrade()).collect();
+        for entry in entries {
+            entry.stop();
+        }
+    }
+}
+
+/// Handles created by one JS environment
+#[derive(Default)]
+pub struct EnvHandles {
+    pub browsers: Registry<BrowserHandle>,
+    pub advertisements: Registry<AdvertisementHandle>,
+    managers: Mutex<Vec<Weak<DiscoveryManager>>>,
+}
+
+impl EnvHandles {
+    /// Track a discovery manager so it is stopped with the environment
+    pub fn insert_manager(&self, manager: DiscoveryManager) -> ManagerBox {
+        let manager = Arc::new(manager);
+        let mut managers = self.managers.lock().unwrap();
+        managers.retain(|weak| weak.strong_count() > 0);
+        managers.push(Arc::downgrade(&manager));
+        ManagerBox(manager)
+    }
+
+    /// Stop every handle and manager of this environment
+    fn stop_all(&self) {
+        self.browsers.stop_all();
+        self.advertisements.stop_all();
+        let managers: Vec<_> = self.managers.lock().unwrap().drain(..).filter_map(|w| w.upgrade()).collect();
+        for manager in managers {
+            manager.stop();
+        }
+    }
+}
+
+impl Drop for EnvHandles {
+    // Runs when the environment's instance data is dropped: worker exit,
+    // Electron renderer reload or navigation, or process exit
+    fn drop(&mut self) {
+        self.stop_all();
+        if environments().is_empty() {
+            fallback::shutdown_daemon();
+        }
+    }
+}
+
+static LOCAL: LocalKey<Arc<EnvHandles>> = LocalKey::new();
+static AT_EXIT: Once = Once::new();
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Held while `refresh_all` runs
+static REFRESHING: Mutex<()> = Mutex::new(());
+
+/// Joins still running from [`stop_in_background`]
+static STOPPING: Lazy<Mutex<Vec<thread::JoinHandle<()>>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Run a blocking stop on its own thread; [`shutdown`] waits for it
+pub fn stop_in_background(stop: impl FnOnce() + Send + 'static) {
+    let mut pending = STOPPING.lock().unwrap();
+    pending.retain(|t| !t.is_finished());
+    pending.push(thread::spawn(stop));
+}
+
+/// Background stops still joining their backend threads
+pub fn pending_stops() -> usize {
+    STOPPING.lock().unwrap().iter().filter(|t| !t.is_finished()).count()
+}
+
+/// Every environment still alive, for process-wide metrics
+static ENVIRONMENTS: Lazy<Mutex<Vec<Weak<EnvHandles>>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Registries of the calling environment
+pub fn local<'cx>(cx: &mut impl Context<'cx>) -> Arc<EnvHandles> {
+    LOCAL
+        .get_or_init(cx, || {
+            AT_EXIT.call_once(|| {
+                unsafe { libc::atexit(shutdown_at_exit) };
+                crate::netwatch::start_default();
+            });
+            let handles = Arc::new(EnvHandles::default());
+            ENVIRONMENTS.lock().unwrap().push(Arc::downgrade(&handles));
+            handles
+        })
+        .clone()
+}
+
+/// Registries of all live environments
+pub fn environments() -> Vec<Arc<EnvHandles>> {
+    let mut envs = ENVIRONMENTS.lock().unwrap();
+    envs.retain(|weak| weak.strong_count() > 0);
+    envs.iter().filter_map(Weak::upgrade).collect()
+}
+
+/// Restart every running browse and re-announce every advertisement
+///
+/// All browsers are paused before any is resumed, so a backend browser
+/// shared by several handles is really closed and opened again. Services
+/// a restarted browser doesn't report again within `grace` are reported
+/// lost.
+pub fn refresh_all(grace: Duration) {
+    if shutting_down() {
+        return;
+    }
+    // A network change and a wake often come together; one refresh at a time
+    let _refreshing = REFRESHING.lock().unwrap();
+    let envs = environments();
+    let paused: Vec<_> = envs.iter().flat_map(|env| env.browsers.entries()).filter(|entry| entry.pause()).collect();
+    for entry in &paused {
+        entry.begin_reconfirm();
+        if let Err(e) = entry.resume() {
+            tracing::warn!("Failed to restart browse of {}: {}", entry.service_type, e);
+        }
+    }
+    for entry in envs.iter().flat_map(|env| env.advertisements.entries()) {
+        entry.refresh();
+    }
+    drop(envs);
+
+    thread::sleep(grace);
+    for entry in paused {
+        entry.expire_unconfirmed();
+    }
+}
+
+/// Whether the process is exiting; long waits should give up
+pub fn shutting_down() -> bool {
+    SHUTTING_DOWN.load(Ordering::SeqCst)
+}
+
+/// Stop every handle in every environment, the network and wake watchers and the fallback daemon
+pub fn shutdown() {
+    SHUTTING_DOWN.store(true, Ordering::SeqCst);
+    crate::netwatch::set_enabled(false);
+    crate::wake::set_enabled(false);
+    for env in environments() {
+        env.stop_all();
+    }
+    let pending = std::mem::take(&mut *STOPPING.lock().unwrap());
+    for stop in pending {
+        let _ = stop.join();
+    }
+    fallback::shutdown_daemon();
+}
+
+extern "C" fn shutdown_at_exit() {
+    shutdown();
+}
+
+/// Introspection snapshot of a handle
+pub struct HandleInfo {
+    pub id: u32,
+    pub service_type: String,
+    pub backend: Backend,
+    pub created_at: SystemTime,
+    pub state: &'static str,
+    /// Browsers only: events waiting for the JS thread
+    pub queue_depth: Option<usize>,
+    /// Backend threads still running
+    pub threads: usize,
+}
@@ -0,0 +1,407 @@
+//! One-call diagnostic for "discovery doesn't work on this machine"
+//!
+//! `check` looks at each layer in turn: the native library, the daemon
+//! socket behind it, a LocalOnly register/browse round-trip through the
+//! daemon, and multicast on every interface. The multicast probe sends a
+//! `_services._dns-sd._udp.local.` query from each interface, then listens
+//! for the copy looped back to this host and for answers from other hosts.
+
+use crate::multicast::{GROUP_V4, GROUP_V6, MDNS_PORT};
+use crate::{Backend, get_backend, native};
+use if_addrs::IfAddr;
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Service type registered by the round-trip check
+const HEALTH_TYPE: &str = "_dnssd-health._tcp";
+
+/// Outcome of one check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Failed,
+    /// Not applicable here (e.g. no native library for the round-trip)
+    Skipped,
+}
+
+impl Status {
+    /// Name reported to JS
+    pub fn name(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Failed => "failed",
+            Status::Skipped => "skipped",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub status: Status,
+    /// What was found, or why it failed or was skipped
+    pub detail: String,
+    pub elapsed: Duration,
+}
+
+impl Check {
+    pub fn timed(start: Instant, result: Result<String, String>) -> Check {
+        let (status, detail) = match result {
+            Ok(detail) => (Status::Ok, detail),
+            Err(detail) => (Status::Failed, detail),
+        };
+        Check { status, detail, elapsed: start.elapsed() }
+    }
+
+    pub fn skipped(detail: &str) -> Check {
+        Check { status: Status::Skipped, detail: detail.to_string(), elapsed: Duration::ZERO }
+    }
+}
+
+/// Multicast results for one interface address
+#[derive(Debug, Clone)]
+pub struct InterfaceHealth {
+    pub name: String,
+    pub index: u32,
+    pub address: IpAddr,
+    /// The query left through this interface
+    pub sent: bool,
+    /// Our own query came back, so the interface delivers multicast to this host
+    pub looped: bool,
+    /// Distinct other hosts heard from on this interface's link
+    pub peers: usize,
+    /// Why joining the group or sending failed
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthReport {
+    /// Backend new handles would use
+    pub backend: Backend,
+    pub native_library: Check,
+    pub daemon: Check,
+    pub round_trip: Check,
+    pub interfaces: Vec<InterfaceHealth>,
+}
+
+impl HealthReport {
+    /// The native checks pass when native is in use, and at least one
+    /// interface carries multicast
+    pub fn healthy(&self) -> bool {
+        let checks = [&self.native_library, &self.daemon, &self.round_trip];
+        let native_ok = self.backend != Backend::Native || checks.iter().all(|c| c.status != Status::Failed);
+        native_ok && self.interfaces.iter().any(|i| i.looped)
+    }
+}
+
+/// Run every check, spending at most about `timeout` on the network ones
+pub fn check(timeout: Duration) -> HealthReport {
+    let backend = get_backend();
+    let start = Instant::now();
+    let library = native::DnsSdLibrary::get().map(|_| crate::ffi::get_library_path().to_string());
+    let loaded = library.is_ok();
+    let native_library = Check::timed(start, library);
+
+    let start = Instant::now();
+    let daemon = match daemon_socket() {
+        Some(result) => Check::timed(start, result),
+        None => Check::skipped("No known daemon socket on this platform"),
+    };
+
+    // The round-trip waits on the daemon; probe multicast meanwhile
+    let (tx, rx) = mpsc::channel();
+    if loaded {
+        thread::spawn(move || {
+            let start = Instant::now();
+            let _ = tx.send(Check::timed(start, round_trip(timeout)));
+        });
+    } else {
+        let _ = tx.send(Check::skipped("Native library not loaded"));
+    }
+    let interfaces = probe_interfaces(timeout);
+    let round_trip = rx
+        .recv_timeout(timeout + Duration::from_secs(1))
+        .unwrap_or_else(|_| Check::skipped("Round-trip did not finish"));
+
+    HealthReport { backend, native_library, daemon, round_trip, interfaces }
+}
+
+/// Connect to the daemon's client socket, if this platform has a known one
+#[cfg(unix)]
+pub fn daemon_socket() -> Option<Result<String, String>> {
+    use std::os::unix::net::UnixStream;
+    use std::path::Path;
+
+    const CANDIDATES: &[&str] = &[
+        "/var/run/mDNSResponder",
+        "/var/run/mdnsd",
+        "/run/mdnsd",
+        "/run/avahi-daemon/socket",
+        "/var/run/avahi-daemon/socket",
+    ];
+    let Some(path) = CANDIDATES.iter().find(|p| Path::new(p).exists()) else {
+        return Some(Err("No daemon socket found (is mDNSResponder or avahi-daemon running?)".to_string()));
+    };
+    Some(
+        UnixStream::connect(path)
+            .map(|_| path.to_string())
+            .map_err(|e| format!("{}: {}", path, e)),
+    )
+}
+
+/// Connect to the Bonjour service's client port
+#[cfg(windows)]
+pub fn daemon_socket() -> Option<Result<String, String>> {
+    use std::net::TcpStream;
+
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, 5354));
+    Some(
+        TcpStream::connect_timeout(&addr, Duration::from_secs(1))
+            .map(|_| addr.to_string())
+            .map_err(|e| format!("{}: {} (is the Bonjour service running?)", addr, e)),
+    )
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn daemon_socket() -> Option<Result<String, String>> {
+    None
+}
+
+/// Register a LocalOnly service and browse until it shows up
+fn round_trip(timeout: Duration) -> Result<String, String> {
+    let interface = crate::interfaces::LOCAL_ONLY;
+    let name = format!("health-{}-{}", std::process::id(), nonce());
+    let (tx, rx) = mpsc::channel::<Result<(), String>>();
+
+    let found = tx.clone();
+    let wanted = name.clone();
+    let mut browser = native::NativeBrowser::new(
+        HEALTH_TYPE,
+        None,
+        interface,
+        0,
+        native::ResolveConfig::default(),
+        true,
+        Default::default(),
+        move |event, info| {
+            if event == "serviceFound" && info.name == wanted {
+                let _ = found.send(Ok(()));
+            }
+        },
+    )
+    .map_err(|e| format!("Browse failed: {}", e))?;
+
+    let options = crate::AdvertiseOptions {
+        name: name.clone(),
+        service_type: HEALTH_TYPE.to_string(),
+        port: 9,
+        interface_index: interface,
+        no_auto_rename: true,
+        ..Default::default()
+    };
+    let registered = tx;
+    let advertisement = native::NativeAdvertisement::new(&options, move |event, data| {
+        if event == "error" {
+            let _ = registered.send(Err(format!("Register failed: {}", data)));
+        }
+    });
+    let mut advertisement = match advertisement {
+        Ok(ad) => ad,
+        Err(e) => {
+            browser.stop();
+            return Err(format!("Register failed: {}", e));
+        }
+    };
+
+    let result = match rx.recv_timeout(timeout) {
+        Ok(Ok(())) => Ok(format!("{}.{}.local.", name, HEALTH_TYPE)),
+        Ok(Err(e)) => Err(e),
+        Err(_) => Err(format!("Registered service not browsed within {}ms", timeout.as_millis())),
+    };
+    advertisement.stop();
+    browser.stop();
+    result
+}
+
+/// Addresses of one interface, with the subnets used to attribute packets
+struct Local {
+    name: String,
+    index: u32,
+    addr: IfAddr,
+}
+
+impl Local {
+    fn ip(&self) -> IpAddr {
+        self.addr.ip()
+    }
+
+    /// Whether a packet from `source` arrived on this interface's link
+    fn on_link(&self, source: &SocketAddr) -> bool {
+        match (&self.addr, source) {
+            (IfAddr::V4(v4), SocketAddr::V4(src)) => {
+                let mask = u32::from(v4.netmask);
+                u32::from(v4.ip) & mask == u32::from(*src.ip()) & mask
+            }
+            (IfAddr::V6(v6), SocketAddr::V6(src)) => {
+                if src.scope_id() != 0 {
+                    return src.scope_id() == self.index;
+                }
+                let mask = u128::from(v6.netmask);
+                u128::from(v6.ip) & mask == u128::from(*src.ip()) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Send a query from every interface and listen for `timeout`
+fn probe_interfaces(timeout: Duration) -> Vec<InterfaceHealth> {
+    let locals: Vec<Local> = match if_addrs::get_if_addrs() {
+        Ok(addrs) => addrs
+            .into_iter()
+            .filter(|a| !a.is_loopback())
+            .map(|a| Local { index: a.index.unwrap_or(0), name: a.name, addr: a.addr })
+            .collect(),
+        Err(e) => {
+            tracing::warn!("Failed to list interfaces: {}", e);
+            return Vec::new();
+        }
+    };
+    let id = nonce() as u16;
+    let query = services_query(id);
+    let mut results: Vec<InterfaceHealth> = locals
+        .iter()
+        .map(|l| InterfaceHealth {
+            name: l.name.clone(),
+            index: l.index,
+            address: l.ip(),
+            sent: false,
+            looped: false,
+            peers: 0,
+            error: None,
+        })
+        .collect();
+
+    let sockets: Vec<UdpSocket> = [false, true]
+        .into_iter()
+        .filter_map(|v6| match open_socket(v6, &locals, &query, &mut results) {
+            Ok(socket) => Some(socket),
+            Err(e) => {
+                for (local, result) in locals.iter().zip(&mut results) {
+                    if local.ip().is_ipv6() == v6 {
+                        result.error = Some(e.clone());
+                    }
+                }
+                None
+            }
+        })
+        .collect();
+
+    let own: HashSet<IpAddr> = locals.iter().map(Local::ip).collect();
+    let mut peers: BTreeMap<usize, BTreeSet<IpAddr>> = BTreeMap::new();
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0u8; 9000];
+    while Instant::now() < deadline {
+        for socket in &sockets {
+            let Ok((len, source)) = socket.recv_from(&mut buf) else { continue };
+            let looped = own.contains(&source.ip()) && is_own_query(&buf[..len], id);
+            // IPv6 sent once per interface, from whichever address it picked
+            let sender = locals.iter().find(|l| l.ip() == source.ip()).map(|l| l.index);
+            for (i, local) in locals.iter().enumerate() {
+                let ours = local.ip() == source.ip() || (source.is_ipv6() && local.ip().is_ipv6() && sender == Some(local.index));
+                if looped && ours {
+                    results[i].looped = true;
+                } else if !own.contains(&source.ip()) && local.on_link(&source) {
+                    peers.entry(i).or_default().insert(source.ip());
+                }
+            }
+        }
+    }
+    for (i, hosts) in peers {
+        results[i].peers = hosts.len();
+    }
+    results
+}
+
+/// Bind the mDNS port for one family, join the group on each of its
+/// interfaces and send the query from each
+fn open_socket(v6: bool, locals: &[Local], query: &[u8], results: &mut [InterfaceHealth]) -> Result<UdpSocket, String> {
+    let domain = if v6 { Domain::IPV6 } else { Domain::IPV4 };
+    let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP)).map_err(|e| e.to_string())?;
+    socket.set_reuse_address(true).map_err(|e| e.to_string())?;
+    #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+    socket.set_reuse_port(true).map_err(|e| e.to_string())?;
+    let bind: SocketAddr = if v6 {
+        socket.set_only_v6(true).map_err(|e| e.to_string())?;
+        socket.set_multicast_loop_v6(true).map_err(|e| e.to_string())?;
+        socket.set_multicast_hops_v6(255).map_err(|e| e.to_string())?;
+        (Ipv6Addr::UNSPECIFIED, MDNS_PORT).into()
+    } else {
+        socket.set_multicast_loop_v4(true).map_err(|e| e.to_string())?;
+        socket.set_multicast_ttl_v4(255).map_err(|e| e.to_string())?;
+        (Ipv4Addr::UNSPECIFIED, MDNS_PORT).into()
+    };
+    socket
+        .bind(&bind.into())
+        .map_err(|e| format!("Failed to bind port {}: {}", MDNS_PORT, e))?;
+    socket.set_read_timeout(Some(Duration::from_millis(50))).map_err(|e| e.to_string())?;
+
+    // IPv6 joins and sends per interface index, however many addresses it has
+    let mut done: BTreeMap<u32, Result<(), String>> = BTreeMap::new();
+    for (local, result) in locals.iter().zip(results.iter_mut()) {
+        let outcome = match local.ip() {
+            IpAddr::V4(ip) if !v6 => socket
+                .join_multicast_v4(&GROUP_V4, &ip)
+                .and_then(|()| socket.set_multicast_if_v4(&ip))
+                .and_then(|()| socket.send_to(query, &SockAddr::from(SocketAddr::from((GROUP_V4, MDNS_PORT)))))
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            IpAddr::V6(_) if v6 => done
+                .entry(local.index)
+                .or_insert_with(|| {
+                    let group = SocketAddr::V6(std::net::SocketAddrV6::new(GROUP_V6, MDNS_PORT, 0, local.index));
+                    socket
+                        .join_multicast_v6(&GROUP_V6, local.index)
+                        .and_then(|()| socket.set_multicast_if_v6(local.index))
+                        .and_then(|()| socket.send_to(query, &SockAddr::from(group)))
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                })
+                .clone(),
+            _ => continue,
+        };
+        match outcome {
+            Ok(()) => result.sent = true,
+            Err(e) => result.error = Some(e),
+        }
+    }
+    Ok(socket.into())
+}
+
+/// PTR query for `_services._dns-sd._udp.local.`, which every responder answers
+fn services_query(id: u16) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(64);
+    packet.extend_from_slice(&id.to_be_bytes());
+    // Flags 0, one question, no answers
+    packet.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in ["_services", "_dns-sd", "_udp", "local"] {
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    // PTR, IN
+    packet.extend_from_slice(&[0, 12, 0, 1]);
+    packet
+}
+
+/// A query (not a response) carrying our transaction id
+fn is_own_query(packet: &[u8], id: u16) -> bool {
+    packet.len() >= 12 && packet[..2] == id.to_be_bytes() && packet[2] & 0x80 == 0
+}
+
+fn nonce() -> u32 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0) ^ std::process::id()
+}
@@ -0,0 +1,249 @@
+//! Per-browser table of reported service instances
+//!
+//! Turns raw backend events into the events delivered to JS: first sightings
+//! become `serviceFound`, later results `serviceUpdated` when they change what
+//! was delivered, and sightings of one instance on several interfaces are
+//! merged or kept apart per [`InterfaceMode`].
+
+use crate::ServiceInfo;
+use std::collections::{HashMap, HashSet};
+
+/// Service type without trailing dot or domain suffix (`_http._tcp`)
+///
+/// Backends disagree on whether `service_type` carries the domain and
+/// trailing dot, so both are stripped before comparing types.
+pub fn bare_type<'a>(service_type: &'a str, domain: &str) -> &'a str {
+    let domain = domain.trim_end_matches('.');
+    let stype = service_type.trim_end_matches('.');
+    stype
+        .strip_suffix(domain)
+        .and_then(|s| s.strip_suffix('.'))
+        .unwrap_or(stype)
+}
+
+/// Stable identity of a service instance across events
+pub fn instance_key(info: &ServiceInfo) -> String {
+    format!(
+        "{}|{}|{}",
+        info.name,
+        bare_type(&info.service_type, &info.domain),
+        info.domain.trim_end_matches('.')
+    )
+}
+
+/// How sightings on different interfaces are reported
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InterfaceMode {
+    /// Merge all interfaces into one logical service
+    Merged,
+    /// Pass every result through, keyed by instance only
+    Raw,
+    /// Report each (instance, interface) pair separately
+    PerInterface,
+}
+
+/// Reported instances, each with its latest result per interface
+pub struct InstanceTable {
+    mode: InterfaceMode,
+    entries: HashMap<String, HashMap<u32, ServiceInfo>>,
+    /// What was last delivered for each entry, to drop repeats
+    delivered: HashMap<String, ServiceInfo>,
+    /// Entries not reported again since `begin_reconfirm`
+    unconfirmed: HashSet<String>,
+}
+
+impl InstanceTable {
+    pub fn new(mode: InterfaceMode) -> Self {
+        InstanceTable {
+            mode,
+            entries: HashMap::new(),
+            delivered: HashMap::new(),
+            unconfirmed: HashSet::new(),
+        }
+    }
+
+    /// Apply a backend event, returning the event to deliver (if any)
+    pub fn apply(&mut self, event: &str, info: ServiceInfo) -> Option<(String, ServiceInfo)> {
+        let key = self.key(&info);
+        if event == "serviceFound" || event == "serviceLost" {
+            self.unconfirmed.remove(&key);
+        }
+
+        match event {
+            "serviceFound" => {
+                let is_new = !self.entries.contains_key(&key);
+                if !info.stale {
+                    self.drop_stale(&info);
+                }
+                let per_if = self.entries.entry(key.clone()).or_default();
+                let event = if is_new { "serviceFound" } else { "serviceUpdated" };
+
+                let info = if self.mode != InterfaceMode::Merged {
+                    // Only the latest result is kept, for `services`
+                    per_if.clear();
+                    per_if.insert(info.interface_index, info.clone());
+                    info
+                } else {
+                    per_if.insert(info.interface_index, info);
+                    merge(per_if)
+                };
+                self.deliver(key, event, info)
+            }
+            "serviceLost" => match self.mode {
+                InterfaceMode::Raw => {
+                    self.entries.remove(&key);
+                    self.delivered.remove(&key);
+                    Some(("serviceLost".to_string(), info))
+                }
+                InterfaceMode::PerInterface => {
+                    // Only the interface where the record disappeared is lost
+                    self.entries.remove(&key)?;
+                    self.delivered.remove(&key);
+                    Some(("serviceLost".to_string(), info))
+                }
+                InterfaceMode::Merged => {
+                    let per_if = self.entries.get_mut(&key)?;
+                    per_if.remove(&info.interface_index);
+                    if per_if.is_empty() {
+                        self.entries.remove(&key);
+                        self.delivered.remove(&key);
+                        Some(("serviceLost".to_string(), info))
+                    } else {
+                        // Still visible on another interface
+                        let merged = merge(per_if);
+                        self.deliver(key, "serviceUpdated", merged)
+                    }
+                }
+            },
+            other => Some((other.to_string(), info)),
+        }
+    }
+
+    /// Record `info` as delivered for `key`, unless it repeats what was
+    /// delivered last
+    fn deliver(&mut self, key: String, event: &str, info: ServiceInfo) -> Option<(String, ServiceInfo)> {
+        if self.delivered.get(&key) == Some(&info) {
+            return None;
+        }
+        self.delivered.insert(key, info.clone());
+        Some((event.to_string(), info))
+    }
+
+    /// Forget unconfirmed cached sightings of `info`'s instance, which may
+    /// carry another interface index, once the network reports it
+    fn drop_stale(&mut self, info: &ServiceInfo) {
+        let instance = instance_key(info);
+        for (key, per_if) in self.entries.iter_mut() {
+            if key == &instance || key.strip_prefix(instance.as_str()).is_some_and(|rest| rest.starts_with('|')) {
+                per_if.retain(|_, seen| !seen.stale);
+            }
+        }
+        self.entries.retain(|_, per_if| !per_if.is_empty());
+        let entries = &self.entries;
+        self.delivered.retain(|key, _| entries.contains_key(key));
+    }
+
+    /// Whether the sighting in `info` (its instance on its interface) is
+    /// currently reported
+    pub fn contains(&self, info: &ServiceInfo) -> bool {
+        self.entries
+            .get(&self.key(info))
+            .is_some_and(|per_if| self.mode != InterfaceMode::Merged || per_if.contains_key(&info.interface_index))
+    }
+
+    /// A reported sighting of the instance called `name`, with the
+    /// interface it was seen on; network sightings win over cached ones
+    pub fn sighting(&self, name: &str) -> Option<ServiceInfo> {
+        let mut sightings = self.entries.values().flat_map(|per_if| per_if.values()).filter(|info| info.name == name);
+        let first = sightings.next()?;
+        match first.stale {
+            true => Some(sightings.find(|info| !info.stale).unwrap_or(first).clone()),
+            false => Some(first.clone()),
+        }
+    }
+
+    /// The result last delivered for `info`'s instance (merged across
+    /// interfaces in [`InterfaceMode::Merged`])
+    pub fn reported(&self, info: &ServiceInfo) -> Option<ServiceInfo> {
+        self.delivered.get(&self.key(info)).cloned()
+    }
+
+    fn key(&self, info: &ServiceInfo) -> String {
+        match self.mode {
+            InterfaceMode::PerInterface => format!("{}|{}", instance_key(info), info.interface_index),
+            _ => instance_key(info),
+        }
+    }
+
+    /// Start tracking which services are reported again, after the browser
+    /// was reopened
+    pub fn begin_reconfirm(&mut self) {
+        self.unconfirmed = self.entries.keys().cloned().collect();
+    }
+
+    /// Remove the services not reported since `begin_reconfirm`, returning
+    /// them as their `serviceLost` events
+    pub fn expire_unconfirmed(&mut self) -> Vec<ServiceInfo> {
+        let mut lost = Vec::new();
+        for key in std::mem::take(&mut self.unconfirmed) {
+            self.delivered.remove(&key);
+            let Some(per_if) = self.entries.remove(&key) else { continue };
+            match self.mode {
+                InterfaceMode::Merged if !per_if.is_empty() => lost.push(merge(&per_if)),
+                _ => lost.extend(per_if.into_values()),
+            }
+        }
+        lost
+    }
+
+    /// Services currently reported, as last delivered, sorted by name
+    pub fn services(&self) -> Vec<ServiceInfo> {
+        let mut services: Vec<ServiceInfo> = self
+            .entries
+            .values()
+            .filter(|per_if| !per_if.is_empty())
+            .map(|per_if| match self.mode {
+                InterfaceMode::Merged => merge(per_if),
+                _ => per_if.values().next().unwrap().clone(),
+            })
+            .collect();
+        services.sort_by(|a, b| (&a.name, a.interface_index).cmp(&(&b.name, b.interface_index)));
+        services
+    }
+}
+
+/// `addressAdded` and `addressRemoved` events turning `before`'s addresses
+/// into `after`'s, each carrying the address that changed
+pub fn address_changes(before: &ServiceInfo, after: &ServiceInfo) -> Vec<(&'static str, ServiceInfo)> {
+    let removed = before.addresses.iter().filter(|addr| !after.addresses.contains(addr)).map(|addr| ("addressRemoved", addr));
+    let added = after.addresses.iter().filter(|addr| !before.addresses.contains(addr)).map(|addr| ("addressAdded", addr));
+    removed
+        .chain(added)
+        .map(|(event, addr)| (event, ServiceInfo { address: Some(addr.clone()), ..after.clone() }))
+        .collect()
+}
+
+/// Merge per-interface results into one logical service
+fn merge(per_if: &HashMap<u32, ServiceInfo>) -> ServiceInfo {
+    let mut interfaces: Vec<&u32> = per_if.keys().collect();
+    interfaces.sort();
+
+    let mut merged = per_if[interfaces[0]].clone();
+    merged.interface_index = 0;
+    for index in &interfaces[1..] {
+        let info = &per_if[*index];
+        for addr in &info.addresses {
+            if !merged.addresses.contains(addr) {
+                merged.addresses.push(addr.clone());
+            }
+        }
+        if merged.host_name.is_empty() {
+            merged.host_name = info.host_name.clone();
+            merged.port = info.port;
+        }
+        merged.txt.extend(info.txt.iter().map(|(k, v)| (k.clone(), v.clone())));
+        merged.ttl = merged.ttl.max(info.ttl);
+        merged.stale &= info.stale;
+    }
+    merged
+}
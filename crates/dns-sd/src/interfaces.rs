@@ -0,0 +1,273 @@
+//! Network interface helpers
+
+use crate::ffi::{K_DNS_SERVICE_INTERFACE_INDEX_LOCAL_ONLY, K_DNS_SERVICE_INTERFACE_INDEX_P2P};
+use std::net::{IpAddr, SocketAddr};
+#[cfg(unix)]
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Interface "index" confining a browse or registration to this machine
+pub const LOCAL_ONLY: u32 = K_DNS_SERVICE_INTERFACE_INDEX_LOCAL_ONLY;
+
+/// Interface "index" for peer-to-peer interfaces only (AWDL, Apple platforms)
+pub const P2P: u32 = K_DNS_SERVICE_INTERFACE_INDEX_P2P;
+
+/// Parse an interface option: an index (0 = all), `local-only` or `p2p`
+pub fn parse_interface(text: &str) -> Result<u32, String> {
+    match text {
+        "local-only" => Ok(LOCAL_ONLY),
+        "p2p" => Ok(P2P),
+        _ => text
+            .parse()
+            .map_err(|_| format!("Invalid interface: {} (expected an index, \"local-only\" or \"p2p\")", text)),
+    }
+}
+
+/// Resolve an interface index to its name (e.g. `en0`)
+#[cfg(unix)]
+pub fn index_to_name(index: u32) -> Option<String> {
+    let mut buf = [0 as libc::c_char; libc::IF_NAMESIZE];
+    // SAFETY: buf is IF_NAMESIZE bytes as required by if_indextoname
+    let ptr = unsafe { libc::if_indextoname(index, buf.as_mut_ptr()) };
+    if ptr.is_null() {
+        return None;
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+/// Resolve an interface index to its name (unsupported on this platform)
+#[cfg(not(unix))]
+pub fn index_to_name(_index: u32) -> Option<String> {
+    None
+}
+
+/// A network interface, for interface-selection UIs
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NetworkInterface {
+    /// Index accepted by the `interface` browse/advertise options
+    pub index: u32,
+    pub name: String,
+    /// Hardware address (`aa:bb:cc:dd:ee:ff`), if the interface has one
+    pub mac: Option<String>,
+    pub addresses: Vec<IpAddr>,
+    pub up: bool,
+    pub multicast: bool,
+    pub loopback: bool,
+}
+
+/// Every interface known to the system, sorted by index
+#[cfg(unix)]
+pub fn list() -> Result<Vec<NetworkInterface>, String> {
+    use std::collections::BTreeMap;
+    use std::ffi::CStr;
+
+    let mut ifap: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: getifaddrs fills ifap with a list freed below
+    if unsafe { libc::getifaddrs(&mut ifap) } != 0 {
+        return Err(format!("getifaddrs failed: {}", std::io::Error::last_os_error()));
+    }
+
+    let mut by_name: BTreeMap<String, NetworkInterface> = BTreeMap::new();
+    let mut cursor = ifap;
+    while !cursor.is_null() {
+        // SAFETY: cursor walks the list returned by getifaddrs
+        let ifa = unsafe { &*cursor };
+        cursor = ifa.ifa_next;
+        let name = unsafe { CStr::from_ptr(ifa.ifa_name) }.to_string_lossy().into_owned();
+        let entry = by_name.entry(name.clone()).or_insert_with(|| {
+            let flags = ifa.ifa_flags as libc::c_int;
+            let c_name = std::ffi::CString::new(name.clone()).unwrap_or_default();
+            NetworkInterface {
+                // SAFETY: c_name is a valid NUL-terminated string
+                index: unsafe { libc::if_nametoindex(c_name.as_ptr()) },
+                name,
+                up: flags & libc::IFF_UP != 0,
+                multicast: flags & libc::IFF_MULTICAST != 0,
+                loopback: flags & libc::IFF_LOOPBACK != 0,
+                ..Default::default()
+            }
+        });
+        if ifa.ifa_addr.is_null() {
+            continue;
+        }
+        // SAFETY: ifa_addr points to a sockaddr of the family it declares
+        unsafe {
+            match (*ifa.ifa_addr).sa_family as libc::c_int {
+                libc::AF_INET => {
+                    let sin = &*(ifa.ifa_addr as *const libc::sockaddr_in);
+                    entry.addresses.push(IpAddr::V4(Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr))));
+                }
+                libc::AF_INET6 => {
+                    let sin6 = &*(ifa.ifa_addr as *const libc::sockaddr_in6);
+                    entry.addresses.push(IpAddr::V6(Ipv6Addr::from(sin6.sin6_addr.s6_addr)));
+                }
+                family => {
+                    if let Some(mac) = link_address(ifa.ifa_addr, family) {
+                        entry.mac = Some(mac);
+                    }
+                }
+            }
+        }
+    }
+    // SAFETY: ifap came from getifaddrs and is no longer referenced
+    unsafe { libc::freeifaddrs(ifap) };
+
+    let mut interfaces: Vec<NetworkInterface> = by_name.into_values().collect();
+    interfaces.sort_by_key(|i| i.index);
+    Ok(interfaces)
+}
+
+/// Hardware address from an AF_PACKET sockaddr
+#[cfg(any(target_os = "linux", target_os = "android"))]
+unsafe fn link_address(addr: *const libc::sockaddr, family: libc::c_int) -> Option<String> {
+    if family != libc::AF_PACKET {
+        return None;
+    }
+    let sll = unsafe { &*(addr as *const libc::sockaddr_ll) };
+    format_mac(&sll.sll_addr[..(sll.sll_halen as usize).min(sll.sll_addr.len())])
+}
+
+/// Hardware address from an AF_LINK sockaddr
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+unsafe fn link_address(addr: *const libc::sockaddr, family: libc::c_int) -> Option<String> {
+    if family != libc::AF_LINK {
+        return None;
+    }
+    let sdl = unsafe { &*(addr as *const libc::sockaddr_dl) };
+    // The address follows the interface name in sdl_data
+    let data = unsafe { std::slice::from_raw_parts(sdl.sdl_data.as_ptr() as *const u8, sdl.sdl_nlen as usize + sdl.sdl_alen as usize) };
+    format_mac(&data[sdl.sdl_nlen as usize..])
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "freebsd"))))]
+unsafe fn link_address(_addr: *const libc::sockaddr, _family: libc::c_int) -> Option<String> {
+    None
+}
+
+/// `aa:bb:...`, or None for an empty or all-zero address
+#[cfg(unix)]
+fn format_mac(bytes: &[u8]) -> Option<String> {
+    if bytes.iter().all(|b| *b == 0) {
+        return None;
+    }
+    Some(bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":"))
+}
+
+/// Interfaces with addresses, from the IP helper API
+///
+/// Hardware addresses aren't available here, and multicast is assumed.
+#[cfg(not(unix))]
+pub fn list() -> Result<Vec<NetworkInterface>, String> {
+    let mut interfaces: Vec<NetworkInterface> = Vec::new();
+    for addr in if_addrs::get_if_addrs().map_err(|e| e.to_string())? {
+        let index = addr.index.unwrap_or(0);
+        let position = match interfaces.iter().position(|i| i.index == index && i.name == addr.name) {
+            Some(position) => position,
+            None => {
+                interfaces.push(NetworkInterface {
+                    index,
+                    name: addr.name.clone(),
+                    up: addr.is_oper_up(),
+                    multicast: true,
+                    loopback: addr.is_loopback(),
+                    ..Default::default()
+                });
+                interfaces.len() - 1
+            }
+        };
+        interfaces[position].addresses.push(addr.ip());
+    }
+    interfaces.sort_by_key(|i| i.index);
+    Ok(interfaces)
+}
+
+/// Interfaces picked by name (`interfaces: ["en0", "eth1"]`)
+///
+/// Names are resolved when a handle opens, since indices change across
+/// boots. The interfaces' subnets attribute results that don't say which
+/// interface they came from (mdns-sd's) to one of them.
+#[derive(Debug, Clone, Default)]
+pub struct InterfaceSet {
+    pub names: Vec<String>,
+    pub indices: Vec<u32>,
+    /// Address and prefix length of each of the interfaces' addresses
+    subnets: Vec<(IpAddr, u8)>,
+}
+
+/// Look up interfaces by name, failing for names the system doesn't know
+pub fn select(names: &[String]) -> Result<InterfaceSet, String> {
+    let known = list()?;
+    let mut set = InterfaceSet { names: names.to_vec(), ..Default::default() };
+    for name in names {
+        let iface = known
+            .iter()
+            .find(|iface| &iface.name == name)
+            .ok_or_else(|| format!("Unknown interface: {}", name))?;
+        set.indices.push(iface.index);
+    }
+    for addr in if_addrs::get_if_addrs().map_err(|e| e.to_string())? {
+        if names.contains(&addr.name) {
+            let prefix = match &addr.addr {
+                if_addrs::IfAddr::V4(v4) => v4.prefixlen,
+                if_addrs::IfAddr::V6(v6) => v6.prefixlen,
+            };
+            set.subnets.push((addr.ip(), prefix));
+        }
+    }
+    Ok(set)
+}
+
+impl InterfaceSet {
+    pub fn contains_index(&self, index: u32) -> bool {
+        self.indices.contains(&index)
+    }
+
+    /// Whether `address` (`fe80::1%en0` scoped or plain) is on the link of
+    /// one of the interfaces
+    pub fn covers_address(&self, address: &str) -> bool {
+        let (ip, scope) = match address.split_once('%') {
+            Some((ip, scope)) => (ip, Some(scope)),
+            None => (address, None),
+        };
+        if let Some(scope) = scope {
+            return self.names.iter().any(|name| name == scope)
+                || scope.parse().is_ok_and(|index| self.contains_index(index));
+        }
+        let Ok(ip) = ip.parse::<IpAddr>() else {
+            return false;
+        };
+        self.subnets.iter().any(|(local, prefix)| same_subnet(ip, *local, *prefix))
+    }
+}
+
+/// Name and index of the interface on whose link `source` is, from the
+/// addresses in `addrs`: by scope for link-local IPv6, else by subnet
+pub fn link_of(addrs: &[if_addrs::Interface], source: &SocketAddr) -> Option<(String, u32)> {
+    if let SocketAddr::V6(v6) = source
+        && v6.scope_id() != 0
+    {
+        let iface = addrs.iter().find(|a| a.index == Some(v6.scope_id()))?;
+        return Some((iface.name.clone(), v6.scope_id()));
+    }
+    addrs
+        .iter()
+        .find(|a| {
+            let prefix = match &a.addr {
+                if_addrs::IfAddr::V4(v4) => v4.prefixlen,
+                if_addrs::IfAddr::V6(v6) => v6.prefixlen,
+            };
+            same_subnet(source.ip(), a.ip(), prefix)
+        })
+        .map(|a| (a.name.clone(), a.index.unwrap_or(0)))
+}
+
+/// Whether the first `prefix` bits of two addresses of one family match
+fn same_subnet(a: IpAddr, b: IpAddr, prefix: u8) -> bool {
+    let (a, b) = match (a, b) {
+        (IpAddr::V4(a), IpAddr::V4(b)) => (u128::from(u32::from(a)) << 96, u128::from(u32::from(b)) << 96),
+        (IpAddr::V6(a), IpAddr::V6(b)) => (u128::from(a), u128::from(b)),
+        _ => return false,
+    };
+    let mask = u128::MAX.checked_shl(128 - u32::from(prefix.min(128))).unwrap_or(0);
+    a & mask == b & mask
+}
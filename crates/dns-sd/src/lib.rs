@@ -2,76 +2,656 @@
 //! 
 //! Tries native backend (Avahi/Bonjour) first, falls back to mdns-sd if unavailable.
 
+mod alias;
+mod cache;
+mod capture;
+mod completion;
+mod conflict;
+pub mod diagnostics;
+mod domains;
+mod emitter;
 mod ffi;
 mod native;
 mod fallback;
+mod filters;
+mod flush;
+mod handles;
+pub mod health;
+mod instances;
+mod interfaces;
+mod logging;
+mod manager;
+mod manifest;
+mod metrics;
+mod mock;
+mod multicast;
+mod model;
+pub mod names;
+mod netwatch;
+mod options;
+mod probe;
+mod queue;
+mod recording;
+pub mod selftest;
+pub mod query;
+#[cfg(feature = "bridge")]
+pub mod rpc;
+mod shared;
+mod tap;
+mod throttle;
+mod ttls;
+mod txt;
+#[cfg(feature = "dns-update")]
+mod update;
+mod wake;
+#[cfg(feature = "bridge")]
+pub mod websocket;
 
+use neon::event::Channel;
+use neon::handle::Root;
 use neon::prelude::*;
-use once_cell::sync::Lazy;
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::Mutex;
+use emitter::{Emitter, EmitterBox};
+use handles::{AdvertisementBox, BrowseState, BrowserBox, HandleEntry, HandleInfo};
+use instances::instance_key;
+use manager::{DiscoveryManager, ManagerBox};
+use queue::EventQueue;
+use throttle::Throttle;
+#[cfg(feature = "dns-update")]
+use options::parse_update_config;
+use options::{parse_advertise_options, parse_browse_options, parse_fallback_config, parse_find_options, parse_mock_script, parse_txt, string_array};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
 
-// Re-export ServiceInfo
-pub use native::ServiceInfo;
-
-// Global handle counter
-static NEXT_HANDLE: AtomicU32 = AtomicU32::new(1);
-
-fn next_handle() -> u32 {
-    NEXT_HANDLE.fetch_add(1, Ordering::SeqCst)
-}
+// Core API, shared with the CLI
+pub use alias::{open_alias, open_host_records};
+pub use capture::{start_capture, stop_capture};
+pub use filters::{NameList, NamePattern, ResultFilter};
+pub use handles::{AdvertisementHandle, BrowserHandle, Stoppable};
+pub use instances::{InstanceTable, InterfaceMode};
+pub use interfaces::{NetworkInterface, list as list_interfaces, parse_interface};
+pub use metrics::{MetricsSnapshot, snapshot as metrics_snapshot};
+pub use model::{AdvertiseData, HostnameConflict, ServiceInfo, TYPE_ENUMERATION};
+pub use options::{AdvertiseOptions, BrowseOptions, FindOptions};
+pub use recording::{load_recording, start_recording, stop_recording};
+pub use txt::TxtRecord;
 
 /// Backend type
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Backend {
     Native,
     Fallback,
+    /// Scripted events for tests (see [`mock`])
+    Mock,
 }
 
+/// Backend forced by `set_backend`, overriding detection
+static BACKEND_OVERRIDE: Mutex<Option<Backend>> = Mutex::new(None);
+
 /// Check which backend is available
-fn get_backend() -> Backend {
-    if native::is_available() {
-        Backend::Native
+///
+/// An override from `set_backend` or the `DNS_SD_BACKEND` environment
+/// variable takes precedence over detection.
+pub fn get_backend() -> Backend {
+    forced_backend().unwrap_or_else(|| if native::is_available() { Backend::Native } else { Backend::Fallback })
+}
+
+/// Backend chosen by `set_backend` or `DNS_SD_BACKEND`, if any
+fn forced_backend() -> Option<Backend> {
+    if let Some(backend) = *BACKEND_OVERRIDE.lock().unwrap() {
+        return Some(backend);
+    }
+    std::env::var("DNS_SD_BACKEND").ok().and_then(|name| Backend::from_name(&name))
+}
+
+/// Whether an operation that failed to start natively may be retried on
+/// the fallback: only for a detected (not forced) backend, and only if
+/// mdns-sd can serve it at all
+fn may_fall_back(backend: Backend, domain: Option<&str>, interface_index: u32) -> bool {
+    backend == Backend::Native
+        && forced_backend().is_none()
+        && domain.is_none_or(is_local_domain)
+        && interface_index != interfaces::LOCAL_ONLY
+        && interface_index != interfaces::P2P
+}
+
+/// Force a backend for handles started from now on (`None` = detect)
+pub fn set_backend(backend: Option<Backend>) -> Result<(), String> {
+    if backend == Some(Backend::Native) && !native::is_available() {
+        return Err("Native backend is not available".to_string());
+    }
+    *BACKEND_OVERRIDE.lock().unwrap() = backend;
+    Ok(())
+}
+
+/// Loopback-only mode set by `set_loopback_only`, overriding the environment
+static LOOPBACK_OVERRIDE: Mutex<Option<bool>> = Mutex::new(None);
+
+/// Whether handles are confined to this machine, for tests on networks
+/// where LAN multicast is blocked or unwanted
+///
+/// Set by `set_loopback_only` or the `DNS_SD_LOOPBACK_ONLY` environment variable.
+pub fn loopback_only() -> bool {
+    LOOPBACK_OVERRIDE
+        .lock()
+        .unwrap()
+        .unwrap_or_else(|| std::env::var("DNS_SD_LOOPBACK_ONLY").is_ok_and(|v| v == "1" || v == "true"))
+}
+
+/// Confine handles started from now on to this machine, or lift that
+///
+/// Native handles without an explicit interface use LocalOnly; the
+/// fallback daemon, including a running one, uses only loopback interfaces.
+pub fn set_loopback_only(enabled: bool) -> Result<(), String> {
+    *LOOPBACK_OVERRIDE.lock().unwrap() = Some(enabled);
+    fallback::configure(fallback::config())
+}
+
+/// Interface index for a native call, honoring loopback-only mode
+fn native_interface(index: u32) -> u32 {
+    if index == 0 && loopback_only() {
+        interfaces::LOCAL_ONLY
     } else {
-        Backend::Fallback
+        index
     }
 }
 
+impl Backend {
+    /// Name reported to JS
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Native => {
+                #[cfg(any(target_os = "macos", target_os = "windows"))]
+                { "bonjour" }
+                #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+                { "native" }
+            }
+            Backend::Fallback => "mdns-sd",
+            Backend::Mock => "mock",
+        }
+    }
+
+    /// Parse a backend name; `native` also accepts `bonjour`/`avahi` and `fallback` means `mdns-sd`
+    pub fn from_name(name: &str) -> Option<Backend> {
+        match name {
+            "native" | "bonjour" | "avahi" => Some(Backend::Native),
+            "mdns-sd" | "fallback" => Some(Backend::Fallback),
+            "mock" => Some(Backend::Mock),
+            _ => None,
+        }
+    }
+}
+
+/// Module entry point; registers the loading environment for teardown
+/// before exporting the `#[neon::export]` functions
+#[neon::main]
+fn main(mut cx: ModuleContext) -> NeonResult<()> {
+    handles::local(&mut cx);
+    neon::registered().export(&mut cx)
+}
+
 /// Get backend info as string
 #[neon::export]
 fn get_backend_info() -> String {
-    match get_backend() {
-        Backend::Native => {
-            #[cfg(target_os = "macos")]
-            { "bonjour".to_string() }
-            #[cfg(target_os = "windows")]
-            { "bonjour".to_string() }
-            #[cfg(not(any(target_os = "macos", target_os = "windows")))]
-            { "native".to_string() }
+    get_backend().name().to_string()
+}
+
+/// Retry native detection, returning the backend new handles will use
+///
+/// Fires the `backendChanged` callback if the result differs.
+#[neon::export]
+fn reprobe_backend() -> String {
+    probe::reprobe().name().to_string()
+}
+
+/// Retry native detection every `interval_ms` until it succeeds, or stop with null
+#[neon::export]
+fn set_backend_probe_interval(interval_ms: Option<f64>) {
+    probe::set_interval(interval_ms.map(|ms| Duration::from_millis(ms.max(1.0) as u64)));
+}
+
+/// Channel and callback for a process-wide notification, which must not
+/// keep the process alive
+///
+/// Each such sink holds one callback for the whole process, so the last
+/// environment (main thread or worker) to set it wins, and nothing is
+/// called once that environment exits.
+fn unref_sink<'cx>(cx: &mut FunctionContext<'cx>, callback: Option<Handle<'cx, JsFunction>>) -> Option<(Channel, Root<JsFunction>)> {
+    callback.map(|callback| {
+        let mut channel = cx.channel();
+        channel.unref(cx);
+        (channel, callback.root(cx))
+    })
+}
+
+/// Holder of a process-wide callback set through [`unref_sink`]
+struct Sink(Mutex<Option<(Channel, Arc<Root<JsFunction>>)>>);
+
+impl Sink {
+    const fn new() -> Self {
+        Sink(Mutex::new(None))
+    }
+
+    /// Replace the callback, or remove it with `None`
+    fn set(&self, sink: Option<(Channel, Root<JsFunction>)>) {
+        *self.0.lock().unwrap() = sink.map(|(channel, callback)| (channel, Arc::new(callback)));
+    }
+
+    fn is_set(&self) -> bool {
+        self.0.lock().unwrap().is_some()
+    }
+
+    /// Call the callback on its JS thread with the arguments `args` builds,
+    /// returning false if there was none to call
+    ///
+    /// The callback is dropped once the thread that set it has exited.
+    fn call<F>(&self, args: F) -> bool
+    where
+        F: for<'a> FnOnce(&mut TaskContext<'a>) -> NeonResult<Vec<Handle<'a, JsValue>>> + Send + 'static,
+    {
+        let mut slot = self.0.lock().unwrap();
+        let Some((channel, callback)) = slot.as_ref() else {
+            return false;
+        };
+        let callback = callback.clone();
+        let sent = channel.try_send(move |mut cx| {
+            let args = args(&mut cx)?;
+            let cb = callback.to_inner(&mut cx);
+            let this = cx.undefined();
+            let _ = cb.call(&mut cx, this, args);
+            Ok(())
+        });
+        if sent.is_err() {
+            *slot = None;
+        }
+        sent.is_ok()
+    }
+}
+
+/// Deliver `callback(backend)` when re-detection changes the backend
+///
+/// Passing null removes it.
+#[neon::export]
+fn set_backend_changed_callback<'cx>(cx: &mut FunctionContext<'cx>, callback: Option<Handle<'cx, JsFunction>>) -> NeonResult<()> {
+    probe::set_callback(unref_sink(cx, callback));
+    Ok(())
+}
+
+/// Watch for network changes, restarting browses and re-announcing
+/// advertisements when addresses change (on by default)
+#[neon::export]
+fn set_network_watch_enabled(enabled: bool) {
+    netwatch::set_enabled(enabled);
+}
+
+/// Deliver `callback(change)` when a network change is detected, before browses restart
+///
+/// Passing null removes it.
+#[neon::export]
+fn set_network_changed_callback<'cx>(cx: &mut FunctionContext<'cx>, callback: Option<Handle<'cx, JsFunction>>) -> NeonResult<()> {
+    netwatch::set_callback(unref_sink(cx, callback));
+    Ok(())
+}
+
+/// Deliver `callback(packet)` for each mDNS packet the fallback hears,
+/// incoming and outgoing
+///
+/// Passing null removes it.
+#[neon::export]
+fn set_packet_tap_callback<'cx>(cx: &mut FunctionContext<'cx>, callback: Option<Handle<'cx, JsFunction>>) -> NeonResult<()> {
+    tap::set_callback(unref_sink(cx, callback));
+    Ok(())
+}
+
+/// Refresh browses, advertisements and the cache when the system resumes
+/// from sleep (off by default)
+#[neon::export]
+fn set_wake_watch_enabled(enabled: bool) {
+    wake::set_enabled(enabled);
+}
+
+/// Deliver `callback(sleptMs)` when a resume from sleep is detected, before
+/// browses restart
+///
+/// Passing null removes it.
+#[neon::export]
+fn set_system_resumed_callback<'cx>(cx: &mut FunctionContext<'cx>, callback: Option<Handle<'cx, JsFunction>>) -> NeonResult<()> {
+    wake::set_callback(unref_sink(cx, callback));
+    Ok(())
+}
+
+/// Resolved services held by the discovery cache
+#[neon::export]
+fn get_cached_services<'cx>(cx: &mut FunctionContext<'cx>) -> JsResult<'cx, JsArray> {
+    let arr = cx.empty_array();
+    for (i, info) in cache::entries().iter().enumerate() {
+        let obj = service_info_to_js(cx, info)?;
+        arr.set(cx, i as u32, obj)?;
+    }
+    Ok(arr)
+}
+
+/// Drop every cached service
+#[neon::export]
+fn clear_cache() {
+    cache::clear();
+}
+
+/// Persist last-known services to `path` (null stops), returning how many it held
+#[neon::export]
+fn set_cache_file<'cx>(cx: &mut FunctionContext<'cx>, path: Option<String>) -> NeonResult<f64> {
+    match cache::set_path(path.map(PathBuf::from)) {
+        Ok(loaded) => Ok(loaded as f64),
+        Err(e) => cx.throw_error(e),
+    }
+}
+
+/// Turn the discovery cache on or off (it starts on)
+#[neon::export]
+fn set_cache_enabled(enabled: bool) {
+    cache::set_enabled(enabled);
+}
+
+/// Deliver `callback(service)` when a cached service expires
+///
+/// Passing null removes it.
+#[neon::export]
+fn set_cache_evicted_callback<'cx>(cx: &mut FunctionContext<'cx>, callback: Option<Handle<'cx, JsFunction>>) -> NeonResult<()> {
+    cache::set_callback(unref_sink(cx, callback));
+    Ok(())
+}
+
+/// Select the backend by name for handles started from now on
+///
+/// `auto` restores detection (native if available, else mdns-sd).
+#[neon::export(name = "setBackend")]
+fn select_backend<'cx>(cx: &mut FunctionContext<'cx>, name: String) -> NeonResult<()> {
+    let backend = match name.as_str() {
+        "auto" => None,
+        name => match Backend::from_name(name) {
+            Some(backend) => Some(backend),
+            None => return cx.throw_type_error(format!("Unknown backend: {}", name)),
+        },
+    };
+    set_backend(backend).or_else(|e| cx.throw_error(e))
+}
+
+/// Confine handles started from now on to this machine (see [`loopback_only`])
+#[neon::export(name = "setLoopbackOnly")]
+fn set_loopback_only_js<'cx>(cx: &mut FunctionContext<'cx>, enabled: bool) -> NeonResult<()> {
+    set_loopback_only(enabled).or_else(|e| cx.throw_error(e))
+}
+
+/// Replace the event script replayed by the mock backend
+#[neon::export]
+fn set_mock_script<'cx>(cx: &mut FunctionContext<'cx>, steps: Handle<'cx, JsArray>) -> NeonResult<()> {
+    mock::set_script(parse_mock_script(cx, steps)?);
+    Ok(())
+}
+
+/// Write every backend event to a JSONL file until `stop_recording`
+#[neon::export(name = "startRecording")]
+fn start_recording_js<'cx>(cx: &mut FunctionContext<'cx>, path: String) -> NeonResult<()> {
+    start_recording(&path).or_else(|e| cx.throw_error(e))
+}
+
+/// Stop the capture in progress
+#[neon::export(name = "stopRecording")]
+fn stop_recording_js() -> bool {
+    stop_recording()
+}
+
+/// Load a capture as the mock backend's script, returning its event count
+#[neon::export(name = "loadRecording")]
+fn load_recording_js<'cx>(cx: &mut FunctionContext<'cx>, path: String) -> NeonResult<f64> {
+    load_recording(&path).map(|count| count as f64).or_else(|e| cx.throw_error(e))
+}
+
+/// Write the fallback's mDNS traffic to a pcap file until `stop_capture`
+#[neon::export(name = "startCapture")]
+fn start_capture_js<'cx>(cx: &mut FunctionContext<'cx>, path: String) -> NeonResult<()> {
+    start_capture(&path).or_else(|e| cx.throw_error(e))
+}
+
+/// Stop the packet capture in progress
+#[neon::export(name = "stopCapture")]
+fn stop_capture_js() -> bool {
+    stop_capture()
+}
+
+/// DNS-SD flag bits by name, for decoding the `flags` field of events
+#[neon::export]
+fn get_service_flags<'cx>(cx: &mut FunctionContext<'cx>) -> JsResult<'cx, JsObject> {
+    let obj = cx.empty_object();
+    for (name, bit) in ffi::DNS_SERVICE_FLAG_NAMES {
+        let bit = cx.number(*bit as f64);
+        obj.set(cx, *name, bit)?;
+    }
+    Ok(obj)
+}
+
+/// Network interfaces with the indices the `interface` options take
+#[neon::export(name = "listInterfaces")]
+fn list_interfaces_js<'cx>(cx: &mut FunctionContext<'cx>) -> JsResult<'cx, JsArray> {
+    let list = list_interfaces().or_else(|e| cx.throw_error(e))?;
+    let arr = cx.empty_array();
+    for (i, iface) in list.iter().enumerate() {
+        let obj = cx.empty_object();
+        let index = cx.number(iface.index);
+        obj.set(cx, "index", index)?;
+        let name = cx.string(&iface.name);
+        obj.set(cx, "name", name)?;
+        if let Some(mac) = &iface.mac {
+            let mac = cx.string(mac);
+            obj.set(cx, "mac", mac)?;
+        }
+        let addresses = cx.empty_array();
+        for (j, addr) in iface.addresses.iter().enumerate() {
+            let addr = cx.string(addr.to_string());
+            addresses.set(cx, j as u32, addr)?;
+        }
+        obj.set(cx, "addresses", addresses)?;
+        for (key, value) in [("up", iface.up), ("multicast", iface.multicast), ("loopback", iface.loopback)] {
+            let value = cx.boolean(value);
+            obj.set(cx, key, value)?;
+        }
+        arr.set(cx, i as u32, obj)?;
+    }
+    Ok(arr)
+}
+
+/// Escape an instance name for use in a full DNS name (`My.Printer` -> `My\.Printer`)
+#[neon::export]
+fn escape_instance_name(name: String) -> String {
+    names::escape_instance_name(&name)
+}
+
+/// Decode `\.`, `\\` and `\DDD` escapes in an instance name
+#[neon::export]
+fn unescape_instance_name(escaped: String) -> String {
+    names::unescape_instance_name(&escaped)
+}
+
+/// Configure the mdns-sd daemon used by the fallback backend
+///
+/// Options are merged into the current configuration and take effect
+/// immediately if fallback handles are running.
+#[neon::export]
+fn configure_fallback<'cx>(cx: &mut FunctionContext<'cx>, options: Handle<'cx, JsObject>) -> NeonResult<()> {
+    let mut config = fallback::config();
+    parse_fallback_config(cx, options, &mut config)?;
+    match fallback::configure(config) {
+        Ok(()) => Ok(()),
+        Err(e) => cx.throw_error(e),
+    }
+}
+
+/// Publish advertisements in a unicast zone through DNS Update, or stop (`null`)
+///
+/// Advertisements started afterwards whose `domain` is the zone are
+/// registered with the zone's server, signed with the given TSIG key.
+#[cfg(feature = "dns-update")]
+#[neon::export]
+fn set_update_server<'cx>(cx: &mut FunctionContext<'cx>, options: Option<Handle<'cx, JsObject>>) -> NeonResult<()> {
+    let config = match options {
+        Some(options) => Some(parse_update_config(cx, options)?),
+        None => None,
+    };
+    update::configure(config);
+    Ok(())
+}
+
+/// Re-validate a cached fallback service instance by its full name
+///
+/// Returns true when a verification was started. The native backend has
+/// no equivalent here and always returns false.
+#[neon::export]
+fn verify_service<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    fullname: String,
+    timeout_ms: Option<f64>,
+) -> NeonResult<bool> {
+    if get_backend() != Backend::Fallback {
+        return Ok(false);
+    }
+    let timeout = timeout_ms
+        .map(|ms| Duration::from_millis(ms.max(0.0) as u64))
+        .unwrap_or(mdns_sd::VERIFY_TIMEOUT_DEFAULT);
+    match fallback::verify(&fullname, timeout) {
+        Ok(started) => Ok(started),
+        Err(e) => cx.throw_error(e),
+    }
+}
+
+/// Diagnose discovery on this machine: native library, daemon socket,
+/// LocalOnly round-trip and multicast per interface
+#[neon::export]
+fn check_health<'cx>(cx: &mut FunctionContext<'cx>, timeout_ms: Option<f64>) -> JsResult<'cx, JsPromise> {
+    let timeout = Duration::from_millis(timeout_ms.map(|ms| ms.max(0.0) as u64).unwrap_or(2000));
+    let channel = cx.channel();
+    let (deferred, promise) = cx.promise();
+
+    thread::spawn(move || {
+        let report = health::check(timeout);
+        let _ = deferred.try_settle_with(&channel, move |mut cx| health_report_to_js(&mut cx, &report));
+    });
+
+    Ok(promise)
+}
+
+fn health_check_to_js<'cx>(cx: &mut impl Context<'cx>, check: &health::Check) -> JsResult<'cx, JsObject> {
+    let obj = cx.empty_object();
+    let status = cx.string(check.status.name());
+    obj.set(cx, "status", status)?;
+    let detail = cx.string(&check.detail);
+    obj.set(cx, "detail", detail)?;
+    let elapsed = cx.number(check.elapsed.as_secs_f64() * 1000.0);
+    obj.set(cx, "elapsedMs", elapsed)?;
+    Ok(obj)
+}
+
+fn health_report_to_js<'cx>(cx: &mut impl Context<'cx>, report: &health::HealthReport) -> JsResult<'cx, JsObject> {
+    let obj = cx.empty_object();
+    let healthy = cx.boolean(report.healthy());
+    obj.set(cx, "healthy", healthy)?;
+    let backend = cx.string(report.backend.name());
+    obj.set(cx, "backend", backend)?;
+    let library = health_check_to_js(cx, &report.native_library)?;
+    obj.set(cx, "nativeLibrary", library)?;
+    let daemon = health_check_to_js(cx, &report.daemon)?;
+    obj.set(cx, "daemon", daemon)?;
+    let round_trip = health_check_to_js(cx, &report.round_trip)?;
+    obj.set(cx, "roundTrip", round_trip)?;
+
+    let interfaces = cx.empty_array();
+    for (i, iface) in report.interfaces.iter().enumerate() {
+        let item = cx.empty_object();
+        let name = cx.string(&iface.name);
+        item.set(cx, "name", name)?;
+        let index = cx.number(iface.index);
+        item.set(cx, "index", index)?;
+        let address = cx.string(iface.address.to_string());
+        item.set(cx, "address", address)?;
+        let sent = cx.boolean(iface.sent);
+        item.set(cx, "sent", sent)?;
+        let looped = cx.boolean(iface.looped);
+        item.set(cx, "looped", looped)?;
+        let peers = cx.number(iface.peers as f64);
+        item.set(cx, "peers", peers)?;
+        if let Some(error) = &iface.error {
+            let error = cx.string(error);
+            item.set(cx, "error", error)?;
         }
-        Backend::Fallback => "mdns-sd".to_string(),
+        interfaces.set(cx, i as u32, item)?;
     }
+    obj.set(cx, "interfaces", interfaces)?;
+    Ok(obj)
 }
 
-// Browser handles storage
-enum BrowserHandle {
-    Native(native::NativeBrowser),
-    Fallback(fallback::FallbackBrowser),
+/// Register, browse and resolve a temporary LocalOnly service through the
+/// backend in use, reporting each stage
+#[neon::export]
+fn run_selftest<'cx>(cx: &mut FunctionContext<'cx>, timeout_ms: Option<f64>) -> JsResult<'cx, JsPromise> {
+    let timeout = Duration::from_millis(timeout_ms.map(|ms| ms.max(0.0) as u64).unwrap_or(3000));
+    let channel = cx.channel();
+    let (deferred, promise) = cx.promise();
+
+    thread::spawn(move || {
+        let report = selftest::run(timeout);
+        let _ = deferred.try_settle_with(&channel, move |mut cx| selftest_report_to_js(&mut cx, &report));
+    });
+
+    Ok(promise)
 }
 
-static BROWSERS: Lazy<Mutex<HashMap<u32, BrowserHandle>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+fn selftest_report_to_js<'cx>(cx: &mut impl Context<'cx>, report: &selftest::SelftestReport) -> JsResult<'cx, JsObject> {
+    let obj = cx.empty_object();
+    let passed = cx.boolean(report.passed());
+    obj.set(cx, "passed", passed)?;
+    let backend = cx.string(report.backend.name());
+    obj.set(cx, "backend", backend)?;
+    let stages = cx.empty_array();
+    for (i, stage) in report.stages.iter().enumerate() {
+        let item = health_check_to_js(cx, &stage.check)?;
+        let name = cx.string(stage.name);
+        item.set(cx, "name", name)?;
+        if let Some(hint) = &stage.hint {
+            let hint = cx.string(hint);
+            item.set(cx, "hint", hint)?;
+        }
+        stages.set(cx, i as u32, item)?;
+    }
+    obj.set(cx, "stages", stages)?;
+    Ok(obj)
+}
 
-// Advertisement handles storage
-enum AdvertisementHandle {
-    Native(native::NativeAdvertisement),
-    Fallback(fallback::FallbackAdvertisement),
+/// Set the minimum level of diagnostic logs (`off` through `trace`)
+#[neon::export]
+fn set_log_level<'cx>(cx: &mut FunctionContext<'cx>, level: String) -> NeonResult<()> {
+    match logging::parse_level(&level) {
+        Some(level) => {
+            logging::set_level(level);
+            Ok(())
+        }
+        None => cx.throw_type_error(format!("Unknown log level: {}", level)),
+    }
 }
 
-static ADVERTISEMENTS: Lazy<Mutex<HashMap<u32, AdvertisementHandle>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+/// Deliver diagnostic logs to `callback(level, target, message)`
+///
+/// Passing null restores the default of writing them to stderr.
+#[neon::export]
+fn set_log_callback<'cx>(cx: &mut FunctionContext<'cx>, callback: Option<Handle<'cx, JsFunction>>) -> NeonResult<()> {
+    logging::set_callback(unref_sink(cx, callback));
+    Ok(())
+}
 
 /// Convert ServiceInfo to JS object
-fn service_info_to_js<'cx>(
+pub(crate) fn service_info_to_js<'cx>(
     cx: &mut impl Context<'cx>,
     info: &ServiceInfo,
 ) -> JsResult<'cx, JsObject> {
@@ -85,12 +665,21 @@ fn service_info_to_js<'cx>(
     
     let domain = cx.string(&info.domain);
     obj.set(cx, "domain", domain)?;
+
+    let full_name = cx.string(&info.full_name);
+    obj.set(cx, "fullName", full_name)?;
     
     let hostname = cx.string(&info.host_name);
     obj.set(cx, "hostName", hostname)?;
     
     let port = cx.number(info.port as f64);
     obj.set(cx, "port", port)?;
+
+    let priority = cx.number(info.priority as f64);
+    obj.set(cx, "priority", priority)?;
+
+    let weight = cx.number(info.weight as f64);
+    obj.set(cx, "weight", weight)?;
     
     let addrs = cx.empty_array();
     for (i, addr) in info.addresses.iter().enumerate() {
@@ -112,85 +701,710 @@ fn service_info_to_js<'cx>(
     if info.ttl > 0 {
         obj.set(cx, "ttl", ttl)?;
     }
+
+    if !info.txt_errors.is_empty() {
+        let errors = cx.empty_array();
+        for (i, error) in info.txt_errors.iter().enumerate() {
+            let error = cx.string(error);
+            errors.set(cx, i as u32, error)?;
+        }
+        obj.set(cx, "txtErrors", errors)?;
+    }
+
+    if let Some(flags) = info.flags {
+        let flags = cx.number(flags as f64);
+        obj.set(cx, "flags", flags)?;
+    }
+
+    if let Some(dnssec) = info.dnssec {
+        let dnssec = cx.string(dnssec.as_str());
+        obj.set(cx, "dnssec", dnssec)?;
+    }
+
+    if let Some(reason) = &info.reason {
+        let reason = cx.string(reason);
+        obj.set(cx, "reason", reason)?;
+    }
+    if let Some(address) = &info.address {
+        let address = cx.string(address);
+        obj.set(cx, "address", address)?;
+    }
+
+    if let Some(timings) = info.timings.filter(|t| t.emitted_at.is_some()) {
+        let timings_obj = cx.empty_object();
+        for (key, phase) in [
+            ("queueMs", timings.queued),
+            ("resolveMs", timings.resolve),
+            ("addressMs", timings.address),
+            ("dispatchMs", timings.dispatch()),
+        ] {
+            if let Some(phase) = phase {
+                let ms = cx.number(phase.as_secs_f64() * 1000.0);
+                timings_obj.set(cx, key, ms)?;
+            }
+        }
+        obj.set(cx, "timings", timings_obj)?;
+    }
+
+    if info.stale {
+        let stale = cx.boolean(true);
+        obj.set(cx, "stale", stale)?;
+    }
+
+    if info.interface_index > 0 {
+        let index = cx.number(info.interface_index as f64);
+        obj.set(cx, "interfaceIndex", index)?;
+        if let Some(name) = interfaces::index_to_name(info.interface_index) {
+            let name = cx.string(name);
+            obj.set(cx, "interfaceName", name)?;
+        }
+    }
     
     Ok(obj)
 }
 
+/// Throw a TypeError for malformed service types before any backend sees them
+fn check_service_type<'cx>(cx: &mut impl Context<'cx>, service_type: &str) -> NeonResult<()> {
+    model::validate_service_type(service_type).or_else(|e| cx.throw_type_error(e))
+}
+
+/// Throw a TypeError for instance names that can't be registered
+fn check_instance_name<'cx>(cx: &mut impl Context<'cx>, name: &str) -> NeonResult<()> {
+    names::validate_instance_name(name).or_else(|e| cx.throw_type_error(e))
+}
+
+/// Whether a domain is the link-local mDNS domain
+fn is_local_domain(domain: &str) -> bool {
+    domain.trim_end_matches('.').eq_ignore_ascii_case("local")
+}
+
+/// Send a single browse event to JS
+///
+/// Sends use `try_send` throughout: once the context that created a handle
+/// is torn down (worker exit, Electron renderer reload) its channel is
+/// closed and late events are dropped instead of panicking.
+fn send_event(channel: &Channel, callback: &Arc<Root<JsFunction>>, event: String, info: ServiceInfo) {
+    let callback = callback.clone();
+    let _ = channel.try_send(move |mut cx| {
+        let cb = callback.to_inner(&mut cx);
+        let this = cx.undefined();
+        let event_val = cx.string(&event);
+        let info_obj = service_info_to_js(&mut cx, &info)?;
+        let _ = cb.call(&mut cx, this, vec![event_val.upcast(), info_obj.upcast()]);
+        Ok(())
+    });
+}
+
 /// Start browsing for services
 #[neon::export]
 fn browse_services<'cx>(
     cx: &mut FunctionContext<'cx>,
     service_type: String,
+    options: Option<Handle<'cx, JsObject>>,
     callback: Handle<'cx, JsFunction>,
-) -> NeonResult<Handle<'cx, JsNumber>> {
+) -> NeonResult<Handle<'cx, JsBox<BrowserBox>>> {
+    let mut opts = BrowseOptions::default();
+    if let Some(obj) = options {
+        parse_browse_options(cx, obj, &mut opts)?;
+    }
+    opts.service_type = service_type;
+    let handle = start_browse(cx, opts, callback)?;
+    Ok(cx.boxed(handle))
+}
+
+/// Start browsing for services, configured by a single options object
+#[neon::export]
+fn browse_services_v2<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    options: Handle<'cx, JsObject>,
+    callback: Handle<'cx, JsFunction>,
+) -> NeonResult<Handle<'cx, JsBox<BrowserBox>>> {
+    let mut opts = BrowseOptions::default();
+    parse_browse_options(cx, options, &mut opts)?;
+    if opts.service_type.is_empty() {
+        return cx.throw_type_error("options.type is required");
+    }
+    let handle = start_browse(cx, opts, callback)?;
+    Ok(cx.boxed(handle))
+}
+
+/// Start a browser for parsed options and register its handle
+fn start_browse<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    options: BrowseOptions,
+    callback: Handle<'cx, JsFunction>,
+) -> NeonResult<BrowserBox> {
+    check_service_type(cx, &options.service_type)?;
+    let service_type = options.service_type.clone();
     let channel = cx.channel();
-    let callback = std::sync::Arc::new(callback.root(cx));
-    
-    let handle_id = next_handle();
+    let callback = Arc::new(callback.root(cx));
     
+    // Shared with the handle for `get_services` and network refreshes
+    let services = Arc::new(Mutex::new(InstanceTable::new(options.interface_mode)));
+
     // Create callback wrapper
-    let make_callback = |channel: neon::event::Channel, callback: std::sync::Arc<neon::handle::Root<JsFunction>>| {
-        move |event: &str, info: ServiceInfo| {
-            let event = event.to_string();
-            let callback = callback.clone();
-            
-            channel.send(move |mut cx| {
-                let cb = callback.to_inner(&mut cx);
-                let this = cx.undefined();
-                let event_val = cx.string(&event);
-                let info_obj = service_info_to_js(&mut cx, &info)?;
-                let _ = cb.call(&mut cx, this, vec![event_val.upcast(), info_obj.upcast()]);
-                Ok(())
+    let make_callback = |channel: Channel, callback: Arc<Root<JsFunction>>| {
+        let table = services.clone();
+        let batch = options.batch.then_some(options.batch_interval);
+        let queue = EventQueue::new(channel, callback, options.queue_size, options.overflow, batch);
+        let pending = Arc::downgrade(&queue);
+        let deliver: BrowseCallback = if options.debounce.is_zero() && options.settle.is_zero() && options.max_events_per_second.is_none() {
+            Arc::new(move |event: &str, info: ServiceInfo| queue.push(event.to_string(), info))
+        } else {
+            let throttle = Throttle::new(options.debounce, options.settle, options.max_events_per_second, move |event: &str, info| {
+                queue.push(event.to_string(), info)
             });
+            Arc::new(move |event: &str, info: ServiceInfo| throttle.push(event, info))
+        };
+
+        let address_filter = options.address_filter;
+        let result_filter = options.result_filter.clone();
+        let strict_txt = options.strict_txt;
+        let profile = options.resolve.profile;
+        let watch_addresses = options.resolve.watch_addresses;
+
+        (Arc::downgrade(&deliver), pending, move |event: &str, mut info: ServiceInfo| {
+            // Strict browsers report malformed TXT records instead of the service
+            let event = if strict_txt && event == "serviceFound" && !info.txt_errors.is_empty() {
+                "txtError"
+            } else {
+                event
+            };
+            address_filter.apply(&mut info.addresses);
+            let mut table = table.lock().unwrap();
+            let Some(event) = result_filter.screen(event, &info, table.contains(&info)) else {
+                return;
+            };
+            let before = if watch_addresses { table.reported(&info) } else { None };
+            let Some((event, mut info)) = table.apply(event, info) else {
+                return;
+            };
+            drop(table);
+            // Marks the timings for JS and starts the dispatch phase
+            if let Some(timings) = info.timings.as_mut().filter(|_| profile) {
+                timings.emitted_at = Some(Instant::now());
+            }
+
+            let changes = match before {
+                Some(before) if event == "serviceUpdated" => instances::address_changes(&before, &info),
+                _ => Vec::new(),
+            };
+            deliver(&event, info);
+            for (event, info) in changes {
+                deliver(event, info);
+            }
+        })
+    };
+
+    let backend = get_backend();
+    let (deliver, queue, on_event) = make_callback(channel, callback);
+    let on_event: BrowseCallback = Arc::new(on_event);
+    let browse = BrowseState { table: services, deliver, queue, options: options.clone() };
+
+    // Kept by the handle so a paused browser can be reopened with the same callback
+    let open = move || open_browser(backend, &options, on_event.clone());
+
+    match open() {
+        Ok(browser) => {
+            let backend = browser.backend().unwrap_or(backend);
+            Ok(handles::local(cx).browsers.insert(&service_type, backend, browser, Some(Box::new(open)), Some(browse), None))
+        }
+        Err(e) => cx.throw_error(e),
+    }
+}
+
+/// Callback shared by every backend browser opened for one handle
+pub type BrowseCallback = Arc<dyn Fn(&str, ServiceInfo) + Send + Sync>;
+
+/// Subscribe `on_event` to a backend browser for `options`
+///
+/// Identical browses share one backend browser (see [`shared`]). If a
+/// detected native backend fails to start the browse, it is retried on
+/// mdns-sd, with `backendFallback` delivered first.
+pub fn open_browser(backend: Backend, options: &BrowseOptions, on_event: BrowseCallback) -> Result<BrowserHandle, String> {
+    model::validate_service_type(&options.service_type)?;
+    if options.all_domains {
+        let domain = options.domain.clone();
+        let options = BrowseOptions { all_domains: false, ..options.clone() };
+        let open = move |domain: Option<&str>, on_event: BrowseCallback| {
+            let options = BrowseOptions { domain: domain.map(str::to_string).or(options.domain.clone()), ..options.clone() };
+            open_browser(backend, &options, on_event)
+        };
+        return domains::browse_all(backend, domain.as_deref(), on_event, Box::new(open)).map(BrowserHandle::Domains);
+    }
+    let on_event = match options.resolve_services {
+        true => match options.completion {
+            Some(completion) => completion::complete_only(on_event, completion, options.resolve.protocol),
+            None => on_event,
+        },
+        false => browse_answers_only(on_event),
+    };
+    let narrowed;
+    let (options, on_event) = match options.interfaces.as_slice() {
+        [] => (options, on_event),
+        _ if options.interface_index != 0 => return Err("interface and interfaces can't be combined".to_string()),
+        names => {
+            let selection = interfaces::select(names)?;
+            // A single interface is browsed on directly, sparing the daemon the others
+            narrowed = BrowseOptions {
+                interface_index: if let [index] = selection.indices[..] { index } else { 0 },
+                ..options.clone()
+            };
+            (&narrowed, on_interfaces(on_event, selection))
+        }
+    };
+    // Last-known services from the cache file, until the network confirms them
+    if !options.service_type.contains("._sub.") {
+        let browse = ServiceInfo {
+            service_type: options.service_type.clone(),
+            domain: options.domain.clone().unwrap_or_else(|| "local".to_string()),
+            ..Default::default()
+        }
+        .normalize();
+        for info in cache::stale(&browse.service_type, &browse.domain, options.interface_index) {
+            on_event("serviceFound", info);
         }
+    }
+    let subscribe = |backend: Backend, on_event: BrowseCallback| {
+        let opened = options.clone();
+        shared::subscribe(backend, options, on_event, move |on_event| open_backend_browser(backend, &opened, on_event))
+            .map(BrowserHandle::Shared)
     };
+    match subscribe(backend, on_event.clone()) {
+        Err(e) if may_fall_back(backend, options.domain.as_deref(), options.interface_index) => {
+            tracing::warn!("Native browse for {} failed to start ({}); retrying on mdns-sd", options.service_type, e);
+            on_event("backendFallback", ServiceInfo {
+                service_type: options.service_type.clone(),
+                domain: options.domain.clone().unwrap_or_else(|| "local".to_string()),
+                ..Default::default()
+            });
+            subscribe(Backend::Fallback, on_event)
+        }
+        result => result,
+    }
+}
+
+/// Reduce events to browse answers for a browser with `resolve: false`
+///
+/// The native browse skips resolving, but cached results and mdns-sd (which
+/// resolves every instance itself) still carry resolved fields. Each
+/// instance is reported found once per interface, with its identity only.
+fn browse_answers_only(on_event: BrowseCallback) -> BrowseCallback {
+    let reported = Mutex::new(HashSet::new());
+    Arc::new(move |event: &str, info: ServiceInfo| {
+        let key = (instance_key(&info), info.interface_index);
+        let identity = ServiceInfo {
+            name: info.name,
+            service_type: info.service_type,
+            domain: info.domain,
+            full_name: info.full_name,
+            interface_index: info.interface_index,
+            flags: info.flags,
+            stale: info.stale,
+            ..Default::default()
+        };
+        match event {
+            // Stale cached sightings don't count, so the network's answer still confirms them
+            "serviceFound" if identity.stale || reported.lock().unwrap().insert(key.clone()) => on_event(event, identity),
+            "serviceFound" => {}
+            "serviceLost" => {
+                reported.lock().unwrap().remove(&key);
+                on_event(event, identity);
+            }
+            _ => on_event(event, identity),
+        }
+    })
+}
 
-    let result = match get_backend() {
+/// Confine a browse to the interfaces of `selection`
+///
+/// Native results carry the interface they were seen on. mdns-sd's don't,
+/// so there only addresses on a selected interface's link are kept, and a
+/// service left without any is not reported (or is reported lost).
+fn on_interfaces(on_event: BrowseCallback, selection: interfaces::InterfaceSet) -> BrowseCallback {
+    let reported = Mutex::new(HashSet::new());
+    Arc::new(move |event: &str, mut info: ServiceInfo| {
+        if info.interface_index != 0 {
+            if selection.contains_index(info.interface_index) {
+                on_event(event, info);
+            }
+            return;
+        }
+        let key = instance_key(&info);
+        match event {
+            "serviceFound" => {
+                let resolved = !info.addresses.is_empty();
+                info.addresses.retain(|addr| selection.covers_address(addr));
+                if resolved && info.addresses.is_empty() {
+                    if reported.lock().unwrap().remove(&key) {
+                        on_event("serviceLost", info);
+                    }
+                    return;
+                }
+                reported.lock().unwrap().insert(key);
+                on_event(event, info);
+            }
+            "serviceLost" if !info.name.is_empty() => {
+                if reported.lock().unwrap().remove(&key) {
+                    on_event(event, info);
+                }
+            }
+            _ => on_event(event, info),
+        }
+    })
+}
+
+/// Open a dedicated backend browser delivering events to `on_event`
+fn open_backend_browser(backend: Backend, options: &BrowseOptions, on_event: BrowseCallback) -> Result<BrowserHandle, String> {
+    let service_type = options.service_type.clone();
+    let on_event = move |event: &str, info: ServiceInfo| {
+        metrics::record_event(event);
+        recording::record_browse(backend, &service_type, event, &info);
+        cache::record(event, &info);
+        on_event(event, info);
+    };
+    let result = match backend {
         Backend::Native => {
-            native::NativeBrowser::new(&service_type, make_callback(channel, callback))
-                .map(BrowserHandle::Native)
+            native::NativeBrowser::new(
+                &options.service_type,
+                options.domain.as_deref(),
+                native_interface(options.interface_index),
+                options.flags,
+                options.resolve,
+                options.resolve_services,
+                options.result_filter.names.clone(),
+                move |event, info| on_event(event, info.normalize()),
+            ).map(BrowserHandle::Native)
+        }
+        Backend::Fallback if options.domain.as_deref().is_some_and(|d| !is_local_domain(d)) => {
+            Err("Wide-area domains require the native backend".to_string())
+        }
+        // The shared mdns-sd daemon browses every interface at once
+        Backend::Fallback if options.interface_index == interfaces::LOCAL_ONLY => {
+            Err("Local-only browsing requires the native backend".to_string())
         }
         Backend::Fallback => {
-            // Convert fallback::ServiceInfo to our ServiceInfo
-            let cb = make_callback(channel, callback);
-            fallback::FallbackBrowser::new(&service_type, move |event, info| {
-                let converted = ServiceInfo {
-                    name: info.name,
-                    service_type: info.service_type,
-                    domain: info.domain,
-                    host_name: info.host_name,
-                    addresses: info.addresses,
-                    port: info.port,
-                    txt: info.txt,
-                    ttl: info.ttl,
-                };
-                cb(event, converted);
+            fallback::FallbackBrowser::new(&options.service_type, move |event, info| {
+                on_event(event, info.normalize());
             }).map(BrowserHandle::Fallback)
         }
+        Backend::Mock => {
+            mock::MockBrowser::new(&options.service_type, move |event, info| {
+                on_event(event, info.normalize());
+            }).map(BrowserHandle::Mock)
+        }
     };
-    
-    match result {
-        Ok(browser) => {
-            BROWSERS.lock().unwrap().insert(handle_id, browser);
-            Ok(cx.number(handle_id as f64))
+    if let Err(e) = &result {
+        metrics::backend_error(&format!("Browse of {} on {}: {}", options.service_type, backend.name(), e));
+    }
+    result
+}
+
+/// Stop browsing without waiting for the browse thread to exit
+#[neon::export]
+fn stop_browse(handle: Handle<JsBox<BrowserBox>>) -> bool {
+    handle.0.stop_in_background()
+}
+
+/// Pause a browser: tear down the underlying browse but keep the handle and callback
+#[neon::export]
+fn pause_browse(handle: Handle<JsBox<BrowserBox>>) -> bool {
+    handle.0.pause()
+}
+
+/// Services the browser currently reports
+///
+/// Reflects events as the backend reports them, possibly ahead of
+/// those still queued for the callback. Empty once the browser is stopped.
+#[neon::export]
+fn get_services<'cx>(cx: &mut FunctionContext<'cx>, handle: Handle<'cx, JsBox<BrowserBox>>) -> JsResult<'cx, JsArray> {
+    let services = handle.0.services();
+    let arr = cx.empty_array();
+    for (i, info) in services.iter().enumerate() {
+        let obj = service_info_to_js(cx, info)?;
+        arr.set(cx, i as u32, obj)?;
+    }
+    Ok(arr)
+}
+
+/// Resolve an instance the browser reported, typically one from a
+/// `resolve: false` browse, on the interface it was seen on
+///
+/// Rejects if the browser doesn't currently report `name` or the resolve
+/// gets no answer.
+#[neon::export]
+fn resolve_instance<'cx>(cx: &mut FunctionContext<'cx>, handle: Handle<'cx, JsBox<BrowserBox>>, name: String) -> JsResult<'cx, JsPromise> {
+    let Some((sighting, options)) = handle.0.sighting(&name) else {
+        return cx.throw_error(format!("{} has not reported {:?}", handle.0.service_type, name));
+    };
+    let backend = handle.0.backend;
+    let channel = cx.channel();
+    let (deferred, promise) = cx.promise();
+
+    thread::spawn(move || {
+        let resolved = resolve_sighting(backend, &options, &sighting);
+        let _ = deferred.try_settle_with(&channel, move |mut cx| match resolved {
+            Ok(info) => service_info_to_js(&mut cx, &info),
+            Err(e) => cx.throw_error(e),
+        });
+    });
+
+    Ok(promise)
+}
+
+/// Resolve `sighting`, an instance reported by a browse with `options`
+///
+/// The native backend resolves it on the sighting's interface. mdns-sd
+/// resolves every instance it browses, so there the browse's own result is
+/// looked up.
+pub fn resolve_sighting(backend: Backend, options: &BrowseOptions, sighting: &ServiceInfo) -> Result<ServiceInfo, String> {
+    let mut info = match backend {
+        Backend::Native => {
+            let mut resolver = native::Resolver::new(
+                sighting.interface_index,
+                &sighting.name,
+                &sighting.service_type,
+                &sighting.domain,
+                options.resolve,
+                Arc::new(AtomicBool::new(false)),
+                Arc::new(|_: &str, _: ServiceInfo| {}),
+            )?;
+            resolver
+                .run()
+                .filter(|info| !info.host_name.is_empty())
+                .map(ServiceInfo::normalize)
+                .ok_or_else(|| format!("No answer resolving {}", sighting.full_name))?
+        }
+        Backend::Fallback | Backend::Mock => {
+            let find = FindOptions {
+                browse: BrowseOptions {
+                    interface_index: sighting.interface_index,
+                    resolve_services: true,
+                    result_filter: Default::default(),
+                    ..options.clone()
+                },
+                name: Some(sighting.name.clone()),
+                timeout: options.resolve.resolve_timeout + options.resolve.address_timeout,
+            };
+            find_blocking(backend, &find)?
         }
+    };
+    options.address_filter.apply(&mut info.addresses);
+    Ok(info)
+}
+
+/// Resume a paused browser by reopening the underlying browse
+#[neon::export]
+fn resume_browse<'cx>(cx: &mut FunctionContext<'cx>, handle: Handle<'cx, JsBox<BrowserBox>>) -> NeonResult<bool> {
+    match handle.0.resume() {
+        Ok(resumed) => Ok(resumed),
         Err(e) => cx.throw_error(e),
     }
 }
 
-/// Stop browsing
+/// Start browsing on behalf of a `Browser` object, emitting named events on it
+#[neon::export]
+fn create_browser<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    target: Handle<'cx, JsObject>,
+    options: Handle<'cx, JsObject>,
+) -> NeonResult<Handle<'cx, JsBox<EmitterBox<BrowserHandle>>>> {
+    let mut opts = BrowseOptions::default();
+    parse_browse_options(cx, options, &mut opts)?;
+    if opts.service_type.is_empty() {
+        return cx.throw_type_error("options.type is required");
+    }
+    let emitter = Emitter::new(cx, target);
+    let callback = emitter.browse_callback(cx)?;
+    let handle = start_browse(cx, opts, callback)?;
+    Ok(cx.boxed(EmitterBox { handle, emitter }))
+}
+
+/// Stop a `Browser` and emit `end`; false if it was already closed
+#[neon::export]
+fn close_browser<'cx>(cx: &mut FunctionContext<'cx>, handle: Handle<'cx, JsBox<EmitterBox<BrowserHandle>>>) -> NeonResult<bool> {
+    handle.close(cx)
+}
+
+/// Find the first resolved service matching `options`
+///
+/// Resolves with the service, or rejects if nothing matches within `timeoutMs`
+/// or `token` is aborted. The underlying browse is stopped either way.
+#[neon::export]
+fn find_service<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    options: Handle<'cx, JsObject>,
+    token: Option<Handle<'cx, JsBox<AbortToken>>>,
+) -> JsResult<'cx, JsPromise> {
+    let find = parse_find_options(cx, options)?;
+    check_service_type(cx, &find.browse.service_type)?;
+    let abort = token.map(|token| token.0.clone()).unwrap_or_default();
+    let channel = cx.channel();
+    let (deferred, promise) = cx.promise();
+
+    thread::spawn(move || {
+        let found = find_abortable(get_backend(), &find, &abort);
+        let _ = deferred.try_settle_with(&channel, move |mut cx| match found {
+            Ok(info) => service_info_to_js(&mut cx, &info),
+            Err(e) => cx.throw_error(e),
+        });
+    });
+
+    Ok(promise)
+}
+
+/// Cancellation flag shared between JS and an operation in flight
+pub struct AbortToken(Arc<AtomicBool>);
+
+impl Finalize for AbortToken {}
+
+/// Create a token to pass to cancellable calls such as `findService`
+#[neon::export]
+fn create_abort_token<'cx>(cx: &mut FunctionContext<'cx>) -> JsResult<'cx, JsBox<AbortToken>> {
+    Ok(cx.boxed(AbortToken(Arc::default())))
+}
+
+/// Abort every operation holding `token`
 #[neon::export]
-fn stop_browse(handle_id: f64) -> bool {
-    let handle_id = handle_id as u32;
-    if let Some(mut browser) = BROWSERS.lock().unwrap().remove(&handle_id) {
-        match &mut browser {
-            BrowserHandle::Native(b) => b.stop(),
-            BrowserHandle::Fallback(b) => b.stop(),
+fn abort(token: Handle<JsBox<AbortToken>>) {
+    token.0.store(true, Ordering::SeqCst);
+}
+
+/// Browse until a service matching `find` resolves or its timeout passes
+pub fn find_blocking(backend: Backend, find: &FindOptions) -> Result<ServiceInfo, String> {
+    find_abortable(backend, find, &AtomicBool::new(false))
+}
+
+/// [`find_blocking`] that also gives up once `abort` is set
+pub fn find_abortable(backend: Backend, find: &FindOptions, abort: &AtomicBool) -> Result<ServiceInfo, String> {
+    let (tx, rx) = mpsc::sync_channel::<ServiceInfo>(1);
+    let matcher = find.clone();
+    let address_filter = find.browse.address_filter;
+    let on_event: BrowseCallback = Arc::new(move |event: &str, mut info: ServiceInfo| {
+        if event != "serviceFound" {
+            return;
         }
-        true
-    } else {
-        false
+        address_filter.apply(&mut info.addresses);
+        if matcher.matches(&info) {
+            let _ = tx.try_send(info);
+        }
+    });
+
+    // Waiting here rather than in the callback so the browser is never
+    // stopped from its own callback
+    let mut browser = open_browser(backend, &find.browse, on_event)?;
+    let deadline = Instant::now() + find.timeout;
+    let found = loop {
+        if abort.load(Ordering::SeqCst) {
+            browser.stop();
+            return Err("Aborted".to_string());
+        }
+        let left = deadline.saturating_duration_since(Instant::now());
+        match rx.recv_timeout(left.min(Duration::from_millis(100))) {
+            Err(RecvTimeoutError::Timeout) if !left.is_zero() && !handles::shutting_down() => continue,
+            result => break result,
+        }
+    };
+    browser.stop();
+    found.map_err(|_| {
+        format!(
+            "No matching {} service found within {}ms",
+            find.browse.service_type,
+            find.timeout.as_millis()
+        )
+    })
+}
+
+/// Create a discovery manager browsing the given service types
+///
+/// The callback receives `("added" | "updated" | "removed", service)` diffs.
+#[neon::export]
+fn create_discovery_manager<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    types: Handle<'cx, JsArray>,
+    options: Option<Handle<'cx, JsObject>>,
+    callback: Handle<'cx, JsFunction>,
+) -> NeonResult<Handle<'cx, JsBox<ManagerBox>>> {
+    let types = string_array(cx, types)?;
+    for service_type in &types {
+        check_service_type(cx, service_type)?;
     }
+    let mut opts = BrowseOptions::default();
+    if let Some(obj) = options {
+        parse_browse_options(cx, obj, &mut opts)?;
+    }
+    let channel = cx.channel();
+    let callback = Arc::new(callback.root(cx));
+    let on_diff = Arc::new(move |event: &str, info: ServiceInfo| {
+        send_event(&channel, &callback, event.to_string(), info);
+    });
+
+    let manager = DiscoveryManager::new(get_backend(), opts, on_diff);
+    for service_type in &types {
+        if let Err(e) = manager.add_type(service_type) {
+            manager.stop();
+            return cx.throw_error(e);
+        }
+    }
+    let manager = handles::local(cx).insert_manager(manager);
+    Ok(cx.boxed(manager))
+}
+
+/// Start browsing another service type in a discovery manager
+#[neon::export]
+fn manager_add_type<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    handle: Handle<'cx, JsBox<ManagerBox>>,
+    service_type: String,
+) -> NeonResult<bool> {
+    check_service_type(cx, &service_type)?;
+    match handle.add_type(&service_type) {
+        Ok(added) => Ok(added),
+        Err(e) => cx.throw_error(e),
+    }
+}
+
+/// Stop browsing a service type in a discovery manager
+#[neon::export]
+fn manager_remove_type(handle: Handle<JsBox<ManagerBox>>, service_type: String) -> bool {
+    handle.remove_type(&service_type)
+}
+
+/// Service types browsed by a discovery manager
+#[neon::export]
+fn manager_get_types<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    handle: Handle<'cx, JsBox<ManagerBox>>,
+) -> JsResult<'cx, JsArray> {
+    let types = handle.types();
+    let arr = cx.empty_array();
+    for (i, service_type) in types.iter().enumerate() {
+        let value = cx.string(service_type);
+        arr.set(cx, i as u32, value)?;
+    }
+    Ok(arr)
+}
+
+/// Current service table of a discovery manager
+#[neon::export]
+fn manager_get_snapshot<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    handle: Handle<'cx, JsBox<ManagerBox>>,
+) -> JsResult<'cx, JsArray> {
+    let services = handle.snapshot();
+    let arr = cx.empty_array();
+    for (i, info) in services.iter().enumerate() {
+        let obj = service_info_to_js(cx, info)?;
+        arr.set(cx, i as u32, obj)?;
+    }
+    Ok(arr)
+}
+
+/// Stop a discovery manager and all of its browsers in the background
+#[neon::export]
+fn stop_discovery_manager(handle: Handle<JsBox<ManagerBox>>) {
+    let manager = handle.0.clone();
+    handles::stop_in_background(move || manager.stop());
 }
 
 /// Advertise a service
@@ -202,88 +1416,587 @@ fn advertise_service<'cx>(
     port: f64,
     txt: Option<Handle<'cx, JsObject>>,
     callback: Handle<'cx, JsFunction>,
-) -> NeonResult<Handle<'cx, JsNumber>> {
-    let port = port as u16;
-    let channel = cx.channel();
-    let callback = std::sync::Arc::new(callback.root(cx));
-    
-    // Extract TXT record
-    let txt_map: Option<HashMap<String, String>> = if let Some(txt_obj) = txt {
-        let keys = txt_obj.get_own_property_names(cx)?;
-        let len = keys.len(cx);
-        let mut map = HashMap::new();
-        for i in 0..len {
-            let key: Handle<JsString> = keys.get(cx, i)?;
-            let key_str = key.value(cx);
-            let val: Handle<JsString> = txt_obj.get(cx, key_str.as_str())?;
-            map.insert(key_str, val.value(cx));
-        }
-        Some(map)
-    } else {
-        None
+) -> NeonResult<Handle<'cx, JsBox<AdvertisementBox>>> {
+    let txt = match txt {
+        Some(txt_obj) => Some(parse_txt(cx, txt_obj, false)?),
+        None => None,
     };
-    
-    let handle_id = next_handle();
+    let opts = AdvertiseOptions {
+        name,
+        service_type,
+        port: port as u16,
+        txt,
+        ..Default::default()
+    };
+    let handle = start_advertise(cx, opts, callback)?;
+    Ok(cx.boxed(handle))
+}
+
+/// Advertise a service, configured by a single options object
+#[neon::export]
+fn advertise_service_v2<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    options: Handle<'cx, JsObject>,
+    callback: Handle<'cx, JsFunction>,
+) -> NeonResult<Handle<'cx, JsBox<AdvertisementBox>>> {
+    let opts = parse_advertise_options(cx, options)?;
+    let handle = start_advertise(cx, opts, callback)?;
+    Ok(cx.boxed(handle))
+}
+
+/// Start an advertisement for parsed options and register its handle
+fn start_advertise<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    options: AdvertiseOptions,
+    callback: Handle<'cx, JsFunction>,
+) -> NeonResult<AdvertisementBox> {
+    check_service_type(cx, &options.service_type)?;
+    check_instance_name(cx, &options.name)?;
+    let channel = cx.channel();
+    let callback = Arc::new(callback.root(cx));
     
     // Create callback wrapper
-    let make_callback = |channel: neon::event::Channel, callback: std::sync::Arc<neon::handle::Root<JsFunction>>| {
-        move |event: &str, data: &str| {
+    let make_callback = |channel: Channel, callback: Arc<Root<JsFunction>>| {
+        move |event: &str, data: &AdvertiseData| {
             let event = event.to_string();
-            let data = data.to_string();
+            let data = data.clone();
             let callback = callback.clone();
             
-            channel.send(move |mut cx| {
+            let _ = channel.try_send(move |mut cx| {
                 let cb = callback.to_inner(&mut cx);
                 let this = cx.undefined();
                 let event_val = cx.string(&event);
-                let data_val = cx.string(&data);
-                let _ = cb.call(&mut cx, this, vec![event_val.upcast(), data_val.upcast()]);
+                let data_val = advertise_event_data(&mut cx, &data)?;
+                let _ = cb.call(&mut cx, this, vec![event_val.upcast(), data_val]);
                 Ok(())
             });
         }
     };
 
-    let result = match get_backend() {
-        Backend::Native => {
-            native::NativeAdvertisement::new(
-                &name,
-                &service_type,
-                port,
-                txt_map.as_ref(),
-                make_callback(channel, callback),
-            ).map(AdvertisementHandle::Native)
+    let backend = get_backend();
+    let result = open_advertisement(backend, &options, make_callback(channel.clone(), callback.clone()));
+    
+    match result {
+        Ok(ad) => {
+            let backend = ad.backend().unwrap_or(backend);
+            // Renames register again on the backend that took the service
+            let service_type = options.service_type.clone();
+            let options = Arc::new(Mutex::new(options));
+            let current = options.clone();
+            let reopen = move || {
+                let options = current.lock().unwrap().clone();
+                open_advertisement(backend, &options, make_callback(channel.clone(), callback.clone()))
+            };
+            let advertisements = &handles::local(cx).advertisements;
+            Ok(advertisements.insert(&service_type, backend, ad, Some(Box::new(reopen)), None, Some(options)))
         }
-        Backend::Fallback => {
-            fallback::FallbackAdvertisement::new(
-                &name,
-                &service_type,
-                port,
-                txt_map.as_ref(),
-                make_callback(channel, callback),
-            ).map(AdvertisementHandle::Fallback)
+        Err(e) => cx.throw_error(e),
+    }
+}
+
+/// Register a service with a backend, reporting `(event, data)` to `callback`
+///
+/// If a detected native backend fails to start the registration, it is
+/// retried on mdns-sd after reporting `backendFallback` with the reason.
+pub fn open_advertisement<F>(backend: Backend, options: &AdvertiseOptions, callback: F) -> Result<AdvertisementHandle, String>
+where
+    F: Fn(&str, &AdvertiseData) + Send + Sync + 'static,
+{
+    model::validate_service_type(&options.service_type)?;
+    names::validate_instance_name(&options.name)?;
+    if options.interface_index != 0 && !options.interfaces.is_empty() {
+        return Err("interface and interfaces can't be combined".to_string());
+    }
+    let callback = Arc::new(callback);
+    let attempt = |backend: Backend| {
+        let name = options.name.clone();
+        let callback = callback.clone();
+        let callback = move |event: &str, data: &AdvertiseData| {
+            recording::record_advertisement(backend, &name, event, data);
+            callback(event, data);
+        };
+        #[cfg(feature = "dns-update")]
+        if backend != Backend::Mock
+            && let Some(config) = options.domain.as_deref().and_then(update::config_for)
+        {
+            return update::WideAreaAdvertisement::new(config, options, text_events(callback)).map(AdvertisementHandle::WideArea);
         }
+        let result = match backend {
+            // One registration per named interface, withdrawn together
+            Backend::Native if !options.interfaces.is_empty() => interfaces::select(&options.interfaces).and_then(|selection| {
+                selection
+                    .indices
+                    .iter()
+                    .map(|&index| {
+                        let options = AdvertiseOptions { interface_index: index, interfaces: Vec::new(), ..options.clone() };
+                        native::NativeAdvertisement::new(&options, text_events(callback.clone())).map(AdvertisementHandle::Native)
+                    })
+                    .collect::<Result<Vec<_>, String>>()
+                    .map(AdvertisementHandle::Bundle)
+            }),
+            Backend::Native => {
+                let options = AdvertiseOptions { interface_index: native_interface(options.interface_index), ..options.clone() };
+                native::NativeAdvertisement::new(&options, text_events(callback)).map(AdvertisementHandle::Native)
+            }
+            Backend::Fallback if options.domain.as_deref().is_some_and(|d| !is_local_domain(d)) => {
+                Err("Wide-area domains require the native backend".to_string())
+            }
+            Backend::Fallback => {
+                fallback::FallbackAdvertisement::new(options, callback).map(AdvertisementHandle::Fallback)
+            }
+            Backend::Mock => {
+                mock::MockAdvertisement::new(options, text_events(callback)).map(AdvertisementHandle::Mock)
+            }
+        };
+        if let Err(e) = &result {
+            metrics::backend_error(&format!("Registration of {} on {}: {}", options.name, backend.name(), e));
+        }
+        result
     };
-    
+    match attempt(backend) {
+        Err(e) if may_fall_back(backend, options.domain.as_deref(), options.interface_index) => {
+            tracing::warn!("Native registration of {} failed ({}); retrying on mdns-sd", options.name, e);
+            callback("backendFallback", &AdvertiseData::Text(e));
+            attempt(Backend::Fallback)
+        }
+        result => result,
+    }
+}
+
+/// `callback` for the backends whose events only carry text
+pub(crate) fn text_events<F>(callback: F) -> impl Fn(&str, &str) + Clone + Send + Sync + 'static
+where
+    F: Fn(&str, &AdvertiseData) + Clone + Send + Sync + 'static,
+{
+    move |event: &str, data: &str| callback(event, &AdvertiseData::Text(data.to_string()))
+}
+
+/// Stop advertising without waiting for the registration thread to exit
+#[neon::export]
+fn stop_advertise(handle: Handle<JsBox<AdvertisementBox>>) -> bool {
+    handle.0.stop_in_background()
+}
+
+/// Stop advertising, resolving once the goodbye packets went out
+///
+/// The fallback confirms when mdns-sd has sent them; a native registration
+/// gets a short grace period after it is deallocated. Resolves to false if
+/// the advertisement was already stopped. Waits at most `timeout_ms`.
+#[neon::export]
+fn stop_advertise_async<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    handle: Handle<'cx, JsBox<AdvertisementBox>>,
+    timeout_ms: Option<f64>,
+) -> JsResult<'cx, JsPromise> {
+    let timeout = timeout_ms.map(|ms| Duration::from_millis(ms.max(0.0) as u64)).unwrap_or(handles::GOODBYE_TIMEOUT);
+    let entry = handle.0.clone();
+    let channel = cx.channel();
+    let (deferred, promise) = cx.promise();
+
+    thread::spawn(move || {
+        let stopped = entry.stop_confirmed(timeout);
+        let _ = deferred.try_settle_with(&channel, move |mut cx| Ok(cx.boolean(stopped)));
+    });
+
+    Ok(promise)
+}
+
+/// Register an advertisement under a new instance name, keeping its handle
+///
+/// Goodbyes for the old name go out first, then `registered` reports the
+/// new one. Rejects if the new name can't be registered, in which case the
+/// old one is registered again; resolves to false if already stopped.
+#[neon::export]
+fn rename_advertisement<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    handle: Handle<'cx, JsBox<AdvertisementBox>>,
+    name: String,
+) -> JsResult<'cx, JsPromise> {
+    check_instance_name(cx, &name)?;
+    let entry = handle.0.clone();
+    let channel = cx.channel();
+    let (deferred, promise) = cx.promise();
+
+    thread::spawn(move || {
+        let result = entry.rename(&name);
+        let _ = deferred.try_settle_with(&channel, move |mut cx| match result {
+            Ok(renamed) => Ok(cx.boolean(renamed)),
+            Err(e) => cx.throw_error(e),
+        });
+    });
+
+    Ok(promise)
+}
+
+/// Move an advertisement to another port, keeping its handle
+///
+/// Resolves to false if the advertisement was already stopped, and rejects
+/// if it can't be registered on the new port (the old one is kept).
+#[neon::export]
+fn update_advertisement_port<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    handle: Handle<'cx, JsBox<AdvertisementBox>>,
+    port: f64,
+) -> JsResult<'cx, JsPromise> {
+    if !(0.0..=65535.0).contains(&port) || port.fract() != 0.0 {
+        return cx.throw_type_error(format!("Invalid port: {}", port));
+    }
+    let entry = handle.0.clone();
+    let channel = cx.channel();
+    let (deferred, promise) = cx.promise();
+
+    thread::spawn(move || {
+        let result = entry.update_port(port as u16);
+        let _ = deferred.try_settle_with(&channel, move |mut cx| match result {
+            Ok(updated) => Ok(cx.boolean(updated)),
+            Err(e) => cx.throw_error(e),
+        });
+    });
+
+    Ok(promise)
+}
+
+/// Publish an extra `.local` host name for this machine
+///
+/// Stopped like an advertisement. `registered` reports the alias's full
+/// name; see [`alias`] for how each backend publishes it.
+#[neon::export]
+fn publish_alias<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    alias: String,
+    callback: Handle<'cx, JsFunction>,
+) -> NeonResult<Handle<'cx, JsBox<AdvertisementBox>>> {
+    let fullname = alias::normalize(&alias).or_else(|e| cx.throw_type_error(e))?;
+    let on_event = host_name_callback(cx, callback);
+    let backend = get_backend();
+    let result = open_alias(backend, &alias, 0, on_event);
+    insert_host_name(cx, &fullname, backend, result)
+}
+
+/// Publish A/AAAA records for any `.local` host name, such as `hub.local`
+///
+/// Stopped like an advertisement; `registered` reports the full name.
+#[neon::export]
+fn publish_host_records<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    hostname: String,
+    addresses: Handle<'cx, JsArray>,
+    callback: Handle<'cx, JsFunction>,
+) -> NeonResult<Handle<'cx, JsBox<AdvertisementBox>>> {
+    let fullname = alias::normalize(&hostname).or_else(|e| cx.throw_type_error(e))?;
+    let addresses = string_array(cx, addresses)?;
+    let addresses: Vec<std::net::IpAddr> = match addresses.iter().map(|a| a.parse()).collect() {
+        Ok(addresses) => addresses,
+        Err(_) => return cx.throw_type_error("addresses must be IPv4 or IPv6 addresses"),
+    };
+    if addresses.is_empty() {
+        return cx.throw_type_error("addresses must not be empty");
+    }
+    let on_event = host_name_callback(cx, callback);
+    let backend = get_backend();
+    let result = open_host_records(backend, &hostname, &addresses, 0, on_event);
+    insert_host_name(cx, &fullname, backend, result)
+}
+
+/// `(event, data)` callback for host name registrations
+fn host_name_callback<'cx>(cx: &mut FunctionContext<'cx>, callback: Handle<'cx, JsFunction>) -> impl Fn(&str, &AdvertiseData) + Send + Sync + 'static {
+    let channel = cx.channel();
+    let callback = Arc::new(callback.root(cx));
+    move |event: &str, data: &AdvertiseData| {
+        let (event, data) = (event.to_string(), data.clone());
+        let callback = callback.clone();
+        let _ = channel.try_send(move |mut cx| {
+            let cb = callback.to_inner(&mut cx);
+            let this = cx.undefined();
+            let args = vec![cx.string(&event).upcast(), advertise_event_data(&mut cx, &data)?];
+            let _ = cb.call(&mut cx, this, args);
+            Ok(())
+        });
+    }
+}
+
+/// Register a host name registration's handle, listed under its full name
+fn insert_host_name<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    fullname: &str,
+    backend: Backend,
+    result: Result<AdvertisementHandle, String>,
+) -> NeonResult<Handle<'cx, JsBox<AdvertisementBox>>> {
     match result {
         Ok(ad) => {
-            ADVERTISEMENTS.lock().unwrap().insert(handle_id, ad);
-            Ok(cx.number(handle_id as f64))
+            let backend = ad.backend().unwrap_or(backend);
+            let handle = handles::local(cx).advertisements.insert(fullname, backend, ad, None, None, None);
+            Ok(cx.boxed(handle))
         }
         Err(e) => cx.throw_error(e),
     }
 }
 
-/// Stop advertising
+/// Advertise several services under one handle
+///
+/// Every spec is validated before anything is registered. If one
+/// registration fails, the others are withdrawn: before throwing when the
+/// backend refuses it outright, or as soon as its `error` event is reported.
+/// Events are reported as `(event, data, index)`, `index` being the spec's
+/// position.
 #[neon::export]
-fn stop_advertise(handle_id: f64) -> bool {
-    let handle_id = handle_id as u32;
-    if let Some(mut ad) = ADVERTISEMENTS.lock().unwrap().remove(&handle_id) {
-        match &mut ad {
-            AdvertisementHandle::Native(a) => a.stop(),
-            AdvertisementHandle::Fallback(a) => a.stop(),
+fn advertise_services<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    specs: Handle<'cx, JsArray>,
+    callback: Handle<'cx, JsFunction>,
+) -> NeonResult<Handle<'cx, JsBox<AdvertisementBox>>> {
+    let mut bundle = Vec::new();
+    for spec in specs.to_vec(cx)? {
+        let spec = spec.downcast_or_throw::<JsObject, _>(cx)?;
+        let options = parse_advertise_options(cx, spec)?;
+        check_service_type(cx, &options.service_type)?;
+        check_instance_name(cx, &options.name)?;
+        bundle.push(options);
+    }
+    if bundle.is_empty() {
+        return cx.throw_type_error("At least one service is required");
+    }
+
+    let channel = cx.channel();
+    let callback = Arc::new(callback.root(cx));
+    let backend = get_backend();
+    // A registration failing after the call returned withdraws the bundle too
+    let failed = Arc::new(AtomicBool::new(false));
+    let entry: Arc<OnceLock<Weak<HandleEntry<AdvertisementHandle>>>> = Arc::new(OnceLock::new());
+    let mut ads = Vec::with_capacity(bundle.len());
+    for (index, options) in bundle.iter().enumerate() {
+        let channel = channel.clone();
+        let callback = callback.clone();
+        let (failed, entry) = (failed.clone(), entry.clone());
+        let on_event = move |event: &str, data: &AdvertiseData| {
+            if event == "error" {
+                failed.store(true, Ordering::SeqCst);
+                if let Some(entry) = entry.get().and_then(Weak::upgrade) {
+                    entry.stop_in_background();
+                }
+            }
+            let event = event.to_string();
+            let data = data.clone();
+            let callback = callback.clone();
+            let _ = channel.try_send(move |mut cx| {
+                let cb = callback.to_inner(&mut cx);
+                let this = cx.undefined();
+                let event_val = cx.string(&event);
+                let data_val = advertise_event_data(&mut cx, &data)?;
+                let index_val = cx.number(index as f64);
+                let _ = cb.call(&mut cx, this, vec![event_val.upcast(), data_val, index_val.upcast()]);
+                Ok(())
+            });
+        };
+        match open_advertisement(backend, options, on_event) {
+            Ok(ad) => ads.push(ad),
+            Err(e) => {
+                let mut opened = AdvertisementHandle::Bundle(ads);
+                handles::stop_in_background(move || opened.stop());
+                return cx.throw_error(format!("{} ({}): {}", options.name, options.service_type, e));
+            }
         }
-        true
-    } else {
-        false
     }
+
+    let types: Vec<&str> = bundle.iter().map(|options| options.service_type.as_str()).collect();
+    let handle = handles::local(cx).advertisements.insert(&types.join(","), backend, AdvertisementHandle::Bundle(ads), None, None, None);
+    let _ = entry.set(Arc::downgrade(&handle.0));
+    // Reported before the entry above existed
+    if failed.load(Ordering::SeqCst) {
+        handle.0.stop_in_background();
+    }
+    Ok(cx.boxed(handle))
+}
+
+/// Advertise the services listed in a manifest file, following later edits
+///
+/// Events are reported as `(event, data, type)`; see [`manifest`].
+#[neon::export]
+fn advertise_from_manifest<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    path: String,
+    callback: Handle<'cx, JsFunction>,
+) -> NeonResult<Handle<'cx, JsBox<AdvertisementBox>>> {
+    let channel = cx.channel();
+    let callback = Arc::new(callback.root(cx));
+    let on_event: manifest::ManifestCallback = Arc::new(move |event: &str, data: &AdvertiseData, service_type: &str| {
+        let (event, data, service_type) = (event.to_string(), data.clone(), service_type.to_string());
+        let callback = callback.clone();
+        let _ = channel.try_send(move |mut cx| {
+            let cb = callback.to_inner(&mut cx);
+            let this = cx.undefined();
+            let args = vec![
+                cx.string(&event).upcast(),
+                advertise_event_data(&mut cx, &data)?,
+                cx.string(&service_type).upcast(),
+            ];
+            let _ = cb.call(&mut cx, this, args);
+            Ok(())
+        });
+    });
+
+    let backend = get_backend();
+    match manifest::ManifestAdvertisement::new(Path::new(&path), backend, on_event) {
+        Ok(ad) => {
+            let handle = handles::local(cx).advertisements.insert(&path, backend, AdvertisementHandle::Manifest(ad), None, None, None);
+            Ok(cx.boxed(handle))
+        }
+        Err(e) => cx.throw_error(e),
+    }
+}
+
+/// Advertise on behalf of an `Advertisement` object, emitting named events on it
+#[neon::export]
+fn create_advertisement<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    target: Handle<'cx, JsObject>,
+    options: Handle<'cx, JsObject>,
+) -> NeonResult<Handle<'cx, JsBox<EmitterBox<AdvertisementHandle>>>> {
+    let opts = parse_advertise_options(cx, options)?;
+    let emitter = Emitter::new(cx, target);
+    let callback = emitter.advertise_callback(cx)?;
+    let handle = start_advertise(cx, opts, callback)?;
+    Ok(cx.boxed(EmitterBox { handle, emitter }))
+}
+
+/// Stop an `Advertisement` and emit `end`; false if it was already closed
+#[neon::export]
+fn close_advertisement<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    handle: Handle<'cx, JsBox<EmitterBox<AdvertisementHandle>>>,
+) -> NeonResult<bool> {
+    handle.close(cx)
+}
+
+/// Convert handle snapshots to a JS array
+fn handle_infos_to_js<'cx>(cx: &mut FunctionContext<'cx>, infos: Vec<HandleInfo>) -> JsResult<'cx, JsArray> {
+    let arr = cx.empty_array();
+    for (i, info) in infos.iter().enumerate() {
+        let obj = cx.empty_object();
+        let id = cx.number(info.id as f64);
+        obj.set(cx, "id", id)?;
+        let stype = cx.string(&info.service_type);
+        obj.set(cx, "type", stype)?;
+        let backend = cx.string(info.backend.name());
+        obj.set(cx, "backend", backend)?;
+        let created = info
+            .created_at
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as f64)
+            .unwrap_or(0.0);
+        let created = cx.number(created);
+        obj.set(cx, "createdAt", created)?;
+        let state = cx.string(info.state);
+        obj.set(cx, "state", state)?;
+        arr.set(cx, i as u32, obj)?;
+    }
+    Ok(arr)
+}
+
+/// List this thread's browsers that have not been garbage collected
+#[neon::export]
+fn list_browsers<'cx>(cx: &mut FunctionContext<'cx>) -> JsResult<'cx, JsArray> {
+    let infos = handles::local(cx).browsers.list();
+    handle_infos_to_js(cx, infos)
+}
+
+/// List this thread's advertisements that have not been garbage collected
+#[neon::export]
+fn list_advertisements<'cx>(cx: &mut FunctionContext<'cx>) -> JsResult<'cx, JsArray> {
+    let infos = handles::local(cx).advertisements.list();
+    handle_infos_to_js(cx, infos)
+}
+
+/// Event, resolve and error counters since the addon loaded
+#[neon::export]
+fn get_metrics<'cx>(cx: &mut FunctionContext<'cx>) -> JsResult<'cx, JsObject> {
+    let snapshot = metrics::snapshot();
+    let obj = cx.empty_object();
+
+    let events = cx.empty_object();
+    for (event, count) in &snapshot.events {
+        let count = cx.number(*count as f64);
+        events.set(cx, event.as_str(), count)?;
+    }
+    obj.set(cx, "events", events)?;
+
+    let resolves = cx.empty_object();
+    for (key, value) in [
+        ("attempted", snapshot.resolves_attempted),
+        ("succeeded", snapshot.resolves_succeeded),
+        ("timedOut", snapshot.resolves_timed_out),
+        ("cached", snapshot.resolves_cached),
+    ] {
+        let value = cx.number(value as f64);
+        resolves.set(cx, key, value)?;
+    }
+    let latency = cx.empty_object();
+    for (key, value) in ["p50", "p90", "p99"].iter().zip(snapshot.resolve_latency_ms) {
+        let value = cx.number(value);
+        latency.set(cx, *key, value)?;
+    }
+    resolves.set(cx, "latencyMs", latency)?;
+    obj.set(cx, "resolves", resolves)?;
+
+    let handles = cx.empty_object();
+    let browsers = cx.number(snapshot.active_browsers as f64);
+    handles.set(cx, "browsers", browsers)?;
+    let advertisements = cx.number(snapshot.active_advertisements as f64);
+    handles.set(cx, "advertisements", advertisements)?;
+    obj.set(cx, "activeHandles", handles)?;
+
+    let errors = cx.number(snapshot.backend_errors as f64);
+    obj.set(cx, "backendErrors", errors)?;
+    let dropped = cx.number(snapshot.events_dropped as f64);
+    obj.set(cx, "droppedEvents", dropped)?;
+    Ok(obj)
+}
+
+/// Convert a JSON value to the equivalent JS value
+fn json_to_js<'cx>(cx: &mut impl Context<'cx>, value: &serde_json::Value) -> JsResult<'cx, JsValue> {
+    use serde_json::Value;
+    Ok(match value {
+        Value::Null => cx.null().upcast(),
+        Value::Bool(b) => cx.boolean(*b).upcast(),
+        Value::Number(n) => cx.number(n.as_f64().unwrap_or_default()).upcast(),
+        Value::String(s) => cx.string(s).upcast(),
+        Value::Array(items) => {
+            let arr = cx.empty_array();
+            for (i, item) in items.iter().enumerate() {
+                let item = json_to_js(cx, item)?;
+                arr.set(cx, i as u32, item)?;
+            }
+            arr.upcast()
+        }
+        Value::Object(fields) => {
+            let obj = cx.empty_object();
+            for (key, field) in fields {
+                let field = json_to_js(cx, field)?;
+                obj.set(cx, key.as_str(), field)?;
+            }
+            obj.upcast()
+        }
+    })
+}
+
+/// JS value of an advertisement event's data: text, or an object for
+/// `hostnameConflict`
+fn advertise_event_data<'cx>(cx: &mut impl Context<'cx>, data: &AdvertiseData) -> JsResult<'cx, JsValue> {
+    match data {
+        AdvertiseData::Text(text) => Ok(cx.string(text).upcast()),
+        AdvertiseData::HostnameConflict(_) => json_to_js(cx, &serde_json::Value::from(data)),
+    }
+}
+
+/// Backend, live handles, cache and recent errors in one report
+#[neon::export]
+fn dump_diagnostics<'cx>(cx: &mut FunctionContext<'cx>) -> JsResult<'cx, JsValue> {
+    let report = diagnostics::dump();
+    json_to_js(cx, &report)
+}
+
+/// The same counters in Prometheus text exposition format
+#[neon::export]
+fn get_metrics_prometheus() -> String {
+    metrics::snapshot().to_prometheus()
 }
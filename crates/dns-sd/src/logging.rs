@@ -0,0 +1,102 @@
+//! Diagnostic logging routed to a JS callback
+//!
+//! The addon logs through `tracing`, and mdns-sd's `log` records are turned
+//! into `tracing` events. Once a level or callback is set, events at or
+//! above the level are delivered to the callback on the JS thread, or
+//! written to stderr when no callback is set.
+
+use crate::Sink;
+use neon::event::Channel;
+use neon::handle::Root;
+use neon::prelude::*;
+use once_cell::sync::OnceCell;
+use std::fmt::{self, Write};
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::{Event, Subscriber};
+use tracing_log::{AsLog, LogTracer, NormalizeEvent, log};
+use tracing_subscriber::layer::{Context as LayerContext, SubscriberExt};
+use tracing_subscriber::{Layer, Registry, reload};
+
+/// JS function receiving `(level, target, message)`
+static SINK: Sink = Sink::new();
+
+/// Changes the level of the installed subscriber
+static LEVEL: OnceCell<reload::Handle<LevelFilter, Registry>> = OnceCell::new();
+
+/// An event's message and its other fields, as `name=value`
+#[derive(Default)]
+struct Message {
+    text: String,
+    fields: String,
+}
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => self.text = format!("{:?}", value),
+            // Where bridged `log` records came from, already in their metadata
+            name if name.starts_with("log.") => {}
+            name => {
+                let _ = write!(self.fields, " {}={:?}", name, value);
+            }
+        }
+    }
+}
+
+struct JsLayer;
+
+impl<S: Subscriber> Layer<S> for JsLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: LayerContext<'_, S>) {
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+        let level = metadata.level().as_str().to_ascii_lowercase();
+        let target = metadata.target().to_string();
+        let mut message = Message::default();
+        event.record(&mut message);
+        let message = message.text + &message.fields;
+
+        if !SINK.is_set() {
+            eprintln!("[dns-sd {}] {}: {}", level, target, message);
+            return;
+        }
+        SINK.call(move |cx| {
+            Ok(vec![
+                cx.string(&level).upcast(),
+                cx.string(&target).upcast(),
+                cx.string(&message).upcast(),
+            ])
+        });
+    }
+}
+
+/// Install the subscriber on first use; nothing is logged before that
+fn install() -> Option<&'static reload::Handle<LevelFilter, Registry>> {
+    LEVEL
+        .get_or_try_init(|| {
+            let (filter, handle) = reload::Layer::new(LevelFilter::WARN);
+            tracing::subscriber::set_global_default(Registry::default().with(filter).with(JsLayer))?;
+            // mdns-sd's records at the chosen level and above become events,
+            // unless the application installed a `log` logger of its own
+            let _ = LogTracer::builder().with_max_level(log::LevelFilter::Warn).init();
+            Ok::<_, tracing::subscriber::SetGlobalDefaultError>(handle)
+        })
+        .ok()
+}
+
+/// Parse `off`, `error`, `warn`, `info`, `debug` or `trace`
+pub fn parse_level(level: &str) -> Option<LevelFilter> {
+    level.parse().ok()
+}
+
+pub fn set_level(level: LevelFilter) {
+    let Some(handle) = install() else { return };
+    let _ = handle.reload(level);
+    log::set_max_level(level.as_log());
+}
+
+/// Route events to `callback`, or back to stderr with `None`
+pub fn set_callback(sink: Option<(Channel, Root<JsFunction>)>) {
+    install();
+    SINK.set(sink);
+}
@@ -0,0 +1,158 @@
+//! Discovery manager: browses a set of service types and keeps an
+//! authoritative table of resolved services
+//!
+//! Consumers get clean `added` / `updated` / `removed` diffs (updates only
+//! when something actually changed) and can read the table at any time.
+
+use crate::handles::{self, BrowserHandle, Stoppable};
+use crate::instances::{InstanceTable, bare_type, instance_key};
+use crate::options::BrowseOptions;
+use crate::{Backend, BrowseCallback, ServiceInfo, open_browser};
+use neon::prelude::*;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+
+/// Diff callback: ("added" | "updated" | "removed", service)
+pub type DiffCallback = Arc<dyn Fn(&str, ServiceInfo) + Send + Sync>;
+
+/// Authoritative service table
+#[derive(Default)]
+struct ServiceTable {
+    services: HashMap<String, ServiceInfo>,
+}
+
+impl ServiceTable {
+    /// Apply a deduplicated browse event, returning the resulting diff
+    fn apply(&mut self, event: &str, info: ServiceInfo) -> Option<&'static str> {
+        let key = instance_key(&info);
+        match event {
+            "serviceFound" | "serviceUpdated" => match self.services.insert(key, info.clone()) {
+                None => Some("added"),
+                Some(prev) if prev != info => Some("updated"),
+                Some(_) => None,
+            },
+            "serviceLost" => self.services.remove(&key).map(|_| "removed"),
+            _ => None,
+        }
+    }
+}
+
+/// Browses several service types and diffs their results
+pub struct DiscoveryManager {
+    backend: Backend,
+    options: BrowseOptions,
+    table: Arc<Mutex<ServiceTable>>,
+    browsers: Mutex<HashMap<String, BrowserHandle>>,
+    on_diff: DiffCallback,
+}
+
+impl DiscoveryManager {
+    pub fn new(backend: Backend, options: BrowseOptions, on_diff: DiffCallback) -> Self {
+        DiscoveryManager {
+            backend,
+            options,
+            table: Arc::new(Mutex::new(ServiceTable::default())),
+            browsers: Mutex::new(HashMap::new()),
+            on_diff,
+        }
+    }
+
+    /// Start browsing a service type (no-op if already browsed)
+    pub fn add_type(&self, service_type: &str) -> Result<bool, String> {
+        let mut browsers = self.browsers.lock().unwrap();
+        if browsers.contains_key(service_type) {
+            return Ok(false);
+        }
+
+        let mut options = self.options.clone();
+        options.service_type = service_type.to_string();
+
+        let instances = Mutex::new(InstanceTable::new(options.interface_mode));
+        let address_filter = options.address_filter;
+        let result_filter = options.result_filter.clone();
+        let table = self.table.clone();
+        let on_diff = self.on_diff.clone();
+        let on_event: BrowseCallback = Arc::new(move |event: &str, mut info: ServiceInfo| {
+            address_filter.apply(&mut info.addresses);
+            let mut instances = instances.lock().unwrap();
+            let Some(event) = result_filter.screen(event, &info, instances.contains(&info)) else {
+                return;
+            };
+            let Some((event, info)) = instances.apply(event, info) else {
+                return;
+            };
+            drop(instances);
+            if let Some(diff) = table.lock().unwrap().apply(&event, info.clone()) {
+                on_diff(diff, info);
+            }
+        });
+
+        let browser = open_browser(self.backend, &options, on_event)?;
+        browsers.insert(service_type.to_string(), browser);
+        Ok(true)
+    }
+
+    /// Stop browsing a service type, removing its services from the table
+    pub fn remove_type(&self, service_type: &str) -> bool {
+        let Some(mut browser) = self.browsers.lock().unwrap().remove(service_type) else {
+            return false;
+        };
+        browser.stop();
+
+        let removed: Vec<ServiceInfo> = {
+            let mut table = self.table.lock().unwrap();
+            let keys: Vec<String> = table
+                .services
+                .iter()
+                .filter(|(_, info)| bare_type(&info.service_type, &info.domain) == bare_type(service_type, &info.domain))
+                .map(|(k, _)| k.clone())
+                .collect();
+            keys.iter().filter_map(|k| table.services.remove(k)).collect()
+        };
+        for info in removed {
+            (self.on_diff)("removed", info);
+        }
+        true
+    }
+
+    /// Service types currently browsed
+    pub fn types(&self) -> Vec<String> {
+        let mut types: Vec<String> = self.browsers.lock().unwrap().keys().cloned().collect();
+        types.sort();
+        types
+    }
+
+    /// Current contents of the service table
+    pub fn snapshot(&self) -> Vec<ServiceInfo> {
+        let table = self.table.lock().unwrap();
+        let mut services: Vec<ServiceInfo> = table.services.values().cloned().collect();
+        services.sort_by_key(instance_key);
+        services
+    }
+
+    /// Stop all browsers
+    pub fn stop(&self) {
+        for (_, mut browser) in self.browsers.lock().unwrap().drain() {
+            browser.stop();
+        }
+    }
+}
+
+/// Manager owned by a JS object; its environment's registry holds a weak reference
+pub struct ManagerBox(pub Arc<DiscoveryManager>);
+
+impl Deref for ManagerBox {
+    type Target = DiscoveryManager;
+
+    fn deref(&self) -> &DiscoveryManager {
+        &self.0
+    }
+}
+
+impl Finalize for ManagerBox {
+    fn finalize<'a, C: Context<'a>>(self, _cx: &mut C) {
+        let manager = self.0;
+        handles::stop_in_background(move || manager.stop());
+    }
+}
@@ -0,0 +1,331 @@
+//! Declarative advertisement manifests
+//!
+//! A manifest lists the services to advertise, either as JSON
+//! (`{"services": [...]}` or a bare array) or as TOML (`[[services]]`
+//! tables, for files ending in `.toml`). Each entry takes `name`, `type`,
+//! `port` and optionally `domain`, `host`, `txt`, `subtypes` and
+//! `interface` (or `interfaces`, a list of names). `{hostname}` in the name, host and TXT values is replaced
+//! with this machine's host name.
+//!
+//! The file is polled for changes. On reload, services whose entry is
+//! unchanged stay registered; removed or edited ones are withdrawn and new
+//! ones registered. A manifest that fails to load leaves the current set
+//! running and is reported as an error.
+
+use crate::handles::{AdvertisementHandle, GOODBYE_TIMEOUT, Stoppable, running};
+use crate::{AdvertiseData, AdvertiseOptions, Backend, TxtRecord, interfaces, model, names, open_advertisement};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the manifest file is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Manifest events: `(event, data, service type)`
+///
+/// `registered` and `error` as for single advertisements; errors about the
+/// manifest itself have an empty type. `reloaded` carries the number of
+/// services now advertised.
+pub type ManifestCallback = Arc<dyn Fn(&str, &AdvertiseData, &str) + Send + Sync>;
+
+/// This machine's host name, without a `.local` suffix
+pub fn local_hostname() -> String {
+    hostname::get()
+        .map(|h| h.to_string_lossy().trim_end_matches('.').trim_end_matches(".local").to_string())
+        .unwrap_or_else(|_| "localhost".to_string())
+}
+
+/// Read and validate the services a manifest describes
+pub fn load(path: &Path) -> Result<Vec<AdvertiseOptions>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let doc = if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("toml")) {
+        let table = text.parse::<toml::Table>().map_err(|e| format!("{}: invalid TOML: {}", path.display(), e))?;
+        toml_to_json(&toml::Value::Table(table))
+    } else {
+        serde_json::from_str(&text).map_err(|e| format!("{}: invalid JSON: {}", path.display(), e))?
+    };
+    let entries = match doc.get("services").unwrap_or(&doc).as_array() {
+        Some(entries) => entries,
+        None => return Err(format!("{}: expected a list of services", path.display())),
+    };
+    let hostname = local_hostname();
+    entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| parse_service(entry, &hostname).map_err(|e| format!("{}: service {}: {}", path.display(), i, e)))
+        .collect()
+}
+
+/// A TOML document as JSON, so both formats are read the same way; dates
+/// become their TOML text
+fn toml_to_json(value: &toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => s.as_str().into(),
+        toml::Value::Integer(n) => (*n).into(),
+        toml::Value::Float(n) => (*n).into(),
+        toml::Value::Boolean(b) => (*b).into(),
+        toml::Value::Datetime(d) => d.to_string().into(),
+        toml::Value::Array(items) => Value::Array(items.iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => table.iter().map(|(k, v)| (k.clone(), toml_to_json(v))).collect(),
+    }
+}
+
+fn parse_service(entry: &Value, hostname: &str) -> Result<AdvertiseOptions, String> {
+    let expand = |s: &str| s.replace("{hostname}", hostname);
+    let string = |key: &str| -> Result<Option<String>, String> {
+        match entry.get(key) {
+            None => Ok(None),
+            Some(value) => value.as_str().map(|s| Some(expand(s))).ok_or_else(|| format!("{} must be a string", key)),
+        }
+    };
+
+    let name = string("name")?.ok_or("name is required")?;
+    let service_type = string("type")?.ok_or("type is required")?;
+    model::validate_service_type(&service_type)?;
+    names::validate_instance_name(&name)?;
+    let port = match entry.get("port").and_then(Value::as_f64) {
+        Some(port) if (0.0..=65535.0).contains(&port) && port.fract() == 0.0 => port as u16,
+        _ => return Err("port must be an integer from 0 to 65535".to_string()),
+    };
+
+    let strict_txt = matches!(entry.get("strictTxt"), Some(Value::Bool(true)));
+    let txt = match entry.get("txt") {
+        None => None,
+        Some(txt) => {
+            let fields = txt.as_object().ok_or("txt must be a table of strings")?;
+            let mut map = TxtRecord::new();
+            for (key, value) in fields {
+                let value = match value {
+                    Value::String(s) => expand(s),
+                    Value::Number(_) | Value::Bool(_) => value.to_string(),
+                    _ => return Err(format!("txt.{} must be a string", key)),
+                };
+                if strict_txt {
+                    map.insert_strict(key, &value)?;
+                } else {
+                    map.insert(key.clone(), value);
+                }
+            }
+            Some(map)
+        }
+    };
+    let ttl = |key: &str| match entry.get(key) {
+        None => Ok(None),
+        Some(ttl) => match ttl.as_f64() {
+            Some(ttl) if ttl >= 0.0 && ttl.fract() == 0.0 => Ok(Some(ttl as u32)),
+            _ => Err(format!("{} must be a whole number of seconds", key)),
+        },
+    };
+    let subtypes = match entry.get("subtypes") {
+        None => Vec::new(),
+        Some(list) => list
+            .as_array()
+            .and_then(|items| items.iter().map(|s| s.as_str().map(str::to_string)).collect())
+            .ok_or("subtypes must be a list of strings")?,
+    };
+
+    let names = match entry.get("interfaces") {
+        None => Vec::new(),
+        Some(list) => list
+            .as_array()
+            .and_then(|items| items.iter().map(|s| s.as_str().map(str::to_string)).collect())
+            .ok_or("interfaces must be a list of interface names")?,
+    };
+
+    Ok(AdvertiseOptions {
+        name,
+        service_type,
+        port,
+        txt,
+        txtvers_first: matches!(entry.get("txtversFirst"), Some(Value::Bool(true))),
+        domain: string("domain")?,
+        host: string("host")?,
+        interface_index: match entry.get("interface") {
+            None => 0,
+            Some(Value::Number(index)) => index.as_f64().unwrap_or_default() as u32,
+            Some(Value::String(name)) => interfaces::parse_interface(name)?,
+            Some(_) => return Err("interface must be an index or \"local-only\"".to_string()),
+        },
+        interfaces: names,
+        subtypes,
+        ttl: ttl("ttl")?,
+        host_ttl: ttl("hostTtl")?,
+        ..Default::default()
+    })
+}
+
+/// Modification stamp used to notice edits
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+/// Services registered from a manifest
+type Registered = Vec<(AdvertiseOptions, AdvertisementHandle)>;
+
+fn register(backend: Backend, options: &AdvertiseOptions, callback: &ManifestCallback) -> Result<AdvertisementHandle, String> {
+    let callback = callback.clone();
+    let service_type = options.service_type.clone();
+    open_advertisement(backend, options, move |event: &str, data: &AdvertiseData| callback(event, data, &service_type))
+        .map_err(|e| format!("{} ({}): {}", options.name, options.service_type, e))
+}
+
+/// Bring `current` in line with `wanted`, keeping unchanged registrations
+fn apply(current: &mut Registered, wanted: Vec<AdvertiseOptions>, backend: Backend, callback: &ManifestCallback) {
+    let (keep, remove): (Registered, Registered) = std::mem::take(current)
+        .into_iter()
+        .partition(|(options, _)| wanted.contains(options));
+    // Edited services are registered again right away, so peers must have
+    // seen the old ones go first
+    let deadline = Instant::now() + GOODBYE_TIMEOUT;
+    for (_, mut ad) in remove {
+        ad.stop_confirmed(deadline);
+    }
+    *current = keep;
+    for options in wanted {
+        if current.iter().any(|(registered, _)| *registered == options) {
+            continue;
+        }
+        match register(backend, &options, callback) {
+            Ok(ad) => current.push((options, ad)),
+            Err(e) => callback("error", &AdvertiseData::Text(e), &options.service_type),
+        }
+    }
+}
+
+/// Advertisements kept in sync with a manifest file
+pub struct ManifestAdvertisement {
+    /// Dropping it stops the watcher, which withdraws every service;
+    /// sending asks it to re-announce them
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl ManifestAdvertisement {
+    /// Register everything in the manifest, then watch it for changes
+    ///
+    /// Fails without registering anything if the manifest doesn't load or
+    /// any service can't be registered.
+    pub fn new(path: &Path, backend: Backend, callback: ManifestCallback) -> Result<Self, String> {
+        let path: PathBuf = path.to_path_buf();
+        let mut seen = stamp(&path);
+        let mut registered: Registered = Vec::new();
+        for options in load(&path)? {
+            match register(backend, &options, &callback) {
+                Ok(ad) => registered.push((options, ad)),
+                Err(e) => {
+                    for (_, mut ad) in registered {
+                        ad.stop();
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::spawn(move || {
+            loop {
+                match stopped.recv_timeout(POLL_INTERVAL) {
+                    Ok(()) => {
+                        registered.iter_mut().for_each(|(_, ad)| ad.refresh());
+                        continue;
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+                let now = stamp(&path);
+                if now == seen {
+                    continue;
+                }
+                seen = now;
+                match load(&path) {
+                    Ok(wanted) => {
+                        tracing::info!("Reloading manifest {}", path.display());
+                        apply(&mut registered, wanted, backend, &callback);
+                        callback("reloaded", &AdvertiseData::Text(registered.len().to_string()), "");
+                    }
+                    Err(e) => callback("error", &AdvertiseData::Text(e), ""),
+                }
+            }
+            let deadline = Instant::now() + GOODBYE_TIMEOUT;
+            for (_, mut ad) in registered {
+                ad.stop_confirmed(deadline);
+            }
+        });
+        Ok(ManifestAdvertisement { stop: Some(stop), thread: Some(thread) })
+    }
+
+    /// Re-announce every service, from the watcher thread
+    pub fn refresh(&self) {
+        if let Some(stop) = &self.stop {
+            let _ = stop.send(());
+        }
+    }
+
+    /// Threads still running, for diagnostics
+    pub fn threads(&self) -> usize {
+        running(&self.thread)
+    }
+
+    pub fn stop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for ManifestAdvertisement {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_text(file: &str, text: &str) -> Result<Vec<AdvertiseOptions>, String> {
+        let path = std::env::temp_dir().join(format!("dns-sd-{}-{}", std::process::id(), file));
+        std::fs::write(&path, text).unwrap();
+        let result = load(&path);
+        let _ = std::fs::remove_file(&path);
+        result
+    }
+
+    #[test]
+    fn loads_toml_manifests() {
+        let services = load_text(
+            "services.toml",
+            r#"
+            # Comment
+            [[services]]
+            name = "web"
+            type = "_http._tcp"
+            port = 80
+            txt = { zeta = "1", alpha = 'two', "quoted key" = "x" }
+
+            [[services]]
+            name = "ssh"
+            type = "_ssh._tcp"
+            port = 22
+            subtypes = ["_a", "_b"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(services.len(), 2);
+        assert_eq!((services[0].name.as_str(), services[0].port), ("web", 80));
+        let txt: Vec<(&str, &str)> = services[0].txt.as_ref().unwrap().iter().collect();
+        assert_eq!(txt, [("zeta", "1"), ("alpha", "two"), ("quoted key", "x")]);
+        assert_eq!(services[1].subtypes, ["_a", "_b"]);
+    }
+
+    #[test]
+    fn reports_toml_error_positions() {
+        let error = load_text("broken.toml", "[[services]]\nname = \"web\nport = 80\n").unwrap_err();
+        assert!(error.contains("invalid TOML") && error.contains("line 2"), "{}", error);
+    }
+}
@@ -0,0 +1,166 @@
+//! Process-wide counters reported by `getMetrics`
+//!
+//! Counters are cumulative since the addon loaded. Resolve latencies keep a
+//! sliding window of the most recent samples for percentiles, and backend
+//! errors the most recent messages for `dumpDiagnostics`.
+
+use crate::handles::{self, HandleInfo};
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+
+/// Resolve latency samples kept for percentiles
+const LATENCY_WINDOW: usize = 1024;
+
+/// Backend error messages kept for diagnostics
+const ERROR_WINDOW: usize = 32;
+
+#[derive(Default)]
+struct Metrics {
+    /// Backend events by name (`serviceFound`, `serviceLost`, ...)
+    events: Mutex<BTreeMap<String, u64>>,
+    resolves_attempted: AtomicU64,
+    resolves_succeeded: AtomicU64,
+    resolves_timed_out: AtomicU64,
+    resolves_cached: AtomicU64,
+    backend_errors: AtomicU64,
+    events_dropped: AtomicU64,
+    latencies: Mutex<VecDeque<Duration>>,
+    errors: Mutex<VecDeque<(SystemTime, String)>>,
+}
+
+static METRICS: Lazy<Metrics> = Lazy::new(Metrics::default);
+
+/// A backend browser delivered `event`
+pub fn record_event(event: &str) {
+    *METRICS.events.lock().unwrap().entry(event.to_string()).or_default() += 1;
+}
+
+/// A service resolve was started
+pub fn resolve_started() {
+    METRICS.resolves_attempted.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A service resolve produced a host and port after `latency`
+pub fn resolve_succeeded(latency: Duration) {
+    METRICS.resolves_succeeded.fetch_add(1, Ordering::Relaxed);
+    let mut latencies = METRICS.latencies.lock().unwrap();
+    if latencies.len() == LATENCY_WINDOW {
+        latencies.pop_front();
+    }
+    latencies.push_back(latency);
+}
+
+/// A service resolve gave up without an answer
+pub fn resolve_timed_out() {
+    METRICS.resolves_timed_out.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A resolve was skipped because the cache held a fresh result
+pub fn resolve_cached() {
+    METRICS.resolves_cached.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A backend call failed (browse/register/resolve setup or callback error)
+pub fn backend_error(message: &str) {
+    METRICS.backend_errors.fetch_add(1, Ordering::Relaxed);
+    let mut errors = METRICS.errors.lock().unwrap();
+    if errors.len() == ERROR_WINDOW {
+        errors.pop_front();
+    }
+    errors.push_back((SystemTime::now(), message.to_string()));
+}
+
+/// The most recent backend errors, oldest first
+pub fn recent_errors() -> Vec<(SystemTime, String)> {
+    METRICS.errors.lock().unwrap().iter().cloned().collect()
+}
+
+/// An event was discarded or coalesced by a full handle queue
+pub fn event_dropped() {
+    METRICS.events_dropped.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Point-in-time copy of the counters
+#[derive(Debug, Clone, Default)]
+pub struct MetricsSnapshot {
+    pub events: BTreeMap<String, u64>,
+    pub resolves_attempted: u64,
+    pub resolves_succeeded: u64,
+    pub resolves_timed_out: u64,
+    /// Resolves answered from the discovery cache instead
+    pub resolves_cached: u64,
+    pub backend_errors: u64,
+    /// Events lost to full handle queues (see `queueSize`/`overflow`)
+    pub events_dropped: u64,
+    /// Resolve latency percentiles in milliseconds (p50, p90, p99); zero without samples
+    pub resolve_latency_ms: [f64; 3],
+    pub active_browsers: usize,
+    pub active_advertisements: usize,
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = ((sorted.len() - 1) as f64 * p).round() as usize;
+    sorted[rank].as_secs_f64() * 1000.0
+}
+
+fn active(handles: Vec<HandleInfo>) -> usize {
+    handles.iter().filter(|h| h.state == "active").count()
+}
+
+/// Copy the current counters
+pub fn snapshot() -> MetricsSnapshot {
+    let mut latencies: Vec<Duration> = METRICS.latencies.lock().unwrap().iter().copied().collect();
+    latencies.sort();
+    let envs = handles::environments();
+
+    MetricsSnapshot {
+        events: METRICS.events.lock().unwrap().clone(),
+        resolves_attempted: METRICS.resolves_attempted.load(Ordering::Relaxed),
+        resolves_succeeded: METRICS.resolves_succeeded.load(Ordering::Relaxed),
+        resolves_timed_out: METRICS.resolves_timed_out.load(Ordering::Relaxed),
+        resolves_cached: METRICS.resolves_cached.load(Ordering::Relaxed),
+        backend_errors: METRICS.backend_errors.load(Ordering::Relaxed),
+        events_dropped: METRICS.events_dropped.load(Ordering::Relaxed),
+        resolve_latency_ms: [0.5, 0.9, 0.99].map(|p| percentile(&latencies, p)),
+        active_browsers: envs.iter().map(|env| active(env.browsers.list())).sum(),
+        active_advertisements: envs.iter().map(|env| active(env.advertisements.list())).sum(),
+    }
+}
+
+impl MetricsSnapshot {
+    /// Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let mut counter = |name: &str, help: &str, value: u64| {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} counter\n{} {}", name, help, name, name, value);
+        };
+        counter("dnssd_resolves_attempted_total", "Service resolves started", self.resolves_attempted);
+        counter("dnssd_resolves_succeeded_total", "Service resolves that produced a host and port", self.resolves_succeeded);
+        counter("dnssd_resolves_timed_out_total", "Service resolves that gave up", self.resolves_timed_out);
+        counter("dnssd_resolves_cached_total", "Service resolves answered from the discovery cache", self.resolves_cached);
+        counter("dnssd_backend_errors_total", "Failed backend calls", self.backend_errors);
+        counter("dnssd_events_dropped_total", "Events lost to full handle queues", self.events_dropped);
+
+        let _ = writeln!(out, "# HELP dnssd_events_total Backend browse events by type\n# TYPE dnssd_events_total counter");
+        for (event, count) in &self.events {
+            let _ = writeln!(out, "dnssd_events_total{{event=\"{}\"}} {}", event, count);
+        }
+
+        let _ = writeln!(out, "# HELP dnssd_resolve_latency_ms Recent service resolve latency\n# TYPE dnssd_resolve_latency_ms summary");
+        for (quantile, value) in ["0.5", "0.9", "0.99"].iter().zip(self.resolve_latency_ms) {
+            let _ = writeln!(out, "dnssd_resolve_latency_ms{{quantile=\"{}\"}} {}", quantile, value);
+        }
+
+        let _ = writeln!(out, "# HELP dnssd_active_handles Live handles by kind\n# TYPE dnssd_active_handles gauge");
+        let _ = writeln!(out, "dnssd_active_handles{{kind=\"browser\"}} {}", self.active_browsers);
+        let _ = writeln!(out, "dnssd_active_handles{{kind=\"advertisement\"}} {}", self.active_advertisements);
+        out
+    }
+}
@@ -0,0 +1,174 @@
+//! Scripted backend for testing discovery logic without multicast
+//!
+//! Every mock browser and advertisement walks the same timeline of steps
+//! from the moment it starts, sleeping each step's delay and reporting the
+//! steps that concern it. Browsers take `serviceFound`/`serviceUpdated`/
+//! `serviceLost` steps for their type; advertisements take `registered`/
+//! `error` steps for their name.
+
+use crate::ServiceInfo;
+use crate::handles::running;
+use crate::instances::bare_type;
+use crate::options::AdvertiseOptions;
+use once_cell::sync::Lazy;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// One scripted event
+#[derive(Debug, Clone, Default)]
+pub struct MockStep {
+    /// Wait before this step, measured from the previous one
+    pub delay: Duration,
+    pub event: String,
+    /// Service the event is about (browse steps) or matched against (advertise steps)
+    pub info: ServiceInfo,
+    /// Payload of advertise steps (defaults to the advertised name)
+    pub data: Option<String>,
+}
+
+impl MockStep {
+    fn is_browse(&self) -> bool {
+        matches!(self.event.as_str(), "serviceFound" | "serviceUpdated" | "serviceLost")
+    }
+}
+
+static SCRIPT: Lazy<Mutex<Arc<Vec<MockStep>>>> = Lazy::new(Default::default);
+
+/// Replace the script used by handles started from now on
+pub fn set_script(steps: Vec<MockStep>) {
+    *SCRIPT.lock().unwrap() = Arc::new(steps);
+}
+
+fn script() -> Arc<Vec<MockStep>> {
+    SCRIPT.lock().unwrap().clone()
+}
+
+/// Sleep until `deadline`, returning false if stopped first
+fn sleep_until(deadline: Instant, stop: &AtomicBool) -> bool {
+    loop {
+        if stop.load(Ordering::SeqCst) {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        thread::sleep((deadline - now).min(Duration::from_millis(10)));
+    }
+}
+
+/// Background replay shared by browsers and advertisements
+struct Replay {
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Replay {
+    /// Walk the script, calling `on_step(index, step, script)` for each step on time
+    fn start<F>(on_step: F) -> Self
+    where
+        F: Fn(usize, &MockStep, &[MockStep]) + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let steps = script();
+        let thread = thread::spawn(move || {
+            let mut at = Instant::now();
+            for (i, step) in steps.iter().enumerate() {
+                at += step.delay;
+                if !sleep_until(at, &thread_stop) {
+                    return;
+                }
+                on_step(i, step, &steps);
+            }
+        });
+        Replay { stop, thread: Some(thread) }
+    }
+
+    fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Replay {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Browser replaying scripted browse steps for one type
+pub struct MockBrowser(Replay);
+
+impl MockBrowser {
+    pub fn new<F>(service_type: &str, callback: F) -> Result<Self, String>
+    where
+        F: Fn(&str, ServiceInfo) + Send + Sync + 'static,
+    {
+        let browsed = bare_type(service_type, "local").to_string();
+        Ok(MockBrowser(Replay::start(move |i, step, steps| {
+            if !step.is_browse() || bare_type(&step.info.service_type, &step.info.domain) != browsed {
+                return;
+            }
+            callback(&step.event, step.info.clone());
+
+            // Steps due at the same moment form one batch, like MoreComing
+            let more_coming = steps[i + 1..].iter().take_while(|s| s.delay.is_zero()).any(MockStep::is_browse);
+            if !more_coming {
+                callback("batchComplete", ServiceInfo {
+                    service_type: step.info.service_type.clone(),
+                    domain: step.info.domain.clone(),
+                    ..Default::default()
+                });
+            }
+        })))
+    }
+
+    pub fn threads(&self) -> usize {
+        running(&self.0.thread)
+    }
+
+    pub fn stop(&mut self) {
+        self.0.stop();
+    }
+}
+
+/// Advertisement replaying scripted registration results
+pub struct MockAdvertisement(Replay);
+
+impl MockAdvertisement {
+    pub fn new<F>(options: &AdvertiseOptions, callback: F) -> Result<Self, String>
+    where
+        F: Fn(&str, &str) + Send + 'static,
+    {
+        let name = options.name.clone();
+        let concerns = move |step: &MockStep| {
+            matches!(step.event.as_str(), "registered" | "error")
+                && (step.info.name.is_empty() || step.info.name == name)
+        };
+
+        let name = options.name.clone();
+        if !script().iter().any(&concerns) {
+            // Unscripted registrations succeed right away
+            callback("registered", &name);
+            return Ok(MockAdvertisement(Replay::start(|_, _, _| {})));
+        }
+        Ok(MockAdvertisement(Replay::start(move |_, step, _| {
+            if concerns(step) {
+                callback(&step.event, step.data.as_deref().unwrap_or(&name));
+            }
+        })))
+    }
+
+    pub fn threads(&self) -> usize {
+        running(&self.0.thread)
+    }
+
+    pub fn stop(&mut self) {
+        self.0.stop();
+    }
+}
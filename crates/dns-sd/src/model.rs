@@ -0,0 +1,446 @@
+//! Service model shared by both backends
+//!
+//! Backends build [`ServiceInfo`] in whatever shape their API hands them and
+//! [`ServiceInfo::normalize`] turns it into the one shape delivered to JS.
+
+use crate::instances::bare_type;
+use crate::{interfaces, names};
+use crate::query::DnssecStatus;
+use mdns_sd::ResolvedService;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// Meta-query type whose results are service types rather than instances (RFC 6763 §9)
+pub const TYPE_ENUMERATION: &str = "_services._dns-sd._udp";
+
+/// Whether browsing `service_type` enumerates service types
+///
+/// Results of such a browse are PTRs to types (`_http` in `_tcp.local.`),
+/// which have no SRV/TXT to resolve.
+pub fn is_type_enumeration(service_type: &str) -> bool {
+    service_type.starts_with(TYPE_ENUMERATION)
+}
+
+/// Check a service type before it reaches a backend
+///
+/// Accepts `_name._tcp` / `_name._udp` with an optional domain and trailing
+/// dot, subtypes as `_sub._sub._name._tcp` or the native `_name._tcp,_sub`,
+/// and the type enumeration meta-query.
+pub fn validate_service_type(service_type: &str) -> Result<(), String> {
+    let invalid = |why: &str| Err(format!("Invalid service type \"{}\": {}", service_type, why));
+    if is_type_enumeration(service_type) {
+        return Ok(());
+    }
+
+    let (base, subtypes) = match service_type.split_once(',') {
+        Some((base, subtypes)) => (base, Some(subtypes)),
+        None => (service_type, None),
+    };
+    let labels: Vec<&str> = base.strip_suffix('.').unwrap_or(base).split('.').collect();
+    let Some(proto) = labels
+        .iter()
+        .position(|l| l.eq_ignore_ascii_case("_tcp") || l.eq_ignore_ascii_case("_udp"))
+    else {
+        return invalid("expected _name._tcp or _name._udp");
+    };
+    if proto == 0 {
+        return invalid("missing service name before the protocol");
+    }
+    if let Err(why) = check_service_name(labels[proto - 1]) {
+        return invalid(&why);
+    }
+    match &labels[..proto - 1] {
+        [] => {}
+        [sub, marker] if marker.eq_ignore_ascii_case("_sub") && !sub.is_empty() => {}
+        _ => return invalid("only a subtype (`_sub._sub.`) may precede the service name"),
+    }
+    if labels[proto + 1..].iter().any(|l| l.is_empty()) {
+        return invalid("empty domain label");
+    }
+    if subtypes.is_some_and(|subtypes| subtypes.split(',').any(str::is_empty)) {
+        return invalid("empty subtype");
+    }
+    Ok(())
+}
+
+/// RFC 6335 §5.1 service name with the DNS-SD underscore
+fn check_service_name(label: &str) -> Result<(), String> {
+    let Some(name) = label.strip_prefix('_') else {
+        return Err(format!("service name \"{}\" must start with an underscore", label));
+    };
+    if name.is_empty() || name.len() > 15 {
+        return Err(format!("service name \"{}\" must be 1-15 characters", name));
+    }
+    if !name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-') {
+        return Err(format!("service name \"{}\" may only contain letters, digits and hyphens", name));
+    }
+    if !name.bytes().any(|b| b.is_ascii_alphabetic()) {
+        return Err(format!("service name \"{}\" must contain a letter", name));
+    }
+    if name.starts_with('-') || name.ends_with('-') || name.contains("--") {
+        return Err(format!("service name \"{}\" has a misplaced hyphen", name));
+    }
+    Ok(())
+}
+
+/// Service info from browse/resolve
+///
+/// Two results are equal when they describe the same service; see the
+/// [`PartialEq`] impl for what is left out.
+#[derive(Debug, Clone, Default)]
+pub struct ServiceInfo {
+    pub name: String,
+    pub service_type: String,
+    pub domain: String,
+    /// Escaped full name (`Instance._type._tcp.domain.`), filled in by [`ServiceInfo::normalize`]
+    pub full_name: String,
+    pub host_name: String,
+    pub addresses: Vec<String>,
+    pub port: u16,
+    /// SRV priority and weight for RFC 2782 selection (0 when the backend doesn't report them)
+    pub priority: u16,
+    pub weight: u16,
+    pub txt: HashMap<String, String>,
+    pub ttl: u32,
+    /// Interface the service was seen on (0 = any/unknown)
+    pub interface_index: u32,
+    /// Problems found while parsing the TXT record (native backend only)
+    pub txt_errors: Vec<String>,
+    /// DNS-SD flags of the browse/resolve callback behind this event (native backend only)
+    pub flags: Option<u32>,
+    /// DNSSEC result of the address lookup, for browses with `validate` (native backend only)
+    pub dnssec: Option<DnssecStatus>,
+    /// Loaded from the persisted cache and not yet confirmed by the network
+    pub stale: bool,
+    /// How long each discovery phase took, for browses with `profile`
+    pub timings: Option<Timings>,
+    /// Why a resolve timed out or gave up, on `resolveTimeout` and `resolveFailed`
+    pub reason: Option<String>,
+    /// The address that appeared or went away, on `addressAdded` and `addressRemoved`
+    pub address: Option<String>,
+}
+
+/// Per-phase timings of one discovered service
+///
+/// Phases a backend doesn't go through stay `None`: mdns-sd resolves on its
+/// own, so the fallback only reports `resolve`, from PTR to resolved
+/// service.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timings {
+    /// Browse callback to the start of the service's resolve
+    pub queued: Option<Duration>,
+    /// Resolve start to the host name, port and TXT
+    pub resolve: Option<Duration>,
+    /// Address lookup start to the first address
+    pub address: Option<Duration>,
+    /// Start of the phase in progress
+    pub phase_started: Option<Instant>,
+    /// When the event left the backend for JS; stamped by profiling browsers
+    pub emitted_at: Option<Instant>,
+}
+
+impl Timings {
+    /// Timings whose first phase starts now
+    pub fn start() -> Self {
+        Timings { phase_started: Some(Instant::now()), ..Default::default() }
+    }
+
+    /// Time spent in the phase in progress, starting the next one
+    pub fn end_phase(&mut self) -> Option<Duration> {
+        let now = Instant::now();
+        self.phase_started.replace(now).map(|started| now - started)
+    }
+
+    /// Backend to JS, while the event is being delivered
+    pub fn dispatch(&self) -> Option<Duration> {
+        self.emitted_at.map(|at| at.elapsed())
+    }
+}
+
+/// Compares what was discovered, not how long discovering it took or the
+/// callback flags it came with, so a repeated result with new timings or
+/// flags isn't reported as an update
+impl PartialEq for ServiceInfo {
+    fn eq(&self, other: &Self) -> bool {
+        let ServiceInfo {
+            name,
+            service_type,
+            domain,
+            full_name,
+            host_name,
+            addresses,
+            port,
+            priority,
+            weight,
+            txt,
+            ttl,
+            interface_index,
+            txt_errors,
+            flags: _,
+            dnssec,
+            stale,
+            timings: _,
+            reason,
+            address,
+        } = self;
+        *name == other.name
+            && *service_type == other.service_type
+            && *domain == other.domain
+            && *full_name == other.full_name
+            && *host_name == other.host_name
+            && *addresses == other.addresses
+            && *port == other.port
+            && *priority == other.priority
+            && *weight == other.weight
+            && *txt == other.txt
+            && *ttl == other.ttl
+            && *interface_index == other.interface_index
+            && *txt_errors == other.txt_errors
+            && *dnssec == other.dnssec
+            && *stale == other.stale
+            && *reason == other.reason
+            && *address == other.address
+    }
+}
+
+impl ServiceInfo {
+    /// Canonical shape: `_http._tcp` in `local`, without domain suffix, subtype or trailing dots
+    pub fn normalize(mut self) -> Self {
+        let domain = self.domain.trim_end_matches('.').to_string();
+        let service_type = bare_type(&self.service_type, &domain);
+        // Subtype browses report the parent type, as the native backend does
+        let service_type = match service_type.split_once("._sub.") {
+            Some((_, parent)) => parent,
+            None => service_type,
+        };
+        self.service_type = service_type.to_string();
+        self.domain = domain;
+        if !self.name.is_empty() {
+            self.full_name = names::full_name(&self.name, &self.service_type, &self.domain);
+        }
+        self
+    }
+}
+
+impl From<&ResolvedService> for ServiceInfo {
+    fn from(resolved: &ResolvedService) -> Self {
+        // The instance is everything before the type, dots included
+        let fullname = resolved.get_fullname();
+        let name = match fullname.strip_suffix(resolved.ty_domain.as_str()).and_then(|n| n.strip_suffix('.')) {
+            Some(name) => name.to_string(),
+            None => names::split_fullname(fullname).map(|(name, _, _)| name).unwrap_or_default(),
+        };
+
+        ServiceInfo {
+            name,
+            service_type: resolved.ty_domain.clone(),
+            domain: "local".to_string(),
+            host_name: resolved.get_hostname().to_string(),
+            addresses: resolved.get_addresses().iter().map(|a| a.to_string()).collect(),
+            port: resolved.get_port(),
+            // mdns-sd keeps the SRV priority and weight to itself; the fallback
+            // fills them in from the SRV record its listener heard
+            txt: resolved.get_properties().iter()
+                .map(|p| (p.key().to_string(), p.val_str().to_string()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Data an advertisement reports with an event
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdvertiseData {
+    /// Registered name, error message, or why the native backend was left
+    Text(String),
+    /// `hostnameConflict` (fallback only)
+    HostnameConflict(HostnameConflict),
+}
+
+/// Another device answering for an advertisement's host name with an
+/// address the advertisement doesn't publish
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostnameConflict {
+    /// Fully qualified, with a trailing dot
+    pub host_name: String,
+    /// What the other device answered with
+    pub address: IpAddr,
+    pub responder: IpAddr,
+    /// Interface whose link the responder is on, if any matched
+    pub interface: Option<String>,
+    pub interface_index: u32,
+}
+
+impl fmt::Display for AdvertiseData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdvertiseData::Text(text) => f.write_str(text),
+            AdvertiseData::HostnameConflict(conflict) => {
+                write!(f, "{} is also answered by {} with {}", conflict.host_name, conflict.responder, conflict.address)
+            }
+        }
+    }
+}
+
+/// Service in the same shape the JS API delivers
+impl From<&ServiceInfo> for Value {
+    fn from(info: &ServiceInfo) -> Self {
+        let mut value = json!({
+            "name": info.name,
+            "type": info.service_type,
+            "domain": info.domain,
+            "fullName": info.full_name,
+            "hostName": info.host_name,
+            "port": info.port,
+            "priority": info.priority,
+            "weight": info.weight,
+            "addresses": info.addresses,
+        });
+        if !info.txt.is_empty() {
+            let mut txt: Vec<(&String, &String)> = info.txt.iter().collect();
+            txt.sort();
+            value["txt"] = txt.into_iter().map(|(k, v)| (k.clone(), Value::from(v.as_str()))).collect();
+        }
+        if info.ttl > 0 {
+            value["ttl"] = info.ttl.into();
+        }
+        if !info.txt_errors.is_empty() {
+            value["txtErrors"] = info.txt_errors.clone().into();
+        }
+        if let Some(flags) = info.flags {
+            value["flags"] = flags.into();
+        }
+        if let Some(dnssec) = info.dnssec {
+            value["dnssec"] = dnssec.as_str().into();
+        }
+        if let Some(reason) = &info.reason {
+            value["reason"] = reason.as_str().into();
+        }
+        if let Some(address) = &info.address {
+            value["address"] = address.as_str().into();
+        }
+        if let Some(timings) = info.timings.filter(|t| t.emitted_at.is_some()) {
+            let phases = [
+                ("queueMs", timings.queued),
+                ("resolveMs", timings.resolve),
+                ("addressMs", timings.address),
+                ("dispatchMs", timings.dispatch()),
+            ];
+            value["timings"] = phases
+                .into_iter()
+                .filter_map(|(key, phase)| Some((key.to_string(), Value::from(phase?.as_secs_f64() * 1000.0))))
+                .collect();
+        }
+        if info.stale {
+            value["stale"] = true.into();
+        }
+        if info.interface_index > 0 {
+            value["interfaceIndex"] = info.interface_index.into();
+            if let Some(name) = interfaces::index_to_name(info.interface_index) {
+                value["interfaceName"] = name.into();
+            }
+        }
+        value
+    }
+}
+
+/// Advertisement event data as the JS API delivers it
+impl From<&AdvertiseData> for Value {
+    fn from(data: &AdvertiseData) -> Self {
+        match data {
+            AdvertiseData::Text(text) => text.as_str().into(),
+            AdvertiseData::HostnameConflict(conflict) => {
+                let mut value = json!({
+                    "hostName": conflict.host_name,
+                    "address": conflict.address.to_string(),
+                    "responder": conflict.responder.to_string(),
+                });
+                if let Some(interface) = &conflict.interface {
+                    value["interface"] = interface.as_str().into();
+                }
+                value["interfaceIndex"] = conflict.interface_index.into();
+                value
+            }
+        }
+    }
+}
+
+/// Service from the JS-shaped object; missing fields keep their defaults
+impl From<&Value> for ServiceInfo {
+    fn from(value: &Value) -> Self {
+        let str_field = |key: &str| value.get(key).and_then(Value::as_str).unwrap_or_default().to_string();
+        let num_field = |key: &str| value.get(key).and_then(Value::as_f64).unwrap_or_default();
+        let strings = |key: &str| -> Vec<String> {
+            value
+                .get(key)
+                .and_then(Value::as_array)
+                .map(|items| items.iter().filter_map(|s| Some(s.as_str()?.to_string())).collect())
+                .unwrap_or_default()
+        };
+        ServiceInfo {
+            name: str_field("name"),
+            service_type: str_field("type"),
+            domain: match str_field("domain") {
+                domain if domain.is_empty() => "local".to_string(),
+                domain => domain,
+            },
+            full_name: str_field("fullName"),
+            host_name: str_field("hostName"),
+            addresses: strings("addresses"),
+            port: num_field("port") as u16,
+            priority: num_field("priority") as u16,
+            weight: num_field("weight") as u16,
+            txt: value
+                .get("txt")
+                .and_then(Value::as_object)
+                .map(|fields| fields.iter().filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string()))).collect())
+                .unwrap_or_default(),
+            ttl: num_field("ttl") as u32,
+            interface_index: num_field("interfaceIndex") as u32,
+            txt_errors: strings("txtErrors"),
+            flags: value.get("flags").and_then(Value::as_f64).map(|flags| flags as u32),
+            dnssec: value.get("dnssec").and_then(Value::as_str).and_then(DnssecStatus::parse),
+            stale: value.get("stale").and_then(Value::as_bool).unwrap_or_default(),
+            // Measured in this process; recorded or relayed ones don't apply
+            timings: None,
+            reason: value.get("reason").and_then(Value::as_str).map(str::to_string),
+            address: value.get("address").and_then(Value::as_str).map(str::to_string),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_matches_the_js_event_shape() {
+        let mut info = ServiceInfo {
+            name: "Printer".into(),
+            service_type: "_ipp._tcp".into(),
+            domain: "local".into(),
+            port: 631,
+            ttl: 120,
+            flags: Some(2),
+            timings: Some(Timings { resolve: Some(Duration::from_millis(5)), ..Default::default() }),
+            ..Default::default()
+        };
+        let value = Value::from(&info);
+        // Empty TXT, unset interface and timings that were never emitted are left out
+        assert!(value.get("txt").is_none());
+        assert!(value.get("interfaceIndex").is_none());
+        assert!(value.get("timings").is_none());
+        assert_eq!(ServiceInfo::from(&value), info);
+
+        info.txt.insert("rp".into(), "queue".into());
+        info.timings.as_mut().unwrap().emitted_at = Some(Instant::now());
+        let value = Value::from(&info);
+        assert_eq!(value["txt"], json!({ "rp": "queue" }));
+        assert_eq!(value["timings"]["resolveMs"], json!(5.0));
+        assert!(value["timings"].get("queueMs").is_none());
+        assert_eq!(ServiceInfo::from(&value), info);
+    }
+}
@@ -0,0 +1,361 @@
+//! Raw mDNS packets sent and heard beside mdns-sd's own sockets
+//!
+//! mdns-sd doesn't tell its users which records carried the cache-flush bit,
+//! so a [`Listener`] joins the group beside it and hands every multicast
+//! packet it hears to the fallback. mdns-sd also fixes its query schedule,
+//! so browsers with a configured cadence browse its cache only and send
+//! their queries through the listener. Its sockets are on the mDNS port,
+//! which makes responders answer over multicast where the daemon hears
+//! them, without binding a wildcard address there that would share the
+//! daemon's unicast replies.
+
+use crate::fallback::FallbackConfig;
+#[cfg(unix)]
+use socket2::{Domain, Protocol, Socket, Type};
+use socket2::{SockAddr, SockRef};
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::net::UdpSocket;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+#[cfg(unix)]
+use std::time::{Duration, Instant};
+
+pub const MDNS_PORT: u16 = 5353;
+pub const GROUP_V4: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+pub const GROUP_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb);
+
+/// PTR query for `name` (e.g. `_http._tcp.local.`), with transaction id 0
+/// as RFC 6762 asks of multicast queries
+pub fn ptr_query(name: &str) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(64);
+    // Id and flags 0, one question, no answers
+    packet.extend_from_slice(&[0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.trim_end_matches('.').split('.') {
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    // PTR, IN
+    packet.extend_from_slice(&[0, 12, 0, 1]);
+    packet
+}
+
+/// Resource record from a packet's answer, authority or additional section
+#[derive(Debug, Clone)]
+pub struct Record {
+    /// Owner name with a trailing dot, as sent
+    pub name: String,
+    pub rrtype: u16,
+    /// The top bit of the class: this record replaces earlier ones of its
+    /// name and type (RFC 6762 section 10.2)
+    pub cache_flush: bool,
+    pub ttl: u32,
+    pub rdata: Vec<u8>,
+}
+
+impl Record {
+    /// The address of an A or AAAA record
+    pub fn address(&self) -> Option<IpAddr> {
+        match self.rdata.len() {
+            4 if self.rrtype == crate::ffi::K_DNS_SERVICE_TYPE_A => {
+                Some(IpAddr::from(<[u8; 4]>::try_from(&self.rdata[..]).ok()?))
+            }
+            16 if self.rrtype == crate::ffi::K_DNS_SERVICE_TYPE_AAAA => {
+                Some(IpAddr::from(<[u8; 16]>::try_from(&self.rdata[..]).ok()?))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Records of a response; None for queries and malformed packets
+pub fn response_records(packet: &[u8]) -> Option<Vec<Record>> {
+    let header = packet.get(..12)?;
+    if header[2] & 0x80 == 0 {
+        return None;
+    }
+    let count = |i: usize| u16::from_be_bytes([header[i], header[i + 1]]) as usize;
+    let mut pos = 12;
+    for _ in 0..count(4) {
+        read_name(packet, &mut pos)?;
+        pos += 4;
+    }
+    let mut records = Vec::new();
+    for _ in 0..count(6) + count(8) + count(10) {
+        let name = read_name(packet, &mut pos)?;
+        let fixed = packet.get(pos..pos + 10)?;
+        let rdlen = u16::from_be_bytes([fixed[8], fixed[9]]) as usize;
+        records.push(Record {
+            name,
+            rrtype: u16::from_be_bytes([fixed[0], fixed[1]]),
+            cache_flush: fixed[2] & 0x80 != 0,
+            ttl: u32::from_be_bytes([fixed[4], fixed[5], fixed[6], fixed[7]]),
+            rdata: packet.get(pos + 10..pos + 10 + rdlen)?.to_vec(),
+        });
+        pos += 10 + rdlen;
+    }
+    Some(records)
+}
+
+/// Name at `pos`, following compression pointers; `pos` ends past it
+fn read_name(packet: &[u8], pos: &mut usize) -> Option<String> {
+    let mut labels = Vec::new();
+    let mut at = *pos;
+    let mut jumped = false;
+    // Bounds pointer loops in malformed packets
+    for _ in 0..128 {
+        let len = *packet.get(at)? as usize;
+        if len & 0xc0 == 0xc0 {
+            let target = ((len & 0x3f) << 8) | *packet.get(at + 1)? as usize;
+            if !jumped {
+                *pos = at + 2;
+                jumped = true;
+            }
+            at = target;
+            continue;
+        }
+        if len == 0 {
+            if !jumped {
+                *pos = at + 1;
+            }
+            return Some(format!("{}.", labels.join(".")));
+        }
+        labels.push(String::from_utf8_lossy(packet.get(at + 1..at + 1 + len)?).into_owned());
+        at += 1 + len;
+    }
+    None
+}
+
+/// Addresses of the interfaces `config` lets the fallback use
+fn interfaces(config: &FallbackConfig) -> Result<Vec<if_addrs::Interface>, String> {
+    let loopback_only = crate::loopback_only();
+    Ok(if_addrs::get_if_addrs()
+        .map_err(|e| format!("Failed to list interfaces: {}", e))?
+        .into_iter()
+        .filter(|a| a.is_loopback() == loopback_only)
+        .filter(|a| config.interfaces.is_empty() || config.interfaces.contains(&a.name))
+        .filter(|a| if a.ip().is_ipv4() { config.ipv4 } else { config.ipv6 })
+        .collect())
+}
+
+/// How often a listener joins the group on interfaces that came up since
+#[cfg(unix)]
+const REJOIN_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Longest a listener waits for packets before checking whether to stop
+#[cfg(unix)]
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Receives every mDNS packet sent to the group on the fallback's
+/// interfaces until dropped
+///
+/// Its sockets are bound to the group addresses, not the wildcard, so
+/// unicast replies still reach mdns-sd alone and aren't heard here. IPv6
+/// needs an interface to bind a link-local group address, so each IPv6
+/// interface gets a socket of its own. Windows can't bind a group address
+/// and has no listener.
+pub struct Listener {
+    sockets: Arc<Sockets>,
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+/// A listener's sockets, shared by its thread and [`Listener::send`]
+struct Sockets {
+    v4: Option<UdpSocket>,
+    /// By interface index
+    v6: Mutex<HashMap<u32, Arc<UdpSocket>>>,
+}
+
+impl Listener {
+    /// Join the mDNS group on the interfaces the fallback configuration
+    /// allows and call `on_packet` with each packet and its source
+    #[cfg(unix)]
+    pub fn start<F>(on_packet: F) -> Result<Listener, String>
+    where
+        F: Fn(&[u8], SocketAddr) + Send + 'static,
+    {
+        let v4 = open_group(SocketAddr::from((GROUP_V4, MDNS_PORT)))
+            .inspect_err(|e| tracing::debug!("mDNS listener has no IPv4 socket: {}", e))
+            .ok();
+        let sockets = Arc::new(Sockets { v4, v6: Mutex::new(HashMap::new()) });
+        sockets.join(&crate::fallback::config());
+        if sockets.v4.is_none() && sockets.v6.lock().unwrap().is_empty() {
+            return Err("No socket could bind the mDNS group".to_string());
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let shared = sockets.clone();
+        let thread = thread::spawn(move || {
+            let mut joined = Instant::now();
+            let mut buf = [0u8; 9000];
+            while !stopped.load(Ordering::SeqCst) {
+                if joined.elapsed() >= REJOIN_INTERVAL {
+                    joined = Instant::now();
+                    shared.join(&crate::fallback::config());
+                }
+                let v6: Vec<Arc<UdpSocket>> = shared.v6.lock().unwrap().values().cloned().collect();
+                let sockets: Vec<&UdpSocket> = shared.v4.iter().chain(v6.iter().map(|socket| &**socket)).collect();
+                for socket in readable(&sockets, POLL_INTERVAL) {
+                    while let Ok((len, source)) = socket.recv_from(&mut buf) {
+                        on_packet(&buf[..len], source);
+                    }
+                }
+            }
+        });
+        Ok(Listener { sockets, stop, thread: Some(thread) })
+    }
+
+    #[cfg(not(unix))]
+    pub fn start<F>(_on_packet: F) -> Result<Listener, String>
+    where
+        F: Fn(&[u8], SocketAddr) + Send + 'static,
+    {
+        Err("Windows can't bind sockets to the mDNS group".to_string())
+    }
+
+    /// Send `packet` to the mDNS group on every interface `config` lets the
+    /// fallback use, returning how many interfaces it went out on
+    ///
+    /// It leaves from the group sockets, whose source port is the mDNS port
+    /// and whose source address is the sending interface's.
+    pub fn send(&self, packet: &[u8], config: &FallbackConfig) -> Result<usize, String> {
+        let loopback_only = crate::loopback_only();
+        let addrs = interfaces(config)?;
+
+        let mut sent = 0;
+        if let Some(socket) = &self.sockets.v4 {
+            let socket = SockRef::from(socket);
+            let group = SockAddr::from(SocketAddr::from((GROUP_V4, MDNS_PORT)));
+            socket
+                .set_multicast_loop_v4(config.multicast_loop_v4 || loopback_only)
+                .and_then(|()| socket.set_multicast_ttl_v4(255))
+                .map_err(|e| e.to_string())?;
+            for addr in &addrs {
+                let IpAddr::V4(ip) = addr.ip() else { continue };
+                match socket.set_multicast_if_v4(&ip).and_then(|()| socket.send_to(packet, &group)) {
+                    Ok(_) => sent += 1,
+                    Err(e) => tracing::debug!("Failed to send query on {} ({}): {}", addr.name, ip, e),
+                }
+            }
+        }
+        // IPv6 sends once per interface index, however many addresses it has
+        let indices: BTreeSet<(u32, &str)> = addrs
+            .iter()
+            .filter(|a| a.ip().is_ipv6())
+            .map(|a| (a.index.unwrap_or(0), a.name.as_str()))
+            .collect();
+        let v6 = self.sockets.v6.lock().unwrap().clone();
+        for (index, name) in indices {
+            // Interfaces that came up since the last join have no socket yet
+            let Some(socket) = v6.get(&index) else { continue };
+            let socket = SockRef::from(&**socket);
+            let group = SockAddr::from(SocketAddr::V6(SocketAddrV6::new(GROUP_V6, MDNS_PORT, 0, index)));
+            let sending = socket
+                .set_multicast_loop_v6(config.multicast_loop_v6 || loopback_only)
+                .and_then(|()| socket.set_multicast_hops_v6(255))
+                .and_then(|()| socket.set_multicast_if_v6(index))
+                .and_then(|()| socket.send_to(packet, &group));
+            match sending {
+                Ok(_) => sent += 1,
+                Err(e) => tracing::debug!("Failed to send query on {} (IPv6): {}", name, e),
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Stop listening without waiting for the thread
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Sockets {
+    /// Join the group on each allowed interface: through the IPv4 socket,
+    /// and with a socket per IPv6 interface, closing those of interfaces
+    /// that went away. Interfaces already joined fail and are skipped.
+    fn join(&self, config: &FallbackConfig) {
+        let Ok(addrs) = interfaces(config) else { return };
+        if let Some(socket) = &self.v4 {
+            for addr in &addrs {
+                if let IpAddr::V4(ip) = addr.ip() {
+                    let _ = socket.join_multicast_v4(&GROUP_V4, &ip);
+                }
+            }
+        }
+        let indices: BTreeSet<u32> = addrs
+            .iter()
+            .filter(|a| a.ip().is_ipv6())
+            .filter_map(|a| a.index)
+            .collect();
+        let mut v6 = self.v6.lock().unwrap();
+        v6.retain(|index, _| indices.contains(index));
+        for index in indices {
+            if v6.contains_key(&index) {
+                continue;
+            }
+            let group = SocketAddr::V6(SocketAddrV6::new(GROUP_V6, MDNS_PORT, 0, index));
+            match open_group(group).and_then(|socket| {
+                socket.join_multicast_v6(&GROUP_V6, index).map_err(|e| e.to_string())?;
+                Ok(socket)
+            }) {
+                Ok(socket) => {
+                    v6.insert(index, Arc::new(socket));
+                }
+                Err(e) => tracing::debug!("mDNS listener has no IPv6 socket on interface {}: {}", index, e),
+            }
+        }
+    }
+}
+
+/// Non-blocking socket bound to `group` on the mDNS port beside the daemon's
+#[cfg(unix)]
+fn open_group(group: SocketAddr) -> Result<UdpSocket, String> {
+    let socket = Socket::new(Domain::for_address(group), Type::DGRAM, Some(Protocol::UDP)).map_err(|e| e.to_string())?;
+    socket.set_reuse_address(true).map_err(|e| e.to_string())?;
+    #[cfg(not(any(target_os = "solaris", target_os = "illumos")))]
+    socket.set_reuse_port(true).map_err(|e| e.to_string())?;
+    if group.is_ipv6() {
+        socket.set_only_v6(true).map_err(|e| e.to_string())?;
+    }
+    socket
+        .bind(&group.into())
+        .map_err(|e| format!("Failed to bind {}: {}", group, e))?;
+    socket.set_nonblocking(true).map_err(|e| e.to_string())?;
+    Ok(socket.into())
+}
+
+/// Those of `sockets` with a packet waiting, after up to `timeout`
+#[cfg(unix)]
+fn readable<'a>(sockets: &[&'a UdpSocket], timeout: Duration) -> Vec<&'a UdpSocket> {
+    use std::os::fd::AsRawFd;
+    let mut fds: Vec<libc::pollfd> = sockets
+        .iter()
+        .map(|socket| libc::pollfd { fd: socket.as_raw_fd(), events: libc::POLLIN, revents: 0 })
+        .collect();
+    // SAFETY: fds is a valid array of fds.len() pollfds
+    let ready = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout.as_millis() as libc::c_int) };
+    if ready <= 0 {
+        return Vec::new();
+    }
+    sockets
+        .iter()
+        .zip(&fds)
+        .filter(|(_, fd)| fd.revents != 0)
+        .map(|(socket, _)| *socket)
+        .collect()
+}
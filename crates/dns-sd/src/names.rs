@@ -0,0 +1,119 @@
+//! DNS name escaping for instance names (RFC 6763 §4.3)
+//!
+//! Instance names are free-form UTF-8 and may contain dots, backslashes and
+//! spaces. Inside a presentation-format domain name those are escaped the
+//! way `DNSServiceConstructFullName` does: `\.`, `\\` and `\DDD` (decimal)
+//! for control characters and space.
+
+/// Longest instance name a DNS label can carry, in bytes
+pub const MAX_INSTANCE_NAME_BYTES: usize = 63;
+
+/// Check an instance name before it reaches a backend
+///
+/// Any UTF-8 is allowed (emoji, CJK, spaces, dots), but the name has to fit
+/// in one DNS label, so the limit is counted in encoded bytes, not characters.
+pub fn validate_instance_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("Instance name must not be empty".to_string());
+    }
+    if name.contains('\0') {
+        return Err(format!("Instance name {:?} must not contain NUL characters", name));
+    }
+    if name.len() > MAX_INSTANCE_NAME_BYTES {
+        return Err(format!(
+            "Instance name {:?} is {} bytes as UTF-8 ({} characters); the limit is {} bytes",
+            name,
+            name.len(),
+            name.chars().count(),
+            MAX_INSTANCE_NAME_BYTES
+        ));
+    }
+    Ok(())
+}
+
+/// Escape an instance name for use as the first label of a full name
+pub fn escape_instance_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            '.' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            c if (c as u32) <= 0x20 || c as u32 == 0x7f => out.push_str(&format!("\\{:03}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Escaped full name of an instance: `Instance._type._tcp.domain.`
+pub fn full_name(name: &str, service_type: &str, domain: &str) -> String {
+    format!("{}.{}.{}.", escape_instance_name(name), service_type.trim_end_matches('.'), domain.trim_end_matches('.'))
+}
+
+/// Undo [`escape_instance_name`], decoding `\DDD` and `\X` escapes
+///
+/// Decoded bytes that don't form valid UTF-8 are replaced with U+FFFD.
+pub fn unescape_instance_name(escaped: &str) -> String {
+    let bytes = escaped.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' || i + 1 == bytes.len() {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        let digits = &bytes[i + 1..bytes.len().min(i + 4)];
+        if digits.len() == 3 && digits.iter().all(u8::is_ascii_digit) {
+            let value = digits.iter().fold(0u32, |n, d| n * 10 + (d - b'0') as u32);
+            if let Ok(byte) = u8::try_from(value) {
+                out.push(byte);
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i + 1]);
+        i += 2;
+    }
+    String::from_utf8(out).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
+}
+
+/// Split a full name into (instance, type, domain)
+///
+/// Instance names may contain dots, escaped or not (mdns-sd doesn't escape
+/// them), so the split is anchored on the `_name._tcp`/`_name._udp` labels
+/// rather than the first dot. The instance comes back unescaped.
+pub fn split_fullname(fullname: &str) -> Option<(String, String, String)> {
+    let fullname = fullname.strip_suffix('.').unwrap_or(fullname);
+
+    // Label boundaries: dots not preceded by an escaping backslash
+    let mut labels = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in fullname.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '.' => {
+                labels.push(&fullname[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    labels.push(&fullname[start..]);
+
+    let proto = (2..labels.len()).rev().find(|&i| {
+        let is_proto = labels[i].eq_ignore_ascii_case("_tcp") || labels[i].eq_ignore_ascii_case("_udp");
+        is_proto && labels[i - 1].starts_with('_')
+    })?;
+    let instance = labels[..proto - 1].join(".");
+    let service_type = labels[proto - 1..=proto].join(".");
+    let domain = match labels[proto + 1..].join(".") {
+        domain if domain.is_empty() => "local".to_string(),
+        domain => domain,
+    };
+    Some((unescape_instance_name(&instance), service_type, domain))
+}
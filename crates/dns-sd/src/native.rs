@@ -1,16 +1,30 @@
 //! Native DNS-SD backend using libloading to dynamically load dns_sd library
 
+use crate::ServiceInfo;
+use crate::filters::NameList;
+use crate::handles::running;
+use crate::model::Timings;
 use crate::ffi::*;
+use crate::metrics;
+use crate::txt;
+use crate::options::AdvertiseOptions;
+use crate::query::{self, DnssecStatus};
 use libloading::Library;
 use once_cell::sync::OnceCell;
 use std::collections::HashMap;
 use std::ffi::{CStr, CString};
+use std::os::raw::c_ushort;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::os::raw::c_void;
 use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+/// Time the daemon is given to send goodbyes after a registration is
+/// deallocated; the API doesn't report when they went out
+pub const GOODBYE_GRACE: Duration = Duration::from_millis(300);
 
 // ----------------------------------------------------------------
 // Cross-platform compat layer
@@ -24,15 +38,14 @@ mod sys {
 #[cfg(windows)]
 mod sys {
     #![allow(non_camel_case_types)]
-    
-    // We need to define compatible types because libc on Windows
-    // doesn't expose them in the top-level or possibly at all in the same way.
-    // However, for FFI with generic C APIs, we need layouts matching C.
-    
+
+    // libc doesn't expose the winsock types, so they are declared with
+    // layouts matching the Windows SDK headers
+
     pub type sa_family_t = u16;
-    
-    pub const AF_INET: u16 = 2;
-    pub const AF_INET6: u16 = 23;
+
+    pub const AF_INET: i32 = 2;
+    pub const AF_INET6: i32 = 23;
 
     #[repr(C)]
     pub struct in_addr {
@@ -61,47 +74,51 @@ mod sys {
         pub sin6_scope_id: u32,
     }
 
-    // POLL structs
+    /// `WSAPOLLFD`: the daemon connection is a `SOCKET` handle, not a CRT
+    /// file descriptor, so it is polled through winsock
     #[repr(C)]
     #[derive(Debug, Clone, Copy)]
     pub struct pollfd {
-        pub fd: i64, // SOCKET is u64/usize usually, but on Windows 64-bit it is 64-bit
-                     // BUT, dns_sd uses int for FD usually? 
-                     // Wait, DNSServiceRefSockFD returns int.
-                     // On Windows, it might return a SOCKET cast to int?
-                     // Or maybe we treat it as raw handle.
+        pub fd: crate::ffi::dnssd_sock_t,
         pub events: i16,
         pub revents: i16,
     }
 
-    pub const POLLIN: i16 = 0x0100; // 0x0300 is RDNORM | RDBAND usually, but let's check WSAPoll.
-                                    // WSAPoll defaults:
-                                    // POLLIN = 0x0100 | 0x0200 (RDNORM | RDBAND)
-                                    // Actually, standard is:
-                                    // #define POLLIN 0x0300
-    
-    // Let's use standard values compatible with WSAPoll
-    // Win32:
-    // POLLIN = 0x0300
-    // But let's check what libc expects if we were using it.
-    // We will just use what WSAPoll expects.
-    
-    // However, since we can't link ws2_32 easily safely in this simple patch without build.rs changes?
-    // Actually, we can use libloading for WSAPoll too!
-    
+    /// `POLLRDNORM | POLLRDBAND`; WSAPoll rejects the Unix POLLIN value
+    pub const POLLIN: i16 = 0x0100 | 0x0200;
+
     #[link(name = "ws2_32")]
     unsafe extern "system" {
-        fn WSAPoll(fdArray: *mut pollfd, fds: u32, timeout: i32) -> i32;
+        fn WSAPoll(fd_array: *mut pollfd, fds: u32, timeout: i32) -> i32;
     }
 
+    /// `poll` over winsock: SOCKET_ERROR (-1) reads as nothing ready, like
+    /// an interrupted poll on Unix
     pub unsafe fn poll(fds: *mut pollfd, nfds: u64, timeout: i32) -> i32 {
-         unsafe { WSAPoll(fds, nfds as u32, timeout) }
+        unsafe { WSAPoll(fds, nfds as u32, timeout) }
     }
 }
 
+/// Whether `DNSServiceRefSockFD` returned a socket that can be polled
+#[cfg(not(windows))]
+fn valid_sock(sock: dnssd_sock_t) -> bool {
+    sock >= 0
+}
+
+#[cfg(windows)]
+fn valid_sock(sock: dnssd_sock_t) -> bool {
+    sock != DNSSD_INVALID_SOCK
+}
+
 
-/// Global library instance
-static LIBRARY: OnceCell<Result<DnsSdLibrary, String>> = OnceCell::new();
+/// Global library instance, kept for the life of the process once loaded
+static LIBRARY: OnceCell<DnsSdLibrary> = OnceCell::new();
+
+/// Why the last load attempt failed; cleared by [`reprobe`] to try again
+static LOAD_ERROR: Mutex<Option<String>> = Mutex::new(None);
+
+/// Whether the daemon answered when last probed; cleared by [`reprobe`]
+static DAEMON_UP: Mutex<Option<bool>> = Mutex::new(None);
 
 /// Loaded DNS-SD library with function pointers
 pub struct DnsSdLibrary {
@@ -111,6 +128,11 @@ pub struct DnsSdLibrary {
     pub register: FnDNSServiceRegister,
     pub get_addr_info: Option<FnDNSServiceGetAddrInfo>, // Optional: missing on Linux Avahi
     pub query_record: FnDNSServiceQueryRecord,
+    pub enumerate_domains: Option<FnDNSServiceEnumerateDomains>, // Optional: only used to find browse domains
+    pub add_record: Option<FnDNSServiceAddRecord>, // Optional: only used for extra records
+    pub update_record: Option<FnDNSServiceUpdateRecord>, // Optional: only used to set record TTLs
+    pub create_connection: Option<FnDNSServiceCreateConnection>, // Optional: missing on Linux Avahi
+    pub register_record: Option<FnDNSServiceRegisterRecord>, // Optional: missing on Linux Avahi
     pub ref_sock_fd: FnDNSServiceRefSockFD,
     pub process_result: FnDNSServiceProcessResult,
     pub ref_deallocate: FnDNSServiceRefDeallocate,
@@ -150,6 +172,21 @@ impl DnsSdLibrary {
 
             let query_record = *lib.get::<FnDNSServiceQueryRecord>(b"DNSServiceQueryRecord\0")
                 .map_err(|e| format!("DNSServiceQueryRecord: {}", e))?;
+            let enumerate_domains = lib.get::<FnDNSServiceEnumerateDomains>(b"DNSServiceEnumerateDomains\0")
+                .ok()
+                .map(|sym| *sym);
+            let add_record = lib.get::<FnDNSServiceAddRecord>(b"DNSServiceAddRecord\0")
+                .ok()
+                .map(|sym| *sym);
+            let update_record = lib.get::<FnDNSServiceUpdateRecord>(b"DNSServiceUpdateRecord\0")
+                .ok()
+                .map(|sym| *sym);
+            let create_connection = lib.get::<FnDNSServiceCreateConnection>(b"DNSServiceCreateConnection\0")
+                .ok()
+                .map(|sym| *sym);
+            let register_record = lib.get::<FnDNSServiceRegisterRecord>(b"DNSServiceRegisterRecord\0")
+                .ok()
+                .map(|sym| *sym);
 
             let ref_sock_fd = *lib.get::<FnDNSServiceRefSockFD>(b"DNSServiceRefSockFD\0")
                 .map_err(|e| format!("DNSServiceRefSockFD: {}", e))?;
@@ -175,6 +212,11 @@ impl DnsSdLibrary {
                 register,
                 get_addr_info,
                 query_record,
+                enumerate_domains,
+                add_record,
+                update_record,
+                create_connection,
+                register_record,
                 ref_sock_fd,
                 process_result,
                 ref_deallocate,
@@ -188,30 +230,141 @@ impl DnsSdLibrary {
     }
     
     /// Get or initialize the global library instance
+    ///
+    /// A failed load is remembered until [`reprobe`] so detection stays cheap.
     pub fn get() -> Result<&'static DnsSdLibrary, String> {
+        if let Some(lib) = LIBRARY.get() {
+            return Ok(lib);
+        }
+        let mut error = LOAD_ERROR.lock().unwrap();
+        if let Some(e) = error.as_ref() {
+            return Err(e.clone());
+        }
         LIBRARY
-            .get_or_init(|| DnsSdLibrary::load())
-            .as_ref()
-            .map_err(|e| e.clone())
+            .get_or_try_init(|| match DnsSdLibrary::load() {
+                Ok(lib) => {
+                    tracing::info!(
+                        "Loaded {} (DNSServiceGetAddrInfo {})",
+                        get_library_path(),
+                        if lib.get_addr_info.is_some() { "available" } else { "missing" }
+                    );
+                    Ok(lib)
+                }
+                Err(e) => {
+                    tracing::info!("Native backend unavailable: {}", e);
+                    *error = Some(e.clone());
+                    Err(e)
+                }
+            })
     }
 }
 
-/// Check if native backend is available
+/// Check if native backend is available: the library loads and its
+/// daemon answers
 pub fn is_available() -> bool {
-    DnsSdLibrary::get().is_ok()
+    let Ok(lib) = DnsSdLibrary::get() else { return false };
+    *DAEMON_UP.lock().unwrap().get_or_insert_with(|| {
+        let up = lib.daemon_responds();
+        if !up {
+            tracing::info!("Native backend unavailable: the daemon isn't running");
+        }
+        up
+    })
 }
 
-/// Service info from browse/resolve
-#[derive(Debug, Clone)]
-pub struct ServiceInfo {
-    pub name: String,
-    pub service_type: String,
-    pub domain: String,
-    pub host_name: String,
-    pub addresses: Vec<String>,
-    pub port: u16,
-    pub txt: HashMap<String, String>,
-    pub ttl: u32,
+/// Retry loading the library if an earlier attempt failed, e.g. because
+/// Bonjour was installed after the process started, and ask the daemon
+/// again, e.g. because avahi-daemon started after the process
+pub fn reprobe() -> bool {
+    if LIBRARY.get().is_none() {
+        *LOAD_ERROR.lock().unwrap() = None;
+    }
+    *DAEMON_UP.lock().unwrap() = None;
+    is_available()
+}
+
+/// Browse callback of the daemon probe, which never reads its results
+unsafe extern "C" fn ignore_browse(
+    _sd_ref: DNSServiceRef,
+    _flags: DNSServiceFlags,
+    _interface_index: u32_t,
+    _error_code: DNSServiceErrorType,
+    _service_name: *const libc::c_char,
+    _reg_type: *const libc::c_char,
+    _reply_domain: *const libc::c_char,
+    _context: *mut c_void,
+) {
+}
+
+impl DnsSdLibrary {
+    /// Whether the daemon accepts a request
+    ///
+    /// Both Bonjour and Avahi's compatibility library load without their
+    /// daemon, and fail each request (Bonjour with ServiceNotRunning) until
+    /// it is up. `DNSServiceCreateConnection` is the cheapest request;
+    /// Avahi lacks it, so a browse that is closed unread stands in.
+    fn daemon_responds(&self) -> bool {
+        let mut sd_ref: DNSServiceRef = ptr::null_mut();
+        // SAFETY: sd_ref is written only on success, and released below
+        let err = unsafe {
+            match self.create_connection {
+                Some(create_connection) => create_connection(&mut sd_ref),
+                None => (self.browse)(
+                    &mut sd_ref,
+                    0,
+                    0,
+                    c"_services._dns-sd._udp".as_ptr(),
+                    ptr::null(),
+                    Some(ignore_browse),
+                    ptr::null_mut(),
+                ),
+            }
+        };
+        if err == K_DNS_SERVICE_ERR_NO_ERROR && !sd_ref.is_null() {
+            // SAFETY: sd_ref came from a successful call above
+            unsafe { (self.ref_deallocate)(sd_ref) };
+        }
+        err == K_DNS_SERVICE_ERR_NO_ERROR
+    }
+}
+
+/// Timing knobs for the per-service resolve step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ResolveConfig {
+    /// How long to wait for DNSServiceResolve to report the host
+    pub resolve_timeout: Duration,
+    /// How long to collect addresses after the host is known
+    pub address_timeout: Duration,
+    /// Address families to look up (kDNSServiceProtocol_*, 0 = both)
+    pub protocol: u32,
+    /// Extra flags for the resolve and address calls (e.g. kDNSServiceFlagsIncludeP2P)
+    pub flags: u32,
+    /// Ask for DNSSEC validation of the address lookups
+    pub validate: bool,
+    /// Record per-phase timings of each resolve
+    pub profile: bool,
+    /// Further attempts at an unanswered resolve or address lookup
+    pub retries: u32,
+    /// Wait before the first retry, doubling for each one after
+    pub retry_delay: Duration,
+    /// Keep browsed services' address lookups open until they are lost
+    pub watch_addresses: bool,
+}
+
+impl Default for ResolveConfig {
+    fn default() -> Self {
+        ResolveConfig {
+            resolve_timeout: Duration::from_millis(3000),
+            address_timeout: Duration::from_millis(2000),
+            protocol: 0,
+            flags: 0,
+            validate: false,
+            profile: false,
+            retries: 2,
+            retry_delay: Duration::from_millis(1000),
+            watch_addresses: false,
+        }
+    }
 }
 
 /// Shared callback type for thread-safe access
@@ -220,6 +373,17 @@ type SharedCallback = Arc<dyn Fn(&str, ServiceInfo) + Send + Sync + 'static>;
 /// Context passed to browse callback
 struct BrowseContext {
     callback: SharedCallback,
+    config: ResolveConfig,
+    /// Resolve results before reporting them (false for type enumeration
+    /// and browse-only browsers)
+    resolve: bool,
+    /// Set when the browser stops, cancelling its in-flight resolves
+    cancel: Arc<AtomicBool>,
+    /// Instances neither resolved nor reported
+    names: NameList,
+    /// Per browsed instance and interface, set when it is lost, for
+    /// resolves watching its addresses
+    watches: Mutex<HashMap<String, Arc<AtomicBool>>>,
 }
 
 /// Browse callback - spawns resolve thread for each service
@@ -235,349 +399,961 @@ unsafe extern "C" fn browse_callback(
 ) {
     unsafe {
         if error_code != K_DNS_SERVICE_ERR_NO_ERROR {
+            let message = format!("Browse callback reported error {}", error_code);
+            metrics::backend_error(&message);
+            tracing::warn!("{}", message);
             return;
         }
 
         let ctx = &*(context as *const BrowseContext);
         
+        // Bonjour and Avahi deliver the name already unescaped, as the fallback does
         let name = CStr::from_ptr(service_name).to_string_lossy().into_owned();
         let service_type = CStr::from_ptr(reg_type).to_string_lossy().into_owned();
         let domain = CStr::from_ptr(reply_domain).to_string_lossy().into_owned();
 
         let is_add = (flags & K_DNS_SERVICE_FLAGS_ADD) != 0;
+        let more_coming = (flags & K_DNS_SERVICE_FLAGS_MORE_COMING) != 0;
+        let watch_key = format!("{}|{}|{}|{}", name, service_type, domain, interface_index);
+
+        // Identity for the batch marker, emitted after this event
+        let batch_info = ServiceInfo {
+            service_type: service_type.clone(),
+            domain: domain.clone(),
+            flags: Some(flags),
+            ..Default::default()
+        };
 
-        if is_add {
+        if !ctx.names.allows(&name) {
+            tracing::trace!("Skipping {:?}: left out by ignoreNames/onlyNames", name);
+        } else if is_add && !ctx.resolve {
+            let info = ServiceInfo {
+                name,
+                service_type,
+                domain,
+                interface_index,
+                flags: Some(flags),
+                ..Default::default()
+            };
+            (ctx.callback)("serviceFound", info);
+        } else if let Some(mut cached) = (is_add && !ctx.config.watch_addresses)
+            .then(|| cached(&name, &service_type, &domain, interface_index))
+            .flatten()
+        {
+            metrics::resolve_cached();
+            cached.flags = Some(flags);
+            (ctx.callback)("serviceFound", cached);
+        } else if is_add {
             // Spawn thread for async resolve
-            let callback = ctx.callback.clone();
-            thread::spawn(move || {
-                resolve_service_full(interface_index, &name, &service_type, &domain, callback);
-            });
+            let resolver = Resolver::new(
+                interface_index,
+                &name,
+                &service_type,
+                &domain,
+                ctx.config,
+                ctx.cancel.clone(),
+                ctx.callback.clone(),
+            );
+            match resolver {
+                Ok(mut resolver) => {
+                    // Until the resolve answers, events carry the browse flags
+                    resolver.ctx.info.lock().unwrap().flags = Some(flags);
+                    if ctx.config.watch_addresses {
+                        let lost = Arc::new(AtomicBool::new(false));
+                        // A repeated add replaces the previous watch
+                        if let Some(previous) = ctx.watches.lock().unwrap().insert(watch_key, lost.clone()) {
+                            previous.store(true, Ordering::SeqCst);
+                        }
+                        resolver.watch(lost);
+                    }
+                    thread::spawn(move || resolver.run());
+                }
+                Err(e) => {
+                    tracing::warn!("Cannot resolve {}: {}", name, e);
+                    let info = ServiceInfo {
+                        name,
+                        service_type,
+                        domain,
+                        interface_index,
+                        flags: Some(flags),
+                        reason: Some(e),
+                        ..Default::default()
+                    };
+                    (ctx.callback)("resolveFailed", info);
+                }
+            }
         } else {
+            if let Some(lost) = ctx.watches.lock().unwrap().remove(&watch_key) {
+                lost.store(true, Ordering::SeqCst);
+            }
             // serviceLost - emit immediately
             let info = ServiceInfo {
                 name,
                 service_type,
                 domain,
-                host_name: String::new(),
-                addresses: vec![],
-                port: 0,
-                txt: HashMap::new(),
-                ttl: 0,
+                interface_index,
+                flags: Some(flags),
+                ..Default::default()
             };
             (ctx.callback)("serviceLost", info);
         }
+
+        // MoreComing cleared: the daemon has delivered everything it has for now.
+        // Avahi's compat layer never sets the flag, so there every event ends a batch.
+        if !more_coming {
+            (ctx.callback)("batchComplete", batch_info);
+        }
     }
 }
 
 
-/// Shared state for resolution process
-struct ResolveState {
-    info: ServiceInfo,
+/// A fresh cached result for a browsed instance, saving its resolve
+fn cached(name: &str, service_type: &str, domain: &str, interface_index: u32) -> Option<ServiceInfo> {
+    let info = ServiceInfo {
+        name: name.to_string(),
+        service_type: service_type.to_string(),
+        domain: domain.to_string(),
+        ..Default::default()
+    };
+    crate::cache::lookup(&info.normalize().full_name, interface_index)
 }
 
-/// Fully resolve a service - gets hostname, port, TXT, and IP addresses
-fn resolve_service_full(
-    interface_index: u32_t,
-    name: &str,
-    service_type: &str,
-    domain: &str,
+/// State reachable from the C callbacks of one [`Resolver`]
+///
+/// Boxed by the resolver so its address stays fixed for as long as any of
+/// the resolver's refs can call back into it.
+struct ResolverContext {
+    info: Mutex<ServiceInfo>,
+    /// Escaped full name reported by DNSServiceResolve, for the SRV query
+    fullname: Mutex<Option<CString>>,
     callback: SharedCallback,
-) {
-    let lib = match DnsSdLibrary::get() {
-        Ok(lib) => lib,
-        Err(_) => return,
-    };
+    cancel: Arc<AtomicBool>,
+    /// Address lookups the daemon reported kDNSServiceErr_Timeout for
+    lookups_timed_out: AtomicUsize,
+    /// Addresses changed since the last report, held while the daemon
+    /// flags more answers as coming
+    addresses_changed: AtomicBool,
+}
 
-    let name_c = match CString::new(name) {
-        Ok(s) => s,
-        Err(_) => return,
-    };
-    let type_c = match CString::new(service_type) {
-        Ok(s) => s,
-        Err(_) => return,
-    };
-    let domain_c = match CString::new(domain) {
-        Ok(s) => s,
-        Err(_) => return,
-    };
+impl ResolverContext {
+    /// Report progress unless the resolve was cancelled
+    fn emit(&self, info: ServiceInfo) {
+        if !self.cancel.load(Ordering::SeqCst) {
+            (self.callback)("serviceFound", info);
+        }
+    }
 
-    // Shared state
-    let state = Arc::new(Mutex::new(ResolveState {
-        info: ServiceInfo {
-            name: name.to_string(),
-            service_type: service_type.to_string(),
-            domain: domain.to_string(),
-            host_name: String::new(),
-            addresses: vec![],
-            port: 0,
-            txt: HashMap::new(),
-            ttl: 0,
-        },
-    }));
-    let state_resolve = state.clone();
-
-    // Step 1: DNSServiceResolve to get hostname, port, TXT
-    unsafe extern "C" fn resolve_cb(
-        _sd_ref: DNSServiceRef,
-        _flags: DNSServiceFlags,
-        _interface_index: u32_t,
-        error_code: DNSServiceErrorType,
-        _fullname: *const libc::c_char,
-        hosttarget: *const libc::c_char,
-        port: libc::c_ushort,
-        txt_len: libc::c_ushort,
-        txt_record: *const libc::c_char,
-        context: *mut c_void,
-    ) {
-        if error_code != K_DNS_SERVICE_ERR_NO_ERROR {
-            return;
+    /// Apply an address answer: added, or withdrawn when the daemon clears
+    /// kDNSServiceFlagsAdd (the record expired or was said goodbye to)
+    ///
+    /// A host with many addresses answers in a burst flagged
+    /// kDNSServiceFlagsMoreComing; the service is reported once, after the
+    /// last of them, instead of once per address.
+    fn address_answer(&self, ip: IpAddr, ttl: u32, flags: DNSServiceFlags) {
+        let changed = if (flags & K_DNS_SERVICE_FLAGS_ADD) != 0 {
+            self.add_address(ip, ttl, flags)
+        } else {
+            self.remove_address(ip, flags)
+        };
+        if changed {
+            self.addresses_changed.store(true, Ordering::SeqCst);
         }
+        if (flags & K_DNS_SERVICE_FLAGS_MORE_COMING) == 0 && self.addresses_changed.swap(false, Ordering::SeqCst) {
+            let snapshot = self.info.lock().unwrap().clone();
+            self.emit(snapshot);
+        }
+    }
 
-        let (mut state, callback) = unsafe {
-             let ctx = &*(context as *const (Arc<Mutex<ResolveState>>, SharedCallback));
-             (ctx.0.lock().unwrap(), ctx.1.clone())
+    /// Drop a withdrawn address, returning whether the service had it
+    fn remove_address(&self, ip: IpAddr, flags: DNSServiceFlags) -> bool {
+        let mut info = self.info.lock().unwrap();
+        let ip = ip.to_string();
+        let Some(index) = info.addresses.iter().position(|addr| *addr == ip) else {
+            return false;
         };
-        
-        unsafe {
-            state.info.host_name = CStr::from_ptr(hosttarget).to_string_lossy().into_owned();
-            state.info.port = u16::from_be(port);
-            state.info.txt = parse_txt_record(txt_record as *const u8, txt_len as usize);
+        info.addresses.remove(index);
+        info.flags = Some(flags);
+        true
+    }
+
+    /// Record an address, returning whether the service needs reporting
+    /// again: the address is new, or its DNSSEC result arrived
+    fn add_address(&self, ip: IpAddr, ttl: u32, flags: DNSServiceFlags) -> bool {
+        let mut info = self.info.lock().unwrap();
+        info.flags = Some(flags);
+        // Only update TTL if we have a valid one
+        if ttl > 0 {
+            info.ttl = ttl;
         }
+        // Validated answers repeat the address with its DNSSEC result
+        let validated = match DnssecStatus::from_flags(flags) {
+            Some(status) if info.dnssec != Some(status) => {
+                info.dnssec = Some(status);
+                true
+            }
+            _ => false,
+        };
+        let ip = ip.to_string();
+        let added = !info.addresses.contains(&ip);
+        if added {
+            info.addresses.push(ip);
+            if let Some(timings) = info.timings.as_mut()
+                && timings.address.is_none()
+            {
+                timings.address = timings.end_phase();
+            }
+        }
+        added || validated
+    }
+}
 
-        // Emit partial result
-        callback("serviceFound", state.info.clone());
+unsafe extern "C" fn resolve_cb(
+    _sd_ref: DNSServiceRef,
+    flags: DNSServiceFlags,
+    _interface_index: u32_t,
+    error_code: DNSServiceErrorType,
+    fullname: *const libc::c_char,
+    hosttarget: *const libc::c_char,
+    port: libc::c_ushort,
+    txt_len: libc::c_ushort,
+    txt_record: *const libc::c_char,
+    context: *mut c_void,
+) {
+    if error_code != K_DNS_SERVICE_ERR_NO_ERROR {
+        return;
     }
+    let ctx = unsafe { &*(context as *const ResolverContext) };
+    let mut info = ctx.info.lock().unwrap();
 
-    let mut resolve_ref: DNSServiceRef = ptr::null_mut();
-    // Bundle context
-    let resolve_ctx = (state.clone(), callback.clone());
-    
-    let err = unsafe {
-        (lib.resolve)(
-            &mut resolve_ref,
-            0,
-            interface_index,
-            name_c.as_ptr(),
-            type_c.as_ptr(),
-            domain_c.as_ptr(),
-            Some(resolve_cb),
-            &resolve_ctx as *const _ as *mut c_void,
-        )
-    };
+    unsafe {
+        if !fullname.is_null() {
+            *ctx.fullname.lock().unwrap() = Some(CStr::from_ptr(fullname).to_owned());
+        }
+        info.host_name = CStr::from_ptr(hosttarget).to_string_lossy().into_owned();
+        if let Some(timings) = info.timings.as_mut()
+            && timings.resolve.is_none()
+        {
+            timings.resolve = timings.end_phase();
+        }
+        info.flags = Some(flags);
+        info.port = u16::from_be(port);
+        let txt = if txt_record.is_null() {
+            txt::ParsedTxt::default()
+        } else {
+            txt::parse(std::slice::from_raw_parts(txt_record as *const u8, txt_len as usize))
+        };
+        for error in &txt.errors {
+            tracing::warn!("Malformed TXT record for {}: {}", info.name, error);
+        }
+        info.txt = txt.entries;
+        info.txt_errors = txt.errors;
+    }
+
+    // Emit partial result
+    let snapshot = info.clone();
+    drop(info);
+    ctx.emit(snapshot);
+}
 
-    if err != K_DNS_SERVICE_ERR_NO_ERROR || resolve_ref.is_null() {
+unsafe extern "C" fn addr_cb(
+    _sd_ref: DNSServiceRef,
+    flags: DNSServiceFlags,
+    _interface_index: u32_t,
+    error_code: DNSServiceErrorType,
+    _hostname: *const libc::c_char,
+    address: *const libc::sockaddr,
+    ttl: u32_t,
+    context: *mut c_void,
+) {
+    let ctx = unsafe { &*(context as *const ResolverContext) };
+    if error_code == K_DNS_SERVICE_ERR_TIMEOUT {
+        ctx.lookups_timed_out.fetch_add(1, Ordering::SeqCst);
+        return;
+    }
+    if error_code != K_DNS_SERVICE_ERR_NO_ERROR || address.is_null() {
         return;
     }
 
-    // Poll until we get hostname (short timeout)
-    poll_service_loop(lib, resolve_ref, 3000, || {
-        let s = state_resolve.lock().unwrap();
-        !s.info.host_name.is_empty()
-    });
-    
     unsafe {
-        (lib.ref_deallocate)(resolve_ref);
+        #[allow(clippy::unnecessary_cast)] // sa_family_t is u8 on BSD/macOS
+        let sa_family = (*address).sa_family as u16;
+        if sa_family == sys::AF_INET as u16 {
+            let addr4 = address as *const sys::sockaddr_in;
+            let ip_bytes = (*addr4).sin_addr.s_addr.to_ne_bytes();
+            ctx.address_answer(IpAddr::V4(Ipv4Addr::from(ip_bytes)), ttl, flags);
+        } else if sa_family == sys::AF_INET6 as u16 {
+            let addr6 = address as *const sys::sockaddr_in6;
+            ctx.address_answer(IpAddr::V6(Ipv6Addr::from((*addr6).sin6_addr.s6_addr)), ttl, flags);
+        }
     }
+}
 
-    // Check if we got host
-    let current_info = {
-         let s = state.lock().unwrap();
-         if s.info.host_name.is_empty() {
-             return; // Failed to resolve host
-         }
-         s.info.clone()
-    };
+/// A/AAAA answers, for Avahi's compat layer which lacks DNSServiceGetAddrInfo
+unsafe extern "C" fn address_record_cb(
+    _sd_ref: DNSServiceRef,
+    flags: DNSServiceFlags,
+    _interface_index: u32_t,
+    error_code: DNSServiceErrorType,
+    _fullname: *const libc::c_char,
+    rrtype: u16,
+    _rrclass: u16,
+    rdlen: u16,
+    rdata: *const c_void,
+    ttl: u32_t,
+    context: *mut c_void,
+) {
+    let ctx = unsafe { &*(context as *const ResolverContext) };
+    if error_code == K_DNS_SERVICE_ERR_TIMEOUT {
+        ctx.lookups_timed_out.fetch_add(1, Ordering::SeqCst);
+        return;
+    }
+    if error_code != K_DNS_SERVICE_ERR_NO_ERROR || rdata.is_null() {
+        return;
+    }
 
-    // Step 2: Resolve IPs
-    // Try DNSServiceGetAddrInfo first (standard DNS-SD way)
-    if let Some(get_addr_info) = lib.get_addr_info {
-        let host_c = match CString::new(current_info.host_name.as_str()) {
-            Ok(s) => s,
-            Err(_) => return,
-        };
+    if rrtype == K_DNS_SERVICE_TYPE_A && rdlen == 4 {
+        let ip_bytes: &[u8; 4] = unsafe { &*(rdata as *const [u8; 4]) };
+        ctx.address_answer(IpAddr::V4(Ipv4Addr::from(*ip_bytes)), ttl, flags);
+    } else if rrtype == K_DNS_SERVICE_TYPE_AAAA && rdlen == 16 {
+        let ip_bytes: &[u8; 16] = unsafe { &*(rdata as *const [u8; 16]) };
+        ctx.address_answer(IpAddr::V6(Ipv6Addr::from(*ip_bytes)), ttl, flags);
+    }
+}
+
+/// SRV answer, for the priority and weight DNSServiceResolve leaves out
+unsafe extern "C" fn srv_record_cb(
+    _sd_ref: DNSServiceRef,
+    _flags: DNSServiceFlags,
+    _interface_index: u32_t,
+    error_code: DNSServiceErrorType,
+    _fullname: *const libc::c_char,
+    rrtype: u16,
+    _rrclass: u16,
+    rdlen: u16,
+    rdata: *const c_void,
+    _ttl: u32_t,
+    context: *mut c_void,
+) {
+    if error_code != K_DNS_SERVICE_ERR_NO_ERROR || rdata.is_null() || rrtype != K_DNS_SERVICE_TYPE_SRV || rdlen < 6 {
+        return;
+    }
+    let ctx = unsafe { &*(context as *const ResolverContext) };
+    let rdata = unsafe { std::slice::from_raw_parts(rdata as *const u8, rdlen as usize) };
+    let priority = u16::from_be_bytes([rdata[0], rdata[1]]);
+    let weight = u16::from_be_bytes([rdata[2], rdata[3]]);
+
+    let mut info = ctx.info.lock().unwrap();
+    if (info.priority, info.weight) != (priority, weight) {
+        info.priority = priority;
+        info.weight = weight;
+        let snapshot = info.clone();
+        drop(info);
+        ctx.emit(snapshot);
+    }
+}
 
+/// Full resolution of one service instance: SRV/TXT, then addresses
+///
+/// Owns the callback context and every DNSServiceRef it opens; refs are
+/// deallocated before the context is freed, on completion or on drop.
+/// Progress is reported as `serviceFound` with the fields known so far.
+/// Setting the `cancel` flag given to [`Resolver::new`] (from any thread)
+/// stops reporting and ends [`Resolver::run`] within one poll interval; a
+/// browser shares one flag among all of its resolves.
+pub struct Resolver {
+    lib: &'static DnsSdLibrary,
+    interface_index: u32,
+    /// Instance name as DNSServiceBrowse delivered it
+    name: CString,
+    service_type: CString,
+    domain: CString,
+    config: ResolveConfig,
+    ctx: Box<ResolverContext>,
+    refs: Vec<DNSServiceRef>,
+    /// Set once the instance is lost, for a resolve that keeps watching its addresses
+    lost: Option<Arc<AtomicBool>>,
+}
 
+unsafe impl Send for Resolver {}
 
-        unsafe extern "C" fn addr_cb(
-            _sd_ref: DNSServiceRef,
-            _flags: DNSServiceFlags,
-            _interface_index: u32_t,
-            error_code: DNSServiceErrorType,
-            _hostname: *const libc::c_char,
-            address: *const libc::sockaddr,
-            ttl: u32_t,
-            context: *mut c_void,
-        ) {
-            if error_code != K_DNS_SERVICE_ERR_NO_ERROR || address.is_null() {
-                 return;
+impl Resolver {
+    pub fn new(
+        interface_index: u32,
+        name: &str,
+        service_type: &str,
+        domain: &str,
+        config: ResolveConfig,
+        cancel: Arc<AtomicBool>,
+        callback: SharedCallback,
+    ) -> Result<Self, String> {
+        let lib = DnsSdLibrary::get()?;
+        let ctx = Box::new(ResolverContext {
+            info: Mutex::new(ServiceInfo {
+                name: name.to_string(),
+                service_type: service_type.to_string(),
+                domain: domain.to_string(),
+                interface_index,
+                // Queued from here until the resolve thread starts
+                timings: config.profile.then(Timings::start),
+                ..Default::default()
+            }),
+            fullname: Mutex::new(None),
+            callback,
+            cancel,
+            lookups_timed_out: AtomicUsize::new(0),
+            addresses_changed: AtomicBool::new(false),
+        });
+        Ok(Resolver {
+            lib,
+            interface_index,
+            name: CString::new(name).map_err(|e| e.to_string())?,
+            service_type: CString::new(service_type).map_err(|e| e.to_string())?,
+            domain: CString::new(domain).map_err(|e| e.to_string())?,
+            config,
+            ctx,
+            refs: Vec::new(),
+            lost: None,
+        })
+    }
+
+    /// Keep watching the addresses after resolving, until `lost` is set
+    pub fn watch(&mut self, lost: Arc<AtomicBool>) {
+        self.lost = Some(lost);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.ctx.cancel.load(Ordering::SeqCst)
+    }
+
+    /// Resolve to completion, returning the final result (None on timeout or cancel)
+    ///
+    /// An unanswered step is reported as `resolveTimeout` and retried up to
+    /// `config.retries` times, waiting `config.retry_delay` before the first
+    /// retry and twice as long before each one after. If it still goes
+    /// unanswered, or the daemon refuses it, `resolveFailed` is reported
+    /// with what is known of the instance and the reason.
+    pub fn run(&mut self) -> Option<ServiceInfo> {
+        metrics::resolve_started();
+        let started = Instant::now();
+        if let Some(timings) = self.ctx.info.lock().unwrap().timings.as_mut() {
+            timings.queued = timings.end_phase();
+        }
+
+        // Step 1: DNSServiceResolve for hostname, port and TXT
+        if let Err(reason) = self.with_retries("DNSServiceResolve", self.config.resolve_timeout, Self::resolve_host) {
+            if self.is_cancelled() {
+                return None;
             }
+            metrics::resolve_timed_out();
+            tracing::debug!("Resolve of {:?}.{:?} gave up: {}", self.name, self.service_type, reason);
+            self.report("resolveFailed", reason);
+            return None;
+        }
+        metrics::resolve_succeeded(started.elapsed());
 
-            let (mut state, callback) = unsafe {
-                 let ctx = &*(context as *const (Arc<Mutex<ResolveState>>, SharedCallback));
-                 (ctx.0.lock().unwrap(), ctx.1.clone())
-            };
-            
-            state.info.ttl = ttl;
+        // Step 2: the SRV record again for its priority and weight (answered from
+        // the daemon's cache), and addresses
+        if let Some(timings) = self.ctx.info.lock().unwrap().timings.as_mut() {
+            timings.phase_started = Some(Instant::now());
+        }
+        let result = self.with_retries("the address lookup", self.config.address_timeout, Self::lookup_addresses);
+        if self.is_cancelled() {
+            return None;
+        }
+        if let Err(reason) = result {
+            tracing::debug!("No address for {:?}: {}", self.name, reason);
+            self.report("resolveFailed", reason);
+        }
 
-            unsafe {
-                let sa_family = (*address).sa_family;
-                let mut ip_str = String::new();
-
-                if u16::from(sa_family) == sys::AF_INET as u16 {
-                    let addr4 = address as *const sys::sockaddr_in;
-                    let ip_bytes = (*addr4).sin_addr.s_addr.to_ne_bytes();
-                    let ip = Ipv4Addr::new(ip_bytes[0], ip_bytes[1], ip_bytes[2], ip_bytes[3]);
-                    ip_str = IpAddr::V4(ip).to_string();
-                } else if u16::from(sa_family) == sys::AF_INET6 as u16 {
-                    let addr6 = address as *const sys::sockaddr_in6;
-                    let ip_bytes = (*addr6).sin6_addr.s6_addr;
-                    let ip = Ipv6Addr::from(ip_bytes);
-                    ip_str = IpAddr::V6(ip).to_string();
-                }
+        // Step 3, for browses with `watchAddresses`: follow address changes
+        // (DHCP renewals, interface switches) until the instance is lost
+        if let Some(lost) = self.lost.clone() {
+            self.watch_addresses(&lost);
+            if self.is_cancelled() {
+                return None;
+            }
+        }
+        Some(self.ctx.info.lock().unwrap().clone())
+    }
 
-                if !ip_str.is_empty() && !state.info.addresses.contains(&ip_str) {
-                    state.info.addresses.push(ip_str);
-                    // Emit update for each new address
-                    callback("serviceFound", state.info.clone());
+    /// Run `step` until it is answered, retrying with exponential backoff
+    ///
+    /// Each unanswered attempt is reported as `resolveTimeout`. Errs with
+    /// the reason for giving up: an error from `step`, every attempt going
+    /// unanswered, or cancellation.
+    fn with_retries(&mut self, what: &str, window: Duration, step: fn(&mut Self) -> Result<bool, String>) -> Result<(), String> {
+        let attempts = self.config.retries + 1;
+        let mut delay = self.config.retry_delay;
+        for attempt in 1..=attempts {
+            if attempt > 1 {
+                tracing::debug!("Retrying {} of {:?} in {:?} (attempt {})", what, self.name, delay, attempt);
+                let deadline = Instant::now() + delay;
+                // Sleep in 100ms chunks to notice cancellation
+                while !self.is_cancelled() && Instant::now() < deadline {
+                    thread::sleep(deadline.saturating_duration_since(Instant::now()).min(Duration::from_millis(100)));
                 }
+                delay = delay.saturating_mul(2);
+            }
+            if self.is_cancelled() {
+                return Err("Cancelled".to_string());
+            }
+            if step(self)? {
+                return Ok(());
+            }
+            if !self.is_cancelled() {
+                let reason = format!("No answer to {} within {}ms (attempt {} of {})", what, window.as_millis(), attempt, attempts);
+                self.report("resolveTimeout", reason);
             }
         }
+        Err(format!("No answer to {} after {} attempts", what, attempts))
+    }
 
-        let mut addr_ref: DNSServiceRef = ptr::null_mut();
-        // Bundle context
-        let addr_ctx = (state.clone(), callback.clone());
+    /// One DNSServiceResolve attempt; true once the host is known
+    fn resolve_host(&mut self) -> Result<bool, String> {
+        let (lib, config, interface_index) = (self.lib, self.config, self.interface_index);
+        let (name, service_type, domain) = (self.name.as_ptr(), self.service_type.as_ptr(), self.domain.as_ptr());
+        let err = self.open(|sd_ref, ctx| unsafe {
+            (lib.resolve)(sd_ref, config.flags, interface_index, name, service_type, domain, Some(resolve_cb), ctx)
+        });
+        if err != K_DNS_SERVICE_ERR_NO_ERROR {
+            let message = format!("DNSServiceResolve failed for {:?}: error {}", self.name, err);
+            metrics::backend_error(&message);
+            tracing::warn!("{}", message);
+            return Err(format!("DNSServiceResolve failed: error {}", err));
+        }
+        self.poll(config.resolve_timeout, |info| !info.host_name.is_empty());
+        self.close_all();
+        Ok(!self.ctx.info.lock().unwrap().host_name.is_empty())
+    }
 
-        let err = unsafe {
-            (get_addr_info)(
-                &mut addr_ref,
-                0, // flags
-                interface_index,
-                0, // any protocol
-                host_c.as_ptr(),
-                Some(addr_cb),
-                &addr_ctx as *const _ as *mut c_void,
-            )
+    /// One round of SRV and address lookups, via DNSServiceGetAddrInfo
+    /// where the library has it; true once an address arrived
+    fn lookup_addresses(&mut self) -> Result<bool, String> {
+        let (lib, config, interface_index) = (self.lib, self.config, self.interface_index);
+        let fullname = self.ctx.fullname.lock().unwrap().clone();
+        if let Some(fullname) = &fullname {
+            let fullname = fullname.as_ptr();
+            self.open(|sd_ref, ctx| unsafe {
+                (lib.query_record)(sd_ref, config.flags, interface_index, fullname, K_DNS_SERVICE_TYPE_SRV, 1, Some(srv_record_cb), ctx)
+            });
+        }
+        self.ctx.lookups_timed_out.store(0, Ordering::SeqCst);
+        let lookups = self.open_address_lookups(true)?;
+        // Collect addresses for the whole window, as more may trickle in,
+        // unless the daemon gave up on every lookup first
+        if !self.refs.is_empty() {
+            let ctx = &self.ctx;
+            self.poll(config.address_timeout, |_| lookups > 0 && ctx.lookups_timed_out.load(Ordering::SeqCst) >= lookups);
+        }
+        self.close_all();
+        let info = self.ctx.info.lock().unwrap();
+        if info.addresses.is_empty() && lookups > 0 && self.ctx.lookups_timed_out.load(Ordering::SeqCst) >= lookups {
+            tracing::info!("Address lookup for {} timed out without an answer", info.host_name);
+        }
+        Ok(!info.addresses.is_empty())
+    }
+
+    /// Start the address lookups for the resolved host, via
+    /// DNSServiceGetAddrInfo where the library has it, returning how many
+    /// opened
+    ///
+    /// With `timeout_flag` the daemon reports when it gives up on them.
+    fn open_address_lookups(&mut self, timeout_flag: bool) -> Result<usize, String> {
+        let (lib, config, interface_index) = (self.lib, self.config, self.interface_index);
+        let host = self.ctx.info.lock().unwrap().host_name.clone();
+        let host_c = CString::new(host).map_err(|e| format!("Invalid host name: {}", e))?;
+        let host = host_c.as_ptr();
+        let address_flags = match config.validate {
+            true => config.flags | K_DNS_SERVICE_FLAGS_VALIDATE,
+            false => config.flags,
         };
+        let start = |flags: DNSServiceFlags, start: &mut dyn FnMut(DNSServiceFlags) -> DNSServiceErrorType| match timeout_flag {
+            true => with_timeout_flag(flags, start),
+            false => start(flags),
+        };
+        let opened = self.refs.len();
+        if let Some(get_addr_info) = lib.get_addr_info {
+            self.open(|sd_ref, ctx| {
+                start(address_flags, &mut |flags| unsafe {
+                    get_addr_info(sd_ref, flags, interface_index, config.protocol, host, Some(addr_cb), ctx)
+                })
+            });
+        } else {
+            let want_a = config.protocol != K_DNS_SERVICE_PROTOCOL_IPV6;
+            let want_aaaa = config.protocol != K_DNS_SERVICE_PROTOCOL_IPV4;
+            for (wanted, rrtype) in [(want_a, K_DNS_SERVICE_TYPE_A), (want_aaaa, K_DNS_SERVICE_TYPE_AAAA)] {
+                if wanted {
+                    self.open(|sd_ref, ctx| {
+                        start(address_flags, &mut |flags| unsafe {
+                            // 1 = kDNSServiceClass_IN
+                            (lib.query_record)(sd_ref, flags, interface_index, host, rrtype, 1, Some(address_record_cb), ctx)
+                        })
+                    });
+                }
+            }
+        }
+        Ok(self.refs.len() - opened)
+    }
 
-        if err == K_DNS_SERVICE_ERR_NO_ERROR && !addr_ref.is_null() {
-            let timeout = 2000;
-            // Simply poll for a while to collect addresses
-            poll_service_loop(lib, addr_ref, timeout, || false);
+    /// Keep the address lookups open until `lost` is set or the resolve is
+    /// cancelled, reporting the service again whenever its addresses change
+    fn watch_addresses(&mut self, lost: &AtomicBool) {
+        // Without the Timeout flag, so the daemon keeps them running
+        match self.open_address_lookups(false) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        while !self.is_cancelled() && !lost.load(Ordering::SeqCst) {
+            self.poll(Duration::from_secs(1), |_| lost.load(Ordering::SeqCst));
+        }
+        self.close_all();
+    }
 
-            unsafe {
-                (lib.ref_deallocate)(addr_ref);
-            }
+    /// Report the instance as known so far with `reason`, unless cancelled
+    fn report(&self, event: &str, reason: String) {
+        if !self.is_cancelled() {
+            let info = ServiceInfo { reason: Some(reason), ..self.ctx.info.lock().unwrap().clone() };
+            (self.ctx.callback)(event, info);
         }
-    } else {
-        // Fallback: Use DNSServiceQueryRecord for A and AAAA records (Avahi Compat)
-        
-        let host_c = match CString::new(current_info.host_name.as_str()) {
-             Ok(s) => s,
-             Err(_) => return,
-        };
+    }
 
-        unsafe extern "C" fn query_cb(
-            _sd_ref: DNSServiceRef,
-            _flags: DNSServiceFlags,
-            _interface_index: u32_t,
-            error_code: DNSServiceErrorType,
-            _fullname: *const libc::c_char,
-            rrtype: u16,
-            _rrclass: u16,
-            rdlen: u16,
-            rdata: *const c_void,
-            ttl: u32_t,
-            context: *mut c_void,
-        ) {
-            if error_code != K_DNS_SERVICE_ERR_NO_ERROR || rdata.is_null() {
-                return;
-            }
+    /// Start an operation with this resolver's context, keeping its ref
+    fn open<F>(&mut self, start: F) -> DNSServiceErrorType
+    where
+        F: FnOnce(*mut DNSServiceRef, *mut c_void) -> DNSServiceErrorType,
+    {
+        let mut sd_ref: DNSServiceRef = ptr::null_mut();
+        let ctx = &*self.ctx as *const ResolverContext as *mut c_void;
+        let err = start(&mut sd_ref, ctx);
+        if err == K_DNS_SERVICE_ERR_NO_ERROR && sd_ref.is_null() {
+            return K_DNS_SERVICE_ERR_UNKNOWN;
+        }
+        if err == K_DNS_SERVICE_ERR_NO_ERROR {
+            self.refs.push(sd_ref);
+        }
+        err
+    }
 
-            let (mut state, callback) = unsafe {
-                 let ctx = &*(context as *const (Arc<Mutex<ResolveState>>, SharedCallback));
-                 (ctx.0.lock().unwrap(), ctx.1.clone())
-            };
-            
-            // Only update TTL if we have a valid one (take the larger one or just latest)
-            if ttl > 0 {
-                state.info.ttl = ttl;
+    /// Process results on all open refs until `done`, cancel or `timeout`
+    fn poll<F>(&self, timeout: Duration, done: F)
+    where
+        F: Fn(&ServiceInfo) -> bool,
+    {
+        let deadline = Instant::now() + timeout;
+        while !self.is_cancelled() && !done(&self.ctx.info.lock().unwrap()) {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                break;
             }
-
-            let mut ip_str = String::new();
-            if rrtype == K_DNS_SERVICE_TYPE_A && rdlen == 4 {
-                let ip_bytes: &[u8; 4] = unsafe { &*(rdata as *const [u8; 4]) };
-                let ip = Ipv4Addr::new(ip_bytes[0], ip_bytes[1], ip_bytes[2], ip_bytes[3]);
-                ip_str = IpAddr::V4(ip).to_string();
-            } else if rrtype == K_DNS_SERVICE_TYPE_AAAA && rdlen == 16 {
-                let ip_bytes: &[u8; 16] = unsafe { &*(rdata as *const [u8; 16]) };
-                let ip = Ipv6Addr::from(*ip_bytes);
-                ip_str = IpAddr::V6(ip).to_string();
+            let mut fds = Vec::with_capacity(self.refs.len());
+            for &sd_ref in &self.refs {
+                let fd = unsafe { (self.lib.ref_sock_fd)(sd_ref) };
+                if !valid_sock(fd) {
+                    return;
+                }
+                fds.push(sys::pollfd { fd: fd as _, events: sys::POLLIN, revents: 0 });
             }
-
-            if !ip_str.is_empty() && !state.info.addresses.contains(&ip_str) {
-                state.info.addresses.push(ip_str);
-                callback("serviceFound", state.info.clone());
+            // Poll in 100ms chunks to notice cancellation
+            let poll_timeout = left.as_millis().clamp(1, 100) as i32;
+            let ready = unsafe { sys::poll(fds.as_mut_ptr(), fds.len() as _, poll_timeout) };
+            if ready > 0 {
+                for (pfd, &sd_ref) in fds.iter().zip(&self.refs) {
+                    if pfd.revents != 0 {
+                        unsafe { (self.lib.process_result)(sd_ref) };
+                    }
+                }
             }
         }
-        
-        
-        let cb_ctx = (state.clone(), callback.clone());
-        let cb_ctx6 = (state.clone(), callback.clone()); // Context needs to stay alive
+    }
+
+    fn close_all(&mut self) {
+        for sd_ref in self.refs.drain(..) {
+            unsafe { (self.lib.ref_deallocate)(sd_ref) };
+        }
+    }
+}
+
+impl Drop for Resolver {
+    fn drop(&mut self) {
+        // Refs first: the context must outlive every callback
+        self.close_all();
+    }
+}
 
-        let mut query_ref: DNSServiceRef = ptr::null_mut();
-        let mut query_ref6: DNSServiceRef = ptr::null_mut();
+/// Raw resource record from DNSServiceQueryRecord: (rrtype, rdata, ttl, flags)
+pub type RawRecord = (u16, Vec<u8>, u32, DNSServiceFlags);
 
-        // 1. Query A Record
-        let err_a = unsafe {
-            (lib.query_record)(
-                &mut query_ref,
-                0,
-                interface_index,
-                host_c.as_ptr(),
-                K_DNS_SERVICE_TYPE_A,
-                1, // kDNSServiceClass_IN
-                Some(query_cb),
-                &cb_ctx as *const _ as *mut c_void,
-            )
+/// Query records of `rrtype` for `name`, collecting answers for `timeout`
+pub fn query_record(name: &str, rrtype: u16, interface_index: u32, flags: DNSServiceFlags, timeout: Duration) -> Result<Vec<RawRecord>, String> {
+    let lib = DnsSdLibrary::get()?;
+    let name_c = CString::new(name).map_err(|e| e.to_string())?;
+
+    struct QueryContext {
+        records: Mutex<Vec<RawRecord>>,
+        /// The daemon reported kDNSServiceErr_Timeout: it stopped asking
+        timed_out: AtomicBool,
+    }
+
+    unsafe extern "C" fn record_cb(
+        _sd_ref: DNSServiceRef,
+        flags: DNSServiceFlags,
+        _interface_index: u32_t,
+        error_code: DNSServiceErrorType,
+        _fullname: *const libc::c_char,
+        rrtype: u16,
+        _rrclass: u16,
+        rdlen: u16,
+        rdata: *const c_void,
+        ttl: u32_t,
+        context: *mut c_void,
+    ) {
+        let ctx = unsafe { &*(context as *const QueryContext) };
+        if error_code == K_DNS_SERVICE_ERR_TIMEOUT {
+            ctx.timed_out.store(true, Ordering::SeqCst);
+            return;
+        }
+        if error_code != K_DNS_SERVICE_ERR_NO_ERROR || (flags & K_DNS_SERVICE_FLAGS_ADD) == 0 {
+            return;
+        }
+        let data = if rdata.is_null() {
+            Vec::new()
+        } else {
+            unsafe { std::slice::from_raw_parts(rdata as *const u8, rdlen as usize) }.to_vec()
         };
-        
-        // 2. Query AAAA Record
-        let err_aaaa = unsafe {
-             (lib.query_record)(
-                &mut query_ref6,
-                0,
-                interface_index,
-                host_c.as_ptr(),
-                K_DNS_SERVICE_TYPE_AAAA,
-                1, // kDNSServiceClass_IN
-                Some(query_cb),
-                &cb_ctx6 as *const _ as *mut c_void,
-             )
+        let mut records = ctx.records.lock().unwrap();
+        match records.iter_mut().find(|(t, d, _, _)| *t == rrtype && *d == data) {
+            // A validated answer repeats the record with its DNSSEC result
+            Some(record) => record.3 = flags,
+            None => records.push((rrtype, data, ttl, flags)),
+        }
+    }
+
+    let ctx = QueryContext { records: Mutex::new(Vec::new()), timed_out: AtomicBool::new(false) };
+    let mut sd_ref: DNSServiceRef = ptr::null_mut();
+    let err = with_timeout_flag(flags, |flags| unsafe {
+        (lib.query_record)(
+            &mut sd_ref,
+            flags,
+            interface_index,
+            name_c.as_ptr(),
+            rrtype,
+            1, // kDNSServiceClass_IN
+            Some(record_cb),
+            &ctx as *const _ as *mut c_void,
+        )
+    });
+    check_error(err)?;
+    if sd_ref.is_null() {
+        return Err("DNSServiceQueryRecord returned null".into());
+    }
+
+    poll_service_loop(lib, sd_ref, timeout.as_millis(), || ctx.timed_out.load(Ordering::SeqCst));
+    unsafe {
+        (lib.ref_deallocate)(sd_ref);
+    }
+
+    let records = ctx.records.into_inner().unwrap();
+    if records.is_empty() && ctx.timed_out.into_inner() {
+        return Err(format!("Query for {} {} timed out: the daemon got no answer", name, query::rrtype_name(rrtype)));
+    }
+    Ok(records)
+}
+
+/// Keep a record query open, calling `on_change` with `true` for each
+/// answer that appears and `false` for each one that goes away, until
+/// `stop` is set
+///
+/// Returns once the daemon has accepted the query; answers arrive on the
+/// returned thread, which ends the query when it exits.
+pub fn watch_record<F>(
+    name: &str,
+    rrtype: u16,
+    interface_index: u32,
+    flags: DNSServiceFlags,
+    stop: Arc<AtomicBool>,
+    on_change: F,
+) -> Result<thread::JoinHandle<()>, String>
+where
+    F: FnMut(bool, RawRecord) + Send + 'static,
+{
+    let lib = DnsSdLibrary::get()?;
+    let name_c = CString::new(name).map_err(|e| e.to_string())?;
+
+    type OnChange = Mutex<Box<dyn FnMut(bool, RawRecord) + Send>>;
+
+    unsafe extern "C" fn record_cb(
+        _sd_ref: DNSServiceRef,
+        flags: DNSServiceFlags,
+        _interface_index: u32_t,
+        error_code: DNSServiceErrorType,
+        _fullname: *const libc::c_char,
+        rrtype: u16,
+        _rrclass: u16,
+        rdlen: u16,
+        rdata: *const c_void,
+        ttl: u32_t,
+        context: *mut c_void,
+    ) {
+        if error_code != K_DNS_SERVICE_ERR_NO_ERROR {
+            tracing::debug!("Watched query reported error {}", error_code);
+            return;
+        }
+        let on_change = unsafe { &*(context as *const OnChange) };
+        let data = if rdata.is_null() {
+            Vec::new()
+        } else {
+            unsafe { std::slice::from_raw_parts(rdata as *const u8, rdlen as usize) }.to_vec()
         };
+        let added = (flags & K_DNS_SERVICE_FLAGS_ADD) != 0;
+        (on_change.lock().unwrap())(added, (rrtype, data, ttl, flags));
+    }
 
-        if (err_a == K_DNS_SERVICE_ERR_NO_ERROR && !query_ref.is_null()) || 
-           (err_aaaa == K_DNS_SERVICE_ERR_NO_ERROR && !query_ref6.is_null()) {
-             
-            let timeout = 2000;
-            let start = std::time::Instant::now();
-            
-            // Poll both refs
-            while start.elapsed().as_millis() < timeout {
-                 if !query_ref.is_null() {
-                      unsafe { (lib.process_result)(query_ref); }
-                 }
-                 if !query_ref6.is_null() {
-                      unsafe { (lib.process_result)(query_ref6); }
-                 }
-                 // Small sleep to prevent busy loop
-                 thread::sleep(Duration::from_millis(50));
+    let ctx_ptr = Box::into_raw(Box::new(Mutex::new(Box::new(on_change) as Box<dyn FnMut(bool, RawRecord) + Send>)));
+    let mut sd_ref: DNSServiceRef = ptr::null_mut();
+    // No kDNSServiceFlagsTimeout: the query stays open until stopped
+    let err = unsafe {
+        (lib.query_record)(
+            &mut sd_ref,
+            flags,
+            interface_index,
+            name_c.as_ptr(),
+            rrtype,
+            1, // kDNSServiceClass_IN
+            Some(record_cb),
+            ctx_ptr as *mut c_void,
+        )
+    };
+    let started = check_error(err).and_then(|()| match sd_ref.is_null() {
+        true => Err("DNSServiceQueryRecord returned null".to_string()),
+        false => Ok(()),
+    });
+    if let Err(e) = started {
+        drop(unsafe { Box::from_raw(ctx_ptr) });
+        return Err(e);
+    }
+
+    let sd_ref_copy = sd_ref as usize;
+    let ctx_copy = ctx_ptr as usize;
+    Ok(thread::spawn(move || {
+        let sd_ref = sd_ref_copy as DNSServiceRef;
+        while !stop.load(Ordering::SeqCst) {
+            unsafe {
+                let fd = (lib.ref_sock_fd)(sd_ref);
+                if !valid_sock(fd) {
+                    tracing::warn!("Query watch exiting: no daemon socket");
+                    break;
+                }
+                let mut pfd = sys::pollfd { fd: fd as _, events: sys::POLLIN, revents: 0 };
+                if sys::poll(&mut pfd, 1, 100) > 0 {
+                    let err = (lib.process_result)(sd_ref);
+                    if err != K_DNS_SERVICE_ERR_NO_ERROR {
+                        tracing::warn!("Query watch exiting: DNSServiceProcessResult error {}", err);
+                        break;
+                    }
+                }
             }
+        }
+        unsafe {
+            (lib.ref_deallocate)(sd_ref);
+            drop(Box::from_raw(ctx_copy as *mut OnChange));
+        }
+    }))
+}
 
+/// Keep enumerating the daemon's browse domains, calling `on_change` with
+/// `true` for each domain that appears and `false` for each one that goes
+/// away, until `stop` is set
+///
+/// Returns once the daemon has accepted the enumeration; domains arrive on
+/// the returned thread, which ends the enumeration when it exits.
+pub fn watch_domains<F>(stop: Arc<AtomicBool>, on_change: F) -> Result<thread::JoinHandle<()>, String>
+where
+    F: FnMut(bool, String) + Send + 'static,
+{
+    let lib = DnsSdLibrary::get()?;
+    let enumerate_domains = lib.enumerate_domains
+        .ok_or_else(|| "DNSServiceEnumerateDomains is not available".to_string())?;
+
+    type OnChange = Mutex<Box<dyn FnMut(bool, String) + Send>>;
+
+    unsafe extern "C" fn domain_cb(
+        _sd_ref: DNSServiceRef,
+        flags: DNSServiceFlags,
+        _interface_index: u32_t,
+        error_code: DNSServiceErrorType,
+        reply_domain: *const libc::c_char,
+        context: *mut c_void,
+    ) {
+        if error_code != K_DNS_SERVICE_ERR_NO_ERROR || reply_domain.is_null() {
+            tracing::debug!("Domain enumeration reported error {}", error_code);
+            return;
+        }
+        let on_change = unsafe { &*(context as *const OnChange) };
+        let domain = unsafe { CStr::from_ptr(reply_domain) }.to_string_lossy().into_owned();
+        let added = (flags & K_DNS_SERVICE_FLAGS_ADD) != 0;
+        (on_change.lock().unwrap())(added, domain);
+    }
+
+    let ctx_ptr = Box::into_raw(Box::new(Mutex::new(Box::new(on_change) as Box<dyn FnMut(bool, String) + Send>)));
+    let mut sd_ref: DNSServiceRef = ptr::null_mut();
+    let err = unsafe {
+        enumerate_domains(
+            &mut sd_ref,
+            K_DNS_SERVICE_FLAGS_BROWSE_DOMAINS,
+            0,
+            Some(domain_cb),
+            ctx_ptr as *mut c_void,
+        )
+    };
+    let started = check_error(err).and_then(|()| match sd_ref.is_null() {
+        true => Err("DNSServiceEnumerateDomains returned null".to_string()),
+        false => Ok(()),
+    });
+    if let Err(e) = started {
+        drop(unsafe { Box::from_raw(ctx_ptr) });
+        return Err(e);
+    }
+
+    let sd_ref_copy = sd_ref as usize;
+    let ctx_copy = ctx_ptr as usize;
+    Ok(thread::spawn(move || {
+        let sd_ref = sd_ref_copy as DNSServiceRef;
+        while !stop.load(Ordering::SeqCst) {
             unsafe {
-                if !query_ref.is_null() { (lib.ref_deallocate)(query_ref); }
-                if !query_ref6.is_null() { (lib.ref_deallocate)(query_ref6); }
+                let fd = (lib.ref_sock_fd)(sd_ref);
+                if !valid_sock(fd) {
+                    tracing::warn!("Domain enumeration exiting: no daemon socket");
+                    break;
+                }
+                let mut pfd = sys::pollfd { fd: fd as _, events: sys::POLLIN, revents: 0 };
+                if sys::poll(&mut pfd, 1, 100) > 0 {
+                    let err = (lib.process_result)(sd_ref);
+                    if err != K_DNS_SERVICE_ERR_NO_ERROR {
+                        tracing::warn!("Domain enumeration exiting: DNSServiceProcessResult error {}", err);
+                        break;
+                    }
+                }
             }
         }
+        unsafe {
+            (lib.ref_deallocate)(sd_ref);
+            drop(Box::from_raw(ctx_copy as *mut OnChange));
+        }
+    }))
+}
+
+/// Start a lookup with kDNSServiceFlagsTimeout, so the daemon reports
+/// kDNSServiceErr_Timeout once it stops asking instead of going quiet
+///
+/// Libraries that reject the flag (Avahi's compat layer takes none) get
+/// the call again without it.
+fn with_timeout_flag<F>(flags: DNSServiceFlags, mut start: F) -> DNSServiceErrorType
+where
+    F: FnMut(DNSServiceFlags) -> DNSServiceErrorType,
+{
+    match start(flags | K_DNS_SERVICE_FLAGS_TIMEOUT) {
+        K_DNS_SERVICE_ERR_UNSUPPORTED | K_DNS_SERVICE_ERR_BAD_FLAGS => start(flags),
+        err => err,
     }
 }
 
@@ -593,7 +1369,7 @@ where F: FnMut() -> bool {
 
         unsafe {
             let fd = (lib.ref_sock_fd)(sd_ref);
-            if fd < 0 { break; }
+            if !valid_sock(fd) { break; }
 
             let mut pfd = sys::pollfd {
                 fd: fd as _,
@@ -614,40 +1390,12 @@ where F: FnMut() -> bool {
 }
 
 
-/// Parse TXT record bytes into key-value map
-fn parse_txt_record(data: *const u8, len: usize) -> HashMap<String, String> {
-    let mut map = HashMap::new();
-    if data.is_null() || len == 0 {
-        return map;
-    }
-
-    let bytes = unsafe { std::slice::from_raw_parts(data, len) };
-    let mut i = 0;
-    while i < bytes.len() {
-        let entry_len = bytes[i] as usize;
-        i += 1;
-        if i + entry_len > bytes.len() {
-            break;
-        }
-        let entry = &bytes[i..i + entry_len];
-        i += entry_len;
-
-        if let Some(eq_pos) = entry.iter().position(|&b| b == b'=') {
-            let key = String::from_utf8_lossy(&entry[..eq_pos]).into_owned();
-            let value = String::from_utf8_lossy(&entry[eq_pos + 1..]).into_owned();
-            map.insert(key, value);
-        } else {
-            let key = String::from_utf8_lossy(entry).into_owned();
-            map.insert(key, String::new());
-        }
-    }
-    map
-}
-
 /// Browser handle for native backend
 pub struct NativeBrowser {
     sd_ref: DNSServiceRef,
     stop_flag: Arc<Mutex<bool>>,
+    /// Cancels resolves still running when the browser stops
+    resolves: Arc<AtomicBool>,
     thread: Option<thread::JoinHandle<()>>,
     _context: *mut BrowseContext,
     stopped: bool,
@@ -656,40 +1404,66 @@ pub struct NativeBrowser {
 unsafe impl Send for NativeBrowser {}
 
 impl NativeBrowser {
-    /// Start browsing for services
-    pub fn new<F>(service_type: &str, callback: F) -> Result<Self, String>
+    /// Start browsing for services, resolving each one unless `resolve` is
+    /// false (type enumeration never resolves)
+    ///
+    /// Instances `names` leaves out are skipped as their browse answers arrive.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new<F>(
+        service_type: &str,
+        domain: Option<&str>,
+        interface_index: u32,
+        flags: DNSServiceFlags,
+        config: ResolveConfig,
+        resolve: bool,
+        names: NameList,
+        callback: F,
+    ) -> Result<Self, String>
     where
         F: Fn(&str, ServiceInfo) + Send + Sync + 'static,
     {
         let lib = DnsSdLibrary::get()?;
         
         let stop_flag = Arc::new(Mutex::new(false));
+        let resolves = Arc::new(AtomicBool::new(false));
         
         let ctx = Box::new(BrowseContext {
             callback: Arc::new(callback),
+            config,
+            resolve: resolve && !crate::model::is_type_enumeration(service_type),
+            cancel: resolves.clone(),
+            names,
+            watches: Mutex::new(HashMap::new()),
         });
-        let ctx_ptr = Box::into_raw(ctx);
-
         let reg_type = CString::new(service_type).map_err(|e| e.to_string())?;
+        let domain_c = domain
+            .map(CString::new)
+            .transpose()
+            .map_err(|e| e.to_string())?;
+        let ctx_ptr = Box::into_raw(ctx);
         
         let mut sd_ref: DNSServiceRef = ptr::null_mut();
         
         let err = unsafe {
             (lib.browse)(
                 &mut sd_ref,
-                0,
-                0,
+                flags,
+                interface_index,
                 reg_type.as_ptr(),
-                ptr::null(),
+                domain_c.as_ref().map_or(ptr::null(), |d| d.as_ptr()),
                 Some(browse_callback),
                 ctx_ptr as *mut c_void,
             )
         };
 
-        check_error(err)?;
-
-        if sd_ref.is_null() {
-            return Err("DNSServiceBrowse returned null".into());
+        let started = check_error(err).and_then(|()| match sd_ref.is_null() {
+            true => Err("DNSServiceBrowse returned null".to_string()),
+            false => Ok(()),
+        });
+        if let Err(e) = started {
+            // The daemon never took the context; free it and the callback it holds
+            drop(unsafe { Box::from_raw(ctx_ptr) });
+            return Err(e);
         }
 
         // Start event loop thread
@@ -710,7 +1484,8 @@ impl NativeBrowser {
 
                 unsafe {
                     let fd = (lib.ref_sock_fd)(sd_ref);
-                    if fd < 0 {
+                    if !valid_sock(fd) {
+                        tracing::warn!("Browse poll loop exiting: no daemon socket");
                         break;
                     }
 
@@ -725,6 +1500,7 @@ impl NativeBrowser {
                     if ready > 0 {
                         let err = (lib.process_result)(sd_ref);
                         if err != K_DNS_SERVICE_ERR_NO_ERROR {
+                            tracing::warn!("Browse poll loop exiting: DNSServiceProcessResult error {}", err);
                             break;
                         }
                     }
@@ -735,12 +1511,18 @@ impl NativeBrowser {
         Ok(NativeBrowser {
             sd_ref,
             stop_flag,
+            resolves,
             thread: Some(thread),
             _context: ctx_ptr,
             stopped: false,
         })
     }
 
+    /// Threads still running, for diagnostics
+    pub fn threads(&self) -> usize {
+        running(&self.thread)
+    }
+
     /// Stop browsing
     pub fn stop(&mut self) {
         if self.stopped {
@@ -749,6 +1531,7 @@ impl NativeBrowser {
         self.stopped = true;
         
         *self.stop_flag.lock().unwrap() = true;
+        self.resolves.store(true, Ordering::SeqCst);
         
         if let Some(thread) = self.thread.take() {
             let _ = thread.join();
@@ -778,9 +1561,12 @@ impl Drop for NativeBrowser {
     }
 }
 
+/// Callback type for advertisement events
+type RegisterCallback = Box<dyn Fn(&str, &str) + Send + 'static>;
+
 /// Context for register callback
 struct RegisterContext {
-    callback: Box<dyn Fn(&str, &str) + Send + 'static>,
+    callback: RegisterCallback,
 }
 
 /// Register callback
@@ -800,11 +1586,95 @@ unsafe extern "C" fn register_callback(
             let name_str = CStr::from_ptr(name).to_string_lossy().into_owned();
             (ctx.callback)("registered", &name_str);
         } else {
+            let message = format!("Registration reported error {}", error_code);
+            metrics::backend_error(&message);
+            tracing::warn!("{}", message);
+            (ctx.callback)("error", &format!("DNS-SD error: {}", error_code));
+        }
+    }
+}
+
+/// RegisterRecord callback
+unsafe extern "C" fn register_record_callback(
+    _sd_ref: DNSServiceRef,
+    _record_ref: DNSRecordRef,
+    _flags: DNSServiceFlags,
+    error_code: DNSServiceErrorType,
+    context: *mut c_void,
+) {
+    unsafe {
+        let ctx = &*(context as *const RegisterContext);
+
+        if error_code == K_DNS_SERVICE_ERR_NO_ERROR {
+            // The record carries no name; the caller's callback knows it
+            (ctx.callback)("registered", "");
+        } else {
+            let message = format!("Record registration reported error {}", error_code);
+            metrics::backend_error(&message);
+            tracing::warn!("{}", message);
             (ctx.callback)("error", &format!("DNS-SD error: {}", error_code));
         }
     }
 }
 
+/// Poll a registration's daemon socket until `stop_flag` is set
+fn registration_loop(sd_ref: DNSServiceRef, stop_flag: Arc<Mutex<bool>>) -> thread::JoinHandle<()> {
+    let sd_ref_copy = sd_ref as usize;
+
+    thread::spawn(move || {
+        let sd_ref = sd_ref_copy as DNSServiceRef;
+        let lib = match DnsSdLibrary::get() {
+            Ok(lib) => lib,
+            Err(_) => return,
+        };
+
+        loop {
+            if *stop_flag.lock().unwrap() {
+                break;
+            }
+
+            unsafe {
+                let fd = (lib.ref_sock_fd)(sd_ref);
+                if !valid_sock(fd) {
+                    tracing::warn!("Registration poll loop exiting: no daemon socket");
+                    break;
+                }
+
+                let mut pfd = sys::pollfd {
+                    fd: fd as _,
+                    events: sys::POLLIN,
+                    revents: 0,
+                };
+
+                let ready = sys::poll(&mut pfd, 1, 100);
+
+                if ready > 0 {
+                    let err = (lib.process_result)(sd_ref);
+                    if err != K_DNS_SERVICE_ERR_NO_ERROR {
+                        tracing::warn!("Registration poll loop exiting: DNSServiceProcessResult error {}", err);
+                        break;
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// TTL of records registered apart from a service: RFC 6762's for
+/// records naming a host
+const HOST_RECORD_TTL: u32 = 120;
+
+/// Uncompressed wire-format domain name, as CNAME rdata
+fn encode_name(name: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(name.len() + 2);
+    for label in name.trim_end_matches('.').split('.').filter(|l| !l.is_empty()) {
+        out.push(label.len() as u8);
+        out.extend_from_slice(label.as_bytes());
+    }
+    out.push(0);
+    out
+}
+
 /// Advertisement handle for native backend
 pub struct NativeAdvertisement {
     sd_ref: DNSServiceRef,
@@ -818,27 +1688,41 @@ unsafe impl Send for NativeAdvertisement {}
 
 impl NativeAdvertisement {
     /// Advertise a service
-    pub fn new<F>(
-        name: &str,
-        service_type: &str,
-        port: u16,
-        txt: Option<&HashMap<String, String>>,
-        callback: F,
-    ) -> Result<Self, String>
+    pub fn new<F>(options: &AdvertiseOptions, callback: F) -> Result<Self, String>
     where
         F: Fn(&str, &str) + Send + 'static,
     {
         let lib = DnsSdLibrary::get()?;
+        let txt = options.txt.as_ref();
         
         let stop_flag = Arc::new(Mutex::new(false));
         
         let ctx = Box::new(RegisterContext {
             callback: Box::new(callback),
         });
+
+        let name_c = CString::new(options.name.as_str()).map_err(|e| e.to_string())?;
+        // Subtypes ride along as a comma-separated list: "_http._tcp,_printer"
+        let mut reg_type = options.service_type.clone();
+        for subtype in &options.subtypes {
+            reg_type.push(',');
+            reg_type.push_str(subtype);
+        }
+        let reg_type = CString::new(reg_type).map_err(|e| e.to_string())?;
+        let domain_c = options.domain.as_deref().map(CString::new).transpose().map_err(|e| e.to_string())?;
+        let host_c = options.host.as_deref().map(CString::new).transpose().map_err(|e| e.to_string())?;
         let ctx_ptr = Box::into_raw(ctx);
 
-        let name_c = CString::new(name).map_err(|e| e.to_string())?;
-        let reg_type = CString::new(service_type).map_err(|e| e.to_string())?;
+        let mut flags: DNSServiceFlags = 0;
+        if options.no_auto_rename {
+            flags |= K_DNS_SERVICE_FLAGS_NO_AUTO_RENAME;
+        }
+        if options.allow_remote_query {
+            flags |= K_DNS_SERVICE_FLAGS_ALLOW_REMOTE_QUERY;
+        }
+        if options.include_p2p {
+            flags |= K_DNS_SERVICE_FLAGS_INCLUDE_P2P;
+        }
         
         // Build TXT record
         let mut txt_ref: TXTRecordRef = [0u8; 16];
@@ -846,8 +1730,9 @@ impl NativeAdvertisement {
             unsafe {
                 (lib.txt_record_create)(&mut txt_ref, 0, ptr::null_mut());
                 
-                for (k, v) in txt_map {
-                    let key_c = CString::new(k.as_str()).unwrap();
+                // TXTRecordSetValue appends, so entries go out in this order
+                for (k, v) in txt_map.ordered(options.txtvers_first) {
+                    let key_c = CString::new(k).unwrap();
                     let _ = (lib.txt_record_set_value)(
                         &mut txt_ref,
                         key_c.as_ptr(),
@@ -869,13 +1754,13 @@ impl NativeAdvertisement {
         let err = unsafe {
             (lib.register)(
                 &mut sd_ref,
-                0,
-                0,
+                flags,
+                options.interface_index,
                 name_c.as_ptr(),
                 reg_type.as_ptr(),
-                ptr::null(),
-                ptr::null(),
-                port.to_be(),
+                domain_c.as_ref().map_or(ptr::null(), |d| d.as_ptr()),
+                host_c.as_ref().map_or(ptr::null(), |h| h.as_ptr()),
+                options.port.to_be(),
                 txt_len,
                 txt_ptr,
                 Some(register_callback),
@@ -883,58 +1768,156 @@ impl NativeAdvertisement {
             )
         };
 
+        // Kept for DNSServiceUpdateRecord, which needs the rdata again; an
+        // empty TXT record is a single zero-length string
+        let txt_bytes = match txt_len {
+            0 => vec![0u8],
+            len => unsafe { std::slice::from_raw_parts(txt_ptr as *const u8, len as usize).to_vec() },
+        };
         if txt.is_some() {
             unsafe {
                 (lib.txt_record_deallocate)(&mut txt_ref);
             }
         }
 
-        check_error(err)?;
+        let started = check_error(err).and_then(|()| match sd_ref.is_null() {
+            true => Err("DNSServiceRegister returned null".to_string()),
+            false => Ok(()),
+        });
+        if let Err(e) = started {
+            // The daemon never took the context; free it and the callback it holds
+            drop(unsafe { Box::from_raw(ctx_ptr) });
+            return Err(e);
+        }
 
-        if sd_ref.is_null() {
-            return Err("DNSServiceRegister returned null".into());
+        // The daemon picks its own TTLs; the TXT record (and with it the
+        // PTR, on mDNSResponder) can be re-registered with a custom one
+        if let Some(ttl) = options.ttl {
+            match lib.update_record {
+                Some(update_record) => {
+                    let err = unsafe {
+                        update_record(sd_ref, ptr::null_mut(), 0, txt_bytes.len() as c_ushort, txt_bytes.as_ptr() as *const c_void, ttl)
+                    };
+                    if err != K_DNS_SERVICE_ERR_NO_ERROR {
+                        tracing::warn!("Couldn't set the TTL of {}: DNSServiceUpdateRecord error {}", options.name, err);
+                    }
+                }
+                None => tracing::warn!("DNSServiceUpdateRecord missing; {} uses the daemon's TTLs", options.name),
+            }
+        }
+        if options.host_ttl.is_some() {
+            tracing::warn!("The DNS-SD daemon chooses SRV and address TTLs; hostTtl is ignored for {}", options.name);
         }
 
-        // Start event loop thread
-        let sd_ref_copy = sd_ref as usize;
-        let stop_flag_clone = stop_flag.clone();
-        
-        let thread = thread::spawn(move || {
-            let sd_ref = sd_ref_copy as DNSServiceRef;
-            let lib = match DnsSdLibrary::get() {
-                Ok(lib) => lib,
-                Err(_) => return,
+        // Extra records belong to the registration and are withdrawn with it
+        for record in &options.records {
+            let err = match lib.add_record {
+                Some(add_record) => {
+                    let mut record_ref: DNSRecordRef = ptr::null_mut();
+                    // 0 lets the daemon pick the TTL
+                    let ttl = record.ttl.or(options.ttl).unwrap_or(0);
+                    unsafe {
+                        add_record(sd_ref, &mut record_ref, 0, record.rrtype, record.rdata.len() as c_ushort, record.rdata.as_ptr() as *const c_void, ttl)
+                    }
+                }
+                None => K_DNS_SERVICE_ERR_UNSUPPORTED,
             };
-
-            loop {
-                if *stop_flag_clone.lock().unwrap() {
-                    break;
+            if let Err(e) = check_error(err) {
+                unsafe {
+                    (lib.ref_deallocate)(sd_ref);
+                    drop(Box::from_raw(ctx_ptr));
                 }
+                return Err(format!("Couldn't add the {} record: {}", query::rrtype_name(record.rrtype), e));
+            }
+        }
 
-                unsafe {
-                    let fd = (lib.ref_sock_fd)(sd_ref);
-                    if fd < 0 {
-                        break;
-                    }
+        let thread = registration_loop(sd_ref, stop_flag.clone());
 
-                    let mut pfd = sys::pollfd {
-                        fd: fd as _,
-                        events: sys::POLLIN,
-                        revents: 0,
-                    };
+        Ok(NativeAdvertisement {
+            sd_ref,
+            stop_flag,
+            thread: Some(thread),
+            _context: ctx_ptr,
+            stopped: false,
+        })
+    }
 
-                    let ready = sys::poll(&mut pfd, 1, 100);
+    /// Publish `alias` (a full name such as `myproduct.local.`) as a CNAME
+    /// for `target`
+    pub fn alias<F>(alias: &str, target: &str, interface_index: u32, callback: F) -> Result<Self, String>
+    where
+        F: Fn(&str, &str) + Send + 'static,
+    {
+        Self::records(alias, &[(K_DNS_SERVICE_TYPE_CNAME, encode_name(target))], interface_index, callback)
+    }
 
-                    if ready > 0 {
-                        let err = (lib.process_result)(sd_ref);
-                        if err != K_DNS_SERVICE_ERR_NO_ERROR {
-                            break;
-                        }
+    /// Publish unique records `(rrtype, rdata)` under `fullname`, apart
+    /// from any service
+    ///
+    /// Uses `DNSServiceRegisterRecord` on a shared connection, which Avahi's
+    /// compatibility library doesn't implement. `registered` reports
+    /// `fullname` once every record was probed and found unique.
+    pub fn records<F>(fullname: &str, records: &[(u16, Vec<u8>)], interface_index: u32, callback: F) -> Result<Self, String>
+    where
+        F: Fn(&str, &str) + Send + 'static,
+    {
+        let lib = DnsSdLibrary::get()?;
+        let (Some(create_connection), Some(register_record)) = (lib.create_connection, lib.register_record) else {
+            return Err("DNSServiceRegisterRecord is not available".to_string());
+        };
+        let name_c = CString::new(fullname).map_err(|e| e.to_string())?;
+
+        let stop_flag = Arc::new(Mutex::new(false));
+        let name = fullname.to_string();
+        let pending = AtomicUsize::new(records.len());
+        let ctx = Box::new(RegisterContext {
+            callback: Box::new(move |event, data| match event {
+                "registered" => {
+                    if pending.fetch_sub(1, Ordering::SeqCst) == 1 {
+                        callback(event, &name);
                     }
                 }
-            }
+                _ => callback(event, data),
+            }),
         });
 
+        let mut sd_ref: DNSServiceRef = ptr::null_mut();
+        check_error(unsafe { create_connection(&mut sd_ref) })?;
+        if sd_ref.is_null() {
+            return Err("DNSServiceCreateConnection returned null".to_string());
+        }
+        let ctx_ptr = Box::into_raw(ctx);
+
+        for (rrtype, rdata) in records {
+            let mut record_ref: DNSRecordRef = ptr::null_mut();
+            let err = unsafe {
+                register_record(
+                    sd_ref,
+                    &mut record_ref,
+                    K_DNS_SERVICE_FLAGS_UNIQUE,
+                    interface_index,
+                    name_c.as_ptr(),
+                    *rrtype,
+                    1, // IN
+                    rdata.len() as c_ushort,
+                    rdata.as_ptr() as *const c_void,
+                    HOST_RECORD_TTL,
+                    Some(register_record_callback),
+                    ctx_ptr as *mut c_void,
+                )
+            };
+            // Deallocating the connection withdraws the records already taken
+            if let Err(e) = check_error(err) {
+                unsafe {
+                    (lib.ref_deallocate)(sd_ref);
+                    drop(Box::from_raw(ctx_ptr));
+                }
+                return Err(format!("Couldn't register the {} record of {}: {}", query::rrtype_name(*rrtype), fullname, e));
+            }
+        }
+
+        let thread = registration_loop(sd_ref, stop_flag.clone());
+
         Ok(NativeAdvertisement {
             sd_ref,
             stop_flag,
@@ -944,6 +1927,11 @@ impl NativeAdvertisement {
         })
     }
 
+    /// Threads still running, for diagnostics
+    pub fn threads(&self) -> usize {
+        running(&self.thread)
+    }
+
     /// Stop advertising
     pub fn stop(&mut self) {
         if self.stopped {
@@ -0,0 +1,272 @@
+//! Network change detection
+//!
+//! A watcher thread waits for the OS to report address changes (netlink on
+//! Linux, IP Helper on Windows, a routing socket on macOS and the BSDs),
+//! lets a burst of them settle, then compares the addresses of interfaces
+//! that are up against the previous snapshot. When they differ,
+//! `networkChanged` is reported with the addresses added and removed, every
+//! running browse is restarted and every advertisement re-announced.
+//! Services a restarted browse doesn't see again within a few seconds are
+//! reported lost, so a laptop that switched networks doesn't keep showing
+//! what it saw on the old one.
+//!
+//! The watcher starts with the first JS environment unless
+//! `DNS_SD_NETWORK_WATCH=0`, and can be turned off and on with
+//! `set_enabled`.
+
+use crate::{Sink, handles, interfaces};
+use neon::event::Channel;
+use neon::handle::Root;
+use neon::prelude::*;
+use std::collections::BTreeSet;
+use std::io;
+use std::net::IpAddr;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the watcher checks whether it was stopped
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Quiet period that ends a burst of changes (DHCP, IPv6 autoconfiguration)
+const SETTLE: Duration = Duration::from_secs(1);
+
+/// Longest a burst is waited out before refreshing anyway
+const SETTLE_MAX: Duration = Duration::from_secs(5);
+
+/// How long a restarted browse has to report a service again before it is
+/// reported lost
+const RECONFIRM: Duration = Duration::from_secs(5);
+
+/// An address on an interface that is up
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct InterfaceAddress {
+    pub interface: String,
+    pub interface_index: u32,
+    pub address: IpAddr,
+}
+
+/// Addresses that appeared and disappeared since the previous snapshot
+#[derive(Clone, Debug, Default)]
+pub struct NetworkChange {
+    pub added: Vec<InterfaceAddress>,
+    pub removed: Vec<InterfaceAddress>,
+}
+
+impl NetworkChange {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// JS function receiving `(change)`
+static SINK: Sink = Sink::new();
+
+/// Stops the running watcher when dropped
+static WATCHER: Mutex<Option<Sender<()>>> = Mutex::new(None);
+
+/// Start the watcher unless `DNS_SD_NETWORK_WATCH=0`; called once per process
+pub fn start_default() {
+    let disabled = std::env::var("DNS_SD_NETWORK_WATCH").is_ok_and(|v| v == "0" || v == "false");
+    if !disabled {
+        set_enabled(true);
+    }
+}
+
+/// Start or stop watching for network changes
+pub fn set_enabled(enabled: bool) {
+    let mut watcher = WATCHER.lock().unwrap();
+    if !enabled {
+        // Dropping the sender ends the watcher
+        *watcher = None;
+        return;
+    }
+    if watcher.is_some() {
+        return;
+    }
+    let (tx, rx) = mpsc::channel::<()>();
+    thread::spawn(move || watch(rx));
+    *watcher = Some(tx);
+}
+
+/// Deliver `networkChanged` notifications to `callback`, or stop with `None`
+pub fn set_callback(sink: Option<(Channel, Root<JsFunction>)>) {
+    SINK.set(sink);
+}
+
+fn stopped(stop: &Receiver<()>) -> bool {
+    !matches!(stop.try_recv(), Err(TryRecvError::Empty))
+}
+
+fn watch(stop: Receiver<()>) {
+    let mut monitor = match Monitor::new() {
+        Ok(monitor) => monitor,
+        Err(e) => {
+            tracing::warn!("Network change detection unavailable: {}", e);
+            return;
+        }
+    };
+    let mut known = snapshot();
+    while !stopped(&stop) {
+        match monitor.wait(POLL_INTERVAL) {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => {
+                tracing::warn!("Network change detection stopped: {}", e);
+                return;
+            }
+        }
+        let deadline = Instant::now() + SETTLE_MAX;
+        while Instant::now() < deadline && matches!(monitor.wait(SETTLE), Ok(true)) {}
+        if stopped(&stop) {
+            return;
+        }
+
+        let current = snapshot();
+        let change = NetworkChange {
+            added: current.difference(&known).cloned().collect(),
+            removed: known.difference(&current).cloned().collect(),
+        };
+        known = current;
+        if !change.is_empty() {
+            tracing::info!("Network changed: {} address(es) added, {} removed", change.added.len(), change.removed.len());
+            notify(change);
+            handles::refresh_all(RECONFIRM);
+        }
+    }
+}
+
+/// Addresses of every interface that is up
+fn snapshot() -> BTreeSet<InterfaceAddress> {
+    let list = match interfaces::list() {
+        Ok(list) => list,
+        Err(e) => {
+            tracing::warn!("{}", e);
+            return BTreeSet::new();
+        }
+    };
+    list.into_iter()
+        .filter(|iface| iface.up)
+        .flat_map(|iface| {
+            iface.addresses.into_iter().map(move |address| InterfaceAddress {
+                interface: iface.name.clone(),
+                interface_index: iface.index,
+                address,
+            })
+        })
+        .collect()
+}
+
+/// Socket the OS reports address changes on; every message counts as a
+/// possible change, which the snapshot comparison then confirms
+#[cfg(unix)]
+struct Monitor(std::os::fd::OwnedFd);
+
+#[cfg(unix)]
+impl Monitor {
+    fn new() -> io::Result<Self> {
+        open_socket().map(Monitor)
+    }
+
+    /// Wait up to `timeout`, returning whether anything changed
+    fn wait(&mut self, timeout: Duration) -> io::Result<bool> {
+        use std::os::fd::AsRawFd;
+        let fd = self.0.as_raw_fd();
+        let mut pfd = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+        // SAFETY: pfd is a single valid pollfd
+        let ready = unsafe { libc::poll(&mut pfd, 1, timeout.as_millis() as libc::c_int) };
+        if ready < 0 {
+            let e = io::Error::last_os_error();
+            return if e.kind() == io::ErrorKind::Interrupted { Ok(false) } else { Err(e) };
+        }
+        let mut buf = [0u8; 8192];
+        let mut changed = false;
+        // Drain everything queued so a burst counts once
+        // SAFETY: reads into a buffer of the given length
+        while ready > 0 && unsafe { libc::recv(fd, buf.as_mut_ptr().cast(), buf.len(), libc::MSG_DONTWAIT) } > 0 {
+            changed = true;
+        }
+        Ok(changed)
+    }
+}
+
+/// Netlink socket subscribed to link and address changes
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn open_socket() -> io::Result<std::os::fd::OwnedFd> {
+    use std::os::fd::FromRawFd;
+    // SAFETY: plain socket call; the descriptor is owned from here on
+    let fd = unsafe { libc::socket(libc::AF_NETLINK, libc::SOCK_RAW | libc::SOCK_CLOEXEC, libc::NETLINK_ROUTE) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let socket = unsafe { std::os::fd::OwnedFd::from_raw_fd(fd) };
+    // SAFETY: sockaddr_nl is plain data, zero is a valid starting value
+    let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+    addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    addr.nl_groups = (libc::RTMGRP_LINK | libc::RTMGRP_IPV4_IFADDR | libc::RTMGRP_IPV6_IFADDR) as u32;
+    let len = std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t;
+    // SAFETY: addr outlives the call and len is its size
+    if unsafe { libc::bind(fd, (&addr as *const libc::sockaddr_nl).cast(), len) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(socket)
+}
+
+/// Routing socket, which reports interface and address changes
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn open_socket() -> io::Result<std::os::fd::OwnedFd> {
+    use std::os::fd::FromRawFd;
+    // SAFETY: plain socket call; the descriptor is owned from here on
+    let fd = unsafe { libc::socket(libc::PF_ROUTE, libc::SOCK_RAW, libc::AF_UNSPEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { std::os::fd::OwnedFd::from_raw_fd(fd) })
+}
+
+/// IP Helper notifications, through if-addrs
+#[cfg(not(unix))]
+struct Monitor(if_addrs::IfChangeNotifier);
+
+#[cfg(not(unix))]
+impl Monitor {
+    fn new() -> io::Result<Self> {
+        if_addrs::IfChangeNotifier::new().map(Monitor)
+    }
+
+    /// Wait up to `timeout`, returning whether anything changed
+    fn wait(&mut self, timeout: Duration) -> io::Result<bool> {
+        match self.0.wait(Some(timeout)) {
+            Ok(changes) => Ok(!changes.is_empty()),
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+fn notify(change: NetworkChange) {
+    SINK.call(move |cx| {
+        let obj = cx.empty_object();
+        let added = addresses_to_js(cx, &change.added)?;
+        obj.set(cx, "added", added)?;
+        let removed = addresses_to_js(cx, &change.removed)?;
+        obj.set(cx, "removed", removed)?;
+        Ok(vec![obj.upcast()])
+    });
+}
+
+fn addresses_to_js<'cx>(cx: &mut impl Context<'cx>, addresses: &[InterfaceAddress]) -> JsResult<'cx, JsArray> {
+    let arr = cx.empty_array();
+    for (i, entry) in addresses.iter().enumerate() {
+        let obj = cx.empty_object();
+        let interface = cx.string(&entry.interface);
+        obj.set(cx, "interface", interface)?;
+        let index = cx.number(entry.interface_index);
+        obj.set(cx, "interfaceIndex", index)?;
+        let address = cx.string(entry.address.to_string());
+        obj.set(cx, "address", address)?;
+        arr.set(cx, i as u32, obj)?;
+    }
+    Ok(arr)
+}
@@ -0,0 +1,640 @@
+//! Option objects accepted by the JS API
+
+use crate::completion::Completion;
+use crate::fallback::FallbackConfig;
+use crate::ffi::{K_DNS_SERVICE_FLAGS_FORCE_MULTICAST, K_DNS_SERVICE_FLAGS_INCLUDE_P2P};
+use crate::filters::{AddressFilter, NamePattern, ResultFilter};
+use crate::instances::InterfaceMode;
+use crate::interfaces;
+use crate::mock::MockStep;
+use crate::native::ResolveConfig;
+use crate::query;
+use crate::queue::{self, OverflowPolicy};
+use crate::txt::TxtRecord;
+#[cfg(feature = "dns-update")]
+use crate::update::UpdateConfig;
+use neon::prelude::*;
+use neon::types::buffer::TypedArray;
+use std::time::Duration;
+
+/// Browse options
+#[derive(Debug, Clone)]
+pub struct BrowseOptions {
+    /// Service type to browse for (e.g. `_http._tcp`)
+    pub service_type: String,
+    /// Browse domain (None = default domains)
+    pub domain: Option<String>,
+    /// Also browse every domain the daemon enumerates (`browseAllDomains`)
+    pub all_domains: bool,
+    /// Interface index to browse on (0 = all)
+    pub interface_index: u32,
+    /// Interfaces to browse on by name (`interfaces`), looked up when the
+    /// browse opens; empty = all
+    pub interfaces: Vec<String>,
+    /// Extra DNSServiceFlags passed through to the native browse call
+    pub flags: u32,
+    /// Coalesce events and deliver them as arrays
+    pub batch: bool,
+    /// Longest time an event waits in a batch before it is flushed
+    pub batch_interval: Duration,
+    /// How sightings of one instance on several interfaces are reported
+    pub interface_mode: InterfaceMode,
+    /// Address classes dropped from results
+    pub address_filter: AddressFilter,
+    /// Services left unreported (`nameFilter`, `txtFilter`)
+    pub result_filter: ResultFilter,
+    /// Resolve timings and address family (native backend only)
+    pub resolve: ResolveConfig,
+    /// Resolve each instance; false reports browse answers only (`resolve: false`)
+    pub resolve_services: bool,
+    /// What services wait for before they are reported; None reports each
+    /// partial result as it arrives (`emitPartial`)
+    pub completion: Option<Completion>,
+    /// Report services with malformed TXT records as `txtError` instead of found
+    pub strict_txt: bool,
+    /// Events held for a stalled JS thread before `overflow` applies
+    pub queue_size: usize,
+    pub overflow: OverflowPolicy,
+    /// Quiet period an instance's events must settle for before delivery
+    pub debounce: Duration,
+    /// Window from an instance's first found/updated event in which later
+    /// ones are merged into it, however busy the instance stays
+    pub settle: Duration,
+    /// Cap on found/updated/lost events delivered per second
+    pub max_events_per_second: Option<f64>,
+    /// Close and reopen the backend browse this often (`refreshIntervalMs`)
+    pub refresh_interval: Option<Duration>,
+}
+
+impl BrowseOptions {
+    /// Pass native flags to the browse and to the resolves it starts
+    fn add_flags(&mut self, flags: u32) {
+        self.flags |= flags;
+        self.resolve.flags |= flags;
+    }
+
+    /// Query over multicast even for names that look like unicast DNS
+    pub fn force_multicast(&mut self) {
+        self.add_flags(K_DNS_SERVICE_FLAGS_FORCE_MULTICAST);
+    }
+}
+
+impl Default for BrowseOptions {
+    fn default() -> Self {
+        BrowseOptions {
+            service_type: String::new(),
+            domain: None,
+            all_domains: false,
+            interface_index: 0,
+            interfaces: Vec::new(),
+            flags: 0,
+            batch: false,
+            batch_interval: Duration::from_millis(50),
+            interface_mode: InterfaceMode::Merged,
+            address_filter: AddressFilter::default(),
+            result_filter: ResultFilter::default(),
+            resolve: ResolveConfig::default(),
+            resolve_services: true,
+            completion: None,
+            strict_txt: false,
+            queue_size: queue::DEFAULT_CAPACITY,
+            overflow: OverflowPolicy::default(),
+            debounce: Duration::ZERO,
+            settle: Duration::ZERO,
+            max_events_per_second: None,
+            refresh_interval: None,
+        }
+    }
+}
+
+/// Advertise options, shared by both backends
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AdvertiseOptions {
+    /// Instance name
+    pub name: String,
+    /// Service type (e.g. `_http._tcp`)
+    pub service_type: String,
+    pub port: u16,
+    /// TXT entries, published in this order
+    pub txt: Option<TxtRecord>,
+    /// Publish `txtvers` before the other TXT entries, wherever it was added
+    pub txtvers_first: bool,
+    /// Registration domain (None = default domain)
+    pub domain: Option<String>,
+    /// Target host (None = this machine)
+    pub host: Option<String>,
+    /// Interface index to register on (0 = all)
+    pub interface_index: u32,
+    /// Interfaces to register on by name, looked up at registration; empty = all
+    pub interfaces: Vec<String>,
+    /// Subtypes (e.g. `_printer`) the service is also registered under
+    pub subtypes: Vec<String>,
+    /// Fail on name conflict instead of picking a new name
+    pub no_auto_rename: bool,
+    /// Answer queries from outside the local link (native backend only)
+    pub allow_remote_query: bool,
+    /// IP addresses to publish (fallback backend only, empty = automatic)
+    pub addresses: Vec<String>,
+    /// Also register over peer-to-peer interfaces such as AWDL (Apple platforms only)
+    pub include_p2p: bool,
+    /// TTL in seconds of the PTR and TXT records (None = backend default)
+    pub ttl: Option<u32>,
+    /// TTL in seconds of the SRV and address records (None = backend default)
+    pub host_ttl: Option<u32>,
+    /// Extra records published under the instance name
+    pub records: Vec<ExtraRecord>,
+}
+
+/// Record attached to a registration besides its PTR, SRV and TXT, such as
+/// the NULL record AirPrint expects
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtraRecord {
+    pub rrtype: u16,
+    /// Wire-format rdata
+    pub rdata: Vec<u8>,
+    /// TTL in seconds (None = the advertisement's `ttl`, else the backend default)
+    pub ttl: Option<u32>,
+}
+
+/// Options for a one-shot service lookup
+#[derive(Debug, Clone)]
+pub struct FindOptions {
+    /// Browse configuration (type, domain, interface, filters...)
+    pub browse: BrowseOptions,
+    /// Instance name to match (case-insensitive, None = any)
+    pub name: Option<String>,
+    /// How long to wait for a match before giving up
+    pub timeout: Duration,
+}
+
+impl FindOptions {
+    /// Whether a resolved service has the wanted name and passes the
+    /// browse's result filters
+    pub fn matches(&self, info: &crate::ServiceInfo) -> bool {
+        self.name.as_ref().is_none_or(|name| name.eq_ignore_ascii_case(&info.name)) && self.browse.result_filter.matches(info)
+    }
+}
+
+/// Read an optional boolean property
+pub fn get_bool<'cx>(cx: &mut impl Context<'cx>, obj: Handle<'cx, JsObject>, key: &str) -> NeonResult<Option<bool>> {
+    Ok(obj.get_opt::<JsBoolean, _, _>(cx, key)?.map(|v| v.value(cx)))
+}
+
+/// Read an optional number property
+pub fn get_number<'cx>(cx: &mut impl Context<'cx>, obj: Handle<'cx, JsObject>, key: &str) -> NeonResult<Option<f64>> {
+    Ok(obj.get_opt::<JsNumber, _, _>(cx, key)?.map(|v| v.value(cx)))
+}
+
+/// Read an optional `interface` property: an index or `"local-only"`
+fn get_interface<'cx>(cx: &mut FunctionContext<'cx>, obj: Handle<'cx, JsObject>) -> NeonResult<Option<u32>> {
+    let Some(value) = obj.get_opt::<JsValue, _, _>(cx, "interface")? else {
+        return Ok(None);
+    };
+    if let Ok(index) = value.downcast::<JsNumber, _>(cx) {
+        return Ok(Some(index.value(cx) as u32));
+    }
+    let text = value.to_string(cx)?.value(cx);
+    match interfaces::parse_interface(&text) {
+        Ok(index) => Ok(Some(index)),
+        Err(e) => cx.throw_type_error(e),
+    }
+}
+
+/// Read an optional string property
+pub fn get_string<'cx>(cx: &mut impl Context<'cx>, obj: Handle<'cx, JsObject>, key: &str) -> NeonResult<Option<String>> {
+    Ok(obj.get_opt::<JsString, _, _>(cx, key)?.map(|v| v.value(cx)))
+}
+
+/// Convert a JS array of strings
+pub fn string_array<'cx>(cx: &mut impl Context<'cx>, arr: Handle<'cx, JsArray>) -> NeonResult<Vec<String>> {
+    let mut out = Vec::new();
+    for value in arr.to_vec(cx)? {
+        let value = value.downcast_or_throw::<JsString, _>(cx)?;
+        out.push(value.value(cx));
+    }
+    Ok(out)
+}
+
+/// Read an optional millisecond property as a Duration
+pub fn get_duration<'cx>(cx: &mut impl Context<'cx>, obj: Handle<'cx, JsObject>, key: &str) -> NeonResult<Option<Duration>> {
+    Ok(get_number(cx, obj, key)?.map(|ms| Duration::from_millis(ms.max(0.0) as u64)))
+}
+
+/// Read `nameFilter`: a glob string or a RegExp
+fn parse_name_filter<'cx>(cx: &mut FunctionContext<'cx>, value: Handle<'cx, JsValue>) -> NeonResult<NamePattern> {
+    if let Ok(glob) = value.downcast::<JsString, _>(cx) {
+        return Ok(NamePattern::Glob(glob.value(cx)));
+    }
+    let regex = value.downcast::<JsObject, _>(cx).ok();
+    let source = match regex {
+        Some(regex) => get_string(cx, regex, "source")?,
+        None => None,
+    };
+    let (Some(regex), Some(source)) = (regex, source) else {
+        return cx.throw_type_error("nameFilter must be a string or a RegExp");
+    };
+    let flags = get_string(cx, regex, "flags")?.unwrap_or_default();
+    NamePattern::regex(&source, &flags).or_else(|e| cx.throw_type_error(e))
+}
+
+/// Parse browse options from JS object into `opts`
+pub fn parse_browse_options<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    obj: Handle<'cx, JsObject>,
+    opts: &mut BrowseOptions,
+) -> NeonResult<()> {
+    if let Some(stype) = get_string(cx, obj, "type")? {
+        opts.service_type = stype;
+    }
+    if let Some(domain) = get_string(cx, obj, "domain")? {
+        opts.domain = Some(domain);
+    }
+    if let Some(all) = get_bool(cx, obj, "browseAllDomains")? {
+        opts.all_domains = all;
+    }
+    if let Some(index) = get_interface(cx, obj)? {
+        opts.interface_index = index;
+    }
+    if let Some(names) = obj.get_opt::<JsArray, _, _>(cx, "interfaces")? {
+        opts.interfaces = string_array(cx, names)?;
+    }
+    if let Some(flags) = get_number(cx, obj, "flags")? {
+        opts.flags = flags as u32;
+    }
+    if cfg!(target_vendor = "apple") && get_bool(cx, obj, "includeP2P")? == Some(true) {
+        // Services found over AWDL only resolve with the flag too
+        opts.add_flags(K_DNS_SERVICE_FLAGS_INCLUDE_P2P);
+    }
+    if get_bool(cx, obj, "forceMulticast")? == Some(true) {
+        opts.force_multicast();
+    }
+    if let Some(profile) = get_bool(cx, obj, "profile")? {
+        opts.resolve.profile = profile;
+    }
+    if let Some(validate) = get_bool(cx, obj, "validate")? {
+        opts.resolve.validate = validate;
+    }
+    if let Some(protocol) = get_string(cx, obj, "protocol")? {
+        match protocol.as_str() {
+            "any" => {}
+            "ipv4" => {
+                opts.resolve.protocol = crate::ffi::K_DNS_SERVICE_PROTOCOL_IPV4;
+                opts.address_filter.exclude_ipv6 = true;
+            }
+            "ipv6" => {
+                opts.resolve.protocol = crate::ffi::K_DNS_SERVICE_PROTOCOL_IPV6;
+                opts.address_filter.exclude_ipv4 = true;
+            }
+            other => return cx.throw_error(format!("Invalid protocol: {}", other)),
+        }
+    }
+
+    if let Some(batch) = get_bool(cx, obj, "batch")? {
+        opts.batch = batch;
+    }
+    if let Some(interval) = get_duration(cx, obj, "batchIntervalMs")? {
+        opts.batch_interval = interval;
+    }
+    if get_bool(cx, obj, "dedup")? == Some(false) {
+        opts.interface_mode = InterfaceMode::Raw;
+    }
+    if get_bool(cx, obj, "perInterface")? == Some(true) {
+        opts.interface_mode = InterfaceMode::PerInterface;
+    }
+    if let Some(v) = get_bool(cx, obj, "excludeLoopback")? {
+        opts.address_filter.exclude_loopback = v;
+    }
+    if let Some(v) = get_bool(cx, obj, "excludeLinkLocal")? {
+        opts.address_filter.exclude_link_local = v;
+    }
+    if let Some(v) = get_bool(cx, obj, "excludeSiteLocal")? {
+        opts.address_filter.exclude_site_local = v;
+    }
+    if let Some(value) = obj.get_opt::<JsValue, _, _>(cx, "nameFilter")? {
+        opts.result_filter.name = Some(parse_name_filter(cx, value)?);
+    }
+    if let Some(names) = obj.get_opt::<JsArray, _, _>(cx, "ignoreNames")? {
+        opts.result_filter.names.ignore = string_array(cx, names)?;
+    }
+    if let Some(names) = obj.get_opt::<JsArray, _, _>(cx, "onlyNames")? {
+        opts.result_filter.names.only = string_array(cx, names)?;
+    }
+    if let Some(txt) = obj.get_opt::<JsObject, _, _>(cx, "txtFilter")? {
+        opts.result_filter.txt = parse_txt(cx, txt, false)?.into_iter().collect();
+    }
+    if let Some(resolve) = get_bool(cx, obj, "resolve")? {
+        opts.resolve_services = resolve;
+    }
+    let wait_for_complete = get_bool(cx, obj, "waitForComplete")?.unwrap_or(false);
+    let min_addresses = get_number(cx, obj, "minimumAddresses")?;
+    if min_addresses.is_some_and(|n| n < 1.0) {
+        return cx.throw_error("minimumAddresses must be at least 1");
+    }
+    // Asking for either implies holding partial results back
+    let emit_partial = get_bool(cx, obj, "emitPartial")?.unwrap_or(!wait_for_complete && min_addresses.is_none());
+    opts.completion = (!emit_partial).then(|| Completion {
+        each_family: wait_for_complete,
+        min_addresses: min_addresses.map_or(1, |n| n as usize),
+    });
+    if let Some(timeout) = get_duration(cx, obj, "resolveTimeoutMs")? {
+        opts.resolve.resolve_timeout = timeout;
+    }
+    if let Some(timeout) = get_duration(cx, obj, "addressTimeoutMs")? {
+        opts.resolve.address_timeout = timeout;
+    }
+    if let Some(retries) = get_number(cx, obj, "resolveRetries")? {
+        if retries < 0.0 {
+            return cx.throw_error("resolveRetries must not be negative");
+        }
+        opts.resolve.retries = retries as u32;
+    }
+    if let Some(delay) = get_duration(cx, obj, "resolveRetryDelayMs")? {
+        opts.resolve.retry_delay = delay;
+    }
+    if let Some(watch) = get_bool(cx, obj, "watchAddresses")? {
+        opts.resolve.watch_addresses = watch;
+    }
+    if let Some(strict) = get_bool(cx, obj, "strictTxt")? {
+        opts.strict_txt = strict;
+    }
+    if let Some(size) = get_number(cx, obj, "queueSize")? {
+        if size < 1.0 {
+            return cx.throw_error("queueSize must be at least 1");
+        }
+        opts.queue_size = size as usize;
+    }
+    if let Some(debounce) = get_duration(cx, obj, "debounceMs")? {
+        opts.debounce = debounce;
+    }
+    if let Some(settle) = get_duration(cx, obj, "settleMs")? {
+        opts.settle = settle;
+    }
+    if let Some(interval) = get_duration(cx, obj, "refreshIntervalMs")? {
+        // Shorter than twice the grace, services would expire mid-refresh
+        opts.refresh_interval = (!interval.is_zero()).then(|| interval.max(2 * crate::shared::REFRESH_GRACE));
+    }
+    if let Some(rate) = get_number(cx, obj, "maxEventsPerSecond")? {
+        if rate <= 0.0 {
+            return cx.throw_error("maxEventsPerSecond must be positive");
+        }
+        opts.max_events_per_second = Some(rate);
+    }
+    if let Some(policy) = get_string(cx, obj, "overflow")? {
+        match OverflowPolicy::from_name(&policy) {
+            Some(policy) => opts.overflow = policy,
+            None => return cx.throw_error(format!("Invalid overflow policy: {}", policy)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse `{ type: 'NULL' | 10, data: Uint8Array, ttl? }`
+fn parse_extra_record<'cx>(cx: &mut FunctionContext<'cx>, obj: Handle<'cx, JsObject>) -> NeonResult<ExtraRecord> {
+    let rrtype = match obj.get_opt::<JsValue, _, _>(cx, "type")? {
+        Some(v) if v.is_a::<JsNumber, _>(cx) => Some(v.downcast_or_throw::<JsNumber, _>(cx)?.value(cx)).filter(|t| (1.0..=65535.0).contains(t)).map(|t| t as u16),
+        Some(v) if v.is_a::<JsString, _>(cx) => query::parse_rrtype(&v.downcast_or_throw::<JsString, _>(cx)?.value(cx)),
+        _ => None,
+    };
+    let Some(rrtype) = rrtype else {
+        return cx.throw_type_error("records[].type must be a record type such as 'NULL' or 10");
+    };
+    let Some(data) = obj.get_opt::<JsTypedArray<u8>, _, _>(cx, "data")? else {
+        return cx.throw_type_error("records[].data must be a Uint8Array of rdata");
+    };
+    let rdata = data.as_slice(cx).to_vec();
+    if rdata.len() > u16::MAX as usize {
+        return cx.throw_type_error("records[].data is longer than 65535 bytes");
+    }
+    let ttl = get_number(cx, obj, "ttl")?.map(|ttl| ttl.max(0.0) as u32);
+    Ok(ExtraRecord { rrtype, rdata, ttl })
+}
+
+/// Parse a TXT object (`{ key: "value" }`), keeping the object's key order
+///
+/// `strict` throws a TypeError for keys RFC 6763 doesn't allow, and for
+/// keys differing only in case, instead of publishing them.
+pub fn parse_txt<'cx>(cx: &mut FunctionContext<'cx>, txt_obj: Handle<'cx, JsObject>, strict: bool) -> NeonResult<TxtRecord> {
+    let keys = txt_obj.get_own_property_names(cx)?;
+    let len = keys.len(cx);
+    let mut txt = TxtRecord::new();
+    for i in 0..len {
+        let key: Handle<JsString> = keys.get(cx, i)?;
+        let key_str = key.value(cx);
+        let val: Handle<JsString> = txt_obj.get(cx, key_str.as_str())?;
+        let val = val.value(cx);
+        if strict {
+            txt.insert_strict(&key_str, &val).or_else(|e| cx.throw_type_error(e))?;
+        } else {
+            txt.insert(key_str, val);
+        }
+    }
+    Ok(txt)
+}
+
+/// Parse advertise options from JS object
+pub fn parse_advertise_options<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    obj: Handle<'cx, JsObject>,
+) -> NeonResult<AdvertiseOptions> {
+    let mut opts = AdvertiseOptions::default();
+
+    match get_string(cx, obj, "name")? {
+        Some(name) => opts.name = name,
+        None => return cx.throw_type_error("options.name is required"),
+    }
+    match get_string(cx, obj, "type")? {
+        Some(stype) => opts.service_type = stype,
+        None => return cx.throw_type_error("options.type is required"),
+    }
+    match get_number(cx, obj, "port")? {
+        Some(port) => opts.port = port as u16,
+        None => return cx.throw_type_error("options.port is required"),
+    }
+    if let Some(txt) = obj.get_opt::<JsObject, _, _>(cx, "txt")? {
+        let strict = get_bool(cx, obj, "strictTxt")? == Some(true);
+        opts.txt = Some(parse_txt(cx, txt, strict)?);
+    }
+    opts.txtvers_first = get_bool(cx, obj, "txtversFirst")?.unwrap_or(false);
+    opts.domain = get_string(cx, obj, "domain")?;
+    opts.host = get_string(cx, obj, "host")?;
+    if let Some(index) = get_interface(cx, obj)? {
+        opts.interface_index = index;
+    }
+    if let Some(names) = obj.get_opt::<JsArray, _, _>(cx, "interfaces")? {
+        opts.interfaces = string_array(cx, names)?;
+    }
+    if let Some(subtypes) = obj.get_opt::<JsArray, _, _>(cx, "subtypes")? {
+        opts.subtypes = string_array(cx, subtypes)?;
+    }
+    opts.no_auto_rename = get_bool(cx, obj, "noAutoRename")?.unwrap_or(false);
+    opts.allow_remote_query = get_bool(cx, obj, "allowRemoteQuery")?.unwrap_or(false);
+    // Other daemons have no P2P interfaces, and Avahi rejects the flag
+    opts.include_p2p = cfg!(target_vendor = "apple") && get_bool(cx, obj, "includeP2P")?.unwrap_or(false);
+    if let Some(addresses) = obj.get_opt::<JsArray, _, _>(cx, "addresses")? {
+        opts.addresses = string_array(cx, addresses)?;
+        if let Some(bad) = opts.addresses.iter().find(|a| a.parse::<std::net::IpAddr>().is_err()) {
+            return cx.throw_type_error(format!("Invalid IP address: {}", bad));
+        }
+    }
+    if let Some(records) = obj.get_opt::<JsArray, _, _>(cx, "records")? {
+        for record in records.to_vec(cx)? {
+            let record = record.downcast_or_throw::<JsObject, _>(cx)?;
+            opts.records.push(parse_extra_record(cx, record)?);
+        }
+    }
+    opts.ttl = get_number(cx, obj, "ttl")?.map(|ttl| ttl.max(0.0) as u32);
+    opts.host_ttl = get_number(cx, obj, "hostTtl")?.map(|ttl| ttl.max(0.0) as u32);
+
+    Ok(opts)
+}
+
+/// Parse find options from JS object
+pub fn parse_find_options<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    obj: Handle<'cx, JsObject>,
+) -> NeonResult<FindOptions> {
+    let mut browse = BrowseOptions::default();
+    parse_browse_options(cx, obj, &mut browse)?;
+    if browse.service_type.is_empty() {
+        return cx.throw_type_error("options.type is required");
+    }
+
+    Ok(FindOptions {
+        browse,
+        name: get_string(cx, obj, "name")?,
+        timeout: get_duration(cx, obj, "timeoutMs")?.unwrap_or(Duration::from_secs(5)),
+    })
+}
+
+/// Shortest wait between a browser's queries
+const MIN_QUERY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Parse fallback daemon options from JS object on top of `config`
+pub fn parse_fallback_config<'cx>(
+    cx: &mut FunctionContext<'cx>,
+    obj: Handle<'cx, JsObject>,
+    config: &mut FallbackConfig,
+) -> NeonResult<()> {
+    if let Some(interfaces) = obj.get_opt::<JsArray, _, _>(cx, "interfaces")? {
+        config.interfaces = string_array(cx, interfaces)?;
+    }
+    if let Some(v) = get_bool(cx, obj, "ipv4")? {
+        config.ipv4 = v;
+    }
+    if let Some(v) = get_bool(cx, obj, "ipv6")? {
+        config.ipv6 = v;
+    }
+    if !config.ipv4 && !config.ipv6 {
+        return cx.throw_error("At least one of ipv4 and ipv6 must be enabled");
+    }
+    if let Some(v) = get_bool(cx, obj, "multicastLoop")? {
+        config.multicast_loop_v4 = v;
+        config.multicast_loop_v6 = v;
+    }
+    if let Some(secs) = get_number(cx, obj, "ipCheckIntervalSecs")? {
+        config.ip_check_interval = Some(secs.max(0.0) as u32);
+    }
+    // Any cadence field replaces mdns-sd's schedule; the others keep its timings
+    let mut cadence = config.cadence.unwrap_or_default();
+    let burst = get_number(cx, obj, "queryBurst")?;
+    if let Some(burst) = burst {
+        cadence.burst = burst.max(1.0) as u32;
+    }
+    let burst_interval = get_duration(cx, obj, "queryBurstIntervalMs")?;
+    if let Some(interval) = burst_interval {
+        cadence.burst_interval = interval;
+    }
+    let retry_interval = get_duration(cx, obj, "queryRetryIntervalMs")?;
+    if let Some(interval) = retry_interval {
+        cadence.retry_interval = interval;
+    }
+    let max_interval = get_duration(cx, obj, "queryMaxIntervalMs")?;
+    if let Some(interval) = max_interval {
+        cadence.max_interval = interval;
+    }
+    if burst.is_some() || burst_interval.is_some() || retry_interval.is_some() || max_interval.is_some() {
+        // Faster than this floods the link for no quicker answers
+        cadence.burst_interval = cadence.burst_interval.max(MIN_QUERY_INTERVAL);
+        cadence.retry_interval = cadence.retry_interval.max(MIN_QUERY_INTERVAL);
+        cadence.max_interval = cadence.max_interval.max(cadence.retry_interval);
+        config.cadence = Some(cadence);
+    }
+
+    Ok(())
+}
+
+/// Parse the DNS Update zone: `{ server, zone, keyName, secret, ttl? }`
+#[cfg(feature = "dns-update")]
+pub fn parse_update_config<'cx>(cx: &mut FunctionContext<'cx>, obj: Handle<'cx, JsObject>) -> NeonResult<UpdateConfig> {
+    let required = |cx: &mut FunctionContext<'cx>, key: &str| match get_string(cx, obj, key)? {
+        Some(value) => Ok(value),
+        None => cx.throw_type_error(format!("{} is required", key)),
+    };
+    let server = required(cx, "server")?;
+    let zone = required(cx, "zone")?;
+    let key_name = required(cx, "keyName")?;
+    let secret = required(cx, "secret")?;
+    let ttl = get_number(cx, obj, "ttl")?.map(|ttl| ttl.max(0.0) as u32);
+    UpdateConfig::new(&server, &zone, &key_name, &secret, ttl).or_else(|e| cx.throw_error(e))
+}
+
+/// Parse the mock backend script: `[{ delayMs?, event, service?, data? }]`
+pub fn parse_mock_script<'cx>(cx: &mut FunctionContext<'cx>, arr: Handle<'cx, JsArray>) -> NeonResult<Vec<MockStep>> {
+    let mut steps = Vec::new();
+    for value in arr.to_vec(cx)? {
+        let obj = value.downcast_or_throw::<JsObject, _>(cx)?;
+        let Some(event) = get_string(cx, obj, "event")? else {
+            return cx.throw_type_error("Mock steps require an event");
+        };
+        let mut info = crate::ServiceInfo {
+            domain: "local".to_string(),
+            ..Default::default()
+        };
+        if let Some(service) = obj.get_opt::<JsObject, _, _>(cx, "service")? {
+            if let Some(name) = get_string(cx, service, "name")? {
+                info.name = name;
+            }
+            if let Some(stype) = get_string(cx, service, "type")? {
+                info.service_type = stype;
+            }
+            if let Some(domain) = get_string(cx, service, "domain")? {
+                info.domain = domain;
+            }
+            if let Some(host) = get_string(cx, service, "hostName")? {
+                info.host_name = host;
+            }
+            if let Some(port) = get_number(cx, service, "port")? {
+                info.port = port as u16;
+            }
+            if let Some(priority) = get_number(cx, service, "priority")? {
+                info.priority = priority as u16;
+            }
+            if let Some(weight) = get_number(cx, service, "weight")? {
+                info.weight = weight as u16;
+            }
+            if let Some(addresses) = service.get_opt::<JsArray, _, _>(cx, "addresses")? {
+                info.addresses = string_array(cx, addresses)?;
+            }
+            if let Some(txt) = service.get_opt::<JsObject, _, _>(cx, "txt")? {
+                info.txt = parse_txt(cx, txt, false)?.into_iter().collect();
+            }
+            if let Some(ttl) = get_number(cx, service, "ttl")? {
+                info.ttl = ttl as u32;
+            }
+            if let Some(index) = get_number(cx, service, "interfaceIndex")? {
+                info.interface_index = index as u32;
+            }
+            if let Some(flags) = get_number(cx, service, "flags")? {
+                info.flags = Some(flags as u32);
+            }
+        }
+        steps.push(MockStep {
+            delay: get_duration(cx, obj, "delayMs")?.unwrap_or_default(),
+            event,
+            info,
+            data: get_string(cx, obj, "data")?,
+        });
+    }
+    Ok(steps)
+}
@@ -0,0 +1,66 @@
+//! Re-detection of the native backend after startup
+//!
+//! Detection normally happens once, so Bonjour installed, or avahi-daemon
+//! started, after the process would go unnoticed. `reprobe` retries the
+//! library load and asks the daemon again, on demand or on a timer, and
+//! reports a `backendChanged` when the backend chosen for new handles
+//! differs as a result. Running handles keep their backend.
+
+use crate::{Backend, Sink, get_backend, native};
+use neon::event::Channel;
+use neon::handle::Root;
+use neon::prelude::*;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// JS function receiving `(backend)`
+static SINK: Sink = Sink::new();
+
+/// Stops the running probe timer when dropped or sent to
+static TIMER: Mutex<Option<Sender<()>>> = Mutex::new(None);
+
+/// Deliver `backendChanged` notifications to `callback`, or stop with `None`
+pub fn set_callback(sink: Option<(Channel, Root<JsFunction>)>) {
+    SINK.set(sink);
+}
+
+/// Probe every `interval` until the native backend is available, or stop with `None`
+pub fn set_interval(interval: Option<Duration>) {
+    let mut timer = TIMER.lock().unwrap();
+    // Dropping the sender ends the previous timer
+    *timer = None;
+    let Some(interval) = interval else { return };
+    if native::is_available() {
+        return;
+    }
+    let (tx, rx) = mpsc::channel::<()>();
+    thread::spawn(move || {
+        while let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(interval) {
+            reprobe();
+            // Once the daemon answers, a stop is left to automatic fallback
+            if native::is_available() {
+                break;
+            }
+        }
+    });
+    *timer = Some(tx);
+}
+
+/// Retry native detection, returning the backend new handles will use
+pub fn reprobe() -> Backend {
+    let before = get_backend();
+    native::reprobe();
+    let after = get_backend();
+    if after != before {
+        tracing::info!("Backend changed from {} to {}", before.name(), after.name());
+        notify(after);
+    }
+    after
+}
+
+fn notify(backend: Backend) {
+    let name = backend.name();
+    SINK.call(move |cx| Ok(vec![cx.string(name).upcast()]));
+}
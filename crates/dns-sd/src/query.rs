@@ -0,0 +1,279 @@
+//! DNS record queries, one-shot or watched
+
+use crate::ffi::*;
+use crate::{Backend, fallback, native};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// Answer to a record query, with rdata decoded for display
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub rrtype: u16,
+    pub ttl: u32,
+    pub data: String,
+    /// DNS-SD flags delivered with the answer (native backend only)
+    pub flags: Option<u32>,
+    /// DNSSEC result, when validation was requested and has finished
+    pub dnssec: Option<DnssecStatus>,
+}
+
+/// Outcome of DNSSEC validation of an answer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DnssecStatus {
+    /// Signed, and the signatures chain up to a trust anchor
+    Secure,
+    /// Provably unsigned: the zone or a parent opted out
+    Insecure,
+    /// Signed, but the signatures don't verify
+    Bogus,
+    /// The daemon couldn't tell, e.g. a resolver stripped the signatures
+    Indeterminate,
+}
+
+impl DnssecStatus {
+    /// Status carried by answer `flags`; None until the answer was validated
+    pub fn from_flags(flags: DNSServiceFlags) -> Option<Self> {
+        [
+            (K_DNS_SERVICE_FLAGS_SECURE, DnssecStatus::Secure),
+            (K_DNS_SERVICE_FLAGS_INSECURE, DnssecStatus::Insecure),
+            (K_DNS_SERVICE_FLAGS_BOGUS, DnssecStatus::Bogus),
+            (K_DNS_SERVICE_FLAGS_INDETERMINATE, DnssecStatus::Indeterminate),
+        ]
+        .into_iter()
+        .find(|(bits, _)| flags & bits == *bits)
+        .map(|(_, status)| status)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DnssecStatus::Secure => "secure",
+            DnssecStatus::Insecure => "insecure",
+            DnssecStatus::Bogus => "bogus",
+            DnssecStatus::Indeterminate => "indeterminate",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        [DnssecStatus::Secure, DnssecStatus::Insecure, DnssecStatus::Bogus, DnssecStatus::Indeterminate]
+            .into_iter()
+            .find(|status| status.as_str() == s)
+    }
+}
+
+const RRTYPE_NAMES: &[(&str, u16)] = &[
+    ("A", K_DNS_SERVICE_TYPE_A),
+    ("CNAME", K_DNS_SERVICE_TYPE_CNAME),
+    ("NULL", K_DNS_SERVICE_TYPE_NULL),
+    ("PTR", K_DNS_SERVICE_TYPE_PTR),
+    ("TXT", K_DNS_SERVICE_TYPE_TXT),
+    ("AAAA", K_DNS_SERVICE_TYPE_AAAA),
+    ("SRV", K_DNS_SERVICE_TYPE_SRV),
+    ("ANY", K_DNS_SERVICE_TYPE_ANY),
+];
+
+/// Record type by mnemonic (`"AAAA"`) or number (`"28"`)
+pub fn parse_rrtype(name: &str) -> Option<u16> {
+    RRTYPE_NAMES
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, t)| *t)
+        .or_else(|| name.parse().ok())
+}
+
+/// Mnemonic for a record type, or `TYPE<n>` (RFC 3597) if unknown
+pub fn rrtype_name(rrtype: u16) -> String {
+    RRTYPE_NAMES
+        .iter()
+        .find(|(_, t)| *t == rrtype)
+        .map(|(n, _)| n.to_string())
+        .unwrap_or_else(|| format!("TYPE{}", rrtype))
+}
+
+/// Query `name` for records of `rrtype`, collecting answers for `timeout`
+///
+/// `force_multicast` asks the native daemon to use mDNS even for names
+/// outside `.local`. `validate` asks it to check DNSSEC signatures, which
+/// needs mDNSResponder and a signed zone; answers then carry a
+/// [`DnssecStatus`] once validation finishes within `timeout`. The fallback
+/// backend can only look up addresses of `.local` hosts, always over
+/// multicast and never validated.
+///
+/// The native query passes `kDNSServiceFlagsTimeout` where the daemon takes
+/// it; if the daemon gives up before any answer arrives, this fails with a
+/// timeout error rather than returning no records.
+pub fn query(backend: Backend, name: &str, rrtype: u16, force_multicast: bool, validate: bool, timeout: Duration) -> Result<Vec<Record>, String> {
+    let mut flags = if force_multicast { K_DNS_SERVICE_FLAGS_FORCE_MULTICAST } else { 0 };
+    if validate {
+        flags |= K_DNS_SERVICE_FLAGS_VALIDATE;
+    }
+    match backend {
+        Backend::Native => Ok(native::query_record(name, rrtype, 0, flags, timeout)?
+            .into_iter()
+            .map(|(rrtype, rdata, ttl, flags)| Record {
+                rrtype,
+                ttl,
+                data: decode_rdata(rrtype, &rdata),
+                flags: Some(flags),
+                dnssec: DnssecStatus::from_flags(flags),
+            })
+            .collect()),
+        Backend::Mock => Err("The mock backend does not answer record queries".to_string()),
+        Backend::Fallback => {
+            let wanted = fallback_filter(rrtype)?;
+            Ok(fallback::resolve_hostname(name, timeout)?
+                .into_iter()
+                .filter(|addr| wanted(addr))
+                .map(address_record)
+                .collect())
+        }
+    }
+}
+
+/// A record query kept open by [`watch`]; stops when dropped
+pub struct QueryWatch {
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl QueryWatch {
+    /// End the query, waiting for its last callback to return
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for QueryWatch {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Query `name` for records of `rrtype` and keep the query open,
+/// calling `on_change` with `recordAdded` or `recordRemoved` as the
+/// answers change, until the returned watch is stopped
+///
+/// Devices keep their host name across DHCP renewals and interface
+/// switches, so watching their address records follows them where a
+/// one-shot [`query`] would go stale. The native query runs without
+/// `kDNSServiceFlagsTimeout`. The fallback backend watches the addresses
+/// of `.local` hosts only.
+pub fn watch<F>(backend: Backend, name: &str, rrtype: u16, force_multicast: bool, validate: bool, on_change: F) -> Result<QueryWatch, String>
+where
+    F: Fn(&str, Record) + Send + 'static,
+{
+    let stop = Arc::new(AtomicBool::new(false));
+    let event = |added: bool| if added { "recordAdded" } else { "recordRemoved" };
+    let thread = match backend {
+        Backend::Native => {
+            let mut flags = if force_multicast { K_DNS_SERVICE_FLAGS_FORCE_MULTICAST } else { 0 };
+            if validate {
+                flags |= K_DNS_SERVICE_FLAGS_VALIDATE;
+            }
+            native::watch_record(name, rrtype, 0, flags, stop.clone(), move |added, (rrtype, rdata, ttl, flags)| {
+                let record = Record {
+                    rrtype,
+                    ttl,
+                    data: decode_rdata(rrtype, &rdata),
+                    flags: Some(flags),
+                    dnssec: DnssecStatus::from_flags(flags),
+                };
+                on_change(event(added), record)
+            })?
+        }
+        Backend::Mock => return Err("The mock backend does not answer record queries".to_string()),
+        Backend::Fallback => {
+            let wanted = fallback_filter(rrtype)?;
+            fallback::watch_hostname(name, stop.clone(), move |added, addr| {
+                if wanted(&addr) {
+                    on_change(event(added), address_record(addr));
+                }
+            })?
+        }
+    };
+    Ok(QueryWatch { stop, thread: Some(thread) })
+}
+
+/// Which addresses the fallback reports for `rrtype`, which must ask for
+/// addresses since that is all mdns-sd looks up
+fn fallback_filter(rrtype: u16) -> Result<impl Fn(&IpAddr) -> bool + Send + 'static, String> {
+    let want_a = matches!(rrtype, K_DNS_SERVICE_TYPE_A | K_DNS_SERVICE_TYPE_ANY);
+    let want_aaaa = matches!(rrtype, K_DNS_SERVICE_TYPE_AAAA | K_DNS_SERVICE_TYPE_ANY);
+    if !want_a && !want_aaaa {
+        return Err(format!("{} queries require the native backend", rrtype_name(rrtype)));
+    }
+    Ok(move |addr: &IpAddr| if addr.is_ipv4() { want_a } else { want_aaaa })
+}
+
+/// Address found by mdns-sd, as the A or AAAA record it came from
+fn address_record(addr: IpAddr) -> Record {
+    Record {
+        rrtype: if addr.is_ipv4() { K_DNS_SERVICE_TYPE_A } else { K_DNS_SERVICE_TYPE_AAAA },
+        ttl: 0,
+        data: addr.to_string(),
+        flags: None,
+        dnssec: None,
+    }
+}
+
+/// Render rdata in presentation format, or hex for types we don't decode
+fn decode_rdata(rrtype: u16, rdata: &[u8]) -> String {
+    match rrtype {
+        K_DNS_SERVICE_TYPE_A if rdata.len() == 4 => {
+            IpAddr::V4(Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3])).to_string()
+        }
+        K_DNS_SERVICE_TYPE_AAAA if rdata.len() == 16 => {
+            let bytes: [u8; 16] = rdata.try_into().unwrap();
+            IpAddr::V6(Ipv6Addr::from(bytes)).to_string()
+        }
+        K_DNS_SERVICE_TYPE_PTR | K_DNS_SERVICE_TYPE_CNAME => {
+            decode_name(rdata).unwrap_or_else(|| hex(rdata))
+        }
+        K_DNS_SERVICE_TYPE_SRV if rdata.len() > 6 => {
+            let field = |i: usize| u16::from_be_bytes([rdata[i], rdata[i + 1]]);
+            match decode_name(&rdata[6..]) {
+                Some(target) => format!("{} {} {} {}", field(0), field(2), field(4), target),
+                None => hex(rdata),
+            }
+        }
+        K_DNS_SERVICE_TYPE_TXT => {
+            let mut strings = Vec::new();
+            let mut i = 0;
+            while i < rdata.len() {
+                let len = rdata[i] as usize;
+                let Some(s) = rdata.get(i + 1..i + 1 + len) else {
+                    return hex(rdata);
+                };
+                strings.push(format!("\"{}\"", String::from_utf8_lossy(s)));
+                i += 1 + len;
+            }
+            strings.join(" ")
+        }
+        _ => hex(rdata),
+    }
+}
+
+/// Uncompressed wire-format domain name (rdata from the daemon is never compressed)
+fn decode_name(data: &[u8]) -> Option<String> {
+    let mut labels = Vec::new();
+    let mut i = 0;
+    loop {
+        let len = *data.get(i)? as usize;
+        if len == 0 {
+            break;
+        }
+        let label = data.get(i + 1..i + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        i += 1 + len;
+    }
+    Some(format!("{}.", labels.join(".")))
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
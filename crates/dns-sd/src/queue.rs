@@ -0,0 +1,221 @@
+//! Per-handle queue of browse events waiting for the JS thread
+//!
+//! Events are queued here rather than sent as one channel closure each. At
+//! most one drain closure per handle is in flight, so a stalled JS thread
+//! holds at most `capacity` events per handle; what happens past that is
+//! the handle's [`OverflowPolicy`].
+
+use crate::instances::instance_key;
+use crate::{ServiceInfo, metrics, service_info_to_js};
+use neon::event::Channel;
+use neon::handle::Root;
+use neon::prelude::*;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Events a handle may hold before its overflow policy applies
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// What to do with an event that arrives while the queue is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Discard the oldest queued event
+    #[default]
+    DropOldest,
+    /// Merge into the latest queued event for the same instance, else discard the oldest
+    Coalesce,
+    /// Discard the new event and report `queueOverflow` before the next delivery
+    Error,
+}
+
+impl OverflowPolicy {
+    /// Parse `dropOldest`, `coalesce` or `error`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "dropOldest" => Some(OverflowPolicy::DropOldest),
+            "coalesce" => Some(OverflowPolicy::Coalesce),
+            "error" => Some(OverflowPolicy::Error),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    pending: VecDeque<(String, ServiceInfo)>,
+    /// A drain closure is queued on the channel
+    scheduled: bool,
+    /// Events were discarded under [`OverflowPolicy::Error`] since the last drain
+    overflowed: bool,
+}
+
+/// Make room by discarding the oldest event, then queue the new one
+///
+/// A discarded `serviceFound` promotes the instance's next queued update to
+/// a find, so JS never sees updates for a service it wasn't told about.
+fn drop_oldest(pending: &mut VecDeque<(String, ServiceInfo)>, event: String, info: ServiceInfo) {
+    let dropped = pending.pop_front();
+    pending.push_back((event, info));
+    if let Some((dropped, lost)) = dropped
+        && dropped == "serviceFound"
+    {
+        let key = instance_key(&lost);
+        let next = pending.iter_mut().find(|(queued, other)| queued != "batchComplete" && instance_key(other) == key);
+        if let Some((next, _)) = next
+            && next == "serviceUpdated"
+        {
+            *next = "serviceFound".to_string();
+        }
+    }
+}
+
+/// Bounded queue between a browser's backend thread and its JS callback
+pub struct EventQueue {
+    state: Mutex<State>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    /// Batch interval; None delivers events one by one as they arrive
+    batch: Option<Duration>,
+    channel: Channel,
+    callback: Arc<Root<JsFunction>>,
+}
+
+impl EventQueue {
+    pub fn new(
+        channel: Channel,
+        callback: Arc<Root<JsFunction>>,
+        capacity: usize,
+        policy: OverflowPolicy,
+        batch: Option<Duration>,
+    ) -> Arc<Self> {
+        Arc::new(EventQueue {
+            state: Mutex::new(State::default()),
+            capacity: capacity.max(1),
+            policy,
+            batch,
+            channel,
+            callback,
+        })
+    }
+
+    /// Events waiting for the JS thread
+    pub fn depth(&self) -> usize {
+        self.state.lock().unwrap().pending.len()
+    }
+
+    /// Queue an event; batching queues flush at batch boundaries or after the interval
+    pub fn push(self: &Arc<Self>, event: String, info: ServiceInfo) {
+        let flush_now = self.batch.is_none() || event == "batchComplete";
+        let first = {
+            let mut state = self.state.lock().unwrap();
+            let first = state.pending.is_empty();
+            self.enqueue(&mut state, event, info);
+            first
+        };
+
+        if flush_now {
+            self.schedule();
+        } else if first && let Some(interval) = self.batch {
+            // First event of a new batch arms the flush timer
+            let queue = self.clone();
+            thread::spawn(move || {
+                thread::sleep(interval);
+                queue.schedule();
+            });
+        }
+    }
+
+    fn enqueue(&self, state: &mut State, event: String, info: ServiceInfo) {
+        if state.pending.len() < self.capacity {
+            state.pending.push_back((event, info));
+            return;
+        }
+        metrics::event_dropped();
+        match self.policy {
+            OverflowPolicy::DropOldest => drop_oldest(&mut state.pending, event, info),
+            OverflowPolicy::Coalesce => {
+                // Batch markers merge with each other, events with their instance's
+                let key = instance_key(&info);
+                let latest = state.pending.iter().rposition(|(queued, other)| {
+                    (queued == "batchComplete") == (event == "batchComplete") && instance_key(other) == key
+                });
+                let Some(i) = latest else {
+                    drop_oldest(&mut state.pending, event, info);
+                    return;
+                };
+                // JS hasn't seen a queued `serviceFound` yet, so it must stay a find
+                match (state.pending[i].0.as_str(), event.as_str()) {
+                    ("batchComplete", _) => {
+                        state.pending.remove(i);
+                        state.pending.push_back((event, info));
+                    }
+                    ("serviceFound", "serviceLost") => {
+                        state.pending.remove(i);
+                        metrics::event_dropped();
+                    }
+                    ("serviceFound", _) => state.pending[i].1 = info,
+                    _ => state.pending[i] = (event, info),
+                }
+            }
+            OverflowPolicy::Error => state.overflowed = true,
+        }
+    }
+
+    /// Queue a drain on the JS thread unless one is already pending
+    fn schedule(self: &Arc<Self>) {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.scheduled || (state.pending.is_empty() && !state.overflowed) {
+                return;
+            }
+            state.scheduled = true;
+        }
+        let queue = self.clone();
+        // Fails once the handle's context is torn down; nothing left to deliver to
+        let _ = self.channel.try_send(move |mut cx| queue.drain(&mut cx));
+    }
+
+    /// Deliver everything queued so far
+    fn drain(&self, cx: &mut TaskContext) -> NeonResult<()> {
+        let (events, overflowed) = {
+            let mut state = self.state.lock().unwrap();
+            state.scheduled = false;
+            (std::mem::take(&mut state.pending), std::mem::take(&mut state.overflowed))
+        };
+        let cb = self.callback.to_inner(cx);
+        let this = cx.undefined();
+
+        if overflowed {
+            let event = cx.string("queueOverflow");
+            let info = service_info_to_js(cx, &ServiceInfo::default())?;
+            let _ = cb.call(cx, this, vec![event.upcast(), info.upcast()]);
+        }
+        match self.batch {
+            None => {
+                for (event, info) in &events {
+                    let event = cx.string(event);
+                    let info = service_info_to_js(cx, info)?;
+                    let _ = cb.call(cx, this, vec![event.upcast(), info.upcast()]);
+                }
+            }
+            Some(_) if events.is_empty() => {}
+            Some(_) => {
+                // One `batch` event: `{ event, service }` objects in arrival order
+                let arr = cx.empty_array();
+                for (i, (event, info)) in events.iter().enumerate() {
+                    let entry = cx.empty_object();
+                    let name = cx.string(event);
+                    entry.set(cx, "event", name)?;
+                    let info_obj = service_info_to_js(cx, info)?;
+                    entry.set(cx, "service", info_obj)?;
+                    arr.set(cx, i as u32, entry)?;
+                }
+                let event = cx.string("batch");
+                let _ = cb.call(cx, this, vec![event.upcast(), arr.upcast()]);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+//! Capture backend events to JSONL and replay them through the mock backend
+//!
+//! Each line is one event with its offset from the start of the capture:
+//!
+//! - `{"t":12.5,"backend":"native","browse":"_http._tcp","event":"serviceFound","service":{...}}`
+//! - `{"t":40,"backend":"native","advertise":"My Service","event":"registered","data":"My Service"}`
+//!
+//! Replaying turns the capture into a mock script with the same timing, so
+//! the events pass through the usual filters and instance tracking again.
+
+use crate::mock::{self, MockStep};
+use crate::{AdvertiseData, Backend, ServiceInfo};
+use serde_json::{Map, Value, json};
+use std::fs::File;
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Recorder {
+    out: LineWriter<File>,
+    started: Instant,
+}
+
+static RECORDER: Mutex<Option<Recorder>> = Mutex::new(None);
+
+/// Start writing events to `path`, replacing any capture in progress
+pub fn start_recording(path: &str) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    *RECORDER.lock().unwrap() = Some(Recorder {
+        out: LineWriter::new(file),
+        started: Instant::now(),
+    });
+    Ok(())
+}
+
+/// Stop the capture in progress, returning false if there was none
+pub fn stop_recording() -> bool {
+    RECORDER.lock().unwrap().take().is_some()
+}
+
+fn write(fields: Value) {
+    let mut slot = RECORDER.lock().unwrap();
+    let Some(recorder) = slot.as_mut() else {
+        return;
+    };
+    let t = recorder.started.elapsed().as_micros() as f64 / 1000.0;
+    let mut line = Map::new();
+    line.insert("t".to_string(), t.into());
+    if let Value::Object(fields) = fields {
+        line.extend(fields);
+    }
+    let line = Value::Object(line);
+    if let Err(e) = writeln!(recorder.out, "{}", line) {
+        tracing::warn!("Recording stopped: {}", e);
+        *slot = None;
+    }
+}
+
+fn is_recording() -> bool {
+    RECORDER.lock().unwrap().is_some()
+}
+
+/// Record a browse event delivered by a backend browser
+pub fn record_browse(backend: Backend, service_type: &str, event: &str, info: &ServiceInfo) {
+    if !is_recording() {
+        return;
+    }
+    write(json!({
+        "backend": backend.name(),
+        "browse": service_type,
+        "event": event,
+        "service": Value::from(info),
+    }));
+}
+
+/// Record an advertisement event
+pub fn record_advertisement(backend: Backend, name: &str, event: &str, data: &AdvertiseData) {
+    if !is_recording() {
+        return;
+    }
+    write(json!({
+        "backend": backend.name(),
+        "advertise": name,
+        "event": event,
+        "data": Value::from(data),
+    }));
+}
+
+/// Load a capture as the mock backend's script, returning the number of steps
+pub fn load_recording(path: &str) -> Result<usize, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let mut steps = Vec::new();
+    let mut last = 0.0;
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: Value = serde_json::from_str(&line).map_err(|e| format!("{}:{}: {}", path, number + 1, e))?;
+        let event = entry.get("event").and_then(Value::as_str).unwrap_or_default();
+        // The mock backend derives batch boundaries itself
+        if event.is_empty() || event == "batchComplete" {
+            continue;
+        }
+
+        let t = entry.get("t").and_then(Value::as_f64).unwrap_or(last).max(last);
+        let (info, data) = match entry.get("advertise").and_then(Value::as_str) {
+            Some(name) => (
+                ServiceInfo { name: name.to_string(), ..Default::default() },
+                entry.get("data").and_then(Value::as_str).map(str::to_string),
+            ),
+            None => (entry.get("service").map(ServiceInfo::from).unwrap_or_default(), None),
+        };
+        steps.push(MockStep {
+            delay: Duration::from_secs_f64((t - last) / 1000.0),
+            event: event.to_string(),
+            info,
+            data,
+        });
+        last = t;
+    }
+
+    let count = steps.len();
+    mock::set_script(steps);
+    Ok(count)
+}
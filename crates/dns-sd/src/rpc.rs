@@ -0,0 +1,363 @@
+//! JSON-RPC 2.0 session shared by the bridge servers
+//!
+//! A session owns the browsers and advertisements its client opened and stops
+//! them when the client goes away. Backend events are pushed to the client as
+//! notifications:
+//!
+//! - `{"method":"event","params":{"handle":1,"event":"serviceFound","service":{...}}}`
+//! - `{"method":"advertisement","params":{"handle":2,"event":"registered","data":"..."}}`
+//!
+//! [`serve`] runs sessions over newline-delimited JSON on stdio or a Unix
+//! socket, for helper processes that host discovery outside the main app.
+
+use crate::filters::{NameList, NamePattern, ResultFilter};
+use serde_json::{Map, Value, json};
+use crate::options::FindOptions;
+use crate::{
+    AdvertiseOptions, AdvertisementHandle, Backend, BrowseCallback, BrowseOptions, BrowserHandle, InstanceTable,
+    ServiceInfo, Stoppable, TxtRecord, find_blocking, open_advertisement, open_browser,
+};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Delivers a message (response or notification) to the client
+pub type Sender = Arc<dyn Fn(Value) + Send + Sync>;
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+/// Error response body
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn params(message: impl Into<String>) -> Self {
+        RpcError { code: INVALID_PARAMS, message: message.into() }
+    }
+}
+
+impl From<String> for RpcError {
+    fn from(message: String) -> Self {
+        RpcError { code: SERVER_ERROR, message }
+    }
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": e.code, "message": e.message },
+        }),
+    }
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+fn required_str<'a>(params: &'a Value, key: &str) -> Result<&'a str, RpcError> {
+    params
+        .get(key)
+        .and_then(Value::as_str)
+        .ok_or_else(|| RpcError::params(format!("params.{} is required", key)))
+}
+
+fn handle_param(params: &Value) -> Result<u32, RpcError> {
+    params
+        .get("handle")
+        .and_then(Value::as_f64)
+        .map(|h| h as u32)
+        .ok_or_else(|| RpcError::params("params.handle is required"))
+}
+
+/// `params.interface`: an index or `"local-only"` (default: all)
+fn interface_param(params: &Value) -> Result<u32, RpcError> {
+    match params.get("interface") {
+        None => Ok(0),
+        Some(Value::Number(index)) => Ok(index.as_f64().unwrap_or_default() as u32),
+        Some(Value::String(name)) => crate::parse_interface(name).map_err(RpcError::params),
+        Some(_) => Err(RpcError::params("params.interface must be an index or \"local-only\"")),
+    }
+}
+
+/// Strings of an array param, empty if it is missing
+fn string_list(params: &Value, key: &str) -> Vec<String> {
+    params
+        .get(key)
+        .and_then(Value::as_array)
+        .map(|items| items.iter().filter_map(Value::as_str).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+fn browse_options(params: &Value) -> Result<BrowseOptions, RpcError> {
+    let result_filter = ResultFilter {
+        name: params.get("nameFilter").and_then(Value::as_str).map(|glob| NamePattern::Glob(glob.to_string())),
+        names: NameList {
+            ignore: string_list(params, "ignoreNames"),
+            only: string_list(params, "onlyNames"),
+        },
+        txt: params
+            .get("txtFilter")
+            .and_then(Value::as_object)
+            .map(|fields| fields.iter().filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string()))).collect())
+            .unwrap_or_default(),
+    };
+    Ok(BrowseOptions {
+        service_type: required_str(params, "type")?.to_string(),
+        domain: params.get("domain").and_then(Value::as_str).map(str::to_string),
+        interface_index: interface_param(params)?,
+        result_filter,
+        resolve_services: params.get("resolve").and_then(Value::as_bool).unwrap_or(true),
+        ..Default::default()
+    })
+}
+
+fn advertise_options(params: &Value) -> Result<AdvertiseOptions, RpcError> {
+    let port = params
+        .get("port")
+        .and_then(Value::as_f64)
+        .ok_or_else(|| RpcError::params("params.port is required"))?;
+    let strict_txt = params.get("strictTxt").and_then(Value::as_bool).unwrap_or(false);
+    let txt = match params.get("txt").and_then(Value::as_object) {
+        Some(fields) if strict_txt => {
+            let mut txt = TxtRecord::new();
+            for (key, value) in fields {
+                let value = value.as_str().ok_or_else(|| RpcError::params(format!("params.txt.{} must be a string", key)))?;
+                txt.insert_strict(key, value).map_err(RpcError::params)?;
+            }
+            Some(txt)
+        }
+        Some(fields) => Some(fields.iter().filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string()))).collect()),
+        None => None,
+    };
+    let subtypes = params
+        .get("subtypes")
+        .and_then(Value::as_array)
+        .map(|items| items.iter().filter_map(|v| Some(v.as_str()?.to_string())).collect())
+        .unwrap_or_default();
+
+    Ok(AdvertiseOptions {
+        name: required_str(params, "name")?.to_string(),
+        service_type: required_str(params, "type")?.to_string(),
+        port: port as u16,
+        txt,
+        txtvers_first: params.get("txtversFirst").and_then(Value::as_bool).unwrap_or(false),
+        domain: params.get("domain").and_then(Value::as_str).map(str::to_string),
+        host: params.get("host").and_then(Value::as_str).map(str::to_string),
+        interface_index: interface_param(params)?,
+        subtypes,
+        ..Default::default()
+    })
+}
+
+/// One connected client
+pub struct Session {
+    backend: Backend,
+    send: Sender,
+    next_handle: u32,
+    browsers: HashMap<u32, BrowserHandle>,
+    advertisements: HashMap<u32, AdvertisementHandle>,
+}
+
+impl Session {
+    pub fn new(backend: Backend, send: Sender) -> Self {
+        Session {
+            backend,
+            send,
+            next_handle: 1,
+            browsers: HashMap::new(),
+            advertisements: HashMap::new(),
+        }
+    }
+
+    /// Handle one raw message from the client
+    pub fn handle_text(&mut self, text: &str) {
+        match serde_json::from_str(text) {
+            Ok(request) => self.handle(&request),
+            Err(e) => (self.send)(response(Value::Null, Err(RpcError { code: PARSE_ERROR, message: e.to_string() }))),
+        }
+    }
+
+    /// Handle one request; the response is sent once the call completes
+    pub fn handle(&mut self, request: &Value) {
+        let id = request.get("id").cloned();
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            let error = RpcError { code: INVALID_REQUEST, message: "method is required".into() };
+            (self.send)(response(id.unwrap_or(Value::Null), Err(error)));
+            return;
+        };
+        let params = request.get("params").cloned().unwrap_or(Value::Object(Map::new()));
+
+        // resolve waits for the network, so it answers from its own thread
+        if method == "resolve" {
+            self.resolve(id, params);
+            return;
+        }
+
+        let result = self.call(method, &params);
+        // Requests without an id are notifications and get no response
+        if let Some(id) = id {
+            (self.send)(response(id, result));
+        }
+    }
+
+    fn call(&mut self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        match method {
+            "getBackend" => Ok(self.backend.name().into()),
+            "browse" => {
+                let options = browse_options(params)?;
+                let handle = self.next_handle();
+                let send = self.send.clone();
+                let table = Mutex::new(InstanceTable::new(options.interface_mode));
+                let result_filter = options.result_filter.clone();
+                let on_event: BrowseCallback = Arc::new(move |event: &str, info: ServiceInfo| {
+                    let mut table = table.lock().unwrap();
+                    let Some(event) = result_filter.screen(event, &info, table.contains(&info)) else {
+                        return;
+                    };
+                    let Some((event, info)) = table.apply(event, info) else {
+                        return;
+                    };
+                    drop(table);
+                    send(notification("event", json!({
+                        "handle": handle,
+                        "event": event,
+                        "service": Value::from(&info),
+                    })));
+                });
+                let browser = open_browser(self.backend, &options, on_event)?;
+                self.browsers.insert(handle, browser);
+                Ok(json!({ "handle": handle }))
+            }
+            "stopBrowse" => {
+                let handle = handle_param(params)?;
+                let stopped = self.browsers.remove(&handle).map(|mut b| b.stop()).is_some();
+                Ok(stopped.into())
+            }
+            "advertise" => {
+                let options = advertise_options(params)?;
+                let handle = self.next_handle();
+                let send = self.send.clone();
+                let ad = open_advertisement(self.backend, &options, move |event, data| {
+                    send(notification("advertisement", json!({
+                        "handle": handle,
+                        "event": event,
+                        "data": Value::from(data),
+                    })));
+                })?;
+                self.advertisements.insert(handle, ad);
+                Ok(json!({ "handle": handle }))
+            }
+            "stopAdvertise" => {
+                let handle = handle_param(params)?;
+                let stopped = self.advertisements.remove(&handle).map(|mut a| a.stop()).is_some();
+                Ok(stopped.into())
+            }
+            _ => Err(RpcError { code: METHOD_NOT_FOUND, message: format!("Unknown method: {}", method) }),
+        }
+    }
+
+    fn resolve(&self, id: Option<Value>, params: Value) {
+        let find = browse_options(&params).map(|browse| FindOptions {
+            browse,
+            name: params.get("name").and_then(Value::as_str).map(str::to_string),
+            timeout: Duration::from_millis(params.get("timeoutMs").and_then(Value::as_f64).unwrap_or(5000.0) as u64),
+        });
+        let backend = self.backend;
+        let send = self.send.clone();
+        thread::spawn(move || {
+            let result = find.and_then(|find| {
+                find_blocking(backend, &find).map(|info| Value::from(&info)).map_err(RpcError::from)
+            });
+            if let Some(id) = id {
+                send(response(id, result));
+            }
+        });
+    }
+
+    fn next_handle(&mut self) -> u32 {
+        let handle = self.next_handle;
+        self.next_handle += 1;
+        handle
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        for (_, mut browser) in self.browsers.drain() {
+            browser.stop();
+        }
+        for (_, mut ad) in self.advertisements.drain() {
+            ad.stop();
+        }
+    }
+}
+
+/// Where [`serve`] reads requests from
+#[derive(Debug, Clone)]
+pub enum Transport {
+    /// One session on stdin/stdout
+    Stdio,
+    /// One session per connection on a Unix domain socket
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+/// Serve newline-delimited JSON-RPC until the input closes (stdio) or the
+/// listener fails (Unix socket)
+pub fn serve(backend: Backend, transport: Transport) -> Result<(), String> {
+    match transport {
+        Transport::Stdio => {
+            let stdout = Arc::new(Mutex::new(std::io::stdout()));
+            run_session(backend, std::io::stdin().lock(), stdout);
+            Ok(())
+        }
+        #[cfg(unix)]
+        Transport::Unix(path) => {
+            use std::os::unix::net::{UnixListener, UnixStream};
+
+            // A socket file left by a dead server would make bind fail
+            if path.exists() && UnixStream::connect(&path).is_err() {
+                let _ = std::fs::remove_file(&path);
+            }
+            let listener =
+                UnixListener::bind(&path).map_err(|e| format!("Failed to bind {}: {}", path.display(), e))?;
+            for stream in listener.incoming() {
+                let stream = stream.map_err(|e| format!("Failed to accept: {}", e))?;
+                let writer = Arc::new(Mutex::new(stream.try_clone().map_err(|e| e.to_string())?));
+                thread::spawn(move || run_session(backend, BufReader::new(stream), writer));
+            }
+            Ok(())
+        }
+    }
+}
+
+fn run_session<W: Write + Send + 'static>(backend: Backend, input: impl BufRead, output: Arc<Mutex<W>>) {
+    let mut session = Session::new(
+        backend,
+        Arc::new(move |message: Value| {
+            let mut output = output.lock().unwrap();
+            let _ = writeln!(output, "{}", message);
+            let _ = output.flush();
+        }),
+    );
+    for line in input.lines() {
+        let Ok(line) = line else { break };
+        if !line.trim().is_empty() {
+            session.handle_text(&line);
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! End-to-end self-test through the backend in use (`runSelftest`)
+//!
+//! Where `health::check` looks at each layer separately, the self-test does
+//! what an application does: it registers a temporary LocalOnly service,
+//! browses for it and resolves it, with the same backend and code paths as
+//! real handles. Each stage reports pass or fail with a hint naming the
+//! likely cause. A failed stage skips the ones that need it.
+
+use crate::health::{self, Check, Status};
+use crate::interfaces::LOCAL_ONLY;
+use crate::{AdvertiseOptions, Backend, BrowseCallback, BrowseOptions, ServiceInfo, Stoppable, get_backend, loopback_only};
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Service type registered by the self-test
+const SELFTEST_TYPE: &str = "_dnssd-selftest._tcp";
+
+/// Port the temporary service advertises, checked by the resolve stage
+const SELFTEST_PORT: u16 = 9;
+
+/// Outcome of one stage
+#[derive(Debug, Clone)]
+pub struct Stage {
+    /// `backend`, `daemon`, `interfaces`, `register`, `browse` or `resolve`
+    pub name: &'static str,
+    pub check: Check,
+    /// Likely cause and what to try, for failed stages
+    pub hint: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SelftestReport {
+    pub backend: Backend,
+    pub stages: Vec<Stage>,
+}
+
+impl SelftestReport {
+    /// No stage failed
+    pub fn passed(&self) -> bool {
+        self.stages.iter().all(|stage| stage.check.status != Status::Failed)
+    }
+}
+
+fn stage(name: &'static str, check: Check, hint: &str) -> Stage {
+    let hint = (check.status == Status::Failed).then(|| hint.to_string());
+    Stage { name, check, hint }
+}
+
+/// Run every stage, waiting at most `timeout` for each answer
+pub fn run(timeout: Duration) -> SelftestReport {
+    let backend = get_backend();
+    let native = backend == Backend::Native;
+    let mut stages = Vec::new();
+
+    let detail = match backend {
+        Backend::Native => format!("native ({})", crate::ffi::get_library_path()),
+        Backend::Fallback => match crate::native::DnsSdLibrary::get() {
+            Ok(_) => "mdns-sd (chosen over the native library)".to_string(),
+            Err(e) => format!("mdns-sd ({})", e),
+        },
+        Backend::Mock => "mock (scripted, no network)".to_string(),
+    };
+    stages.push(stage("backend", Check::timed(Instant::now(), Ok(detail)), ""));
+
+    let start = Instant::now();
+    let daemon = match (backend, health::daemon_socket()) {
+        (Backend::Native, Some(result)) => Check::timed(start, result),
+        (Backend::Native, None) => Check::skipped("No known daemon socket on this platform"),
+        (Backend::Fallback, _) => Check::skipped("The mdns-sd fallback runs in this process"),
+        (Backend::Mock, _) => Check::skipped("Not using a daemon"),
+    };
+    stages.push(stage(
+        "daemon",
+        daemon,
+        "The DNS-SD daemon isn't running: start avahi-daemon (Linux), mDNSResponder (macOS) or the Bonjour service (Windows)",
+    ));
+
+    let start = Instant::now();
+    let interfaces = if loopback_only() || backend == Backend::Mock {
+        Check::skipped("Not using the network")
+    } else {
+        Check::timed(start, usable_interfaces())
+    };
+    stages.push(stage(
+        "interfaces",
+        interfaces,
+        "No interface is up with multicast: connect to a network. Discovery on this machine still works",
+    ));
+
+    let name = format!("selftest-{}-{}", std::process::id(), nonce());
+    let (tx, rx) = mpsc::channel::<Result<(), String>>();
+    let options = AdvertiseOptions {
+        name: name.clone(),
+        service_type: SELFTEST_TYPE.to_string(),
+        port: SELFTEST_PORT,
+        interface_index: LOCAL_ONLY,
+        no_auto_rename: true,
+        ..Default::default()
+    };
+    let start = Instant::now();
+    let advertisement = crate::open_advertisement(backend, &options, move |event, data| {
+        let _ = match event {
+            "registered" => tx.send(Ok(())),
+            "error" => tx.send(Err(data.to_string())),
+            _ => Ok(()),
+        };
+    });
+    let registered = advertisement.as_ref().map_err(Clone::clone).and_then(|_| match rx.recv_timeout(timeout) {
+        Ok(Ok(())) => Ok(format!("{}.{}.local.", name, SELFTEST_TYPE)),
+        Ok(Err(e)) => Err(e),
+        Err(_) => Err(format!("No answer within {}ms", timeout.as_millis())),
+    });
+    let register_hint = if native {
+        "The daemon refused the registration: check that it is running and allows publishing (Avahi: disable-publishing in avahi-daemon.conf)"
+    } else {
+        "The mdns-sd daemon couldn't publish: another process may hold UDP port 5353 without sharing it"
+    };
+    let ok = registered.is_ok();
+    stages.push(stage("register", Check::timed(start, registered), register_hint));
+
+    let browse_options = BrowseOptions {
+        service_type: SELFTEST_TYPE.to_string(),
+        // mdns-sd browses every interface; the service answers on loopback only
+        interface_index: if native { LOCAL_ONLY } else { 0 },
+        resolve_services: false,
+        ..Default::default()
+    };
+    let sighting = if ok {
+        let start = Instant::now();
+        let found = browse(backend, &browse_options, &name, timeout);
+        let sighting = found.as_ref().ok().cloned();
+        let browse_hint = if native {
+            "The daemon registered the service but didn't report it to a browse: restart the daemon"
+        } else {
+            "The service was published but not heard back: a firewall may block UDP port 5353, or multicast loop is off (multicastLoopV4/V6)"
+        };
+        stages.push(stage("browse", Check::timed(start, found.map(|info| info.full_name)), browse_hint));
+        sighting
+    } else {
+        stages.push(stage("browse", Check::skipped("Nothing registered"), ""));
+        None
+    };
+
+    if let Some(sighting) = sighting {
+        let start = Instant::now();
+        let options = BrowseOptions { resolve_services: true, ..browse_options };
+        let resolved = crate::resolve_sighting(backend, &options, &sighting).and_then(|info| {
+            if info.port == SELFTEST_PORT && !info.host_name.is_empty() {
+                Ok(format!("{}:{} {:?}", info.host_name, info.port, info.addresses))
+            } else {
+                Err(format!("Resolved to {}:{} instead of port {}", info.host_name, info.port, SELFTEST_PORT))
+            }
+        });
+        let resolve_hint = if native {
+            "The service was found but didn't resolve: the daemon may be overloaded, or this host's name is in conflict"
+        } else {
+            "The service was found but didn't resolve: its SRV/TXT answers were lost, often to a firewall on UDP port 5353"
+        };
+        stages.push(stage("resolve", Check::timed(start, resolved), resolve_hint));
+    } else {
+        stages.push(stage("resolve", Check::skipped("Nothing browsed"), ""));
+    }
+
+    if let Ok(mut advertisement) = advertisement {
+        advertisement.stop();
+    }
+    SelftestReport { backend, stages }
+}
+
+/// Browse until the instance `name` shows up
+fn browse(backend: Backend, options: &BrowseOptions, name: &str, timeout: Duration) -> Result<ServiceInfo, String> {
+    let (tx, rx) = mpsc::sync_channel::<ServiceInfo>(1);
+    let wanted = name.to_string();
+    let on_event: BrowseCallback = Arc::new(move |event: &str, info: ServiceInfo| {
+        if event == "serviceFound" && info.name == wanted {
+            let _ = tx.try_send(info);
+        }
+    });
+    let mut browser = crate::open_browser(backend, options, on_event)?;
+    let found = rx.recv_timeout(timeout);
+    browser.stop();
+    found.map_err(|_| format!("Not found within {}ms", timeout.as_millis()))
+}
+
+/// Names of the interfaces that are up, multicast-capable and addressed
+fn usable_interfaces() -> Result<String, String> {
+    let usable: Vec<String> = crate::list_interfaces()?
+        .into_iter()
+        .filter(|i| i.up && i.multicast && !i.loopback && !i.addresses.is_empty())
+        .map(|i| i.name)
+        .collect();
+    if usable.is_empty() {
+        return Err("No usable interface".to_string());
+    }
+    Ok(usable.join(", "))
+}
+
+fn nonce() -> u32 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0)
+}
@@ -0,0 +1,321 @@
+//! Reference-counted sharing of identical backend browsers
+//!
+//! Handles browsing the same type with the same backend-level settings share
+//! one underlying browser. Raw events are fanned out to every subscriber, and
+//! each subscriber applies its own filters, instance table and batching. The
+//! backend browser is stopped when the last subscriber goes away.
+//!
+//! A browser asked to refresh (`refreshIntervalMs`) is closed and opened
+//! again on that schedule, so the backend queries afresh. Services it
+//! doesn't report again within [`REFRESH_GRACE`] are reported lost.
+
+use crate::handles::{BrowserHandle, Stoppable};
+use crate::instances::{bare_type, instance_key};
+use crate::filters::NameList;
+use crate::native::ResolveConfig;
+use crate::options::BrowseOptions;
+use crate::{Backend, BrowseCallback, ServiceInfo};
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a refreshed browser has to report a service again before it
+/// is reported lost
+pub const REFRESH_GRACE: Duration = Duration::from_secs(5);
+
+/// Opens the backend browser of a shared browse, again on each refresh
+type Open = Box<dyn Fn(BrowseCallback) -> Result<BrowserHandle, String> + Send + Sync>;
+
+/// Settings that change what the backend browser itself does
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct BrowseKey {
+    backend: Backend,
+    service_type: String,
+    domain: Option<String>,
+    interface_index: u32,
+    flags: u32,
+    resolve: ResolveConfig,
+    resolve_services: bool,
+    names: NameList,
+}
+
+impl BrowseKey {
+    fn new(backend: Backend, options: &BrowseOptions) -> Self {
+        if backend == Backend::Fallback {
+            // mdns-sd keeps one browse per type on the shared daemon and
+            // ignores the native-only settings; it resolves every instance
+            // regardless, so browse-only subscribers share its browse too
+            return BrowseKey {
+                backend,
+                service_type: bare_type(&options.service_type, "local").to_string(),
+                domain: None,
+                interface_index: 0,
+                flags: 0,
+                resolve: ResolveConfig::default(),
+                resolve_services: true,
+                names: NameList::default(),
+            };
+        }
+        BrowseKey {
+            backend,
+            service_type: options.service_type.clone(),
+            domain: options.domain.clone(),
+            interface_index: options.interface_index,
+            flags: options.flags,
+            resolve: options.resolve,
+            resolve_services: options.resolve_services,
+            names: options.result_filter.names.clone(),
+        }
+    }
+}
+
+/// One backend browser and its subscribers
+struct SharedBrowse {
+    subscribers: Mutex<HashMap<u32, BrowseCallback>>,
+    /// Services currently reported, replayed to late subscribers
+    known: Mutex<HashMap<(String, u32), ServiceInfo>>,
+    browser: Mutex<Option<BrowserHandle>>,
+    open: Open,
+    /// Shortest refresh interval any subscriber asked for
+    refresh: Mutex<Option<Duration>>,
+    /// Known services not reported again since the last refresh began
+    unconfirmed: Mutex<HashSet<(String, u32)>>,
+    /// Set once the last subscriber left; nothing may reopen the browser
+    closed: AtomicBool,
+}
+
+impl SharedBrowse {
+    /// Record and fan out a raw backend event
+    fn dispatch(&self, event: &str, info: ServiceInfo) {
+        let key = (instance_key(&info), info.interface_index);
+        match event {
+            "serviceFound" => {
+                self.unconfirmed.lock().unwrap().remove(&key);
+                self.known.lock().unwrap().insert(key, info.clone());
+            }
+            "serviceLost" => {
+                self.known.lock().unwrap().remove(&key);
+            }
+            _ => {}
+        }
+
+        let subscribers: Vec<BrowseCallback> = self.subscribers.lock().unwrap().values().cloned().collect();
+        for subscriber in subscribers {
+            subscriber(event, info.clone());
+        }
+    }
+
+    /// Close the backend browser and open it again, reporting the services
+    /// it doesn't find again within [`REFRESH_GRACE`] lost
+    fn refresh(self: &Arc<Self>) {
+        let old = self.browser.lock().unwrap().take();
+        if let Some(mut old) = old {
+            old.stop();
+        }
+        *self.unconfirmed.lock().unwrap() = self.known.lock().unwrap().keys().cloned().collect();
+        match (self.open)(dispatcher(self)) {
+            Ok(mut browser) => {
+                let mut slot = self.browser.lock().unwrap();
+                if self.closed.load(Ordering::SeqCst) {
+                    drop(slot);
+                    browser.stop();
+                    return;
+                }
+                *slot = Some(browser);
+            }
+            Err(e) => tracing::warn!("Failed to reopen browser for refresh: {}", e),
+        }
+
+        thread::sleep(REFRESH_GRACE);
+        let unconfirmed = std::mem::take(&mut *self.unconfirmed.lock().unwrap());
+        let lost: Vec<ServiceInfo> = {
+            let mut known = self.known.lock().unwrap();
+            unconfirmed.iter().filter_map(|key| known.remove(key)).collect()
+        };
+        for info in lost {
+            self.dispatch("serviceLost", info);
+        }
+    }
+
+    /// Refresh on the shortest interval asked for, until the browse closes
+    fn refresh_periodically(weak: Weak<SharedBrowse>) {
+        let mut last = Instant::now();
+        loop {
+            thread::sleep(Duration::from_secs(1));
+            let Some(entry) = weak.upgrade() else { return };
+            if entry.closed.load(Ordering::SeqCst) {
+                return;
+            }
+            let Some(interval) = *entry.refresh.lock().unwrap() else { continue };
+            if last.elapsed() >= interval {
+                tracing::debug!("Refreshing browser after {:?}", interval);
+                entry.refresh();
+                last = Instant::now();
+            }
+        }
+    }
+
+    /// Lower the refresh interval to `interval`, starting the refresh
+    /// thread on the first request
+    fn request_refresh(self: &Arc<Self>, interval: Option<Duration>) {
+        let Some(interval) = interval else { return };
+        let mut refresh = self.refresh.lock().unwrap();
+        if refresh.is_none() {
+            let weak = Arc::downgrade(self);
+            thread::spawn(move || SharedBrowse::refresh_periodically(weak));
+        }
+        *refresh = Some(refresh.map_or(interval, |current| current.min(interval)));
+    }
+}
+
+/// Callback feeding a backend browser's events to `entry`
+fn dispatcher(entry: &Arc<SharedBrowse>) -> BrowseCallback {
+    let weak = Arc::downgrade(entry);
+    Arc::new(move |event: &str, info: ServiceInfo| {
+        if let Some(entry) = weak.upgrade() {
+            entry.dispatch(event, info);
+        }
+    })
+}
+
+static SHARED: Lazy<Mutex<HashMap<BrowseKey, Arc<SharedBrowse>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+static NEXT_SUBSCRIBER: AtomicU32 = AtomicU32::new(1);
+
+/// A subscriber's share of a backend browser
+pub struct Subscription {
+    key: BrowseKey,
+    id: u32,
+    active: bool,
+}
+
+impl Subscription {
+    pub fn backend(&self) -> Backend {
+        self.key.backend
+    }
+}
+
+impl Stoppable for Subscription {
+    /// Threads of the shared backend browser, counted for each subscriber
+    fn threads(&self) -> usize {
+        let Some(entry) = SHARED.lock().unwrap().get(&self.key).cloned() else {
+            return 0;
+        };
+        entry.browser.lock().unwrap().as_ref().map_or(0, Stoppable::threads)
+    }
+
+    fn stop(&mut self) {
+        if !std::mem::replace(&mut self.active, false) {
+            return;
+        }
+
+        let browser = {
+            let mut shared = SHARED.lock().unwrap();
+            let Some(entry) = shared.get(&self.key) else {
+                return;
+            };
+            let mut subscribers = entry.subscribers.lock().unwrap();
+            subscribers.remove(&self.id);
+            if !subscribers.is_empty() {
+                return;
+            }
+            drop(subscribers);
+            shared.remove(&self.key).and_then(|entry| {
+                let mut browser = entry.browser.lock().unwrap();
+                entry.closed.store(true, Ordering::SeqCst);
+                browser.take()
+            })
+        };
+
+        // Last subscriber: stop the backend browser outside the registry lock
+        if let Some(mut browser) = browser {
+            browser.stop();
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Introspection snapshot of a shared backend browser
+pub struct SharedInfo {
+    pub backend: Backend,
+    pub service_type: String,
+    pub domain: Option<String>,
+    pub interface_index: u32,
+    /// Handles sharing the browser
+    pub subscribers: usize,
+    /// Services it currently reports
+    pub known: usize,
+    pub refresh: Option<Duration>,
+}
+
+/// Snapshots of every running shared browser
+pub fn list() -> Vec<SharedInfo> {
+    let shared = SHARED.lock().unwrap();
+    let mut infos: Vec<SharedInfo> = shared
+        .iter()
+        .map(|(key, entry)| SharedInfo {
+            backend: key.backend,
+            service_type: key.service_type.clone(),
+            domain: key.domain.clone(),
+            interface_index: key.interface_index,
+            subscribers: entry.subscribers.lock().unwrap().len(),
+            known: entry.known.lock().unwrap().len(),
+            refresh: *entry.refresh.lock().unwrap(),
+        })
+        .collect();
+    infos.sort_by(|a, b| a.service_type.cmp(&b.service_type));
+    infos
+}
+
+/// Subscribe to a backend browser for `options`, opening it if needed
+///
+/// `open` is only called when no identical browser is running yet, and
+/// again on each refresh.
+pub fn subscribe(
+    backend: Backend,
+    options: &BrowseOptions,
+    on_event: BrowseCallback,
+    open: impl Fn(BrowseCallback) -> Result<BrowserHandle, String> + Send + Sync + 'static,
+) -> Result<Subscription, String> {
+    let key = BrowseKey::new(backend, options);
+    let id = NEXT_SUBSCRIBER.fetch_add(1, Ordering::SeqCst);
+    let mut shared = SHARED.lock().unwrap();
+
+    if let Some(entry) = shared.get(&key) {
+        // Replay what the running browser already knows; holding the
+        // subscriber list keeps new events queued behind the replay
+        let mut subscribers = entry.subscribers.lock().unwrap();
+        let known: Vec<ServiceInfo> = entry.known.lock().unwrap().values().cloned().collect();
+        for info in known {
+            on_event("serviceFound", info);
+        }
+        subscribers.insert(id, on_event);
+        drop(subscribers);
+        entry.request_refresh(options.refresh_interval);
+        return Ok(Subscription { key, id, active: true });
+    }
+
+    let entry = Arc::new(SharedBrowse {
+        subscribers: Mutex::new(HashMap::from([(id, on_event)])),
+        known: Mutex::new(HashMap::new()),
+        browser: Mutex::new(None),
+        open: Box::new(open),
+        refresh: Mutex::new(None),
+        unconfirmed: Mutex::new(HashSet::new()),
+        closed: AtomicBool::new(false),
+    });
+    let browser = (entry.open)(dispatcher(&entry))?;
+    *entry.browser.lock().unwrap() = Some(browser);
+    entry.request_refresh(options.refresh_interval);
+    shared.insert(key.clone(), entry);
+
+    Ok(Subscription { key, id, active: true })
+}
@@ -0,0 +1,143 @@
+//! Raw mDNS packets heard by the fallback, for protocol debugging
+//!
+//! The fallback's [`Listener`](crate::multicast::Listener) hands every
+//! packet sent to the mDNS group to [`deliver`], which passes it to each
+//! tap: the JS callback set with `setPacketTapCallback` and, from Rust,
+//! anything registered with [`add`]. Packets sent from one of this host's addresses
+//! are outgoing, which includes the daemon's own while multicast loop is on
+//! (the default). Nothing is heard while no fallback handle is running, or
+//! on Windows, where the listener can't start.
+
+use crate::{Sink, interfaces};
+use neon::event::Channel;
+use neon::handle::Root;
+use neon::prelude::*;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How long the interface list used to attribute packets is reused
+const INTERFACES_TTL: Duration = Duration::from_secs(5);
+
+/// One packet heard on the mDNS port
+#[derive(Debug, Clone)]
+pub struct Packet {
+    pub time: SystemTime,
+    /// Sent from one of this host's addresses
+    pub outgoing: bool,
+    pub source: SocketAddr,
+    /// Interface whose link the source is on (None if no subnet matches)
+    pub interface: Option<String>,
+    pub interface_index: u32,
+    pub data: Vec<u8>,
+}
+
+/// Receiver of tapped packets
+pub type Tap = Arc<dyn Fn(&Packet) + Send + Sync>;
+
+static TAPS: Lazy<Mutex<HashMap<u32, Tap>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+static NEXT_TAP: AtomicU32 = AtomicU32::new(1);
+
+/// JS function receiving `(packet)`
+static SINK: Sink = Sink::new();
+
+/// Id of the JS callback's tap
+static JS_TAP: Mutex<Option<u32>> = Mutex::new(None);
+
+/// Interface addresses, refreshed every [`INTERFACES_TTL`]
+static INTERFACES: Lazy<Mutex<(Instant, Vec<if_addrs::Interface>)>> =
+    Lazy::new(|| Mutex::new((Instant::now() - INTERFACES_TTL, Vec::new())));
+
+/// Start passing packets to `tap`, returning its id for [`remove`]
+pub fn add(tap: Tap) -> u32 {
+    let id = NEXT_TAP.fetch_add(1, Ordering::SeqCst);
+    TAPS.lock().unwrap().insert(id, tap);
+    // The listener only runs once something needs its packets
+    crate::fallback::listen();
+    id
+}
+
+/// Whether any tap is added
+pub fn active() -> bool {
+    !TAPS.lock().unwrap().is_empty()
+}
+
+/// Stop passing packets to the tap `id`
+pub fn remove(id: u32) {
+    TAPS.lock().unwrap().remove(&id);
+}
+
+/// Pass a packet heard from `source` to every tap
+pub fn deliver(data: &[u8], source: SocketAddr) {
+    let taps: Vec<Tap> = TAPS.lock().unwrap().values().cloned().collect();
+    if taps.is_empty() {
+        return;
+    }
+    let (outgoing, link) = {
+        let mut interfaces = INTERFACES.lock().unwrap();
+        if interfaces.0.elapsed() >= INTERFACES_TTL {
+            *interfaces = (Instant::now(), if_addrs::get_if_addrs().unwrap_or_default());
+        }
+        let outgoing = interfaces.1.iter().any(|a| a.ip() == source.ip());
+        (outgoing, interfaces::link_of(&interfaces.1, &source))
+    };
+    let packet = Packet {
+        time: SystemTime::now(),
+        outgoing,
+        source,
+        interface_index: link.as_ref().map_or(0, |(_, index)| *index),
+        interface: link.map(|(name, _)| name),
+        data: data.to_vec(),
+    };
+    for tap in taps {
+        tap(&packet);
+    }
+}
+
+/// Deliver each packet to `callback`, or stop with `None`
+pub fn set_callback(sink: Option<(Channel, Root<JsFunction>)>) {
+    let mut js_tap = JS_TAP.lock().unwrap();
+    if let Some(id) = js_tap.take() {
+        remove(id);
+    }
+    if sink.is_none() {
+        SINK.set(None);
+        return;
+    }
+    SINK.set(sink);
+    *js_tap = Some(add(Arc::new(|packet: &Packet| {
+        let packet = packet.clone();
+        // Without a callback left to call, the tap goes too
+        if !SINK.call(move |cx| Ok(vec![packet_to_js(cx, &packet)?.upcast()]))
+            && let Some(id) = JS_TAP.lock().unwrap().take()
+        {
+            remove(id);
+        }
+    })));
+}
+
+fn packet_to_js<'cx>(cx: &mut impl Context<'cx>, packet: &Packet) -> JsResult<'cx, JsObject> {
+    let obj = cx.empty_object();
+    let direction = cx.string(if packet.outgoing { "out" } else { "in" });
+    obj.set(cx, "direction", direction)?;
+    let data = JsBuffer::from_slice(cx, &packet.data)?;
+    obj.set(cx, "data", data)?;
+    let address = cx.string(packet.source.ip().to_string());
+    obj.set(cx, "address", address)?;
+    let port = cx.number(packet.source.port());
+    obj.set(cx, "port", port)?;
+    if let Some(name) = &packet.interface {
+        let name = cx.string(name);
+        obj.set(cx, "interface", name)?;
+    }
+    let index = cx.number(packet.interface_index);
+    obj.set(cx, "interfaceIndex", index)?;
+    let time = packet.time.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as f64).unwrap_or(0.0);
+    let time = cx.number(time);
+    obj.set(cx, "timestamp", time)?;
+    Ok(obj)
+}
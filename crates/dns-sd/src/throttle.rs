@@ -0,0 +1,167 @@
+//! Debouncing and rate limiting of browse events before they reach JS
+//!
+//! Found/updated/lost events are held per instance: a newer event for the
+//! same instance replaces the held one and restarts its debounce window.
+//! Held events are released once their window has passed and the rate
+//! budget allows, so a device flapping between found and lost costs one
+//! event (or none) instead of one per cycle. Other events pass straight
+//! through.
+//!
+//! A settle window holds an instance's first found/updated event for a
+//! fixed time without restarting, so the burst a resolve produces (host,
+//! then each address) reaches JS as one event even if it never goes quiet.
+
+use crate::ServiceInfo;
+use crate::instances::instance_key;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+type Emit = Box<dyn Fn(&str, ServiceInfo) + Send + Sync>;
+
+struct Held {
+    key: String,
+    event: String,
+    info: ServiceInfo,
+    due: Instant,
+}
+
+struct State {
+    /// In order of each instance's first held event
+    held: Vec<Held>,
+    /// Instances JS has been told about (found and not lost since)
+    reported: HashSet<String>,
+    tokens: f64,
+    refilled: Instant,
+    timer_armed: bool,
+}
+
+/// Per-browser debouncer and rate limiter
+pub struct Throttle {
+    debounce: Duration,
+    settle: Duration,
+    /// Events per second; also the burst size
+    rate: Option<f64>,
+    state: Mutex<State>,
+    emit: Emit,
+}
+
+impl Throttle {
+    pub fn new<F>(debounce: Duration, settle: Duration, rate: Option<f64>, emit: F) -> Arc<Self>
+    where
+        F: Fn(&str, ServiceInfo) + Send + Sync + 'static,
+    {
+        Arc::new(Throttle {
+            debounce,
+            settle,
+            rate,
+            state: Mutex::new(State {
+                held: Vec::new(),
+                reported: HashSet::new(),
+                tokens: rate.unwrap_or(0.0).max(1.0),
+                refilled: Instant::now(),
+                timer_armed: false,
+            }),
+            emit: Box::new(emit),
+        })
+    }
+
+    pub fn push(self: &Arc<Self>, event: &str, info: ServiceInfo) {
+        if !matches!(event, "serviceFound" | "serviceUpdated" | "serviceLost") {
+            (self.emit)(event, info);
+            return;
+        }
+        {
+            let key = instance_key(&info);
+            let now = Instant::now();
+            let mut state = self.state.lock().unwrap();
+            match state.held.iter_mut().find(|held| held.key == key) {
+                Some(held) => {
+                    held.event = event.to_string();
+                    held.info = info;
+                    // A settle deadline stands; debouncing only pushes it later
+                    held.due = held.due.max(now + self.debounce);
+                }
+                None => {
+                    let hold = match event {
+                        "serviceLost" => self.debounce,
+                        _ => self.debounce.max(self.settle),
+                    };
+                    state.held.push(Held { key, event: event.to_string(), info, due: now + hold });
+                }
+            }
+        }
+        self.pump();
+    }
+
+    /// Release every held event that is due and within budget, arming a
+    /// timer for the rest
+    fn pump(self: &Arc<Self>) {
+        let (ready, wake) = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            if let Some(rate) = self.rate {
+                let elapsed = now.duration_since(state.refilled).as_secs_f64();
+                state.tokens = (state.tokens + elapsed * rate).min(rate.max(1.0));
+                state.refilled = now;
+            }
+
+            let mut ready = Vec::new();
+            let mut i = 0;
+            while i < state.held.len() {
+                if state.held[i].due > now {
+                    i += 1;
+                    continue;
+                }
+                if self.rate.is_some() {
+                    if state.tokens < 1.0 {
+                        break;
+                    }
+                    state.tokens -= 1.0;
+                }
+                let held = state.held.remove(i);
+                if let Some(event) = settle(&mut state.reported, &held) {
+                    ready.push((event, held.info));
+                }
+            }
+
+            let wake = if state.held.is_empty() || state.timer_armed {
+                None
+            } else {
+                let next_due = state.held.iter().map(|held| held.due).min().unwrap_or(now);
+                let next_token = match self.rate {
+                    Some(rate) if state.tokens < 1.0 => now + Duration::from_secs_f64((1.0 - state.tokens) / rate),
+                    _ => now,
+                };
+                state.timer_armed = true;
+                Some(next_due.max(next_token).max(now + Duration::from_millis(1)))
+            };
+            (ready, wake)
+        };
+
+        for (event, info) in ready {
+            (self.emit)(event, info);
+        }
+        if let Some(wake) = wake {
+            let throttle = self.clone();
+            thread::spawn(move || {
+                thread::sleep(wake.saturating_duration_since(Instant::now()));
+                throttle.state.lock().unwrap().timer_armed = false;
+                throttle.pump();
+            });
+        }
+    }
+}
+
+/// What a held event means to JS given what it has already been told
+fn settle(reported: &mut HashSet<String>, held: &Held) -> Option<&'static str> {
+    if held.event == "serviceLost" {
+        return reported.remove(&held.key).then_some("serviceLost");
+    }
+    if reported.insert(held.key.clone()) {
+        Some("serviceFound")
+    } else {
+        Some("serviceUpdated")
+    }
+}
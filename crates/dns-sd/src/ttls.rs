@@ -0,0 +1,88 @@
+//! SRV records heard by the fallback
+//!
+//! mdns-sd's `ResolvedService` doesn't carry the TTLs of the records it was
+//! built from, nor the SRV priority and weight. The fallback's listener
+//! hears the same responses, so [`record`] keeps what each instance's SRV
+//! record was last sent with, and resolved services report it through
+//! [`srv`]. An instance whose answer the listener didn't hear (a unicast
+//! reply) has none of it.
+
+use crate::multicast;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+const TYPE_SRV: u16 = 33;
+
+/// What an SRV record was last heard with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Srv {
+    /// Seconds
+    pub ttl: u32,
+    pub priority: u16,
+    pub weight: u16,
+}
+
+/// SRV records by lowercase instance full name, without the trailing dot
+static SRV_RECORDS: Lazy<Mutex<HashMap<String, Srv>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn key(fullname: &str) -> String {
+    fullname.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Take in the SRV records of a packet heard on the mDNS port
+pub fn record(packet: &[u8]) {
+    let Some(records) = multicast::response_records(packet) else { return };
+    let mut srv = records.iter().filter(|record| record.rrtype == TYPE_SRV).peekable();
+    if srv.peek().is_none() {
+        return;
+    }
+    let mut heard = SRV_RECORDS.lock().unwrap();
+    for record in srv {
+        // Priority, weight and port precede the target
+        if record.ttl == 0 || record.rdata.len() < 6 {
+            heard.remove(&key(&record.name));
+            continue;
+        }
+        let srv = Srv {
+            ttl: record.ttl,
+            priority: u16::from_be_bytes([record.rdata[0], record.rdata[1]]),
+            weight: u16::from_be_bytes([record.rdata[2], record.rdata[3]]),
+        };
+        heard.insert(key(&record.name), srv);
+    }
+}
+
+/// The SRV record last heard for `fullname`
+pub fn srv(fullname: &str) -> Option<Srv> {
+    SRV_RECORDS.lock().unwrap().get(&key(fullname)).copied()
+}
+
+/// Forget everything heard, when the fallback daemon shuts down
+pub fn clear() {
+    SRV_RECORDS.lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_priority_and_weight() {
+        // Response with one SRV answer: Web._http._tcp.local, TTL 120,
+        // priority 10, weight 20, port 80, target host.local
+        let mut packet = vec![0, 0, 0x84, 0, 0, 0, 0, 1, 0, 0, 0, 0];
+        for label in ["Web", "_http", "_tcp", "local"] {
+            packet.push(label.len() as u8);
+            packet.extend_from_slice(label.as_bytes());
+        }
+        packet.push(0);
+        packet.extend_from_slice(&[0, 33, 0x80, 1, 0, 0, 0, 120]);
+        let rdata = [&[0, 10, 0, 20, 0, 80][..], b"\x04host\x05local\x00"].concat();
+        packet.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        packet.extend_from_slice(&rdata);
+
+        record(&packet);
+        assert_eq!(srv("web._http._tcp.local."), Some(Srv { ttl: 120, priority: 10, weight: 20 }));
+    }
+}
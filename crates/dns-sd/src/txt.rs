@@ -0,0 +1,169 @@
+//! TXT record parsing (RFC 6763 §6) with malformed-record reporting, and
+//! the ordered entries advertisements publish
+
+use std::collections::HashMap;
+
+/// Longest key RFC 6763 §6.4 recommends; longer ones still work
+pub const RECOMMENDED_KEY_LEN: usize = 9;
+
+/// Longest `key=value` string one TXT entry can carry
+pub const MAX_ENTRY_LEN: usize = 255;
+
+/// Check a key against RFC 6763 §6.4: printable US-ASCII other than `=`
+pub fn validate_key(key: &str) -> Result<(), String> {
+    if key.is_empty() {
+        return Err("TXT key must not be empty".to_string());
+    }
+    if let Some(c) = key.chars().find(|c| !matches!(c, ' '..='~') || *c == '=') {
+        return Err(format!("TXT key {:?} contains {:?}; keys are printable ASCII without '='", key, c));
+    }
+    Ok(())
+}
+
+/// TXT entries to publish, kept in the order they were added
+///
+/// Keys compare case-insensitively, as in RFC 6763 §6.4: setting a key
+/// again replaces its value in place rather than adding a second entry.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TxtRecord {
+    entries: Vec<(String, String)>,
+}
+
+impl TxtRecord {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set `key`, returning the value it replaced
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) -> Option<String> {
+        let (key, value) = (key.into(), value.into());
+        match self.entries.iter_mut().find(|(k, _)| k.eq_ignore_ascii_case(&key)) {
+            Some(entry) => Some(std::mem::replace(&mut entry.1, value)),
+            None => {
+                self.entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    /// Add an entry for strict mode, rejecting invalid and duplicate keys
+    /// and entries too long to encode; keys above the recommended length
+    /// are only warned about
+    pub fn insert_strict(&mut self, key: &str, value: &str) -> Result<(), String> {
+        validate_key(key)?;
+        if self.entries.iter().any(|(k, _)| k.eq_ignore_ascii_case(key)) {
+            return Err(format!("Duplicate TXT key {:?} (keys are case-insensitive)", key));
+        }
+        if key.len() + 1 + value.len() > MAX_ENTRY_LEN {
+            return Err(format!("TXT entry {:?} is longer than {} bytes", key, MAX_ENTRY_LEN));
+        }
+        if key.len() > RECOMMENDED_KEY_LEN {
+            tracing::warn!("TXT key {:?} is longer than the recommended {} characters", key, RECOMMENDED_KEY_LEN);
+        }
+        self.insert(key, value);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Entries in publishing order, with `txtvers` moved to the front when
+    /// `txtvers_first` is set (RFC 6763 §6.7)
+    pub fn ordered(&self, txtvers_first: bool) -> Vec<(&str, &str)> {
+        let mut entries: Vec<(&str, &str)> = self.iter().collect();
+        if txtvers_first && let Some(i) = entries.iter().position(|(k, _)| k.eq_ignore_ascii_case("txtvers")) {
+            let txtvers = entries.remove(i);
+            entries.insert(0, txtvers);
+        }
+        entries
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for TxtRecord {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut txt = TxtRecord::new();
+        for (key, value) in iter {
+            txt.insert(key, value);
+        }
+        txt
+    }
+}
+
+impl IntoIterator for TxtRecord {
+    type Item = (String, String);
+    type IntoIter = std::vec::IntoIter<(String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+/// Parsed key/value pairs and the problems found on the way
+#[derive(Debug, Default)]
+pub struct ParsedTxt {
+    pub entries: HashMap<String, String>,
+    pub errors: Vec<String>,
+}
+
+/// Parse TXT rdata, keeping every well-formed entry
+///
+/// Follows RFC 6763: the first occurrence of a key wins (keys compare
+/// case-insensitively) and entries without a key are ignored. A length byte
+/// running past the end stops parsing since nothing after it can be framed.
+pub fn parse(bytes: &[u8]) -> ParsedTxt {
+    let mut parsed = ParsedTxt::default();
+    // A lone zero byte is the canonical empty TXT record
+    if bytes == [0] {
+        return parsed;
+    }
+
+    let mut seen: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let offset = i;
+        let entry_len = bytes[i] as usize;
+        i += 1;
+        if entry_len == 0 {
+            parsed.errors.push(format!("zero-length entry at byte {}", offset));
+            continue;
+        }
+        if i + entry_len > bytes.len() {
+            parsed.errors.push(format!(
+                "entry at byte {} claims {} bytes but only {} remain",
+                offset,
+                entry_len,
+                bytes.len() - i
+            ));
+            break;
+        }
+        let entry = &bytes[i..i + entry_len];
+        i += entry_len;
+
+        let (key, value) = match entry.iter().position(|&b| b == b'=') {
+            Some(eq) => (&entry[..eq], String::from_utf8_lossy(&entry[eq + 1..]).into_owned()),
+            None => (entry, String::new()),
+        };
+        if key.is_empty() {
+            parsed.errors.push(format!("entry at byte {} has no key", offset));
+            continue;
+        }
+        let key = String::from_utf8_lossy(key).into_owned();
+        let folded = key.to_ascii_lowercase();
+        if seen.contains(&folded) {
+            parsed.errors.push(format!("duplicate key \"{}\" at byte {}", key, offset));
+            continue;
+        }
+        seen.push(folded);
+        parsed.entries.insert(key, value);
+    }
+    parsed
+}
@@ -0,0 +1,583 @@
+//! Wide-area registration via DNS Update (RFC 2136)
+//!
+//! Once a zone is configured with `configure`, advertisements whose domain
+//! is that zone are published by sending signed dynamic updates to its
+//! primary server instead of going through mDNS: a PTR record under
+//! `_type._tcp.<zone>` (and one per subtype), plus SRV and TXT records for
+//! the instance. Stopping the advertisement deletes them again.
+//!
+//! Updates are signed with TSIG using HMAC-SHA256 (RFC 8945), the default
+//! for BIND and Knot keys. Server responses are checked for their result
+//! code but their signatures aren't verified. When no `host` is given, an
+//! A or AAAA record for `<hostname>.<zone>` is added with the address this
+//! machine uses to reach the server; host records are left in place on
+//! stop since other services may point at them. After a network change the
+//! host record is moved to the address now used.
+
+use crate::AdvertiseOptions;
+use crate::handles::running;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use sha2::Sha256;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long to wait for each reply, and how many times to send an update
+const REPLY_TIMEOUT: Duration = Duration::from_secs(3);
+const ATTEMPTS: usize = 3;
+/// Largest `Name (n)` tried when the instance name is taken
+const MAX_RENAMES: u32 = 10;
+
+const TYPE_A: u16 = 1;
+const TYPE_SOA: u16 = 6;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_AAAA: u16 = 28;
+const TYPE_SRV: u16 = 33;
+const TYPE_TSIG: u16 = 250;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+const CLASS_NONE: u16 = 254;
+const CLASS_ANY: u16 = 255;
+const OPCODE_UPDATE: u16 = 5 << 11;
+const RCODE_YXDOMAIN: u16 = 6;
+const TSIG_FUDGE: u16 = 300;
+
+/// Zone that advertisements are published into
+#[derive(Debug, Clone)]
+pub struct UpdateConfig {
+    /// Primary server for the zone
+    pub server: SocketAddr,
+    /// Zone name, e.g. `dns-sd.example.com`
+    pub zone: String,
+    /// TSIG key name
+    pub key_name: String,
+    /// TSIG key secret, decoded
+    pub secret: Vec<u8>,
+    /// TTL of the published records, in seconds
+    pub ttl: u32,
+}
+
+impl UpdateConfig {
+    /// Build a configuration from user input: `host[:port]` and a base64 secret
+    pub fn new(server: &str, zone: &str, key_name: &str, secret: &str, ttl: Option<u32>) -> Result<Self, String> {
+        let server = match server.parse::<SocketAddr>() {
+            Ok(addr) => addr,
+            Err(_) => match server.parse::<IpAddr>() {
+                Ok(ip) => SocketAddr::new(ip, 53),
+                Err(_) => {
+                    let lookup = if server.contains(':') { server.to_socket_addrs() } else { (server, 53).to_socket_addrs() };
+                    lookup
+                        .map_err(|e| format!("Cannot resolve update server {}: {}", server, e))?
+                        .next()
+                        .ok_or_else(|| format!("Cannot resolve update server {}", server))?
+                }
+            },
+        };
+        let zone = zone.trim_end_matches('.').to_string();
+        if zone.is_empty() || crate::is_local_domain(&zone) {
+            return Err("The update zone must be a unicast DNS domain".to_string());
+        }
+        // Key files may wrap the secret
+        let secret: String = secret.split_whitespace().collect();
+        let secret = STANDARD.decode(secret).map_err(|_| "The TSIG secret must be base64")?;
+        if secret.is_empty() {
+            return Err("The TSIG secret is empty".to_string());
+        }
+        Ok(UpdateConfig {
+            server,
+            zone,
+            key_name: key_name.trim_end_matches('.').to_string(),
+            secret,
+            ttl: ttl.unwrap_or(3600),
+        })
+    }
+}
+
+static CONFIG: Lazy<Mutex<Option<UpdateConfig>>> = Lazy::new(|| Mutex::new(None));
+
+/// Set or clear the zone used for wide-area registration
+///
+/// Running advertisements keep the configuration they started with.
+pub fn configure(config: Option<UpdateConfig>) {
+    *CONFIG.lock().unwrap() = config;
+}
+
+/// The configuration to register into `domain` with, if it's the configured zone
+pub fn config_for(domain: &str) -> Option<UpdateConfig> {
+    let config = CONFIG.lock().unwrap();
+    config
+        .as_ref()
+        .filter(|c| c.zone.eq_ignore_ascii_case(domain.trim_end_matches('.')))
+        .cloned()
+}
+
+/// Records of one registered service
+struct Records {
+    config: UpdateConfig,
+    options: AdvertiseOptions,
+    /// Instance name actually registered, after any renames
+    name: String,
+}
+
+impl Records {
+    fn instance(&self) -> Vec<String> {
+        let mut labels = vec![self.name.clone()];
+        labels.extend(self.service_type());
+        labels
+    }
+
+    fn service_type(&self) -> Vec<String> {
+        let mut labels = split(&self.options.service_type);
+        labels.extend(split(&self.config.zone));
+        labels
+    }
+
+    fn subtype(&self, subtype: &str) -> Vec<String> {
+        let mut labels = vec![subtype.to_string(), "_sub".to_string()];
+        labels.extend(self.service_type());
+        labels
+    }
+
+    fn host(&self) -> Vec<String> {
+        match &self.options.host {
+            Some(host) => split(host),
+            None => {
+                let mut labels = vec![crate::manifest::local_hostname()];
+                labels.extend(split(&self.config.zone));
+                labels
+            }
+        }
+    }
+
+    fn ptr_rdata(&self) -> Result<Vec<u8>, String> {
+        let mut rdata = Vec::new();
+        write_name(&mut rdata, &self.instance())?;
+        Ok(rdata)
+    }
+
+    fn srv_rdata(&self) -> Result<Vec<u8>, String> {
+        let mut rdata = Vec::new();
+        rdata.extend_from_slice(&0u16.to_be_bytes());
+        rdata.extend_from_slice(&0u16.to_be_bytes());
+        rdata.extend_from_slice(&self.options.port.to_be_bytes());
+        write_name(&mut rdata, &self.host())?;
+        Ok(rdata)
+    }
+
+    fn txt_rdata(&self) -> Vec<u8> {
+        let mut rdata = Vec::new();
+        let entries = self.options.txt.as_ref().map(|t| t.ordered(self.options.txtvers_first)).unwrap_or_default();
+        for (key, value) in entries {
+            let entry = format!("{}={}", key, value);
+            let bytes = &entry.as_bytes()[..entry.len().min(255)];
+            rdata.push(bytes.len() as u8);
+            rdata.extend_from_slice(bytes);
+        }
+        if rdata.is_empty() {
+            rdata.push(0);
+        }
+        rdata
+    }
+
+    /// TTL of the PTR and TXT records
+    fn ttl(&self) -> u32 {
+        self.options.ttl.unwrap_or(self.config.ttl)
+    }
+
+    /// TTL of the SRV and address records
+    fn host_ttl(&self) -> u32 {
+        self.options.host_ttl.unwrap_or(self.config.ttl)
+    }
+
+    /// Update adding the service, failing if the instance name is in use
+    fn add(&self, local: IpAddr) -> Result<Message, String> {
+        let (ttl, host_ttl) = (self.ttl(), self.host_ttl());
+        let mut message = Message::new(&self.config.zone)?;
+        message.prerequisite(&self.instance(), TYPE_ANY, CLASS_NONE, &[])?;
+        message.update(&self.service_type(), TYPE_PTR, CLASS_IN, ttl, &self.ptr_rdata()?)?;
+        for subtype in &self.options.subtypes {
+            message.update(&self.subtype(subtype), TYPE_PTR, CLASS_IN, ttl, &self.ptr_rdata()?)?;
+        }
+        message.update(&self.instance(), TYPE_SRV, CLASS_IN, host_ttl, &self.srv_rdata()?)?;
+        message.update(&self.instance(), TYPE_TXT, CLASS_IN, ttl, &self.txt_rdata())?;
+        for record in &self.options.records {
+            message.update(&self.instance(), record.rrtype, CLASS_IN, record.ttl.unwrap_or(ttl), &record.rdata)?;
+        }
+        if self.options.host.is_none() {
+            let (rtype, rdata) = address_record(local);
+            message.update(&self.host(), rtype, CLASS_IN, host_ttl, &rdata)?;
+        }
+        Ok(message)
+    }
+
+    /// Update replacing the host record for `old` with one for `new`
+    fn readdress(&self, old: IpAddr, new: IpAddr) -> Result<Message, String> {
+        let mut message = Message::new(&self.config.zone)?;
+        let (rtype, rdata) = address_record(old);
+        message.update(&self.host(), rtype, CLASS_NONE, 0, &rdata)?;
+        let (rtype, rdata) = address_record(new);
+        message.update(&self.host(), rtype, CLASS_IN, self.host_ttl(), &rdata)?;
+        Ok(message)
+    }
+
+    /// Update deleting the service's PTR records and everything at its instance name
+    fn remove(&self) -> Result<Message, String> {
+        let mut message = Message::new(&self.config.zone)?;
+        message.update(&self.service_type(), TYPE_PTR, CLASS_NONE, 0, &self.ptr_rdata()?)?;
+        for subtype in &self.options.subtypes {
+            message.update(&self.subtype(subtype), TYPE_PTR, CLASS_NONE, 0, &self.ptr_rdata()?)?;
+        }
+        message.update(&self.instance(), TYPE_ANY, CLASS_ANY, 0, &[])?;
+        Ok(message)
+    }
+}
+
+/// A service registered in a unicast zone
+pub struct WideAreaAdvertisement {
+    /// Dropping it makes the worker withdraw the service; sending makes it
+    /// check whether the host's address changed
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl WideAreaAdvertisement {
+    /// Send the registration in the background, reporting `registered` or `error`
+    pub fn new<F>(config: UpdateConfig, options: &AdvertiseOptions, callback: F) -> Result<Self, String>
+    where
+        F: Fn(&str, &str) + Send + 'static,
+    {
+        let mut records = Records { config, options: options.clone(), name: options.name.clone() };
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::spawn(move || {
+            let mut local = match register(&mut records) {
+                Ok(local) => {
+                    callback("registered", &records.name);
+                    local
+                }
+                Err(e) => {
+                    callback("error", &e);
+                    return;
+                }
+            };
+            loop {
+                match stopped.recv_timeout(Duration::from_secs(3600)) {
+                    Ok(()) => readdress(&records, &mut local),
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            if let Err(e) = records.remove().map_err(UpdateError::Invalid).and_then(|m| send(&records.config, m)) {
+                tracing::warn!("Failed to withdraw {} from {}: {}", records.name, records.config.zone, e);
+            }
+        });
+        Ok(WideAreaAdvertisement { stop: Some(stop), thread: Some(thread) })
+    }
+
+    /// Update the host record if the address used to reach the server changed
+    pub fn refresh(&self) {
+        if let Some(stop) = &self.stop {
+            let _ = stop.send(());
+        }
+    }
+
+    /// Threads still running, for diagnostics
+    pub fn threads(&self) -> usize {
+        running(&self.thread)
+    }
+
+    pub fn stop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for WideAreaAdvertisement {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Add the records, renaming on conflict unless that's disabled; returns
+/// the address put in the host record
+fn register(records: &mut Records) -> Result<IpAddr, String> {
+    let local = local_address(records.config.server)?;
+    for n in 2..=MAX_RENAMES + 1 {
+        match send(&records.config, records.add(local)?) {
+            Err(UpdateError::Rcode(RCODE_YXDOMAIN)) if !records.options.no_auto_rename && n <= MAX_RENAMES => {
+                records.name = format!("{} ({})", records.options.name, n);
+            }
+            Err(UpdateError::Rcode(RCODE_YXDOMAIN)) => {
+                return Err(format!("Name conflict: {} is already registered in {}", records.name, records.config.zone));
+            }
+            result => return result.map(|()| local).map_err(|e| e.to_string()),
+        }
+    }
+    unreachable!()
+}
+
+/// Move the host record to the address now used to reach the server
+fn readdress(records: &Records, local: &mut IpAddr) {
+    if records.options.host.is_some() {
+        return;
+    }
+    let current = match local_address(records.config.server) {
+        Ok(current) if current != *local => current,
+        Ok(_) => return,
+        Err(e) => {
+            tracing::warn!("{}", e);
+            return;
+        }
+    };
+    let sent = records.readdress(*local, current).map_err(UpdateError::Invalid).and_then(|m| send(&records.config, m));
+    match sent {
+        Ok(()) => {
+            tracing::info!("Updated {} in {} from {} to {}", records.name, records.config.zone, local, current);
+            *local = current;
+        }
+        Err(e) => tracing::warn!("Failed to update the address of {} in {}: {}", records.name, records.config.zone, e),
+    }
+}
+
+/// Type and data of the A or AAAA record for `ip`
+fn address_record(ip: IpAddr) -> (u16, Vec<u8>) {
+    match ip {
+        IpAddr::V4(ip) => (TYPE_A, ip.octets().to_vec()),
+        IpAddr::V6(ip) => (TYPE_AAAA, ip.octets().to_vec()),
+    }
+}
+
+/// Address this machine uses to reach `server`
+fn local_address(server: SocketAddr) -> Result<IpAddr, String> {
+    let bind: SocketAddr = if server.is_ipv4() { ([0, 0, 0, 0], 0).into() } else { ([0u16; 8], 0).into() };
+    let socket = UdpSocket::bind(bind).map_err(|e| e.to_string())?;
+    socket.connect(server).map_err(|e| format!("Cannot reach update server {}: {}", server, e))?;
+    socket.local_addr().map(|addr| addr.ip()).map_err(|e| e.to_string())
+}
+
+enum UpdateError {
+    Io(String),
+    /// A name that can't be written in a DNS message
+    Invalid(String),
+    Rcode(u16),
+}
+
+impl std::fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdateError::Io(e) | UpdateError::Invalid(e) => f.write_str(e),
+            UpdateError::Rcode(rcode) => {
+                let name = match rcode {
+                    1 => "FORMERR",
+                    2 => "SERVFAIL",
+                    4 => "NOTIMP",
+                    5 => "REFUSED",
+                    6 => "YXDOMAIN",
+                    7 => "YXRRSET",
+                    8 => "NXRRSET",
+                    9 => "NOTAUTH (TSIG key rejected)",
+                    10 => "NOTZONE",
+                    _ => return write!(f, "DNS update failed with rcode {}", rcode),
+                };
+                write!(f, "DNS update failed: {}", name)
+            }
+        }
+    }
+}
+
+/// Sign and send an update, waiting for the server's answer
+fn send(config: &UpdateConfig, message: Message) -> Result<(), UpdateError> {
+    let (id, packet) = message.sign(config).map_err(UpdateError::Invalid)?;
+    let io = |e: std::io::Error| UpdateError::Io(format!("Update server {}: {}", config.server, e));
+    let bind: SocketAddr = if config.server.is_ipv4() { ([0, 0, 0, 0], 0).into() } else { ([0u16; 8], 0).into() };
+    let socket = UdpSocket::bind(bind).map_err(io)?;
+    socket.connect(config.server).map_err(io)?;
+    socket.set_read_timeout(Some(REPLY_TIMEOUT)).map_err(io)?;
+
+    let mut buf = [0u8; 1500];
+    for _ in 0..ATTEMPTS {
+        socket.send(&packet).map_err(io)?;
+        loop {
+            let len = match socket.recv(&mut buf) {
+                Ok(len) => len,
+                Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => break,
+                Err(e) => return Err(io(e)),
+            };
+            // Ignore stray datagrams and anything that isn't our reply
+            if len < 12 || u16::from_be_bytes([buf[0], buf[1]]) != id || buf[2] & 0x80 == 0 {
+                continue;
+            }
+            return match u16::from_be_bytes([buf[2], buf[3]]) & 0xf {
+                0 => Ok(()),
+                rcode => Err(UpdateError::Rcode(rcode)),
+            };
+        }
+    }
+    Err(UpdateError::Io(format!("Update server {} did not answer", config.server)))
+}
+
+/// An UPDATE message being assembled
+struct Message {
+    zone: Vec<u8>,
+    prerequisites: Vec<Vec<u8>>,
+    updates: Vec<Vec<u8>>,
+}
+
+impl Message {
+    fn new(zone: &str) -> Result<Self, String> {
+        let mut section = Vec::new();
+        write_name(&mut section, &split(zone))?;
+        section.extend_from_slice(&TYPE_SOA.to_be_bytes());
+        section.extend_from_slice(&CLASS_IN.to_be_bytes());
+        Ok(Message { zone: section, prerequisites: Vec::new(), updates: Vec::new() })
+    }
+
+    fn prerequisite(&mut self, name: &[String], rrtype: u16, class: u16, rdata: &[u8]) -> Result<(), String> {
+        self.prerequisites.push(record(name, rrtype, class, 0, rdata)?);
+        Ok(())
+    }
+
+    fn update(&mut self, name: &[String], rrtype: u16, class: u16, ttl: u32, rdata: &[u8]) -> Result<(), String> {
+        self.updates.push(record(name, rrtype, class, ttl, rdata)?);
+        Ok(())
+    }
+
+    /// Wire form with a TSIG record appended, and its message ID
+    fn sign(self, config: &UpdateConfig) -> Result<(u16, Vec<u8>), String> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        self.sign_at(config, message_id(), now)
+    }
+
+    /// [`Message::sign`] with the message ID and signing time given
+    fn sign_at(self, config: &UpdateConfig, id: u16, now: u64) -> Result<(u16, Vec<u8>), String> {
+        let mut packet = Vec::new();
+        packet.extend_from_slice(&id.to_be_bytes());
+        packet.extend_from_slice(&OPCODE_UPDATE.to_be_bytes());
+        packet.extend_from_slice(&1u16.to_be_bytes());
+        packet.extend_from_slice(&(self.prerequisites.len() as u16).to_be_bytes());
+        packet.extend_from_slice(&(self.updates.len() as u16).to_be_bytes());
+        packet.extend_from_slice(&0u16.to_be_bytes());
+        packet.extend_from_slice(&self.zone);
+        self.prerequisites.iter().chain(&self.updates).for_each(|rr| packet.extend_from_slice(rr));
+
+        let mut key_name = Vec::new();
+        write_name(&mut key_name, &split(&config.key_name.to_ascii_lowercase()))?;
+        let mut algorithm = Vec::new();
+        write_name(&mut algorithm, &split("hmac-sha256"))?;
+        let time_signed = &now.to_be_bytes()[2..];
+
+        // RFC 8945 section 4.3.3: the message, then the TSIG variables
+        let mut signed = packet.clone();
+        signed.extend_from_slice(&key_name);
+        signed.extend_from_slice(&CLASS_ANY.to_be_bytes());
+        signed.extend_from_slice(&0u32.to_be_bytes());
+        signed.extend_from_slice(&algorithm);
+        signed.extend_from_slice(time_signed);
+        signed.extend_from_slice(&TSIG_FUDGE.to_be_bytes());
+        signed.extend_from_slice(&0u16.to_be_bytes());
+        signed.extend_from_slice(&0u16.to_be_bytes());
+        let mut mac = Hmac::<Sha256>::new_from_slice(&config.secret).expect("HMAC takes keys of any length");
+        mac.update(&signed);
+        let mac = mac.finalize().into_bytes();
+
+        let mut rdata = algorithm;
+        rdata.extend_from_slice(time_signed);
+        rdata.extend_from_slice(&TSIG_FUDGE.to_be_bytes());
+        rdata.extend_from_slice(&(mac.len() as u16).to_be_bytes());
+        rdata.extend_from_slice(&mac);
+        rdata.extend_from_slice(&id.to_be_bytes());
+        rdata.extend_from_slice(&0u16.to_be_bytes());
+        rdata.extend_from_slice(&0u16.to_be_bytes());
+        packet.extend_from_slice(&record(&split(&config.key_name), TYPE_TSIG, CLASS_ANY, 0, &rdata)?);
+        packet[10..12].copy_from_slice(&1u16.to_be_bytes());
+        Ok((id, packet))
+    }
+}
+
+/// Fresh message ID; unpredictable enough to reject stray replies
+fn message_id() -> u16 {
+    static COUNTER: std::sync::atomic::AtomicU16 = std::sync::atomic::AtomicU16::new(0);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.subsec_nanos());
+    (nanos as u16 ^ (nanos >> 16) as u16).wrapping_add(COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed))
+}
+
+fn split(name: &str) -> Vec<String> {
+    name.trim_end_matches('.').split('.').filter(|l| !l.is_empty()).map(str::to_string).collect()
+}
+
+/// Uncompressed wire form of a name; labels are limited to 63 bytes
+fn write_name(out: &mut Vec<u8>, labels: &[String]) -> Result<(), String> {
+    for label in labels {
+        if label.len() > 63 {
+            return Err(format!("DNS label longer than 63 bytes: {}", label));
+        }
+        out.push(label.len() as u8);
+        out.extend_from_slice(label.as_bytes());
+    }
+    out.push(0);
+    Ok(())
+}
+
+fn record(name: &[String], rrtype: u16, class: u16, ttl: u32, rdata: &[u8]) -> Result<Vec<u8>, String> {
+    let mut rr = Vec::new();
+    write_name(&mut rr, name)?;
+    rr.extend_from_slice(&rrtype.to_be_bytes());
+    rr.extend_from_slice(&class.to_be_bytes());
+    rr.extend_from_slice(&ttl.to_be_bytes());
+    rr.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+    rr.extend_from_slice(rdata);
+    Ok(rr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(text: &str) -> Vec<u8> {
+        (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap()).collect()
+    }
+
+    #[test]
+    fn signs_with_tsig_hmac_sha256() {
+        // "secret-key-for-testing"; the MAC was checked against hickory-proto's
+        // TSIG verifier (RFC 8945 section 4.3.3 digest components)
+        let secret = "c2VjcmV0LWtleS1mb3ItdGVzdGluZw==";
+        let config = UpdateConfig::new("192.0.2.1", "example.com", "update-key", secret, None).unwrap();
+        let mut message = Message::new("example.com").unwrap();
+        let mut rdata = Vec::new();
+        write_name(&mut rdata, &split("Web._http._tcp.example.com")).unwrap();
+        message.update(&split("_http._tcp.example.com"), TYPE_PTR, CLASS_IN, 3600, &rdata).unwrap();
+        let (id, packet) = message.sign_at(&config, 0x1234, 1_700_000_000).unwrap();
+        assert_eq!(id, 0x1234);
+
+        // ARCOUNT counts the TSIG record, the only additional record
+        assert_eq!(packet[10..12], [0, 1]);
+        // Header, zone, the PTR update, then the TSIG record
+        let unsigned = 12 + 17 + 62;
+        assert_eq!(packet.len(), unsigned + 22 + 61);
+        let tsig = &packet[unsigned..];
+        assert_eq!(&tsig[..12], b"\x0aupdate-key\x00");
+        assert_eq!(tsig[12..22], [0, 250, 0, 255, 0, 0, 0, 0, 0, 61]);
+        let rdata = &tsig[22..];
+        assert_eq!(&rdata[..13], b"\x0bhmac-sha256\x00");
+        // Time signed (48 bits), fudge, MAC size, MAC, original ID, error, other length
+        assert_eq!(rdata[13..19], [0, 0, 0x65, 0x53, 0xf1, 0x00]);
+        assert_eq!(rdata[19..23], [1, 44, 0, 32]);
+        assert_eq!(rdata[23..55], hex("c4517b8557b7f13ca42ae1441f456fde265f826dcf06f3b2ab72f753f3a4a913")[..]);
+        assert_eq!(rdata[55..], [0x12, 0x34, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn rejects_labels_over_63_bytes() {
+        let mut out = Vec::new();
+        assert!(write_name(&mut out, &["a".repeat(63)]).is_ok());
+        assert!(write_name(&mut out, &["a".repeat(64)]).unwrap_err().contains("longer than 63 bytes"));
+    }
+}
@@ -0,0 +1,92 @@
+//! Resume-from-sleep detection
+//!
+//! A ticking thread compares how much wall-clock time passed with how much
+//! its monotonic clock saw. The monotonic clock stops while the system
+//! sleeps on Linux and macOS, and on every platform the tick itself comes
+//! late after a resume, so a gap of several seconds means the machine was
+//! asleep. Services seen before then may be long gone, and cached TTLs
+//! didn't count down, so on wake the discovery cache is expired and every
+//! browse restarted and advertisement re-announced, as after a network
+//! change. `systemResumed` is reported with how long the machine slept.
+//!
+//! The thread ticks for the life of the process, so it only runs once
+//! turned on with `set_enabled`.
+
+use crate::{Sink, cache, handles};
+use neon::event::Channel;
+use neon::handle::Root;
+use neon::prelude::*;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the clocks are compared
+const TICK: Duration = Duration::from_secs(2);
+
+/// Unaccounted time taken as a sleep rather than scheduling noise or a
+/// clock adjustment
+const SLEEP_THRESHOLD: Duration = Duration::from_secs(10);
+
+/// Time for interfaces to come back up before refreshing
+const SETTLE: Duration = Duration::from_secs(2);
+
+/// How long a restarted browse has to report a service again; longer than
+/// after a network change, since Wi-Fi may still be reassociating
+const RECONFIRM: Duration = Duration::from_secs(10);
+
+/// JS function receiving `(sleptMs)`
+static SINK: Sink = Sink::new();
+
+/// Stops the running watcher when dropped
+static WATCHER: Mutex<Option<Sender<()>>> = Mutex::new(None);
+
+/// Start or stop watching for resumes from sleep
+pub fn set_enabled(enabled: bool) {
+    let mut watcher = WATCHER.lock().unwrap();
+    if !enabled {
+        // Dropping the sender ends the watcher
+        *watcher = None;
+        return;
+    }
+    if watcher.is_some() {
+        return;
+    }
+    let (tx, rx) = mpsc::channel::<()>();
+    thread::spawn(move || {
+        let mut wall = SystemTime::now();
+        let mut mono = Instant::now();
+        while let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(TICK) {
+            let (now_wall, now_mono) = (SystemTime::now(), Instant::now());
+            // A wall clock set backwards counts as no time passed
+            let wall_elapsed = now_wall.duration_since(wall).unwrap_or_default();
+            let mono_elapsed = now_mono.duration_since(mono);
+            (wall, mono) = (now_wall, now_mono);
+
+            let slept = wall_elapsed.saturating_sub(mono_elapsed).max(mono_elapsed.saturating_sub(TICK));
+            if slept >= SLEEP_THRESHOLD {
+                resumed(slept);
+                // The refresh itself takes a while
+                (wall, mono) = (SystemTime::now(), Instant::now());
+            }
+        }
+    });
+    *watcher = Some(tx);
+}
+
+/// Deliver `systemResumed` notifications to `callback`, or stop with `None`
+pub fn set_callback(sink: Option<(Channel, Root<JsFunction>)>) {
+    SINK.set(sink);
+}
+
+fn resumed(slept: Duration) {
+    tracing::info!("System resumed after about {}s asleep", slept.as_secs());
+    notify(slept);
+    cache::expire();
+    thread::sleep(SETTLE);
+    handles::refresh_all(RECONFIRM);
+}
+
+fn notify(slept: Duration) {
+    SINK.call(move |cx| Ok(vec![cx.number(slept.as_millis() as f64).upcast()]));
+}
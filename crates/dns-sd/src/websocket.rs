@@ -0,0 +1,238 @@
+//! WebSocket bridge: the [`rpc`](crate::rpc) protocol over a local WebSocket
+//!
+//! Each text message is one JSON-RPC request; responses and event
+//! notifications come back as text messages. Every connection gets its own
+//! [`Session`], so closing the socket stops whatever that client started.
+
+use crate::Backend;
+use crate::rpc::Session;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::Value;
+use sha1::{Digest, Sha1};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Largest request head or message accepted from a client
+const MAX_MESSAGE: usize = 1 << 20;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xa;
+
+/// WebSocket bridge settings
+#[derive(Debug, Clone)]
+pub struct BridgeConfig {
+    /// Listen address (loopback by default; the bridge has no authentication)
+    pub addr: SocketAddr,
+    /// Browser `Origin` headers accepted; a request with any other `Origin`
+    /// is refused, so other sites' pages can't reach the bridge
+    pub allowed_origins: Vec<String>,
+    /// Accept every `Origin`, for pages whose origin isn't known in advance
+    pub allow_any_origin: bool,
+}
+
+impl BridgeConfig {
+    /// Whether a request with this `Origin` header may connect; clients
+    /// that aren't browsers send none
+    fn accepts(&self, origin: Option<&str>) -> bool {
+        match origin {
+            None => true,
+            Some(origin) => self.allow_any_origin || self.allowed_origins.iter().any(|allowed| allowed == origin),
+        }
+    }
+}
+
+impl Default for BridgeConfig {
+    fn default() -> Self {
+        BridgeConfig {
+            addr: SocketAddr::from(([127, 0, 0, 1], 8765)),
+            allowed_origins: Vec::new(),
+            allow_any_origin: false,
+        }
+    }
+}
+
+/// Accept bridge connections until the listener fails
+pub fn serve(backend: Backend, config: BridgeConfig) -> Result<(), String> {
+    let listener = TcpListener::bind(config.addr).map_err(|e| format!("Failed to bind {}: {}", config.addr, e))?;
+    let config = Arc::new(config);
+    for stream in listener.incoming() {
+        let stream = stream.map_err(|e| format!("Failed to accept: {}", e))?;
+        let config = config.clone();
+        thread::spawn(move || {
+            let _ = handle_connection(backend, &config, stream);
+        });
+    }
+    Ok(())
+}
+
+fn handle_connection(backend: Backend, config: &BridgeConfig, mut stream: TcpStream) -> std::io::Result<()> {
+    let Some(key) = handshake(config, &mut stream)? else {
+        return Ok(());
+    };
+    stream.write_all(
+        format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            accept_key(&key)
+        )
+        .as_bytes(),
+    )?;
+
+    let writer = Arc::new(Mutex::new(stream.try_clone()?));
+    let sender = writer.clone();
+    let mut session = Session::new(
+        backend,
+        Arc::new(move |message: Value| {
+            let _ = write_frame(&mut sender.lock().unwrap(), OP_TEXT, message.to_string().as_bytes());
+        }),
+    );
+
+    let mut message = Vec::new();
+    loop {
+        let (fin, opcode, payload) = read_frame(&mut stream)?;
+        match opcode {
+            OP_TEXT | OP_CONTINUATION => {
+                message.extend_from_slice(&payload);
+                if message.len() > MAX_MESSAGE {
+                    break;
+                }
+                if fin {
+                    session.handle_text(&String::from_utf8_lossy(&message));
+                    message.clear();
+                }
+            }
+            OP_PING => write_frame(&mut writer.lock().unwrap(), OP_PONG, &payload)?,
+            OP_CLOSE => {
+                let _ = write_frame(&mut writer.lock().unwrap(), OP_CLOSE, &payload);
+                break;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Read the HTTP upgrade request, returning the client key if it is acceptable
+fn handshake(config: &BridgeConfig, stream: &mut TcpStream) -> std::io::Result<Option<String>> {
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() > MAX_MESSAGE || stream.read(&mut byte)? == 0 {
+            return Ok(None);
+        }
+        head.push(byte[0]);
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let header = |name: &str| {
+        head.lines().skip(1).find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim().eq_ignore_ascii_case(name).then(|| value.trim().to_string())
+        })
+    };
+
+    let is_upgrade = head.starts_with("GET ")
+        && header("Upgrade").is_some_and(|v| v.eq_ignore_ascii_case("websocket"));
+    let Some(key) = header("Sec-WebSocket-Key").filter(|_| is_upgrade) else {
+        stream.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")?;
+        return Ok(None);
+    };
+    if !config.accepts(header("Origin").as_deref()) {
+        stream.write_all(b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n")?;
+        return Ok(None);
+    }
+    Ok(Some(key))
+}
+
+/// Read one frame: (fin, opcode, unmasked payload)
+fn read_frame(stream: &mut TcpStream) -> std::io::Result<(bool, u8, Vec<u8>)> {
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header)?;
+    let fin = header[0] & 0x80 != 0;
+    let opcode = header[0] & 0x0f;
+    let masked = header[1] & 0x80 != 0;
+
+    let len = match header[1] & 0x7f {
+        126 => {
+            let mut ext = [0u8; 2];
+            stream.read_exact(&mut ext)?;
+            u16::from_be_bytes(ext) as usize
+        }
+        127 => {
+            let mut ext = [0u8; 8];
+            stream.read_exact(&mut ext)?;
+            u64::from_be_bytes(ext) as usize
+        }
+        len => len as usize,
+    };
+    if len > MAX_MESSAGE {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "frame too large"));
+    }
+
+    let mut mask = [0u8; 4];
+    if masked {
+        stream.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload)?;
+    if masked {
+        for (i, b) in payload.iter_mut().enumerate() {
+            *b ^= mask[i % 4];
+        }
+    }
+    Ok((fin, opcode, payload))
+}
+
+/// Write one unfragmented, unmasked frame
+fn write_frame(stream: &mut TcpStream, opcode: u8, payload: &[u8]) -> std::io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame)
+}
+
+/// `Sec-WebSocket-Accept` for a client key (RFC 6455 §4.2.2)
+fn accept_key(key: &str) -> String {
+    STANDARD.encode(Sha1::digest(format!("{}{}", key, HANDSHAKE_GUID)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_key_matches_rfc_6455() {
+        // RFC 6455 section 1.3
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn origins_are_refused_unless_allowed() {
+        let mut config = BridgeConfig::default();
+        assert!(config.accepts(None));
+        assert!(!config.accepts(Some("https://example.com")));
+
+        config.allowed_origins.push("http://localhost:3000".into());
+        assert!(config.accepts(Some("http://localhost:3000")));
+        assert!(!config.accepts(Some("https://example.com")));
+
+        config.allow_any_origin = true;
+        assert!(config.accepts(Some("https://example.com")));
+    }
+}
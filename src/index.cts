@@ -3,22 +3,112 @@
 import { EventEmitter } from 'events';
 import * as addon from './load.cjs';
 
+// Opaque handle object owned by the addon; released on stop or garbage collection
+type AddonHandle = { readonly __addonHandle: never };
+
+// Opaque cancellation flag checked by long-running addon calls
+type AbortToken = { readonly __abortToken: never };
+
 // Declare the addon functions (Neon exports camelCase names)
 declare module "./load.cjs" {
   function browseServices(
     serviceType: string,
+    options: BrowseOptions | undefined,
+    callback: (event: string, data: unknown) => void
+  ): AddonHandle;
+  function browseServicesV2(
+    options: BrowseOptions & { type: string },
     callback: (event: string, data: unknown) => void
-  ): number;
-  function stopBrowse(handle: number): boolean;
+  ): AddonHandle;
+  function stopBrowse(handle: AddonHandle): boolean;
+  function pauseBrowse(handle: AddonHandle): boolean;
+  function resumeBrowse(handle: AddonHandle): boolean;
+  function getServices(handle: AddonHandle): Service[];
+  function resolveInstance(handle: AddonHandle, name: string): Promise<Service>;
   function advertiseService(
     name: string,
     serviceType: string,
     port: number,
     txt: Record<string, string> | undefined,
     callback: (event: string, data: unknown) => void
-  ): number;
-  function stopAdvertise(handle: number): boolean;
+  ): AddonHandle;
+  function advertiseServiceV2(
+    options: Omit<AdvertiseOptions, 'hostName'> & { host?: string },
+    callback: (event: string, data: unknown) => void
+  ): AddonHandle;
+  function stopAdvertise(handle: AddonHandle): boolean;
+  function stopAdvertiseAsync(handle: AddonHandle, timeoutMs?: number): Promise<boolean>;
+  function renameAdvertisement(handle: AddonHandle, name: string): Promise<boolean>;
+  function updateAdvertisementPort(handle: AddonHandle, port: number): Promise<boolean>;
+  function publishAlias(alias: string, callback: (event: string, data: unknown) => void): AddonHandle;
+  function publishHostRecords(
+    hostname: string,
+    addresses: string[],
+    callback: (event: string, data: unknown) => void
+  ): AddonHandle;
+  function advertiseFromManifest(
+    path: string,
+    callback: (event: string, data: unknown, serviceType: string) => void
+  ): AddonHandle;
+  function advertiseServices(
+    specs: (Omit<AdvertiseOptions, 'hostName' | 'signal'> & { host?: string })[],
+    callback: (event: string, data: unknown, index: number) => void
+  ): AddonHandle;
+  function createBrowser(target: Browser, options: BrowseOptions & { type: string }): AddonHandle;
+  function closeBrowser(handle: AddonHandle): boolean;
+  function createAdvertisement(target: Advertisement, options: Omit<AdvertiseOptions, 'hostName'> & { host?: string }): AddonHandle;
+  function closeAdvertisement(handle: AddonHandle): boolean;
+  function findService(options: FindOptions & { type: string }, token?: AbortToken): Promise<Service>;
+  function createAbortToken(): AbortToken;
+  function abort(token: AbortToken): void;
+  function createDiscoveryManager(
+    types: string[],
+    options: BrowseOptions | undefined,
+    callback: (event: string, data: unknown) => void
+  ): AddonHandle;
+  function managerAddType(handle: AddonHandle, serviceType: string): boolean;
+  function managerRemoveType(handle: AddonHandle, serviceType: string): boolean;
+  function managerGetTypes(handle: AddonHandle): string[];
+  function managerGetSnapshot(handle: AddonHandle): Service[];
+  function stopDiscoveryManager(handle: AddonHandle): void;
+  function configureFallback(options: FallbackConfig): void;
+  function setUpdateServer(options: UpdateServerConfig | null): void;
+  function verifyService(fullname: string, timeoutMs?: number): boolean;
   function getBackendInfo(): string;
+  function listInterfaces(): NetworkInterface[];
+  function setNetworkWatchEnabled(enabled: boolean): void;
+  function setNetworkChangedCallback(callback: NetworkChangedCallback | null): void;
+  function setPacketTapCallback(callback: ((packet: MdnsPacket) => void) | null): void;
+  function setWakeWatchEnabled(enabled: boolean): void;
+  function setSystemResumedCallback(callback: ((sleptMs: number) => void) | null): void;
+  function listBrowsers(): HandleInfo[];
+  function listAdvertisements(): HandleInfo[];
+  function getMetrics(): Metrics;
+  function getCachedServices(): Service[];
+  function clearCache(): void;
+  function setCacheFile(path: string | null): number;
+  function setCacheEnabled(enabled: boolean): void;
+  function setCacheEvictedCallback(callback: ((service: Service) => void) | null): void;
+  function checkHealth(timeoutMs?: number): Promise<HealthReport>;
+  function dumpDiagnostics(): Diagnostics;
+  function runSelftest(timeoutMs?: number): Promise<SelftestReport>;
+  function getMetricsPrometheus(): string;
+  function setLogLevel(level: LogLevel): void;
+  function setLogCallback(callback: LogCallback | null): void;
+  function setBackend(name: BackendName): void;
+  function reprobeBackend(): string;
+  function setBackendProbeInterval(intervalMs: number | null): void;
+  function setBackendChangedCallback(callback: BackendChangedCallback | null): void;
+  function setMockScript(steps: MockStep[]): void;
+  function setLoopbackOnly(enabled: boolean): void;
+  function startRecording(path: string): void;
+  function stopRecording(): boolean;
+  function loadRecording(path: string): number;
+  function startCapture(path: string): void;
+  function stopCapture(): boolean;
+  function getServiceFlags(): Record<ServiceFlagName, number>;
+  function escapeInstanceName(name: string): string;
+  function unescapeInstanceName(escaped: string): string;
 }
 
 const DEBOUNCE_TIMEOUT = 100;
@@ -28,107 +118,556 @@ export type Service = {
   name: string;
   type: string;
   domain: string;
+  // Escaped full name, e.g. 'Living\032Room._http._tcp.local.'; stable key for the instance
+  fullName: string;
   hostName: string;
   addresses: string[];
   port: number;
+  // SRV priority and weight for RFC 2782 selection; 0 on the mdns-sd backend
+  // when the SRV answer wasn't multicast
+  priority: number;
+  weight: number;
   txt?: Record<string, string>;
   ttl?: number;
+  // Absent when interfaces are merged (the default)
+  interfaceIndex?: number;
+  interfaceName?: string;
+  // Problems found in the TXT record (native backend); well-formed entries are still in `txt`
+  txtErrors?: string[];
+  // Raw DNS-SD flags of the callback behind the event (native backend); decode with ServiceFlags
+  flags?: number;
+  // DNSSEC result of the address lookup, for browses with `validate` (native backend)
+  dnssec?: DnssecStatus;
+  // Loaded from the cache file and not yet confirmed by the network
+  stale?: boolean;
+  // Per-phase discovery timings, for browses with `profile`
+  timings?: ServiceTimings;
+  // Why the resolve timed out or gave up, on 'resolveTimeout' and 'resolveFailed'
+  reason?: string;
+  // The address that appeared or went away, on 'addressAdded' and 'addressRemoved'
+  address?: string;
+};
+
+// Milliseconds per phase; phases a backend doesn't go through are absent
+export type ServiceTimings = {
+  // Browse answer to the start of the resolve (native backend)
+  queueMs?: number;
+  // Resolve of host name, port and TXT (fallback: PTR to resolved service)
+  resolveMs?: number;
+  // Address lookup to the first address (native backend)
+  addressMs?: number;
+  // Backend to this event, including queueing, debouncing and batching
+  dispatchMs?: number;
+};
+
+export type DnssecStatus = 'secure' | 'insecure' | 'bogus' | 'indeterminate';
+
+export type ServiceFlagName =
+  | 'MoreComing' | 'Add' | 'Default' | 'NoAutoRename' | 'Shared' | 'Unique'
+  | 'BrowseDomains' | 'RegistrationDomains' | 'LongLivedQuery' | 'AllowRemoteQuery'
+  | 'ForceMulticast' | 'ReturnIntermediates' | 'NonBrowsable' | 'ShareConnection'
+  | 'SuppressUnusable' | 'Timeout' | 'IncludeP2P' | 'WakeOnResolve' | 'BackgroundTrafficClass'
+  | 'IncludeAWDL' | 'Validate' | 'Secure' | 'Insecure' | 'Bogus' | 'Indeterminate'
+  | 'UnicastResponse' | 'ExpiredAnswer' | 'AllowExpiredAnswers';
+
+// kDNSServiceFlags* bits by name, e.g. `service.flags & ServiceFlags.MoreComing`
+export const ServiceFlags: Readonly<Record<ServiceFlagName, number>> = Object.freeze(addon.getServiceFlags());
+
+export type HandleInfo = {
+  id: number;
+  type: string;
+  backend: string;
+  // Milliseconds since the Unix epoch
+  createdAt: number;
+  state: 'active' | 'paused' | 'stopped';
+};
+
+export type BackendName = 'auto' | 'native' | 'mdns-sd' | 'mock';
+
+// One event of the mock backend's timeline
+export type MockStep = {
+  // Wait before this step, from the previous step (default 0)
+  delayMs?: number;
+  event: 'serviceFound' | 'serviceUpdated' | 'serviceLost' | 'registered' | 'error';
+  // Browse steps: the service reported. Advertise steps: matched by name (absent = every advertisement)
+  service?: Partial<Service>;
+  // Advertise steps: the event payload (default: the advertised name)
+  data?: string;
+};
+
+export type LogLevel = 'off' | 'error' | 'warn' | 'info' | 'debug' | 'trace';
+
+// `target` is the Rust module that logged, e.g. `dns_sd::native` or `mdns_sd::service_daemon`
+// Receives the backend new handles will use after re-detection
+export type BackendChangedCallback = (backend: string) => void;
+
+export type LogCallback = (level: Exclude<LogLevel, 'off'>, target: string, message: string) => void;
+
+export type Metrics = {
+  // Backend browse events by name, e.g. `serviceFound`
+  events: Record<string, number>;
+  resolves: {
+    attempted: number;
+    succeeded: number;
+    timedOut: number;
+    // Answered from the discovery cache instead of resolving again
+    cached: number;
+    // Percentiles over recent successful resolves
+    latencyMs: { p50: number; p90: number; p99: number };
+  };
+  activeHandles: { browsers: number; advertisements: number };
+  backendErrors: number;
+  // Events discarded by full handle queues
+  droppedEvents: number;
+};
+
+export type NetworkInterface = {
+  // Value for the `interface` browse/advertise options
+  index: number;
+  name: string;
+  // Absent without a hardware address (e.g. loopback, tunnels, Windows)
+  mac?: string;
+  addresses: string[];
+  up: boolean;
+  multicast: boolean;
+  loopback: boolean;
+};
+
+export type NetworkChange = {
+  // Addresses of interfaces that are up, compared with before the change
+  added: { interface: string; interfaceIndex: number; address: string }[];
+  removed: { interface: string; interfaceIndex: number; address: string }[];
+};
+
+// Called when a change is detected, before browses restart
+export type NetworkChangedCallback = (change: NetworkChange) => void;
+
+export type MdnsPacket = {
+  // 'out' when sent from one of this machine's addresses
+  direction: 'in' | 'out';
+  data: Buffer;
+  address: string;
+  port: number;
+  // Interface whose subnet the source is on
+  interface?: string;
+  interfaceIndex: number;
+  // Milliseconds since the epoch
+  timestamp: number;
+};
+
+// Another device answered for a host name the mdns-sd fallback publishes
+export type HostnameConflict = {
+  hostName: string;
+  // Address the other device answered with
+  address: string;
+  // Where its answer came from
+  responder: string;
+  interface?: string;
+  interfaceIndex: number;
+};
+
+export type HealthCheck = {
+  status: 'ok' | 'failed' | 'skipped';
+  // What was found, or why it failed or was skipped
+  detail: string;
+  elapsedMs: number;
+};
+
+export type HealthReport = {
+  // Native checks pass (when native is in use) and some interface carries multicast
+  healthy: boolean;
+  backend: string;
+  nativeLibrary: HealthCheck;
+  daemon: HealthCheck;
+  // LocalOnly register/browse through the daemon
+  roundTrip: HealthCheck;
+  interfaces: {
+    name: string;
+    index: number;
+    address: string;
+    sent: boolean;
+    // Our own query came back to this host
+    looped: boolean;
+    // Other hosts heard from on this link
+    peers: number;
+    error?: string;
+  }[];
+};
+
+export type SelftestReport = {
+  // No stage failed
+  passed: boolean;
+  backend: string;
+  // backend, daemon, interfaces, register, browse and resolve, in that order
+  stages: (HealthCheck & {
+    name: 'backend' | 'daemon' | 'interfaces' | 'register' | 'browse' | 'resolve';
+    // Likely cause of a failure and what to try
+    hint?: string;
+  })[];
+};
+
+export type Diagnostics = {
+  version: string;
+  // e.g. 'linux-x86_64'
+  platform: string;
+  time: number;
+  backend: string;
+  nativeLibrary: { path: string; loaded: boolean; error?: string };
+  loopbackOnly: boolean;
+  // Fallback handles holding the mdns-sd daemon (0 = not running)
+  fallbackDaemonRefs: number;
+  // Live handles of every thread; `environment` tells the threads apart
+  handles: {
+    environment: number;
+    kind: 'browser' | 'advertisement';
+    id: number;
+    type: string;
+    backend: string;
+    state: 'active' | 'paused' | 'stopped';
+    createdAt: number;
+    // Backend threads still running
+    threads: number;
+    // Browsers: events waiting for the JS thread
+    queueDepth?: number;
+  }[];
+  // Backend browsers shared by identical browses
+  sharedBrowsers: {
+    backend: string;
+    type: string;
+    domain?: string;
+    interfaceIndex: number;
+    subscribers: number;
+    services: number;
+    refreshIntervalMs?: number;
+  }[];
+  // Stopped handles still joining their backend threads
+  pendingStops: number;
+  cache: Service[];
+  droppedEvents: number;
+  backendErrors: number;
+  // The last 32 backend errors, oldest first
+  recentErrors: { time: number; message: string }[];
+};
+
+export type BrowseOptions = {
+  domain?: string;
+  // Also browse every domain the daemon enumerates; results carry their domain
+  browseAllDomains?: boolean;
+  // Interface index (0 = all), 'local-only' to stay on this machine, or 'p2p' for AWDL only
+  interface?: number | 'local-only' | 'p2p';
+  // Interface names, e.g. ['en0', 'eth1'], looked up when the browse starts
+  interfaces?: string[];
+  // Apple platforms: also use peer-to-peer interfaces (AWDL); ignored elsewhere
+  includeP2P?: boolean;
+  // Address families to resolve
+  protocol?: 'any' | 'ipv4' | 'ipv6';
+  // Raw DNSServiceFlags for the native browse call
+  flags?: number;
+  // Bonjour: browse and resolve over mDNS even outside `.local`
+  forceMulticast?: boolean;
+  // Bonjour: check DNSSEC signatures of resolved addresses; see Service.dnssec
+  validate?: boolean;
+  // Report browse answers (name, type, domain, interface) without resolving
+  // host, port, TXT or addresses (default: true)
+  resolve?: boolean;
+  // Withhold serviceFound until the host and an address of each family
+  // are known, or answers stop coming with at least one address
+  waitForComplete?: boolean;
+  // Withhold serviceFound until the service has this many addresses
+  minimumAddresses?: number;
+  // Report each partial resolve result as it arrives (default: true unless
+  // waitForComplete or minimumAddresses is set)
+  emitPartial?: boolean;
+  // Attach per-phase timings to service events; see Service.timings
+  profile?: boolean;
+  // Coalesce addon events into batches to reduce event loop wakeups
+  batch?: boolean;
+  batchIntervalMs?: number;
+  // Merge an instance seen on several interfaces into one service (default: true)
+  dedup?: boolean;
+  // Report each (instance, interface) pair separately
+  perInterface?: boolean;
+  // Only report instances whose name matches: a glob of `*` and `?`
+  // (ignoring case) or a RegExp, checked before events reach JS
+  nameFilter?: string | RegExp;
+  // Instance names (exact or glob) to skip, or the only ones to report;
+  // the native backend doesn't even resolve the others
+  ignoreNames?: string[];
+  onlyNames?: string[];
+  // TXT entries the service must carry; '' only requires the key
+  txtFilter?: Record<string, string>;
+  // Drop address classes from `Service.addresses`
+  excludeLoopback?: boolean;
+  excludeLinkLocal?: boolean;
+  excludeSiteLocal?: boolean;
+  // Native backend resolve timings
+  resolveTimeoutMs?: number;
+  addressTimeoutMs?: number;
+  // Native backend: retries of an unanswered resolve, the first after
+  // resolveRetryDelayMs and each later one after twice the previous wait
+  resolveRetries?: number;
+  resolveRetryDelayMs?: number;
+  // Keep following resolved services' addresses, emitting 'addressAdded'
+  // and 'addressRemoved' as they change (native backend)
+  watchAddresses?: boolean;
+  // Emit 'error' instead of reporting services whose TXT record is malformed
+  strictTxt?: boolean;
+  // Events held while the event loop is busy before `overflow` applies (default: 1024)
+  queueSize?: number;
+  overflow?: 'dropOldest' | 'coalesce' | 'error';
+  // Hold an instance's events until it has been quiet this long
+  debounceMs?: number;
+  // Merge an instance's events for this long after its first one, so a
+  // resolve's host and address answers arrive as one event
+  settleMs?: number;
+  // Cap found/updated/lost events per second; excess is held and merged per instance
+  maxEventsPerSecond?: number;
+  // Re-issue the browse this often; services not seen again are reported lost (at least 10000)
+  refreshIntervalMs?: number;
+  // Stop browsing (or reject findService) when aborted
+  signal?: AbortSignal;
+};
+
+export type FallbackConfig = {
+  // Interface names to use, e.g. ['eth0'] (default: all)
+  interfaces?: string[];
+  ipv4?: boolean;
+  ipv6?: boolean;
+  // Receive multicast sent by this host (needed to see local services)
+  multicastLoop?: boolean;
+  // How often network interfaces are rescanned
+  ipCheckIntervalSecs?: number;
+  // Browse query schedule: `queryBurst` queries `queryBurstIntervalMs` apart,
+  // then retries from `queryRetryIntervalMs`, doubling up to `queryMaxIntervalMs`
+  queryBurst?: number;
+  queryBurstIntervalMs?: number;
+  queryRetryIntervalMs?: number;
+  queryMaxIntervalMs?: number;
+};
+
+export type UpdateServerConfig = {
+  // Primary server for the zone: 'host' or 'host:port' (default port 53)
+  server: string;
+  // Zone that advertisements with this `domain` are published into
+  zone: string;
+  // TSIG key name and base64 secret (HMAC-SHA256)
+  keyName: string;
+  secret: string;
+  // TTL of the published records in seconds (default: 3600)
+  ttl?: number;
+};
+
+export type FindOptions = BrowseOptions & {
+  // Instance name to match (case-insensitive)
+  name?: string;
+  // Reject if nothing matches in time (default: 5000)
+  timeoutMs?: number;
 };
 
 export type AdvertiseOptions = {
+  // Instance name, any UTF-8 up to 63 bytes (not characters)
   name: string;
   type: string;
   domain?: string;
+  // Published host name, e.g. "kiosk-3" (default: this machine)
   hostName?: string;
   port: number;
+  // Published in key order
   txt?: Record<string, string>;
+  // Publish `txtvers` first, wherever it is in `txt`
+  txtversFirst?: boolean;
+  // Throw a TypeError for TXT keys RFC 6763 doesn't allow instead of publishing them
+  strictTxt?: boolean;
+  // Interface index (0 = all), 'local-only' to stay on this machine, or 'p2p' for AWDL only
+  interface?: number | 'local-only' | 'p2p';
+  // Interface names, e.g. ['en0', 'eth1'], looked up at registration
+  interfaces?: string[];
+  // Apple platforms: also use peer-to-peer interfaces (AWDL); ignored elsewhere
+  includeP2P?: boolean;
+  subtypes?: string[];
+  // Fail with a conflict error instead of picking a new name
+  noAutoRename?: boolean;
+  allowRemoteQuery?: boolean;
+  // Fallback: publish only these IPs instead of every host address
+  addresses?: string[];
+  // TTL in seconds of the PTR and TXT records (default: backend's)
+  ttl?: number;
+  // TTL in seconds of the SRV and address records; wide-area only
+  hostTtl?: number;
+  // Extra records under the instance name (e.g. AirPrint's NULL record);
+  // data is the wire-format rdata. Not published by the fallback
+  records?: { type: string | number; data: Uint8Array; ttl?: number }[];
+  // Stop advertising when aborted
+  signal?: AbortSignal;
 };
 
+// Call `stop` when `signal` aborts; returns a function that detaches the listener
+function stopOnAbort(signal: AbortSignal | undefined, stop: () => void): () => void {
+  if (!signal) return () => {};
+  if (signal.aborted) {
+    stop();
+    return () => {};
+  }
+  signal.addEventListener('abort', stop, { once: true });
+  return () => signal.removeEventListener('abort', stop);
+}
+
+// Identity of a service (per interface when the addon reports one)
+function serviceKey(service: Service): string {
+  return `${service.name}|${service.type}|${service.domain}|${service.interfaceIndex ?? ''}`;
+}
+
 // DnsSdBrowse class
 export interface DnsSdBrowse {
   on(event: 'serviceFound', listener: (service: Service) => void): this;
+  on(event: 'serviceUpdated', listener: (service: Service) => void): this;
   on(event: 'serviceLost', listener: (service: Service) => void): this;
+  on(event: 'batchComplete', listener: () => void): this;
+  on(event: 'resolveTimeout', listener: (service: Service) => void): this;
+  on(event: 'resolveFailed', listener: (service: Service) => void): this;
+  on(event: 'addressAdded', listener: (service: Service) => void): this;
+  on(event: 'addressRemoved', listener: (service: Service) => void): this;
+  on(event: 'backendFallback', listener: () => void): this;
   on(event: 'error', listener: (error: Error) => void): this;
   emit(event: 'serviceFound', service: Service): boolean;
+  emit(event: 'serviceUpdated', service: Service): boolean;
   emit(event: 'serviceLost', service: Service): boolean;
+  emit(event: 'batchComplete'): boolean;
+  emit(event: 'resolveTimeout', service: Service): boolean;
+  emit(event: 'resolveFailed', service: Service): boolean;
+  emit(event: 'addressAdded', service: Service): boolean;
+  emit(event: 'addressRemoved', service: Service): boolean;
+  emit(event: 'backendFallback'): boolean;
   emit(event: 'error', error: Error): boolean;
 }
 
 export class DnsSdBrowse extends EventEmitter {
-  private _handle: number;
+  private _handle: AddonHandle;
   private _stopped: boolean = false;
   private _services: Map<string, Service> = new Map();
+  private _reported: Set<string> = new Set();
   private _pendingEmit: Map<string, ReturnType<typeof setTimeout>> = new Map();
+  private _detachSignal: () => void = () => {};
+  // Browse-only services never get addresses, so don't wait for one
+  private _browseOnly: boolean;
 
-  constructor(serviceType: string) {
+  constructor(serviceType: string, options?: BrowseOptions) {
     super();
-    this._handle = addon.browseServices(serviceType, (event, data) => {
-      if (this._stopped) return;
+    const { signal, ...rest } = options ?? {};
+    this._browseOnly = rest.resolve === false;
+    this._handle = addon.browseServicesV2({ ...rest, type: serviceType }, (event, data) => this._onEvent(event, data));
+    this._detachSignal = stopOnAbort(signal, () => this.stop());
+  }
 
-      switch (event) {
-        case 'serviceFound': {
-          const incoming = data as Service;
-          const key = `${incoming.name}|${incoming.type}|${incoming.domain}`;
-
-          // Get or create service entry
-          let service = this._services.get(key);
-          if (service) {
-            // Merge addresses (deduplicate)
-            const allAddresses = new Set([...service.addresses, ...incoming.addresses]);
-            // Normalize addresses, remove ends %eth-name
-            service.addresses = Array.from(allAddresses).map(addr => addr.replace(/%[^%]+$/, ''));
-            // Update other fields in case they changed
-            service.hostName = incoming.hostName;
-            service.port = incoming.port;
-            if (incoming.txt) {
-              service.txt = { ...service.txt, ...incoming.txt };
-            }
-            if (incoming.ttl) {
-              service.ttl = incoming.ttl;
-            }
-          } else {
-            service = { ...incoming };
-            // Normalize addresses, remove ends %eth-name
-            service.addresses = Array.from(service.addresses).map(addr => addr.replace(/%[^%]+$/, ''));
-            this._services.set(key, service);
-          }
+  private _onEvent(event: string, data: unknown): void {
+    if (this._stopped) return;
 
-          // Debounce emit - wait 100ms for more addresses to arrive
-          const existingTimeout = this._pendingEmit.get(key);
-          if (existingTimeout) {
-            clearTimeout(existingTimeout);
+    switch (event) {
+      case 'serviceFound':
+      case 'serviceUpdated': {
+        const incoming = data as Service;
+        const key = serviceKey(incoming);
+
+        // Get or create service entry
+        let service = this._services.get(key);
+        if (service) {
+          // Merge addresses (deduplicate)
+          const allAddresses = new Set([...service.addresses, ...incoming.addresses]);
+          // Normalize addresses, remove ends %eth-name
+          service.addresses = Array.from(allAddresses).map(addr => addr.replace(/%[^%]+$/, ''));
+          // Update other fields in case they changed
+          service.hostName = incoming.hostName;
+          service.port = incoming.port;
+          if (incoming.txt) {
+            service.txt = { ...service.txt, ...incoming.txt };
           }
-          const timeout = setTimeout(() => {
-            this._pendingEmit.delete(key);
-            const svc = this._services.get(key);
-            if (svc && !this._stopped && svc.addresses.length > 0) {
-              this.emit('serviceFound', { ...svc });
-            }
-          }, DEBOUNCE_TIMEOUT);
-          this._pendingEmit.set(key, timeout);
-          break;
+          if (incoming.ttl) {
+            service.ttl = incoming.ttl;
+          }
+        } else {
+          service = { ...incoming };
+          // Normalize addresses, remove ends %eth-name
+          service.addresses = Array.from(service.addresses).map(addr => addr.replace(/%[^%]+$/, ''));
+          this._services.set(key, service);
         }
-        case 'serviceLost': {
-          const lost = data as Service;
-          const key = `${lost.name}|${lost.type}|${lost.domain}`;
-          const service = this._services.get(key);
-          if (service) {
-            this._services.delete(key);
-            // Clear any pending emit
-            const timeout = this._pendingEmit.get(key);
-            if (timeout) {
-              clearTimeout(timeout);
-              this._pendingEmit.delete(key);
+
+        // Debounce emit - wait 100ms for more addresses to arrive
+        const existingTimeout = this._pendingEmit.get(key);
+        if (existingTimeout) {
+          clearTimeout(existingTimeout);
+        }
+        const timeout = setTimeout(() => {
+          this._pendingEmit.delete(key);
+          const svc = this._services.get(key);
+          if (svc && !this._stopped && (svc.addresses.length > 0 || this._browseOnly)) {
+            if (this._reported.has(key)) {
+              this.emit('serviceUpdated', { ...svc });
+            } else {
+              this._reported.add(key);
+              this.emit('serviceFound', { ...svc });
             }
-            this.emit('serviceLost', service);
           }
-          break;
+        }, DEBOUNCE_TIMEOUT);
+        this._pendingEmit.set(key, timeout);
+        break;
+      }
+      case 'serviceLost': {
+        const lost = data as Service;
+        const key = serviceKey(lost);
+        const service = this._services.get(key);
+        if (service) {
+          this._services.delete(key);
+          this._reported.delete(key);
+          // Clear any pending emit
+          const timeout = this._pendingEmit.get(key);
+          if (timeout) {
+            clearTimeout(timeout);
+            this._pendingEmit.delete(key);
+          }
+          this.emit('serviceLost', service);
         }
-        case 'error':
-          this.emit('error', new Error(data as string));
-          break;
+        break;
       }
-    });
+      case 'batchComplete':
+        this.emit('batchComplete');
+        break;
+      case 'resolveTimeout':
+      case 'resolveFailed':
+        this.emit(event, data as Service);
+        break;
+      case 'addressAdded':
+      case 'addressRemoved': {
+        const change = data as Service;
+        const service = this._services.get(serviceKey(change));
+        if (!service || !change.address) break;
+        const address = change.address.replace(/%[^%]+$/, '');
+        // serviceUpdated only ever adds addresses here, so drop removed ones
+        if (event === 'addressRemoved') {
+          service.addresses = service.addresses.filter(addr => addr !== address);
+        }
+        this.emit(event, { ...service, address });
+        break;
+      }
+      case 'backendFallback':
+        this.emit('backendFallback');
+        break;
+      case 'batch':
+        for (const entry of data as { event: string; service: Service }[]) {
+          this._onEvent(entry.event, entry.service);
+        }
+        break;
+      case 'txtError': {
+        const service = data as Service;
+        this.emit('error', new Error(`Malformed TXT record for ${service.name}: ${(service.txtErrors ?? []).join('; ')}`));
+        break;
+      }
+      case 'queueOverflow':
+        this.emit('error', new Error('Event queue overflowed; events were dropped'));
+        break;
+      case 'error':
+        this.emit('error', new Error(data as string));
+        break;
+    }
   }
 
   stop(): void {
@@ -139,30 +678,57 @@ export class DnsSdBrowse extends EventEmitter {
         clearTimeout(timeout);
       }
       this._pendingEmit.clear();
+      this._detachSignal();
       addon.stopBrowse(this._handle);
     }
   }
+
+  // Suspend discovery without losing listeners; resume() picks up where it left off
+  pause(): void {
+    if (!this._stopped) {
+      addon.pauseBrowse(this._handle);
+    }
+  }
+
+  resume(): void {
+    if (!this._stopped) {
+      addon.resumeBrowse(this._handle);
+    }
+  }
+
+  // Services found and not yet lost, read from the native side; empty after stop()
+  getServices(): Service[] {
+    return addon.getServices(this._handle);
+  }
+
+  // Resolve a service this browser reported, on the interface it was seen on
+  async resolve(instanceName: string): Promise<Service> {
+    return stripScopes(await addon.resolveInstance(this._handle, instanceName));
+  }
 }
 
 // DnsSdAdvertisement class
 export interface DnsSdAdvertisement {
   on(event: 'registered', listener: (name: string) => void): this;
+  on(event: 'backendFallback', listener: (reason: string) => void): this;
+  on(event: 'hostnameConflict', listener: (conflict: HostnameConflict) => void): this;
   on(event: 'error', listener: (error: Error) => void): this;
   emit(event: 'registered', name: string): boolean;
+  emit(event: 'backendFallback', reason: string): boolean;
+  emit(event: 'hostnameConflict', conflict: HostnameConflict): boolean;
   emit(event: 'error', error: Error): boolean;
 }
 
 export class DnsSdAdvertisement extends EventEmitter {
-  private _handle: number;
+  private _handle: AddonHandle;
   private _stopped: boolean = false;
+  private _detachSignal: () => void = () => {};
 
   constructor(options: AdvertiseOptions) {
     super();
-    this._handle = addon.advertiseService(
-      options.name,
-      options.type,
-      options.port,
-      options.txt,
+    const { hostName, signal, ...rest } = options;
+    this._handle = addon.advertiseServiceV2(
+      { ...rest, host: hostName },
       (event, data) => {
         if (this._stopped) return;
 
@@ -170,35 +736,567 @@ export class DnsSdAdvertisement extends EventEmitter {
           case 'registered':
             this.emit('registered', data as string);
             break;
+          case 'backendFallback':
+            this.emit('backendFallback', data as string);
+            break;
+          case 'hostnameConflict':
+            this.emit('hostnameConflict', data as HostnameConflict);
+            break;
           case 'error':
             this.emit('error', new Error(data as string));
             break;
         }
       }
     );
+    this._detachSignal = stopOnAbort(signal, () => this.stop());
+  }
+
+  stop(): void {
+    if (!this._stopped) {
+      this._stopped = true;
+      this._detachSignal();
+      addon.stopAdvertise(this._handle);
+    }
+  }
+
+  // Like stop(), but resolves once peers were sent goodbyes (waiting at most `timeoutMs`, default 2000)
+  async stopAsync(timeoutMs?: number): Promise<void> {
+    if (!this._stopped) {
+      this._stopped = true;
+      this._detachSignal();
+      await addon.stopAdvertiseAsync(this._handle, timeoutMs);
+    }
+  }
+
+  // Re-register under a new instance name; 'registered' reports the name peers see
+  async rename(name: string): Promise<void> {
+    if (this._stopped || !(await addon.renameAdvertisement(this._handle, name))) {
+      throw new Error('Advertisement is stopped');
+    }
+  }
+
+  // Move the service to another port, e.g. after the server rebinds its listener
+  async updatePort(port: number): Promise<void> {
+    if (this._stopped || !(await addon.updateAdvertisementPort(this._handle, port))) {
+      throw new Error('Advertisement is stopped');
+    }
+  }
+}
+
+// DnsSdAdvertisementBundle class
+export interface DnsSdAdvertisementBundle {
+  on(event: 'registered', listener: (name: string, index: number) => void): this;
+  on(event: 'backendFallback', listener: (reason: string, index: number) => void): this;
+  on(event: 'hostnameConflict', listener: (conflict: HostnameConflict, index: number) => void): this;
+  on(event: 'error', listener: (error: Error, index: number) => void): this;
+  emit(event: 'registered', name: string, index: number): boolean;
+  emit(event: 'backendFallback', reason: string, index: number): boolean;
+  emit(event: 'hostnameConflict', conflict: HostnameConflict, index: number): boolean;
+  emit(event: 'error', error: Error, index: number): boolean;
+}
+
+export class DnsSdAdvertisementBundle extends EventEmitter {
+  private _handle: AddonHandle;
+  private _stopped: boolean = false;
+  private _detachSignal: () => void = () => {};
+
+  // `index` in events is the service's position in `specs`
+  constructor(specs: Omit<AdvertiseOptions, 'signal'>[], signal?: AbortSignal) {
+    super();
+    this._handle = addon.advertiseServices(
+      specs.map(({ hostName, ...rest }) => ({ ...rest, host: hostName })),
+      (event, data, index) => {
+        if (this._stopped) return;
+
+        switch (event) {
+          case 'registered':
+            this.emit('registered', data as string, index);
+            break;
+          case 'backendFallback':
+            this.emit('backendFallback', data as string, index);
+            break;
+          case 'hostnameConflict':
+            this.emit('hostnameConflict', data as HostnameConflict, index);
+            break;
+          case 'error':
+            this.emit('error', new Error(data as string), index);
+            // The addon has withdrawn the other services already
+            this.stop();
+            break;
+        }
+      }
+    );
+    this._detachSignal = stopOnAbort(signal, () => this.stop());
   }
 
   stop(): void {
     if (!this._stopped) {
       this._stopped = true;
+      this._detachSignal();
       addon.stopAdvertise(this._handle);
     }
   }
+
+  // Like stop(), but resolves once peers were sent goodbyes (waiting at most `timeoutMs`, default 2000)
+  async stopAsync(timeoutMs?: number): Promise<void> {
+    if (!this._stopped) {
+      this._stopped = true;
+      this._detachSignal();
+      await addon.stopAdvertiseAsync(this._handle, timeoutMs);
+    }
+  }
+}
+
+// DnsSdHostName class
+export interface DnsSdHostName {
+  on(event: 'registered', listener: (name: string) => void): this;
+  on(event: 'backendFallback', listener: (reason: string) => void): this;
+  on(event: 'hostnameConflict', listener: (conflict: HostnameConflict) => void): this;
+  on(event: 'error', listener: (error: Error) => void): this;
+  emit(event: 'registered', name: string): boolean;
+  emit(event: 'backendFallback', reason: string): boolean;
+  emit(event: 'hostnameConflict', conflict: HostnameConflict): boolean;
+  emit(event: 'error', error: Error): boolean;
+}
+
+export class DnsSdHostName extends EventEmitter {
+  private _handle: AddonHandle;
+  private _stopped: boolean = false;
+  private _detachSignal: () => void = () => {};
+
+  // With `addresses`, publishes them for `hostname`; otherwise makes `hostname` an alias of this host
+  constructor(hostname: string, options?: { addresses?: string[]; signal?: AbortSignal }) {
+    super();
+    const onEvent = (event: string, data: unknown) => {
+      if (this._stopped) return;
+
+      switch (event) {
+        case 'registered':
+          this.emit('registered', data as string);
+          break;
+        case 'backendFallback':
+          this.emit('backendFallback', data as string);
+          break;
+        case 'hostnameConflict':
+          this.emit('hostnameConflict', data as HostnameConflict);
+          break;
+        case 'error':
+          this.emit('error', new Error(data as string));
+          break;
+      }
+    };
+    this._handle = options?.addresses
+      ? addon.publishHostRecords(hostname, options.addresses, onEvent)
+      : addon.publishAlias(hostname, onEvent);
+    this._detachSignal = stopOnAbort(options?.signal, () => this.stop());
+  }
+
+  stop(): void {
+    if (!this._stopped) {
+      this._stopped = true;
+      this._detachSignal();
+      addon.stopAdvertise(this._handle);
+    }
+  }
+
+  // Like stop(), but resolves once peers were sent goodbyes (waiting at most `timeoutMs`, default 2000)
+  async stopAsync(timeoutMs?: number): Promise<void> {
+    if (!this._stopped) {
+      this._stopped = true;
+      this._detachSignal();
+      await addon.stopAdvertiseAsync(this._handle, timeoutMs);
+    }
+  }
+}
+
+// DnsSdManifestAdvertisement class
+export interface DnsSdManifestAdvertisement {
+  on(event: 'registered', listener: (name: string, serviceType: string) => void): this;
+  on(event: 'backendFallback', listener: (reason: string, serviceType: string) => void): this;
+  on(event: 'hostnameConflict', listener: (conflict: HostnameConflict, serviceType: string) => void): this;
+  on(event: 'error', listener: (error: Error, serviceType: string) => void): this;
+  on(event: 'reloaded', listener: (count: number) => void): this;
+  emit(event: 'registered', name: string, serviceType: string): boolean;
+  emit(event: 'backendFallback', reason: string, serviceType: string): boolean;
+  emit(event: 'hostnameConflict', conflict: HostnameConflict, serviceType: string): boolean;
+  emit(event: 'error', error: Error, serviceType: string): boolean;
+  emit(event: 'reloaded', count: number): boolean;
+}
+
+export class DnsSdManifestAdvertisement extends EventEmitter {
+  private _handle: AddonHandle;
+  private _stopped: boolean = false;
+  private _detachSignal: () => void = () => {};
+
+  constructor(path: string, signal?: AbortSignal) {
+    super();
+    this._handle = addon.advertiseFromManifest(path, (event, data, serviceType) => {
+      if (this._stopped) return;
+
+      switch (event) {
+        case 'registered':
+          this.emit('registered', data as string, serviceType);
+          break;
+        case 'backendFallback':
+          this.emit('backendFallback', data as string, serviceType);
+          break;
+        case 'hostnameConflict':
+          this.emit('hostnameConflict', data as HostnameConflict, serviceType);
+          break;
+        case 'error':
+          // serviceType is '' for errors about the manifest itself
+          this.emit('error', new Error(data as string), serviceType);
+          break;
+        case 'reloaded':
+          this.emit('reloaded', Number(data));
+          break;
+      }
+    });
+    this._detachSignal = stopOnAbort(signal, () => this.stop());
+  }
+
+  stop(): void {
+    if (!this._stopped) {
+      this._stopped = true;
+      this._detachSignal();
+      addon.stopAdvertise(this._handle);
+    }
+  }
+
+  // Like stop(), but resolves once peers were sent goodbyes (waiting at most `timeoutMs`, default 2000)
+  async stopAsync(timeoutMs?: number): Promise<void> {
+    if (!this._stopped) {
+      this._stopped = true;
+      this._detachSignal();
+      await addon.stopAdvertiseAsync(this._handle, timeoutMs);
+    }
+  }
+}
+
+// DiscoveryManager class
+export interface DiscoveryManager {
+  on(event: 'added', listener: (service: Service) => void): this;
+  on(event: 'updated', listener: (service: Service) => void): this;
+  on(event: 'removed', listener: (service: Service) => void): this;
+  emit(event: 'added' | 'updated' | 'removed', service: Service): boolean;
+}
+
+// Remove trailing %iface scope from addresses
+function stripScopes(service: Service): Service {
+  return { ...service, addresses: service.addresses.map(addr => addr.replace(/%[^%]+$/, '')) };
+}
+
+export class DiscoveryManager extends EventEmitter {
+  private _handle: AddonHandle;
+  private _stopped: boolean = false;
+  private _detachSignal: () => void = () => {};
+
+  constructor(types: string[], options?: BrowseOptions) {
+    super();
+    const { signal, ...rest } = options ?? {};
+    this._handle = addon.createDiscoveryManager(types, rest, (event, data) => {
+      if (this._stopped) return;
+      this.emit(event as 'added' | 'updated' | 'removed', stripScopes(data as Service));
+    });
+    this._detachSignal = stopOnAbort(signal, () => this.stop());
+  }
+
+  addType(serviceType: string): boolean {
+    return addon.managerAddType(this._handle, serviceType);
+  }
+
+  removeType(serviceType: string): boolean {
+    return addon.managerRemoveType(this._handle, serviceType);
+  }
+
+  getTypes(): string[] {
+    return addon.managerGetTypes(this._handle);
+  }
+
+  getSnapshot(): Service[] {
+    return addon.managerGetSnapshot(this._handle).map(stripScopes);
+  }
+
+  stop(): void {
+    if (!this._stopped) {
+      this._stopped = true;
+      this._detachSignal();
+      addon.stopDiscoveryManager(this._handle);
+    }
+  }
+}
+
+// Browser class: bonjour/mdns-style events, emitted by the addon itself
+export interface Browser {
+  on(event: 'up', listener: (service: Service) => void): this;
+  on(event: 'update', listener: (service: Service) => void): this;
+  on(event: 'down', listener: (service: Service) => void): this;
+  on(event: 'backendFallback', listener: () => void): this;
+  on(event: 'error', listener: (error: Error) => void): this;
+  on(event: 'end', listener: () => void): this;
+}
+
+export class Browser extends EventEmitter {
+  private _handle: AddonHandle;
+  private _detachSignal: () => void = () => {};
+
+  constructor(serviceType: string, options?: BrowseOptions) {
+    super();
+    const { signal, ...rest } = options ?? {};
+    this._handle = addon.createBrowser(this, { ...rest, type: serviceType });
+    this._detachSignal = stopOnAbort(signal, () => this.stop());
+  }
+
+  // Emits 'end' the first time; nothing is emitted afterwards
+  stop(): void {
+    this._detachSignal();
+    addon.closeBrowser(this._handle);
+  }
+}
+
+// Advertisement class: 'up' once registered (with the final name), 'error', 'end'
+export interface Advertisement {
+  on(event: 'up', listener: (name: string) => void): this;
+  on(event: 'backendFallback', listener: (reason: string) => void): this;
+  on(event: 'hostnameConflict', listener: (conflict: HostnameConflict) => void): this;
+  on(event: 'error', listener: (error: Error) => void): this;
+  on(event: 'end', listener: () => void): this;
+}
+
+export class Advertisement extends EventEmitter {
+  private _handle: AddonHandle;
+  private _detachSignal: () => void = () => {};
+
+  constructor(options: AdvertiseOptions) {
+    super();
+    const { hostName, signal, ...rest } = options;
+    this._handle = addon.createAdvertisement(this, { ...rest, host: hostName });
+    this._detachSignal = stopOnAbort(signal, () => this.stop());
+  }
+
+  stop(): void {
+    this._detachSignal();
+    addon.closeAdvertisement(this._handle);
+  }
 }
 
 // Main DnsSd class with static methods
 export class DnsSd {
-  static search(serviceType: string): DnsSdBrowse {
-    return new DnsSdBrowse(serviceType);
+  static search(serviceType: string, options?: BrowseOptions): DnsSdBrowse {
+    return new DnsSdBrowse(serviceType, options);
   }
 
   static advertise(options: AdvertiseOptions): DnsSdAdvertisement {
     return new DnsSdAdvertisement(options);
   }
 
+  static advertiseServices(specs: Omit<AdvertiseOptions, 'signal'>[], options?: { signal?: AbortSignal }): DnsSdAdvertisementBundle {
+    return new DnsSdAdvertisementBundle(specs, options?.signal);
+  }
+
+  static advertiseFromManifest(path: string, options?: { signal?: AbortSignal }): DnsSdManifestAdvertisement {
+    return new DnsSdManifestAdvertisement(path, options?.signal);
+  }
+
+  // Make this host reachable as `${alias}.local` too
+  static publishAlias(alias: string, options?: { signal?: AbortSignal }): DnsSdHostName {
+    return new DnsSdHostName(alias, { signal: options?.signal });
+  }
+
+  // Publish A/AAAA records for `hostname` (e.g. 'hub.local'), apart from any service
+  static publishHostRecords(hostname: string, addresses: string[], options?: { signal?: AbortSignal }): DnsSdHostName {
+    return new DnsSdHostName(hostname, { addresses, signal: options?.signal });
+  }
+
+  static async findService(serviceType: string, options?: FindOptions): Promise<Service> {
+    const { signal, ...rest } = options ?? {};
+    signal?.throwIfAborted();
+    const token = addon.createAbortToken();
+    const onAbort = () => addon.abort(token);
+    signal?.addEventListener('abort', onAbort, { once: true });
+    try {
+      return stripScopes(await addon.findService({ ...rest, type: serviceType }, token));
+    } catch (error) {
+      // Reject with the signal's reason, as fetch() and friends do
+      signal?.throwIfAborted();
+      throw error;
+    } finally {
+      signal?.removeEventListener('abort', onAbort);
+    }
+  }
+
+  static manage(types: string[], options?: BrowseOptions): DiscoveryManager {
+    return new DiscoveryManager(types, options);
+  }
+
+  static configureFallback(options: FallbackConfig): void {
+    addon.configureFallback(options);
+  }
+
+  // Publish advertisements whose domain is `zone` with signed DNS Update; null stops
+  static setUpdateServer(options: UpdateServerConfig | null): void {
+    addon.setUpdateServer(options);
+  }
+
+  // Re-query a service the fallback backend has cached; unreachable ones are
+  // reported as serviceLost. Returns false if there was nothing to verify.
+  static verifyService(service: Service | string, timeoutMs?: number): boolean {
+    return addon.verifyService(typeof service === 'string' ? service : service.fullName, timeoutMs);
+  }
+
+  // Escape `.`, `\` and spaces/control characters for use in a full DNS name
+  static escapeInstanceName(name: string): string {
+    return addon.escapeInstanceName(name);
+  }
+
+  static unescapeInstanceName(escaped: string): string {
+    return addon.unescapeInstanceName(escaped);
+  }
+
   static getBackendInfo(): string {
     return addon.getBackendInfo();
   }
+
+  static setBackend(name: BackendName): void {
+    addon.setBackend(name);
+  }
+
+  // Retry native detection (e.g. after Bonjour was installed or avahi-daemon started); returns the backend new handles will use
+  static reprobeBackend(): string {
+    return addon.reprobeBackend();
+  }
+
+  // Retry native detection on a timer until it succeeds; null stops it
+  static setBackendProbeInterval(intervalMs: number | null): void {
+    addon.setBackendProbeInterval(intervalMs);
+  }
+
+  static onBackendChanged(callback: BackendChangedCallback | null): void {
+    addon.setBackendChangedCallback(callback);
+  }
+
+  static setMockScript(steps: MockStep[]): void {
+    addon.setMockScript(steps);
+  }
+
+  // Keep handles started afterwards on this machine, for tests where LAN multicast is blocked
+  static setLoopbackOnly(enabled: boolean): void {
+    addon.setLoopbackOnly(enabled);
+  }
+
+  static startRecording(path: string): void {
+    addon.startRecording(path);
+  }
+
+  static stopRecording(): boolean {
+    return addon.stopRecording();
+  }
+
+  // Write the fallback's mDNS packets to a pcap file, for Wireshark or tcpdump
+  static startCapture(path: string): void {
+    addon.startCapture(path);
+  }
+
+  static stopCapture(): boolean {
+    return addon.stopCapture();
+  }
+
+  // Load a capture as the mock script and switch to the mock backend; returns the event count
+  static replayRecording(path: string): number {
+    const count = addon.loadRecording(path);
+    addon.setBackend('mock');
+    return count;
+  }
+
+  // Network interfaces, for picking the `interface` option
+  static listInterfaces(): NetworkInterface[] {
+    return addon.listInterfaces();
+  }
+
+  // Restart browses and re-announce advertisements when addresses change (on by default)
+  static setNetworkWatchEnabled(enabled: boolean): void {
+    addon.setNetworkWatchEnabled(enabled);
+  }
+
+  static onNetworkChanged(callback: NetworkChangedCallback | null): void {
+    addon.setNetworkChangedCallback(callback);
+  }
+
+  // Raw packets the fallback hears sent to the mDNS group
+  static onPacket(callback: ((packet: MdnsPacket) => void) | null): void {
+    addon.setPacketTapCallback(callback);
+  }
+
+  // Refresh browses, advertisements and the cache after the system sleeps (off by default)
+  static setWakeWatchEnabled(enabled: boolean): void {
+    addon.setWakeWatchEnabled(enabled);
+  }
+
+  static onSystemResumed(callback: ((sleptMs: number) => void) | null): void {
+    addon.setSystemResumedCallback(callback);
+  }
+
+  static listBrowsers(): HandleInfo[] {
+    return addon.listBrowsers();
+  }
+
+  static listAdvertisements(): HandleInfo[] {
+    return addon.listAdvertisements();
+  }
+
+  static getMetrics(): Metrics {
+    return addon.getMetrics();
+  }
+
+  // One-call diagnostic: native library, daemon socket, LocalOnly round-trip and multicast per interface
+  static checkHealth(timeoutMs?: number): Promise<HealthReport> {
+    return addon.checkHealth(timeoutMs);
+  }
+
+  // Register, browse and resolve a temporary LocalOnly service, reporting each stage with hints
+  static runSelftest(timeoutMs?: number): Promise<SelftestReport> {
+    return addon.runSelftest(timeoutMs);
+  }
+
+  // Backend, live handles, cache and recent errors in one report, for bug reports
+  static dumpDiagnostics(): Diagnostics {
+    return addon.dumpDiagnostics();
+  }
+
+  // Resolved services shared by all browsers, kept until their TTL runs out
+  static getCachedServices(): Service[] {
+    return addon.getCachedServices();
+  }
+
+  // Persist last-known services for warm starts; returns how many the file held
+  static setCacheFile(path: string | null): number {
+    return addon.setCacheFile(path);
+  }
+
+  static clearCache(): void {
+    addon.clearCache();
+  }
+
+  static setCacheEnabled(enabled: boolean): void {
+    addon.setCacheEnabled(enabled);
+  }
+
+  // Called with each service whose cache entry expired (cacheEvicted)
+  static onCacheEvicted(callback: ((service: Service) => void) | null): void {
+    addon.setCacheEvictedCallback(callback);
+  }
+
+  static getMetricsPrometheus(): string {
+    return addon.getMetricsPrometheus();
+  }
+
+  static setLogLevel(level: LogLevel): void {
+    addon.setLogLevel(level);
+  }
+
+  static setLogCallback(callback: LogCallback | null): void {
+    addon.setLogCallback(callback);
+  }
 }
 
 export default DnsSd;